@@ -0,0 +1,372 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::io::{Result, Error, ErrorKind};
+
+use loader::code::{MethodBody, ExceptionClauseKind};
+use loader::instructions::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+  /// Execution continues with the next instruction, including the untaken side of a conditional branch.
+  Fallthrough,
+  Branch,
+  /// One of the targets of a switch. The default case is a Fallthrough edge.
+  Switch(u32),
+  /// leave and leave.s. Finally handlers run on the way, but they're only linked through Exceptional edges.
+  Leave,
+  /// From a block inside a protected region to the filter or handler of that region.
+  Exceptional
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+  pub from: usize,
+  pub to: usize,
+  pub kind: EdgeKind
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+  pub start: u32,
+  /// The offset just past the last instruction of the block.
+  pub end: u32,
+  pub instructions: Vec<(u32, Instruction)>
+}
+
+/// A basic-block control-flow graph of a method body. Block 0 is always the entry block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlFlowGraph {
+  pub blocks: Vec<BasicBlock>,
+  pub edges: Vec<Edge>
+}
+
+/// The immediate dominators of the blocks of a control-flow graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DominatorTree {
+  /// None for the entry block and for unreachable blocks.
+  pub immediate_dominators: Vec<Option<usize>>,
+  reachable: Vec<bool>
+}
+
+fn invalid_target(offset: u32, target: u32) -> Error {
+  Error::new(ErrorKind::InvalidData, format!("IL_{:04x}: branch target IL_{:04x} is not the start of an instruction", offset, target))
+}
+
+// Instructions after which execution never continues with the next one
+pub fn ends_flow(instruction: &Instruction) -> bool {
+  match *instruction {
+    Instruction::Br(_) | Instruction::BrS(_) | Instruction::Leave(_) | Instruction::LeaveS(_) |
+    Instruction::Ret | Instruction::Throw | Instruction::Rethrow | Instruction::Endfinally |
+    Instruction::Endfilter | Instruction::Jmp(_) => true,
+    _ => false
+  }
+}
+
+impl ControlFlowGraph {
+  pub fn build(body: &MethodBody) -> Result<ControlFlowGraph> {
+    let instructions = body.instructions().collect::<Result<Vec<_>>>()?;
+    let offsets = instructions.iter().map(|&(offset, _)| offset).collect::<BTreeSet<_>>();
+    let code_size = body.code.len() as u32;
+
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+
+    for (position, &(offset, ref instruction)) in instructions.iter().enumerate() {
+      let mut targets = instruction.branch_target().into_iter().collect::<Vec<_>>();
+      if let Instruction::Switch(ref switch_targets) = *instruction {
+        targets.extend(switch_targets.iter().cloned());
+      }
+
+      for target in targets {
+        if !offsets.contains(&target) {
+          return Err(invalid_target(offset, target));
+        }
+        leaders.insert(target);
+      }
+
+      let is_branch = ends_flow(instruction) || instruction.branch_target().is_some() || match *instruction {
+        Instruction::Switch(_) => true,
+        _ => false
+      };
+
+      if is_branch {
+        if let Some(&(next, _)) = instructions.get(position + 1) {
+          leaders.insert(next);
+        }
+      }
+    }
+
+    for clause in &body.exception_clauses {
+      let mut boundaries = vec![
+        clause.try_offset, clause.try_offset + clause.try_length,
+        clause.handler_offset, clause.handler_offset + clause.handler_length
+      ];
+      if let ExceptionClauseKind::Filter(filter_offset) = clause.kind {
+        boundaries.push(filter_offset);
+      }
+
+      for boundary in boundaries {
+        if boundary == code_size {
+          continue;
+        }
+        if !offsets.contains(&boundary) {
+          return Err(Error::new(ErrorKind::InvalidData,
+            format!("Exception handling clause boundary IL_{:04x} is not the start of an instruction", boundary)));
+        }
+        leaders.insert(boundary);
+      }
+    }
+
+    let mut blocks: Vec<BasicBlock> = vec![];
+    for (offset, instruction) in instructions {
+      if leaders.contains(&offset) || blocks.is_empty() {
+        blocks.push(BasicBlock { start: offset, end: offset, instructions: vec![] });
+      }
+
+      let block = blocks.last_mut().unwrap();
+      block.instructions.push((offset, instruction));
+    }
+
+    // Block ends are the start of the next block, or the end of the code for the last one
+    for position in 0 .. blocks.len() {
+      blocks[position].end = blocks.get(position + 1).map(|next| next.start).unwrap_or(code_size);
+    }
+
+    let mut graph = ControlFlowGraph { blocks, edges: vec![] };
+    let mut edges = vec![];
+
+    for (from, block) in graph.blocks.iter().enumerate() {
+      let &(_, ref last) = block.instructions.last().unwrap();
+
+      match *last {
+        Instruction::Leave(target) | Instruction::LeaveS(target) =>
+          edges.push(Edge { from, to: graph.block_at(target).unwrap(), kind: EdgeKind::Leave }),
+        Instruction::Switch(ref targets) => for (case, &target) in targets.iter().enumerate() {
+          edges.push(Edge { from, to: graph.block_at(target).unwrap(), kind: EdgeKind::Switch(case as u32) });
+        },
+        ref other => if let Some(target) = other.branch_target() {
+          edges.push(Edge { from, to: graph.block_at(target).unwrap(), kind: EdgeKind::Branch });
+        }
+      }
+
+      if !ends_flow(last) && from + 1 < graph.blocks.len() {
+        edges.push(Edge { from, to: from + 1, kind: EdgeKind::Fallthrough });
+      }
+
+      for clause in &body.exception_clauses {
+        if clause.try_contains(block.start) {
+          let handler_start = match clause.kind {
+            ExceptionClauseKind::Filter(filter_offset) => filter_offset,
+            _ => clause.handler_offset
+          };
+
+          let edge = Edge { from, to: graph.block_at(handler_start).unwrap(), kind: EdgeKind::Exceptional };
+          if !edges.contains(&edge) {
+            edges.push(edge);
+          }
+        }
+      }
+    }
+
+    graph.edges = edges;
+    Ok(graph)
+  }
+
+  /// Returns the block which starts at the given offset.
+  pub fn block_at(&self, offset: u32) -> Option<usize> {
+    self.blocks.iter().position(|block| block.start == offset)
+  }
+
+  /// Returns the block which contains the instruction at the given offset.
+  pub fn block_containing(&self, offset: u32) -> Option<usize> {
+    self.blocks.iter().position(|block| block.start <= offset && offset < block.end)
+  }
+
+  pub fn successors(&self, block: usize) -> Vec<usize> {
+    let mut res = vec![];
+    for edge in self.edges.iter().filter(|edge| edge.from == block) {
+      if !res.contains(&edge.to) {
+        res.push(edge.to);
+      }
+    }
+    res
+  }
+
+  pub fn predecessors(&self, block: usize) -> Vec<usize> {
+    let mut res = vec![];
+    for edge in self.edges.iter().filter(|edge| edge.to == block) {
+      if !res.contains(&edge.from) {
+        res.push(edge.from);
+      }
+    }
+    res
+  }
+
+  /// Returns which blocks can be reached from the entry block, following all kinds of edges.
+  pub fn reachable(&self) -> Vec<bool> {
+    let mut reachable = vec![false; self.blocks.len()];
+    let mut pending = vec![0];
+
+    while let Some(block) = pending.pop() {
+      if block >= reachable.len() || reachable[block] {
+        continue;
+      }
+      reachable[block] = true;
+      pending.extend(self.successors(block));
+    }
+
+    reachable
+  }
+
+  /// Returns the blocks in reverse postorder, starting with the entry block. Unreachable blocks are left out.
+  pub fn reverse_postorder(&self) -> Vec<usize> {
+    let mut order = vec![];
+    let mut visited = vec![false; self.blocks.len()];
+
+    if self.blocks.is_empty() {
+      return order;
+    }
+
+    // An explicit stack of (block, next successor to visit), so that long methods can't overflow the native stack
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+
+    while let Some(&(block, next)) = stack.last() {
+      let successors = self.successors(block);
+
+      if next < successors.len() {
+        let successor = successors[next];
+        stack.last_mut().unwrap().1 += 1;
+        if !visited[successor] {
+          visited[successor] = true;
+          stack.push((successor, 0));
+        }
+      } else {
+        order.push(block);
+        stack.pop();
+      }
+    }
+
+    order.reverse();
+    order
+  }
+
+  /// Computes the dominator tree with the iterative algorithm of Cooper, Harvey and Kennedy.
+  pub fn dominators(&self) -> DominatorTree {
+    let order = self.reverse_postorder();
+    let mut position_in_order = vec![usize::max_value(); self.blocks.len()];
+    for (position, &block) in order.iter().enumerate() {
+      position_in_order[block] = position;
+    }
+
+    let mut idom: Vec<Option<usize>> = vec![None; self.blocks.len()];
+    if order.is_empty() {
+      return DominatorTree { immediate_dominators: idom, reachable: vec![] };
+    }
+    idom[0] = Some(0);
+
+    let intersect = |idom: &Vec<Option<usize>>, mut a: usize, mut b: usize| {
+      while a != b {
+        while position_in_order[a] > position_in_order[b] {
+          a = idom[a].unwrap();
+        }
+        while position_in_order[b] > position_in_order[a] {
+          b = idom[b].unwrap();
+        }
+      }
+      a
+    };
+
+    let mut changed = true;
+    while changed {
+      changed = false;
+
+      for &block in order.iter().skip(1) {
+        let mut new_idom = None;
+        for predecessor in self.predecessors(block) {
+          if idom[predecessor].is_none() {
+            continue;
+          }
+          new_idom = Some(match new_idom {
+            None => predecessor,
+            Some(current) => intersect(&idom, predecessor, current)
+          });
+        }
+
+        if new_idom.is_some() && idom[block] != new_idom {
+          idom[block] = new_idom;
+          changed = true;
+        }
+      }
+    }
+
+    // The entry block has no immediate dominator
+    idom[0] = None;
+    let mut reachable = vec![false; self.blocks.len()];
+    for &block in &order {
+      reachable[block] = true;
+    }
+
+    DominatorTree { immediate_dominators: idom, reachable }
+  }
+
+  /// Formats the graph for Graphviz. Exceptional edges are dashed.
+  pub fn to_dot(&self, name: &str) -> String {
+    let mut res = String::new();
+
+    writeln!(&mut res, "digraph \"{}\" {{", name.replace('"', "\\\"")).unwrap();
+    res.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+
+    for (position, block) in self.blocks.iter().enumerate() {
+      let mut label = String::new();
+      for &(offset, ref instruction) in &block.instructions {
+        write!(&mut label, "IL_{:04x}: {}\\l", offset, instruction.mnemonic()).unwrap();
+      }
+      writeln!(&mut res, "  b{} [label=\"{}\"];", position, label).unwrap();
+    }
+
+    for edge in &self.edges {
+      let attributes = match edge.kind {
+        EdgeKind::Fallthrough => String::new(),
+        EdgeKind::Branch => " [label=\"branch\"]".to_string(),
+        EdgeKind::Switch(case) => format!(" [label=\"case {}\"]", case),
+        EdgeKind::Leave => " [label=\"leave\"]".to_string(),
+        EdgeKind::Exceptional => " [style=dashed]".to_string()
+      };
+      writeln!(&mut res, "  b{} -> b{}{};", edge.from, edge.to, attributes).unwrap();
+    }
+
+    res.push_str("}\n");
+    res
+  }
+}
+
+impl DominatorTree {
+  pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+    self.immediate_dominators.get(block).and_then(|&idom| idom)
+  }
+
+  /// Returns true if every path from the entry block to `block` goes through `dominator`.
+  /// Every reachable block dominates itself. Unreachable blocks don't dominate and aren't dominated.
+  pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+    if !self.reachable.get(dominator).cloned().unwrap_or(false) || !self.reachable.get(block).cloned().unwrap_or(false) {
+      return false;
+    }
+
+    let mut current = block;
+    loop {
+      if current == dominator {
+        return true;
+      }
+      match self.immediate_dominator(current) {
+        Some(idom) => current = idom,
+        None => return false
+      }
+    }
+  }
+
+  /// Returns the blocks whose immediate dominator is the given block.
+  pub fn children(&self, block: usize) -> Vec<usize> {
+    (0 .. self.immediate_dominators.len()).filter(|&child| self.immediate_dominator(child) == Some(block)).collect()
+  }
+}
@@ -0,0 +1,964 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Cursor;
+
+use analysis::cfg::ends_flow;
+use loader::code::{MethodBody, ExceptionClauseKind};
+use loader::instructions::{Instruction, Token};
+use loader::stream::TableId;
+use metadata::Metadata;
+use metadata::signature::{TypeSig, FieldSig, MethodSignature};
+use metadata::tables::*;
+
+/// The types tracked on the evaluation stack (ECMA 335, III.1.8.1.2). Object references and value types
+/// aren't told apart by class, and Unknown stands for generic parameters and types from other assemblies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackType {
+  Int32,
+  Int64,
+  NativeInt,
+  Float,
+  Object,
+  ManagedPointer,
+  ValueType,
+  Unknown
+}
+
+impl fmt::Display for StackType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match *self {
+      StackType::Int32 => "int32",
+      StackType::Int64 => "int64",
+      StackType::NativeInt => "native int",
+      StackType::Float => "F",
+      StackType::Object => "O",
+      StackType::ManagedPointer => "&",
+      StackType::ValueType => "valuetype",
+      StackType::Unknown => "unknown"
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationError {
+  /// The full name of the method, e.g. `Sample.Program.Main`.
+  pub method: String,
+  pub offset: u32,
+  pub reason: String
+}
+
+impl fmt::Display for VerificationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}, IL_{:04x}: {}", self.method, self.offset, self.reason)
+  }
+}
+
+impl Error for VerificationError {
+  fn description(&self) -> &str {
+    &self.reason
+  }
+}
+
+type VerifyResult<T> = ::std::result::Result<T, VerificationError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegionKind {
+  Try,
+  Catch,
+  Filter,
+  Finally,
+  Fault
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Region {
+  start: u32,
+  end: u32,
+  kind: RegionKind
+}
+
+impl Region {
+  fn contains(&self, offset: u32) -> bool {
+    self.start <= offset && offset < self.end
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+  Add,
+  Sub,
+  // mul, div and rem
+  Numeric,
+  // and, or, xor, div.un, rem.un and the overflow checking operations
+  Integer
+}
+
+fn binary_result(op: BinaryOp, a: StackType, b: StackType) -> Option<StackType> {
+  use self::StackType::*;
+
+  match (a, b) {
+    (Unknown, _) | (_, Unknown) => Some(Unknown),
+    (Int32, Int32) => Some(Int32),
+    (Int32, NativeInt) | (NativeInt, Int32) | (NativeInt, NativeInt) => Some(NativeInt),
+    (Int64, Int64) => Some(Int64),
+    (Float, Float) if op != BinaryOp::Integer => Some(Float),
+    (ManagedPointer, Int32) | (ManagedPointer, NativeInt) if op == BinaryOp::Add || op == BinaryOp::Sub => Some(ManagedPointer),
+    (Int32, ManagedPointer) | (NativeInt, ManagedPointer) if op == BinaryOp::Add => Some(ManagedPointer),
+    (ManagedPointer, ManagedPointer) if op == BinaryOp::Sub => Some(NativeInt),
+    _ => None
+  }
+}
+
+// Operands of ceq, the other comparisons and the two-operand branches (ECMA 335, III.1.5, table 4).
+// Object references can only be checked for equality, or against null with cgt.un.
+fn comparable(a: StackType, b: StackType, allows_objects: bool) -> bool {
+  match (a, b) {
+    (StackType::Object, StackType::Object) => allows_objects,
+    (StackType::ManagedPointer, StackType::ManagedPointer) => true,
+    (StackType::ManagedPointer, _) | (_, StackType::ManagedPointer) => false,
+    _ => binary_result(BinaryOp::Numeric, a, b).is_some()
+  }
+}
+
+fn assignable(actual: StackType, expected: StackType) -> bool {
+  match (actual, expected) {
+    (StackType::Unknown, _) | (_, StackType::Unknown) => true,
+    (StackType::Int32, StackType::NativeInt) | (StackType::NativeInt, StackType::Int32) => true,
+    _ => actual == expected
+  }
+}
+
+fn merge_types(a: StackType, b: StackType) -> Option<StackType> {
+  if a == b {
+    Some(a)
+  } else if a == StackType::Unknown || b == StackType::Unknown {
+    Some(StackType::Unknown)
+  } else {
+    None
+  }
+}
+
+struct Verifier<'a> {
+  meta: &'a Metadata,
+  method: String,
+  body: &'a MethodBody,
+  args: Vec<StackType>,
+  locals: Vec<StackType>,
+  return_type: Option<StackType>,
+  regions: Vec<Region>
+}
+
+impl<'a> Verifier<'a> {
+  fn error(&self, offset: u32, reason: String) -> VerificationError {
+    VerificationError { method: self.method.clone(), offset, reason }
+  }
+
+  fn pop(&self, stack: &mut Vec<StackType>, offset: u32) -> VerifyResult<StackType> {
+    stack.pop().ok_or_else(|| self.error(offset, "Stack underflow".to_string()))
+  }
+
+  fn pop_assignable(&self, stack: &mut Vec<StackType>, offset: u32, expected: StackType) -> VerifyResult<()> {
+    let actual = self.pop(stack, offset)?;
+    if assignable(actual, expected) {
+      Ok(())
+    } else {
+      Err(self.error(offset, format!("Expected {} on the stack, found {}", expected, actual)))
+    }
+  }
+
+  // Pops an operand which has to be one of the given types
+  fn pop_one_of(&self, stack: &mut Vec<StackType>, offset: u32, expected: &[StackType]) -> VerifyResult<StackType> {
+    let actual = self.pop(stack, offset)?;
+    if actual == StackType::Unknown || expected.contains(&actual) {
+      Ok(actual)
+    } else {
+      let expected = expected.iter().map(|type_| type_.to_string()).collect::<Vec<_>>();
+      Err(self.error(offset, format!("Expected {} on the stack, found {}", expected.join(" or "), actual)))
+    }
+  }
+
+  fn pop_integer(&self, stack: &mut Vec<StackType>, offset: u32) -> VerifyResult<StackType> {
+    self.pop_one_of(stack, offset, &[StackType::Int32, StackType::NativeInt])
+  }
+
+  fn pop_address(&self, stack: &mut Vec<StackType>, offset: u32) -> VerifyResult<StackType> {
+    self.pop_one_of(stack, offset, &[StackType::ManagedPointer, StackType::NativeInt])
+  }
+
+  fn pop_object(&self, stack: &mut Vec<StackType>, offset: u32) -> VerifyResult<StackType> {
+    self.pop_one_of(stack, offset, &[StackType::Object])
+  }
+
+  fn stack_type_of(&self, type_sig: &TypeSig) -> StackType {
+    match *type_sig {
+      TypeSig::Boolean | TypeSig::Char | TypeSig::I1 | TypeSig::U1 | TypeSig::I2 | TypeSig::U2 |
+      TypeSig::I4 | TypeSig::U4 => StackType::Int32,
+      TypeSig::I8 | TypeSig::U8 => StackType::Int64,
+      TypeSig::I | TypeSig::U | TypeSig::Ptr(_) | TypeSig::FnPtr(_) => StackType::NativeInt,
+      TypeSig::R4 | TypeSig::R8 => StackType::Float,
+      TypeSig::String | TypeSig::Object | TypeSig::Class(_) | TypeSig::SzArray(_) | TypeSig::Array(_, _) => StackType::Object,
+      TypeSig::ByRef(_) => StackType::ManagedPointer,
+      TypeSig::TypedByRef => StackType::ValueType,
+      TypeSig::ValueType(ref type_) => self.value_type_stack_type(type_),
+      TypeSig::GenericInst(ref generic_type, _) => self.stack_type_of(generic_type),
+      TypeSig::CModReqd(_, ref inner) | TypeSig::CModOpt(_, ref inner) | TypeSig::Pinned(ref inner) => self.stack_type_of(inner),
+      TypeSig::Void | TypeSig::Var(_) | TypeSig::MVar(_) | TypeSig::Sentinel => StackType::Unknown
+    }
+  }
+
+  // Enums defined in this module behave like their underlying type. Value types from other
+  // assemblies might be enums as well, so nothing is assumed about them.
+  fn value_type_stack_type(&self, type_: &TypeDefOrRef) -> StackType {
+    let type_def = match *type_ {
+      TypeDefOrRef::TypeDef(type_def) => type_def,
+      TypeDefOrRef::TypeRef(_) => return StackType::Unknown,
+      TypeDefOrRef::TypeSpec(_) => return StackType::ValueType
+    };
+
+    let is_enum = self.meta.get_entry(&type_def)
+      .map(|entry| self.meta.get_type_name(&entry.extends) == "System.Enum")
+      .unwrap_or(false);
+
+    if !is_enum {
+      return StackType::ValueType;
+    }
+
+    self.meta.type_members(&type_def).fields.iter()
+      .filter(|field| self.meta.get_entry(*field).map(|entry| entry.flags & 0x0010 == 0).unwrap_or(false))
+      .filter_map(|field| self.meta.get_field_signature(field).ok())
+      .map(|field_sig| self.stack_type_of(&field_sig.type_))
+      .next()
+      .unwrap_or(StackType::Int32)
+  }
+
+  // The stack type of a type token, as used by box, ldobj, unbox.any and others
+  fn token_stack_type(&self, token: &Token) -> StackType {
+    match *token {
+      Token::Table(TableId::TypeDef, index) => {
+        let type_def = Index::<TypeDefEntry>::new(index);
+        match self.meta.get_entry(&type_def).map(|entry| self.meta.get_type_name(&entry.extends)) {
+          Some(ref base) if base == "System.ValueType" || base == "System.Enum" =>
+            self.value_type_stack_type(&TypeDefOrRef::TypeDef(type_def)),
+          _ => StackType::Object
+        }
+      },
+      Token::Table(TableId::TypeSpec, index) => self.meta.get_entry(&Index::<TypeSpecEntry>::new(index))
+        .and_then(|entry| self.meta.get_blob(&entry.signature))
+        .and_then(|blob| TypeSig::from_blob(blob).ok())
+        .map(|type_sig| self.stack_type_of(&type_sig))
+        .unwrap_or(StackType::Unknown),
+      _ => StackType::Unknown
+    }
+  }
+
+  fn method_signature(&self, token: &Token, offset: u32) -> VerifyResult<(MethodSignature, Option<MethodDefOrRef>)> {
+    let method = match *token {
+      Token::Table(TableId::MethodDef, index) => MethodDefOrRef::MethodDef(Index::new(index)),
+      Token::Table(TableId::MemberRef, index) => MethodDefOrRef::MethodRef(Index::new(index)),
+      Token::Table(TableId::MethodSpec, index) => self.meta.get_method_spec(&Index::new(index))
+        .map(|method_spec| method_spec.method)
+        .map_err(|error| self.error(offset, error.to_string()))?,
+      Token::Table(TableId::StandAloneSig, index) => {
+        let signature = self.meta.get_entry(&Index::<StandAloneSigEntry>::new(index))
+          .and_then(|entry| self.meta.get_blob(&entry.signature))
+          .ok_or_else(|| self.error(offset, format!("Invalid StandAloneSig token {:08x}", token.to_raw())))
+          .and_then(|blob| MethodSignature::from_blob(blob).map_err(|error| self.error(offset, error.to_string())))?;
+        return Ok((signature, None));
+      },
+      _ => return Err(self.error(offset, format!("Expected a method token, found {:08x}", token.to_raw())))
+    };
+
+    let signature = match method {
+      MethodDefOrRef::MethodDef(ref index) => self.meta.get_method_def_signature(index),
+      MethodDefOrRef::MethodRef(ref index) => self.meta.get_member_ref_signature(index)
+    };
+
+    signature.map(|signature| (signature, Some(method))).map_err(|error| self.error(offset, error.to_string()))
+  }
+
+  fn field_type(&self, token: &Token, offset: u32) -> VerifyResult<StackType> {
+    let blob = match *token {
+      Token::Table(TableId::Field, index) => self.meta.get_entry(&Index::<FieldEntry>::new(index))
+        .and_then(|entry| self.meta.get_blob(&entry.signature)),
+      Token::Table(TableId::MemberRef, index) => self.meta.get_entry(&Index::<MemberRefEntry>::new(index))
+        .and_then(|entry| self.meta.get_blob(&entry.signature)),
+      _ => None
+    };
+
+    blob.ok_or_else(|| self.error(offset, format!("Expected a field token, found {:08x}", token.to_raw())))
+      .and_then(|blob| FieldSig::from_blob(blob).map_err(|error| self.error(offset, error.to_string())))
+      .map(|field_sig| self.stack_type_of(&field_sig.type_))
+  }
+
+  // The type pushed by newobj, or the type of `this` inside a method
+  fn owner_stack_type(&self, method: &Option<MethodDefOrRef>) -> StackType {
+    match *method {
+      Some(MethodDefOrRef::MethodDef(ref index)) => match self.meta.get_method_owner(index) {
+        Some(owner) => self.token_stack_type(&Token::Table(TableId::TypeDef, owner.0)),
+        None => StackType::Unknown
+      },
+      Some(MethodDefOrRef::MethodRef(ref index)) => match self.meta.get_entry(index).map(|entry| entry.class) {
+        Some(MemberRefParent::TypeDef(owner)) => self.token_stack_type(&Token::Table(TableId::TypeDef, owner.0)),
+        Some(MemberRefParent::TypeSpec(owner)) => self.token_stack_type(&Token::Table(TableId::TypeSpec, owner.0)),
+        _ => StackType::Unknown
+      },
+      None => StackType::Unknown
+    }
+  }
+
+  fn arg(&self, index: u16, offset: u32) -> VerifyResult<StackType> {
+    self.args.get(index as usize).cloned()
+      .ok_or_else(|| self.error(offset, format!("Argument index {} is out of range, the method has {} arguments", index, self.args.len())))
+  }
+
+  fn local(&self, index: u16, offset: u32) -> VerifyResult<StackType> {
+    self.locals.get(index as usize).cloned()
+      .ok_or_else(|| self.error(offset, format!("Local variable index {} is out of range, the method has {} locals", index, self.locals.len())))
+  }
+
+  fn regions_containing(&self, offset: u32) -> Vec<Region> {
+    self.regions.iter().filter(|region| region.contains(offset)).cloned().collect()
+  }
+
+  fn check_branch(&self, offset: u32, target: u32) -> VerifyResult<()> {
+    let source_regions = self.regions_containing(offset);
+    let target_regions = self.regions_containing(target);
+
+    // Branches may only enter a try block through its first instruction
+    let leaves_region = source_regions.iter().any(|region| !target_regions.contains(region));
+    let enters_region = target_regions.iter()
+      .any(|region| !source_regions.contains(region) && !(region.kind == RegionKind::Try && region.start == target));
+
+    if leaves_region || enters_region {
+      Err(self.error(offset, format!("Branch to IL_{:04x} crosses the boundary of a protected region or handler", target)))
+    } else {
+      Ok(())
+    }
+  }
+
+  fn check_leave(&self, offset: u32, target: u32) -> VerifyResult<()> {
+    let source_regions = self.regions_containing(offset);
+
+    for region in self.regions_containing(target) {
+      if !source_regions.contains(&region) && !(region.kind == RegionKind::Try && region.start == target) {
+        return Err(self.error(offset, format!("leave target IL_{:04x} is inside a region which doesn't contain the leave", target)));
+      }
+    }
+
+    for region in source_regions {
+      let exits = !region.contains(target);
+      if exits && (region.kind == RegionKind::Filter || region.kind == RegionKind::Finally || region.kind == RegionKind::Fault) {
+        return Err(self.error(offset, format!("leave can't exit a {:?} block", region.kind).to_lowercase()));
+      }
+    }
+
+    Ok(())
+  }
+
+  fn in_region(&self, offset: u32, kinds: &[RegionKind]) -> bool {
+    self.regions_containing(offset).iter().any(|region| kinds.contains(&region.kind))
+  }
+
+  /// Applies the stack effect of a single instruction.
+  fn step(&self, offset: u32, instruction: &Instruction, stack: &mut Vec<StackType>) -> VerifyResult<()> {
+    use self::StackType::*;
+
+    match *instruction {
+      Instruction::Nop | Instruction::Break | Instruction::Unaligned(_) | Instruction::Volatile |
+      Instruction::Tail | Instruction::Constrained(_) | Instruction::No(_) | Instruction::Readonly => (),
+
+      Instruction::Ldarg0 => stack.push(self.arg(0, offset)?),
+      Instruction::Ldarg1 => stack.push(self.arg(1, offset)?),
+      Instruction::Ldarg2 => stack.push(self.arg(2, offset)?),
+      Instruction::Ldarg3 => stack.push(self.arg(3, offset)?),
+      Instruction::LdargS(index) => stack.push(self.arg(index as u16, offset)?),
+      Instruction::Ldarg(index) => stack.push(self.arg(index, offset)?),
+      Instruction::LdargaS(index) => {
+        self.arg(index as u16, offset)?;
+        stack.push(ManagedPointer);
+      },
+      Instruction::Ldarga(index) => {
+        self.arg(index, offset)?;
+        stack.push(ManagedPointer);
+      },
+      Instruction::StargS(index) => {
+        let expected = self.arg(index as u16, offset)?;
+        self.pop_assignable(stack, offset, expected)?;
+      },
+      Instruction::Starg(index) => {
+        let expected = self.arg(index, offset)?;
+        self.pop_assignable(stack, offset, expected)?;
+      },
+
+      Instruction::Ldloc0 => stack.push(self.local(0, offset)?),
+      Instruction::Ldloc1 => stack.push(self.local(1, offset)?),
+      Instruction::Ldloc2 => stack.push(self.local(2, offset)?),
+      Instruction::Ldloc3 => stack.push(self.local(3, offset)?),
+      Instruction::LdlocS(index) => stack.push(self.local(index as u16, offset)?),
+      Instruction::Ldloc(index) => stack.push(self.local(index, offset)?),
+      Instruction::LdlocaS(index) => {
+        self.local(index as u16, offset)?;
+        stack.push(ManagedPointer);
+      },
+      Instruction::Ldloca(index) => {
+        self.local(index, offset)?;
+        stack.push(ManagedPointer);
+      },
+      Instruction::Stloc0 | Instruction::Stloc1 | Instruction::Stloc2 | Instruction::Stloc3 |
+      Instruction::StlocS(_) | Instruction::Stloc(_) => {
+        let index = match *instruction {
+          Instruction::Stloc0 => 0,
+          Instruction::Stloc1 => 1,
+          Instruction::Stloc2 => 2,
+          Instruction::Stloc3 => 3,
+          Instruction::StlocS(index) => index as u16,
+          Instruction::Stloc(index) => index,
+          _ => unreachable!()
+        };
+        let expected = self.local(index, offset)?;
+        self.pop_assignable(stack, offset, expected)?;
+      },
+
+      Instruction::Ldnull | Instruction::Ldstr(_) => stack.push(Object),
+      Instruction::LdcI4M1 | Instruction::LdcI40 | Instruction::LdcI41 | Instruction::LdcI42 | Instruction::LdcI43 |
+      Instruction::LdcI44 | Instruction::LdcI45 | Instruction::LdcI46 | Instruction::LdcI47 | Instruction::LdcI48 |
+      Instruction::LdcI4S(_) | Instruction::LdcI4(_) => stack.push(Int32),
+      Instruction::LdcI8(_) => stack.push(Int64),
+      Instruction::LdcR4(_) | Instruction::LdcR8(_) => stack.push(Float),
+
+      Instruction::Dup => {
+        let value = self.pop(stack, offset)?;
+        stack.push(value);
+        stack.push(value);
+      },
+      Instruction::Pop => {
+        self.pop(stack, offset)?;
+      },
+
+      Instruction::Jmp(_) => if !stack.is_empty() {
+        return Err(self.error(offset, "The stack must be empty at jmp".to_string()));
+      },
+      Instruction::Call(ref token) | Instruction::Callvirt(ref token) | Instruction::Newobj(ref token) |
+      Instruction::Calli(ref token) => {
+        if let Instruction::Calli(_) = *instruction {
+          self.pop_one_of(stack, offset, &[NativeInt])?;
+        }
+
+        let (signature, method) = self.method_signature(token, offset)?;
+        for param in signature.params.iter().rev() {
+          let expected = self.stack_type_of(param);
+          self.pop_assignable(stack, offset, expected)?;
+        }
+
+        match *instruction {
+          Instruction::Newobj(_) => stack.push(self.owner_stack_type(&method)),
+          _ => {
+            if signature.has_this() {
+              self.pop_one_of(stack, offset, &[Object, ManagedPointer, ValueType])?;
+            }
+            if signature.return_type != TypeSig::Void {
+              stack.push(self.stack_type_of(&signature.return_type));
+            }
+          }
+        }
+      },
+      Instruction::Ret => {
+        if !self.regions_containing(offset).is_empty() {
+          return Err(self.error(offset, "ret isn't allowed inside a protected region or handler".to_string()));
+        }
+
+        if let Some(return_type) = self.return_type {
+          self.pop_assignable(stack, offset, return_type)?;
+        }
+
+        if !stack.is_empty() {
+          return Err(self.error(offset, format!("The stack must be empty after ret, but has {} more values", stack.len())));
+        }
+      },
+
+      Instruction::Br(_) | Instruction::BrS(_) => (),
+      Instruction::Brfalse(_) | Instruction::BrfalseS(_) | Instruction::Brtrue(_) | Instruction::BrtrueS(_) => {
+        self.pop_one_of(stack, offset, &[Int32, Int64, NativeInt, Object, ManagedPointer])?;
+      },
+      Instruction::Beq(_) | Instruction::BeqS(_) | Instruction::BneUn(_) | Instruction::BneUnS(_) |
+      Instruction::Ceq | Instruction::CgtUn => {
+        let b = self.pop(stack, offset)?;
+        let a = self.pop(stack, offset)?;
+        if !comparable(a, b, true) {
+          return Err(self.error(offset, format!("Can't compare {} with {}", a, b)));
+        }
+        match *instruction {
+          Instruction::Ceq | Instruction::CgtUn => stack.push(Int32),
+          _ => ()
+        }
+      },
+      Instruction::Bge(_) | Instruction::BgeS(_) | Instruction::Bgt(_) | Instruction::BgtS(_) |
+      Instruction::Ble(_) | Instruction::BleS(_) | Instruction::Blt(_) | Instruction::BltS(_) |
+      Instruction::BgeUn(_) | Instruction::BgeUnS(_) | Instruction::BgtUn(_) | Instruction::BgtUnS(_) |
+      Instruction::BleUn(_) | Instruction::BleUnS(_) | Instruction::BltUn(_) | Instruction::BltUnS(_) |
+      Instruction::Cgt | Instruction::Clt | Instruction::CltUn => {
+        let b = self.pop(stack, offset)?;
+        let a = self.pop(stack, offset)?;
+        if !comparable(a, b, false) {
+          return Err(self.error(offset, format!("Can't compare {} with {}", a, b)));
+        }
+        match *instruction {
+          Instruction::Cgt | Instruction::Clt | Instruction::CltUn => stack.push(Int32),
+          _ => ()
+        }
+      },
+      Instruction::Switch(_) => {
+        self.pop_integer(stack, offset)?;
+      },
+
+      Instruction::Add | Instruction::Sub | Instruction::Mul | Instruction::Div | Instruction::Rem |
+      Instruction::DivUn | Instruction::RemUn | Instruction::And | Instruction::Or | Instruction::Xor |
+      Instruction::AddOvf | Instruction::AddOvfUn | Instruction::MulOvf | Instruction::MulOvfUn |
+      Instruction::SubOvf | Instruction::SubOvfUn => {
+        let op = match *instruction {
+          Instruction::Add => BinaryOp::Add,
+          Instruction::Sub => BinaryOp::Sub,
+          Instruction::Mul | Instruction::Div | Instruction::Rem => BinaryOp::Numeric,
+          _ => BinaryOp::Integer
+        };
+
+        let b = self.pop(stack, offset)?;
+        let a = self.pop(stack, offset)?;
+        match binary_result(op, a, b) {
+          Some(result) => stack.push(result),
+          None => return Err(self.error(offset, format!("Invalid operands for {}: {} and {}", instruction.mnemonic(), a, b)))
+        }
+      },
+      Instruction::Shl | Instruction::Shr | Instruction::ShrUn => {
+        self.pop_integer(stack, offset)?;
+        let value = self.pop_one_of(stack, offset, &[Int32, Int64, NativeInt])?;
+        stack.push(value);
+      },
+      Instruction::Neg => {
+        let value = self.pop_one_of(stack, offset, &[Int32, Int64, NativeInt, Float])?;
+        stack.push(value);
+      },
+      Instruction::Not => {
+        let value = self.pop_one_of(stack, offset, &[Int32, Int64, NativeInt])?;
+        stack.push(value);
+      },
+      Instruction::Ckfinite => {
+        self.pop_one_of(stack, offset, &[Float])?;
+        stack.push(Float);
+      },
+
+      Instruction::ConvI1 | Instruction::ConvI2 | Instruction::ConvI4 | Instruction::ConvU1 | Instruction::ConvU2 |
+      Instruction::ConvU4 | Instruction::ConvOvfI1 | Instruction::ConvOvfI2 | Instruction::ConvOvfI4 |
+      Instruction::ConvOvfU1 | Instruction::ConvOvfU2 | Instruction::ConvOvfU4 | Instruction::ConvOvfI1Un |
+      Instruction::ConvOvfI2Un | Instruction::ConvOvfI4Un | Instruction::ConvOvfU1Un | Instruction::ConvOvfU2Un |
+      Instruction::ConvOvfU4Un => {
+        self.pop_one_of(stack, offset, &[Int32, Int64, NativeInt, Float, ManagedPointer])?;
+        stack.push(Int32);
+      },
+      Instruction::ConvI8 | Instruction::ConvU8 | Instruction::ConvOvfI8 | Instruction::ConvOvfU8 |
+      Instruction::ConvOvfI8Un | Instruction::ConvOvfU8Un => {
+        self.pop_one_of(stack, offset, &[Int32, Int64, NativeInt, Float, ManagedPointer])?;
+        stack.push(Int64);
+      },
+      Instruction::ConvI | Instruction::ConvU | Instruction::ConvOvfI | Instruction::ConvOvfU |
+      Instruction::ConvOvfIUn | Instruction::ConvOvfUUn => {
+        self.pop_one_of(stack, offset, &[Int32, Int64, NativeInt, Float, ManagedPointer])?;
+        stack.push(NativeInt);
+      },
+      Instruction::ConvR4 | Instruction::ConvR8 | Instruction::ConvRUn => {
+        self.pop_one_of(stack, offset, &[Int32, Int64, NativeInt, Float])?;
+        stack.push(Float);
+      },
+
+      Instruction::LdindI1 | Instruction::LdindU1 | Instruction::LdindI2 | Instruction::LdindU2 |
+      Instruction::LdindI4 | Instruction::LdindU4 | Instruction::LdindI8 | Instruction::LdindI |
+      Instruction::LdindR4 | Instruction::LdindR8 | Instruction::LdindRef => {
+        self.pop_address(stack, offset)?;
+        stack.push(match *instruction {
+          Instruction::LdindI8 => Int64,
+          Instruction::LdindI => NativeInt,
+          Instruction::LdindR4 | Instruction::LdindR8 => Float,
+          Instruction::LdindRef => Object,
+          _ => Int32
+        });
+      },
+      Instruction::StindI1 | Instruction::StindI2 | Instruction::StindI4 | Instruction::StindI8 |
+      Instruction::StindI | Instruction::StindR4 | Instruction::StindR8 | Instruction::StindRef => {
+        let expected = match *instruction {
+          Instruction::StindI8 => Int64,
+          Instruction::StindI => NativeInt,
+          Instruction::StindR4 | Instruction::StindR8 => Float,
+          Instruction::StindRef => Object,
+          _ => Int32
+        };
+        self.pop_assignable(stack, offset, expected)?;
+        self.pop_address(stack, offset)?;
+      },
+
+      Instruction::Ldobj(ref token) => {
+        self.pop_address(stack, offset)?;
+        stack.push(self.token_stack_type(token));
+      },
+      Instruction::Stobj(ref token) => {
+        let expected = self.token_stack_type(token);
+        self.pop_assignable(stack, offset, expected)?;
+        self.pop_address(stack, offset)?;
+      },
+      Instruction::Cpobj(_) => {
+        self.pop_address(stack, offset)?;
+        self.pop_address(stack, offset)?;
+      },
+      Instruction::Initobj(_) => {
+        self.pop_address(stack, offset)?;
+      },
+      Instruction::Cpblk | Instruction::Initblk => {
+        self.pop_integer(stack, offset)?;
+        self.pop(stack, offset)?;
+        self.pop_address(stack, offset)?;
+      },
+      Instruction::Localloc => {
+        self.pop_integer(stack, offset)?;
+        stack.push(NativeInt);
+      },
+      Instruction::Sizeof(_) => stack.push(Int32),
+
+      Instruction::Ldfld(ref token) | Instruction::Ldflda(ref token) => {
+        let field_type = self.field_type(token, offset)?;
+        self.pop_one_of(stack, offset, &[Object, ManagedPointer, ValueType, NativeInt])?;
+        stack.push(match *instruction {
+          Instruction::Ldflda(_) => ManagedPointer,
+          _ => field_type
+        });
+      },
+      Instruction::Stfld(ref token) => {
+        let field_type = self.field_type(token, offset)?;
+        self.pop_assignable(stack, offset, field_type)?;
+        self.pop_one_of(stack, offset, &[Object, ManagedPointer, NativeInt])?;
+      },
+      Instruction::Ldsfld(ref token) => stack.push(self.field_type(token, offset)?),
+      Instruction::Ldsflda(ref token) => {
+        self.field_type(token, offset)?;
+        stack.push(ManagedPointer);
+      },
+      Instruction::Stsfld(ref token) => {
+        let field_type = self.field_type(token, offset)?;
+        self.pop_assignable(stack, offset, field_type)?;
+      },
+
+      Instruction::Castclass(_) | Instruction::Isinst(_) => {
+        self.pop_object(stack, offset)?;
+        stack.push(Object);
+      },
+      Instruction::Box(ref token) => {
+        let expected = self.token_stack_type(token);
+        self.pop_assignable(stack, offset, expected)?;
+        stack.push(Object);
+      },
+      Instruction::Unbox(_) => {
+        self.pop_object(stack, offset)?;
+        stack.push(ManagedPointer);
+      },
+      Instruction::UnboxAny(ref token) => {
+        self.pop_object(stack, offset)?;
+        stack.push(self.token_stack_type(token));
+      },
+
+      Instruction::Newarr(_) => {
+        self.pop_integer(stack, offset)?;
+        stack.push(Object);
+      },
+      Instruction::Ldlen => {
+        self.pop_object(stack, offset)?;
+        stack.push(NativeInt);
+      },
+      Instruction::Ldelema(_) | Instruction::Ldelem(_) | Instruction::LdelemI1 | Instruction::LdelemU1 |
+      Instruction::LdelemI2 | Instruction::LdelemU2 | Instruction::LdelemI4 | Instruction::LdelemU4 |
+      Instruction::LdelemI8 | Instruction::LdelemI | Instruction::LdelemR4 | Instruction::LdelemR8 |
+      Instruction::LdelemRef => {
+        self.pop_integer(stack, offset)?;
+        self.pop_object(stack, offset)?;
+        stack.push(match *instruction {
+          Instruction::Ldelema(_) => ManagedPointer,
+          Instruction::Ldelem(ref token) => self.token_stack_type(token),
+          Instruction::LdelemI8 => Int64,
+          Instruction::LdelemI => NativeInt,
+          Instruction::LdelemR4 | Instruction::LdelemR8 => Float,
+          Instruction::LdelemRef => Object,
+          _ => Int32
+        });
+      },
+      Instruction::Stelem(_) | Instruction::StelemI | Instruction::StelemI1 | Instruction::StelemI2 |
+      Instruction::StelemI4 | Instruction::StelemI8 | Instruction::StelemR4 | Instruction::StelemR8 |
+      Instruction::StelemRef => {
+        let expected = match *instruction {
+          Instruction::Stelem(ref token) => self.token_stack_type(token),
+          Instruction::StelemI8 => Int64,
+          Instruction::StelemI => NativeInt,
+          Instruction::StelemR4 | Instruction::StelemR8 => Float,
+          Instruction::StelemRef => Object,
+          _ => Int32
+        };
+        self.pop_assignable(stack, offset, expected)?;
+        self.pop_integer(stack, offset)?;
+        self.pop_object(stack, offset)?;
+      },
+
+      Instruction::Ldtoken(_) | Instruction::Arglist | Instruction::Refanytype => {
+        if let Instruction::Refanytype = *instruction {
+          self.pop_one_of(stack, offset, &[ValueType])?;
+        }
+        stack.push(ValueType);
+      },
+      Instruction::Mkrefany(_) => {
+        self.pop_address(stack, offset)?;
+        stack.push(ValueType);
+      },
+      Instruction::Refanyval(_) => {
+        self.pop_one_of(stack, offset, &[ValueType])?;
+        stack.push(ManagedPointer);
+      },
+      Instruction::Ldftn(_) => stack.push(NativeInt),
+      Instruction::Ldvirtftn(_) => {
+        self.pop_object(stack, offset)?;
+        stack.push(NativeInt);
+      },
+
+      Instruction::Throw => {
+        self.pop_object(stack, offset)?;
+      },
+      Instruction::Rethrow => if !self.in_region(offset, &[RegionKind::Catch]) {
+        return Err(self.error(offset, "rethrow is only allowed inside a catch handler".to_string()));
+      },
+      Instruction::Leave(_) | Instruction::LeaveS(_) => if !stack.is_empty() {
+        return Err(self.error(offset, format!("The stack must be empty at leave, but has {} values", stack.len())));
+      },
+      Instruction::Endfinally => if !self.in_region(offset, &[RegionKind::Finally, RegionKind::Fault]) {
+        return Err(self.error(offset, "endfinally is only allowed inside a finally or fault handler".to_string()));
+      },
+      Instruction::Endfilter => {
+        if !self.in_region(offset, &[RegionKind::Filter]) {
+          return Err(self.error(offset, "endfilter is only allowed inside a filter".to_string()));
+        }
+        self.pop_one_of(stack, offset, &[Int32])?;
+        if !stack.is_empty() {
+          return Err(self.error(offset, "The stack must be empty after endfilter".to_string()));
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn verify(&self) -> VerifyResult<()> {
+    let mut instructions = vec![];
+    let mut reader = Cursor::new(&self.body.code[..]);
+    while (reader.position() as usize) < self.body.code.len() {
+      let offset = reader.position() as u32;
+      let instruction = Instruction::read(&mut reader).map_err(|error| self.error(offset, error.to_string()))?;
+      instructions.push((offset, instruction));
+    }
+
+    let positions = instructions.iter().enumerate().map(|(position, &(offset, _))| (offset, position)).collect::<HashMap<_, _>>();
+    let position_of = |offset: u32, target: u32| positions.get(&target).cloned()
+      .ok_or_else(|| self.error(offset, format!("Branch target IL_{:04x} is not the start of an instruction", target)));
+
+    for region in &self.regions {
+      if !positions.contains_key(&region.start) || (region.end != self.body.code.len() as u32 && !positions.contains_key(&region.end)) {
+        return Err(self.error(region.start, format!("{:?} region IL_{:04x}-IL_{:04x} doesn't match instruction boundaries", region.kind, region.start, region.end)));
+      }
+    }
+
+    let max_stack = self.body.header.max_stack as usize;
+    let mut states: Vec<Option<Vec<StackType>>> = vec![None; instructions.len()];
+    let mut pending = vec![];
+
+    let merge = |states: &mut Vec<Option<Vec<StackType>>>, pending: &mut Vec<usize>, position: usize, stack: &Vec<StackType>| -> VerifyResult<()> {
+      let offset = instructions[position].0;
+
+      if !stack.is_empty() && self.regions.iter().any(|region| region.kind == RegionKind::Try && region.start == offset) {
+        return Err(self.error(offset, "The stack must be empty on entry to a try block".to_string()));
+      }
+
+      let merged = match states[position] {
+        None => stack.clone(),
+        Some(ref existing) => {
+          if existing.len() != stack.len() {
+            return Err(self.error(offset, format!("Stack depth differs between paths: {} and {}", existing.len(), stack.len())));
+          }
+
+          let mut merged = vec![];
+          for (&a, &b) in existing.iter().zip(stack.iter()) {
+            match merge_types(a, b) {
+              Some(type_) => merged.push(type_),
+              None => return Err(self.error(offset, format!("Stack types differ between paths: {} and {}", a, b)))
+            }
+          }
+
+          if merged == *existing {
+            return Ok(());
+          }
+          merged
+        }
+      };
+
+      states[position] = Some(merged);
+      pending.push(position);
+      Ok(())
+    };
+
+    if !instructions.is_empty() {
+      merge(&mut states, &mut pending, 0, &vec![])?;
+    }
+
+    for clause in &self.body.exception_clauses {
+      let exception = vec![StackType::Object];
+      let handler_stack = match clause.kind {
+        ExceptionClauseKind::Finally | ExceptionClauseKind::Fault => vec![],
+        _ => exception.clone()
+      };
+
+      let handler = position_of(clause.handler_offset, clause.handler_offset)?;
+      merge(&mut states, &mut pending, handler, &handler_stack)?;
+
+      if let ExceptionClauseKind::Filter(filter_offset) = clause.kind {
+        let filter = position_of(filter_offset, filter_offset)?;
+        merge(&mut states, &mut pending, filter, &exception)?;
+      }
+    }
+
+    while let Some(position) = pending.pop() {
+      let (offset, ref instruction) = instructions[position];
+      let mut stack = states[position].clone().unwrap();
+
+      self.step(offset, instruction, &mut stack)?;
+
+      if stack.len() > max_stack {
+        return Err(self.error(offset, format!("Stack depth {} exceeds .maxstack {}", stack.len(), max_stack)));
+      }
+
+      match *instruction {
+        Instruction::Leave(target) | Instruction::LeaveS(target) => {
+          let target_position = position_of(offset, target)?;
+          self.check_leave(offset, target)?;
+          merge(&mut states, &mut pending, target_position, &vec![])?;
+        },
+        Instruction::Switch(ref targets) => for &target in targets {
+          let target_position = position_of(offset, target)?;
+          self.check_branch(offset, target)?;
+          merge(&mut states, &mut pending, target_position, &stack)?;
+        },
+        ref other => if let Some(target) = other.branch_target() {
+          let target_position = position_of(offset, target)?;
+          self.check_branch(offset, target)?;
+          merge(&mut states, &mut pending, target_position, &stack)?;
+        }
+      }
+
+      if !ends_flow(instruction) {
+        if position + 1 >= instructions.len() {
+          return Err(self.error(offset, "Control falls through the end of the method".to_string()));
+        }
+
+        let next = instructions[position + 1].0;
+        if !instruction.is_prefix() {
+          self.check_branch(offset, next)?;
+        }
+        merge(&mut states, &mut pending, position + 1, &stack)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl Metadata {
+  /// Verifies the IL of a method: stack depths and types, branch targets, local and argument indices,
+  /// and control transfers between exception handling regions. Methods without a body always pass.
+  pub fn verify_method(&self, method: &Index<MethodDefEntry>) -> VerifyResult<()> {
+    let name = self.get_method_full_name(&MethodDefOrRef::MethodDef(*method));
+    let error = |reason: String| VerificationError { method: name.clone(), offset: 0, reason };
+
+    let position = method.0.checked_sub(1).ok_or_else(|| error("Invalid method index 0".to_string()))?;
+    let body = match self.method_bodies.get(&position) {
+      Some(body) => body,
+      None => return Ok(())
+    };
+
+    let signature = self.get_method_def_signature(method).map_err(|e| error(e.to_string()))?;
+    let locals = self.get_local_var_signature(&body.header).map_err(|e| error(e.to_string()))?;
+
+    let mut verifier = Verifier {
+      meta: self,
+      method: name.clone(),
+      body,
+      args: vec![],
+      locals: vec![],
+      return_type: None,
+      regions: vec![]
+    };
+
+    if signature.has_this() {
+      let this_type = match verifier.owner_stack_type(&Some(MethodDefOrRef::MethodDef(*method))) {
+        StackType::Object => StackType::Object,
+        StackType::Unknown => StackType::Unknown,
+        _ => StackType::ManagedPointer
+      };
+      verifier.args.push(this_type);
+    }
+
+    let params = signature.params.iter().map(|param| verifier.stack_type_of(param)).collect::<Vec<_>>();
+    verifier.args.extend(params);
+
+    if let Some(locals) = locals {
+      let locals = locals.locals.iter()
+        .map(|local| if local.by_ref { StackType::ManagedPointer } else { verifier.stack_type_of(&local.type_) })
+        .collect();
+      verifier.locals = locals;
+    }
+
+    if signature.return_type != TypeSig::Void {
+      let return_type = verifier.stack_type_of(&signature.return_type);
+      verifier.return_type = Some(return_type);
+    }
+
+    for clause in &body.exception_clauses {
+      // Offsets and lengths come straight from the image, so their sums may overflow
+      let region_end = |start: u32, length: u32| start.checked_add(length)
+        .ok_or_else(|| VerificationError { method: name.clone(), offset: start, reason: "Exception handling region is out of range".to_string() });
+
+      verifier.regions.push(Region { start: clause.try_offset, end: region_end(clause.try_offset, clause.try_length)?, kind: RegionKind::Try });
+
+      let kind = match clause.kind {
+        ExceptionClauseKind::Catch(_) => RegionKind::Catch,
+        ExceptionClauseKind::Filter(filter_offset) => {
+          verifier.regions.push(Region { start: filter_offset, end: clause.handler_offset, kind: RegionKind::Filter });
+          RegionKind::Catch
+        },
+        ExceptionClauseKind::Finally => RegionKind::Finally,
+        ExceptionClauseKind::Fault => RegionKind::Fault
+      };
+      verifier.regions.push(Region { start: clause.handler_offset, end: region_end(clause.handler_offset, clause.handler_length)?, kind });
+    }
+
+    // Clauses protecting the same block share its try region
+    let mut unique = vec![];
+    for region in verifier.regions.drain(..) {
+      if !unique.contains(&region) {
+        unique.push(region);
+      }
+    }
+    verifier.regions = unique;
+
+    verifier.verify()
+  }
+
+  /// Verifies every method with a body, returning the first error found in each failing method.
+  pub fn verify_all(&self) -> Vec<VerificationError> {
+    let method_count = self.get_table::<MethodDefEntry>().map(|x| x.len()).unwrap_or(0) as u32;
+
+    (1 .. method_count + 1)
+      .filter_map(|method| self.verify_method(&Index::new(method)).err())
+      .collect()
+  }
+}
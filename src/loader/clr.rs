@@ -1,5 +1,5 @@
 
-use std::io::{Read, Seek, Cursor, SeekFrom, Result};
+use std::io::{Read, Seek, Cursor, SeekFrom, Result, Error, ErrorKind};
 use std::collections::HashMap;
 use std::iter::FromIterator;
 
@@ -9,9 +9,12 @@ use utils::stream::*;
 use loader::pe::{DataDirectory, Section, PEFile};
 use loader::code::*;
 use metadata::heap::{StringHeap, UserStringHeap, BlobHeap, Heaps};
-use loader::stream::{StreamHeader, MetaDataTablesStream, StreamReader};
+use loader::stream::{StreamHeader, MetaDataTablesStream, StreamReader, TableId};
+use loader::instructions::Token;
 
 use metadata::Metadata;
+use metadata::signature::TypeSig;
+use metadata::tables::{Index, MethodDefEntry, FieldEntry, FieldRVAEntry, ClassLayoutEntry, TypeDefOrRef};
 
 #[derive(Debug)]
 pub struct CLIHeader {
@@ -135,11 +138,10 @@ impl CLRImage {
 
     let strings: StringHeap = read_stream(&mut reader, metadata_header_offset, &metadata_header, "#Strings")?;
     let user_strings: UserStringHeap = read_stream(&mut reader, metadata_header_offset, &metadata_header, "#US")?;
+    let blobs: BlobHeap = read_stream(&mut reader, metadata_header_offset, &metadata_header, "#Blob")?;
     let metadata_stream: MetaDataTablesStream = read_stream(&mut reader, metadata_header_offset, &metadata_header, "#~")?;
 
-    let heaps = Heaps {
-      strings, user_strings, blobs: BlobHeap { blobs: HashMap::new() }
-    };
+    let heaps = Heaps { strings, user_strings, blobs };
 
     let mut method_bodies = HashMap::new();
     
@@ -148,6 +150,11 @@ impl CLRImage {
       let method_defs = metadata_stream.tables.get::<MethodDefEntry>().unwrap();
 
       for (i, method_def) in method_defs.iter().enumerate() {
+        // Abstract, runtime-implemented and P/Invoke methods have no body
+        if method_def.rva == 0 {
+          continue;
+        }
+
         let (section, offset) = pe.rva_to_section_offset(method_def.rva).unwrap();
         // CONSIDER not creating a new cursor for each method
         let mut cursor = Cursor::new(&section.data);
@@ -157,8 +164,47 @@ impl CLRImage {
       }
     }
 
-    let metadata = Metadata { heaps, tables: metadata_stream.tables, method_bodies };
+    let mut metadata = Metadata { heaps, tables: metadata_stream.tables, method_bodies, field_data: HashMap::new() };
+
+    let mut field_data = vec![];
+    for entry in metadata.get_table::<FieldRVAEntry>().map(|entries| &entries[..]).unwrap_or(&[]) {
+      let size = field_data_size(&metadata, &entry.field);
+      if let (Some((section, offset)), Some(size)) = (pe.rva_to_section_offset(entry.rva), size) {
+        let data = section.data.get(offset as usize .. offset as usize + size)
+          .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("The data of field {} is outside of its section", entry.field.0)))?;
+        field_data.push((entry.field.0 - 1, data.to_vec()));
+      }
+    }
+    metadata.field_data.extend(field_data);
 
     Ok(CLRImage { cli_header, strong_name_signature, metadata })
   }
+
+  /// The method to run when the image is executed, if it has one.
+  pub fn entry_point(&self) -> Option<Index<MethodDefEntry>> {
+    match Token::from_raw(self.cli_header.entry_point_token) {
+      Ok(Token::Table(TableId::MethodDef, index)) => Some(Index::new(index)),
+      _ => None
+    }
+  }
+}
+
+/// The size of the data of a field with an RVA. Array initializers are stored in fields of structs
+/// whose size is given by the ClassLayout table.
+pub fn field_data_size(meta: &Metadata, field: &Index<FieldEntry>) -> Option<usize> {
+  let type_ = match meta.get_field_signature(field) {
+    Ok(signature) => signature.type_,
+    Err(_) => return None
+  };
+
+  match type_ {
+    TypeSig::Boolean | TypeSig::I1 | TypeSig::U1 => Some(1),
+    TypeSig::Char | TypeSig::I2 | TypeSig::U2 => Some(2),
+    TypeSig::I4 | TypeSig::U4 | TypeSig::R4 => Some(4),
+    TypeSig::I8 | TypeSig::U8 | TypeSig::R8 => Some(8),
+    TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)) => meta.get_table::<ClassLayoutEntry>()
+      .and_then(|layouts| layouts.iter().find(|layout| layout.parent == type_def))
+      .map(|layout| layout.class_size as usize),
+    _ => None
+  }
 }
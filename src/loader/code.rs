@@ -1,7 +1,7 @@
 
 #![allow(non_upper_case_globals)]
 
-use std::io::{Read, Result};
+use std::io::{Read, Result, Error, ErrorKind};
 use byteorder::{ReadBytesExt, LittleEndian};
 
 bitflags! {
@@ -16,19 +16,93 @@ bitflags! {
 
 #[derive(Debug)]
 pub struct MethodHeader {
-  flags: MethodHeaderFlags,
+  pub flags: MethodHeaderFlags,
   // Present, but useless
   //header_size: u8,
-  max_stack: u16,
-  code_size: u32,
-  local_var_signature_token: u32
+  pub max_stack: u16,
+  pub code_size: u32,
+  pub local_var_signature_token: u32
+}
+
+bitflags! {
+  pub flags SectionFlags: u8 {
+    const EHTable      = 0x01,
+    const OptILTable   = 0x02,
+    const FatSection   = 0x40,
+    const MoreSects    = 0x80
+  }
+}
+
+/// What an exception handling clause does, along with its kind-specific data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionClauseKind {
+  /// A typed catch handler with the metadata token of the caught type.
+  Catch(u32),
+  /// A filter, which starts at the given IL offset and ends where the handler starts.
+  Filter(u32),
+  Finally,
+  Fault
+}
+
+/// An exception handling clause (ECMA 335, II.25.4.6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionClause {
+  pub kind: ExceptionClauseKind,
+  pub try_offset: u32,
+  pub try_length: u32,
+  pub handler_offset: u32,
+  pub handler_length: u32
+}
+
+impl ExceptionClause {
+  fn from_parts(flags: u32, try_offset: u32, try_length: u32, handler_offset: u32, handler_length: u32, extra: u32) -> Result<ExceptionClause> {
+    let kind = match flags {
+      0x0000 => ExceptionClauseKind::Catch(extra),
+      0x0001 => ExceptionClauseKind::Filter(extra),
+      0x0002 => ExceptionClauseKind::Finally,
+      0x0004 => ExceptionClauseKind::Fault,
+      otherwise => return Err(Error::new(ErrorKind::InvalidData, format!("Invalid exception clause flags: {:x}", otherwise)))
+    };
+
+    Ok(ExceptionClause { kind, try_offset, try_length, handler_offset, handler_length })
+  }
+
+  fn read_small<R: Read>(reader: &mut R) -> Result<ExceptionClause> {
+    let flags = reader.read_u16::<LittleEndian>()? as u32;
+    let try_offset = reader.read_u16::<LittleEndian>()? as u32;
+    let try_length = reader.read_u8()? as u32;
+    let handler_offset = reader.read_u16::<LittleEndian>()? as u32;
+    let handler_length = reader.read_u8()? as u32;
+    let extra = reader.read_u32::<LittleEndian>()?;
+
+    ExceptionClause::from_parts(flags, try_offset, try_length, handler_offset, handler_length, extra)
+  }
+
+  fn read_fat<R: Read>(reader: &mut R) -> Result<ExceptionClause> {
+    let flags = reader.read_u32::<LittleEndian>()?;
+    let try_offset = reader.read_u32::<LittleEndian>()?;
+    let try_length = reader.read_u32::<LittleEndian>()?;
+    let handler_offset = reader.read_u32::<LittleEndian>()?;
+    let handler_length = reader.read_u32::<LittleEndian>()?;
+    let extra = reader.read_u32::<LittleEndian>()?;
+
+    ExceptionClause::from_parts(flags, try_offset, try_length, handler_offset, handler_length, extra)
+  }
+
+  pub fn try_contains(&self, offset: u32) -> bool {
+    self.try_offset <= offset && offset < self.try_offset + self.try_length
+  }
+
+  pub fn handler_contains(&self, offset: u32) -> bool {
+    self.handler_offset <= offset && offset < self.handler_offset + self.handler_length
+  }
 }
 
 impl MethodHeader {
   pub fn read<R: Read>(reader: &mut R) -> Result<MethodHeader> {
-    let flags_and_tiny_size = reader.read_u16::<LittleEndian>()?;
+    let first_byte = reader.read_u8()?;
 
-    let is_tiny = (flags_and_tiny_size & 0b11) as u16 == TinyFormat.bits();
+    let is_tiny = (first_byte & 0b11) as u16 == TinyFormat.bits();
 
     let flags: MethodHeaderFlags;
     let max_stack: u16;
@@ -37,11 +111,13 @@ impl MethodHeader {
 
     if is_tiny {
       flags = TinyFormat;
-      code_size = ((flags_and_tiny_size & 0xFC) >> 2) as u32;
+      // The tiny header is a single byte
+      code_size = (first_byte >> 2) as u32;
       max_stack = 8;
       local_var_signature_token = 0;
     } else {
-      flags = MethodHeaderFlags::from_bits(flags_and_tiny_size & 0xFFF).unwrap();
+      let flags_and_size = (first_byte as u16) | ((reader.read_u8()? as u16) << 8);
+      flags = MethodHeaderFlags::from_bits_truncate(flags_and_size & 0xFFF);
       // header size should be (flags_and_tiny_size >> 12)
       max_stack = reader.read_u16::<LittleEndian>()?;
       code_size = reader.read_u32::<LittleEndian>()?;
@@ -54,8 +130,9 @@ impl MethodHeader {
 
 #[derive(Debug)]
 pub struct MethodBody {
-  header: MethodHeader,
-  code: Vec<u8>
+  pub header: MethodHeader,
+  pub code: Vec<u8>,
+  pub exception_clauses: Vec<ExceptionClause>
 }
 
 impl MethodBody {
@@ -64,6 +141,56 @@ impl MethodBody {
     let mut code = vec![0u8; header.code_size as usize];
     reader.read_exact(&mut code)?;
 
-    Ok(MethodBody { header, code })
+    let mut exception_clauses = vec![];
+
+    if header.flags.contains(MoreSections) {
+      // Data sections start at the next 4-byte boundary after the code. Only fat headers (12 bytes,
+      // always 4-byte aligned themselves) can have them.
+      let padding = (4 - (12 + header.code_size) % 4) % 4;
+      for _ in 0 .. padding {
+        reader.read_u8()?;
+      }
+
+      loop {
+        let section_flags = SectionFlags::from_bits_truncate(reader.read_u8()?);
+
+        // The data size includes the 4-byte section header
+        let data_size = if section_flags.contains(FatSection) {
+          (reader.read_u8()? as u32) | ((reader.read_u8()? as u32) << 8) | ((reader.read_u8()? as u32) << 16)
+        } else {
+          let data_size = reader.read_u8()? as u32;
+          // Reserved
+          reader.read_u16::<LittleEndian>()?;
+          data_size
+        };
+
+        let data_size = data_size.saturating_sub(4);
+
+        let mut unread = data_size;
+
+        if section_flags.contains(EHTable) {
+          let clause_size = if section_flags.contains(FatSection) { 24 } else { 12 };
+
+          for _ in 0 .. data_size / clause_size {
+            exception_clauses.push(if clause_size == 24 {
+              ExceptionClause::read_fat(reader)?
+            } else {
+              ExceptionClause::read_small(reader)?
+            });
+          }
+
+          unread = data_size % clause_size;
+        }
+
+        let mut data = vec![0u8; unread as usize];
+        reader.read_exact(&mut data)?;
+
+        if !section_flags.contains(MoreSects) {
+          break;
+        }
+      }
+    }
+
+    Ok(MethodBody { header, code, exception_clauses })
   }
 }
@@ -0,0 +1,436 @@
+use std::io::{Cursor, Result, Error, ErrorKind};
+use byteorder::{ReadBytesExt, LittleEndian};
+use enum_primitive::FromPrimitive;
+
+use loader::code::MethodBody;
+use loader::stream::TableId;
+
+/// A metadata token, as used by instruction operands. The upper byte selects the table,
+/// the lower 3 bytes are a 1-based row index or, for strings, an offset into the #US heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Token {
+  Table(TableId, u32),
+  UserString(u32)
+}
+
+impl Token {
+  pub fn from_raw(raw: u32) -> Result<Token> {
+    let table = (raw >> 24) as u8;
+    let index = raw & 0x00FF_FFFF;
+
+    if table == 0x70 {
+      return Ok(Token::UserString(index));
+    }
+
+    TableId::from_u8(table)
+      .map(|table_id| Token::Table(table_id, index))
+      .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Invalid metadata token: {:08x}", raw)))
+  }
+
+  pub fn to_raw(&self) -> u32 {
+    match *self {
+      Token::Table(table_id, index) => ((table_id as u32) << 24) | index,
+      Token::UserString(index) => 0x7000_0000 | index
+    }
+  }
+}
+
+macro_rules! operand_type {
+  (u8) => (u8);
+  (u16) => (u16);
+  (i8) => (i8);
+  (i32) => (i32);
+  (i64) => (i64);
+  (f32) => (f32);
+  (f64) => (f64);
+  (token) => (Token);
+  (target8) => (u32);
+  (target32) => (u32);
+  (switch) => (Vec<u32>);
+}
+
+// Branch targets are relative to the start of the next instruction, and are stored as absolute offsets
+macro_rules! read_operand {
+  ($reader: expr, u8) => ($reader.read_u8()?);
+  ($reader: expr, u16) => ($reader.read_u16::<LittleEndian>()?);
+  ($reader: expr, i8) => ($reader.read_i8()?);
+  ($reader: expr, i32) => ($reader.read_i32::<LittleEndian>()?);
+  ($reader: expr, i64) => ($reader.read_i64::<LittleEndian>()?);
+  ($reader: expr, f32) => ($reader.read_f32::<LittleEndian>()?);
+  ($reader: expr, f64) => ($reader.read_f64::<LittleEndian>()?);
+  ($reader: expr, token) => (Token::from_raw($reader.read_u32::<LittleEndian>()?)?);
+  ($reader: expr, target8) => ({
+    let delta = $reader.read_i8()? as i64;
+    ($reader.position() as i64 + delta) as u32
+  });
+  ($reader: expr, target32) => ({
+    let delta = $reader.read_i32::<LittleEndian>()? as i64;
+    ($reader.position() as i64 + delta) as u32
+  });
+  ($reader: expr, switch) => ({
+    let count = $reader.read_u32::<LittleEndian>()?;
+    let mut deltas = Vec::with_capacity(::std::cmp::min(count, 1024) as usize);
+    for _ in 0 .. count {
+      deltas.push($reader.read_i32::<LittleEndian>()? as i64);
+    }
+    let base = $reader.position() as i64;
+    deltas.into_iter().map(|delta| (base + delta) as u32).collect::<Vec<_>>()
+  });
+}
+
+macro_rules! instructions {
+  {
+    one_byte: [$($opcode: expr => $name: ident $(($kind: ident))* = $mnemonic: expr),*],
+    two_byte: [$($opcode2: expr => $name2: ident $(($kind2: ident))* = $mnemonic2: expr),*]
+  } => {
+    /// A CIL instruction (ECMA 335, Partition III). Prefixes are decoded as separate instructions.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Instruction {
+      $($name $((operand_type!($kind)))*),*,
+      $($name2 $((operand_type!($kind2)))*),*
+    }
+
+    impl Instruction {
+      /// Decodes the instruction at the current position of the reader.
+      pub fn read(reader: &mut Cursor<&[u8]>) -> Result<Instruction> {
+        let offset = reader.position();
+
+        Ok(match reader.read_u8()? {
+          0xFE => match reader.read_u8()? {
+            $($opcode2 => Instruction::$name2 $((read_operand!(reader, $kind2)))*),*,
+            otherwise => return Err(Error::new(ErrorKind::InvalidData,
+              format!("Invalid opcode fe {:02x} at IL_{:04x}", otherwise, offset)))
+          },
+          $($opcode => Instruction::$name $((read_operand!(reader, $kind)))*),*,
+          otherwise => return Err(Error::new(ErrorKind::InvalidData,
+            format!("Invalid opcode {:02x} at IL_{:04x}", otherwise, offset)))
+        })
+      }
+
+      pub fn mnemonic(&self) -> &'static str {
+        match *self {
+          $(Instruction::$name { .. } => $mnemonic),*,
+          $(Instruction::$name2 { .. } => $mnemonic2),*
+        }
+      }
+
+      pub fn opcode(&self) -> u16 {
+        match *self {
+          $(Instruction::$name { .. } => $opcode),*,
+          $(Instruction::$name2 { .. } => 0xFE00 | $opcode2),*
+        }
+      }
+    }
+  }
+}
+
+instructions! {
+  one_byte: [
+    0x00 => Nop = "nop",
+    0x01 => Break = "break",
+    0x02 => Ldarg0 = "ldarg.0",
+    0x03 => Ldarg1 = "ldarg.1",
+    0x04 => Ldarg2 = "ldarg.2",
+    0x05 => Ldarg3 = "ldarg.3",
+    0x06 => Ldloc0 = "ldloc.0",
+    0x07 => Ldloc1 = "ldloc.1",
+    0x08 => Ldloc2 = "ldloc.2",
+    0x09 => Ldloc3 = "ldloc.3",
+    0x0A => Stloc0 = "stloc.0",
+    0x0B => Stloc1 = "stloc.1",
+    0x0C => Stloc2 = "stloc.2",
+    0x0D => Stloc3 = "stloc.3",
+    0x0E => LdargS(u8) = "ldarg.s",
+    0x0F => LdargaS(u8) = "ldarga.s",
+    0x10 => StargS(u8) = "starg.s",
+    0x11 => LdlocS(u8) = "ldloc.s",
+    0x12 => LdlocaS(u8) = "ldloca.s",
+    0x13 => StlocS(u8) = "stloc.s",
+    0x14 => Ldnull = "ldnull",
+    0x15 => LdcI4M1 = "ldc.i4.m1",
+    0x16 => LdcI40 = "ldc.i4.0",
+    0x17 => LdcI41 = "ldc.i4.1",
+    0x18 => LdcI42 = "ldc.i4.2",
+    0x19 => LdcI43 = "ldc.i4.3",
+    0x1A => LdcI44 = "ldc.i4.4",
+    0x1B => LdcI45 = "ldc.i4.5",
+    0x1C => LdcI46 = "ldc.i4.6",
+    0x1D => LdcI47 = "ldc.i4.7",
+    0x1E => LdcI48 = "ldc.i4.8",
+    0x1F => LdcI4S(i8) = "ldc.i4.s",
+    0x20 => LdcI4(i32) = "ldc.i4",
+    0x21 => LdcI8(i64) = "ldc.i8",
+    0x22 => LdcR4(f32) = "ldc.r4",
+    0x23 => LdcR8(f64) = "ldc.r8",
+    0x25 => Dup = "dup",
+    0x26 => Pop = "pop",
+    0x27 => Jmp(token) = "jmp",
+    0x28 => Call(token) = "call",
+    0x29 => Calli(token) = "calli",
+    0x2A => Ret = "ret",
+    0x2B => BrS(target8) = "br.s",
+    0x2C => BrfalseS(target8) = "brfalse.s",
+    0x2D => BrtrueS(target8) = "brtrue.s",
+    0x2E => BeqS(target8) = "beq.s",
+    0x2F => BgeS(target8) = "bge.s",
+    0x30 => BgtS(target8) = "bgt.s",
+    0x31 => BleS(target8) = "ble.s",
+    0x32 => BltS(target8) = "blt.s",
+    0x33 => BneUnS(target8) = "bne.un.s",
+    0x34 => BgeUnS(target8) = "bge.un.s",
+    0x35 => BgtUnS(target8) = "bgt.un.s",
+    0x36 => BleUnS(target8) = "ble.un.s",
+    0x37 => BltUnS(target8) = "blt.un.s",
+    0x38 => Br(target32) = "br",
+    0x39 => Brfalse(target32) = "brfalse",
+    0x3A => Brtrue(target32) = "brtrue",
+    0x3B => Beq(target32) = "beq",
+    0x3C => Bge(target32) = "bge",
+    0x3D => Bgt(target32) = "bgt",
+    0x3E => Ble(target32) = "ble",
+    0x3F => Blt(target32) = "blt",
+    0x40 => BneUn(target32) = "bne.un",
+    0x41 => BgeUn(target32) = "bge.un",
+    0x42 => BgtUn(target32) = "bgt.un",
+    0x43 => BleUn(target32) = "ble.un",
+    0x44 => BltUn(target32) = "blt.un",
+    0x45 => Switch(switch) = "switch",
+    0x46 => LdindI1 = "ldind.i1",
+    0x47 => LdindU1 = "ldind.u1",
+    0x48 => LdindI2 = "ldind.i2",
+    0x49 => LdindU2 = "ldind.u2",
+    0x4A => LdindI4 = "ldind.i4",
+    0x4B => LdindU4 = "ldind.u4",
+    0x4C => LdindI8 = "ldind.i8",
+    0x4D => LdindI = "ldind.i",
+    0x4E => LdindR4 = "ldind.r4",
+    0x4F => LdindR8 = "ldind.r8",
+    0x50 => LdindRef = "ldind.ref",
+    0x51 => StindRef = "stind.ref",
+    0x52 => StindI1 = "stind.i1",
+    0x53 => StindI2 = "stind.i2",
+    0x54 => StindI4 = "stind.i4",
+    0x55 => StindI8 = "stind.i8",
+    0x56 => StindR4 = "stind.r4",
+    0x57 => StindR8 = "stind.r8",
+    0x58 => Add = "add",
+    0x59 => Sub = "sub",
+    0x5A => Mul = "mul",
+    0x5B => Div = "div",
+    0x5C => DivUn = "div.un",
+    0x5D => Rem = "rem",
+    0x5E => RemUn = "rem.un",
+    0x5F => And = "and",
+    0x60 => Or = "or",
+    0x61 => Xor = "xor",
+    0x62 => Shl = "shl",
+    0x63 => Shr = "shr",
+    0x64 => ShrUn = "shr.un",
+    0x65 => Neg = "neg",
+    0x66 => Not = "not",
+    0x67 => ConvI1 = "conv.i1",
+    0x68 => ConvI2 = "conv.i2",
+    0x69 => ConvI4 = "conv.i4",
+    0x6A => ConvI8 = "conv.i8",
+    0x6B => ConvR4 = "conv.r4",
+    0x6C => ConvR8 = "conv.r8",
+    0x6D => ConvU4 = "conv.u4",
+    0x6E => ConvU8 = "conv.u8",
+    0x6F => Callvirt(token) = "callvirt",
+    0x70 => Cpobj(token) = "cpobj",
+    0x71 => Ldobj(token) = "ldobj",
+    0x72 => Ldstr(token) = "ldstr",
+    0x73 => Newobj(token) = "newobj",
+    0x74 => Castclass(token) = "castclass",
+    0x75 => Isinst(token) = "isinst",
+    0x76 => ConvRUn = "conv.r.un",
+    0x79 => Unbox(token) = "unbox",
+    0x7A => Throw = "throw",
+    0x7B => Ldfld(token) = "ldfld",
+    0x7C => Ldflda(token) = "ldflda",
+    0x7D => Stfld(token) = "stfld",
+    0x7E => Ldsfld(token) = "ldsfld",
+    0x7F => Ldsflda(token) = "ldsflda",
+    0x80 => Stsfld(token) = "stsfld",
+    0x81 => Stobj(token) = "stobj",
+    0x82 => ConvOvfI1Un = "conv.ovf.i1.un",
+    0x83 => ConvOvfI2Un = "conv.ovf.i2.un",
+    0x84 => ConvOvfI4Un = "conv.ovf.i4.un",
+    0x85 => ConvOvfI8Un = "conv.ovf.i8.un",
+    0x86 => ConvOvfU1Un = "conv.ovf.u1.un",
+    0x87 => ConvOvfU2Un = "conv.ovf.u2.un",
+    0x88 => ConvOvfU4Un = "conv.ovf.u4.un",
+    0x89 => ConvOvfU8Un = "conv.ovf.u8.un",
+    0x8A => ConvOvfIUn = "conv.ovf.i.un",
+    0x8B => ConvOvfUUn = "conv.ovf.u.un",
+    0x8C => Box(token) = "box",
+    0x8D => Newarr(token) = "newarr",
+    0x8E => Ldlen = "ldlen",
+    0x8F => Ldelema(token) = "ldelema",
+    0x90 => LdelemI1 = "ldelem.i1",
+    0x91 => LdelemU1 = "ldelem.u1",
+    0x92 => LdelemI2 = "ldelem.i2",
+    0x93 => LdelemU2 = "ldelem.u2",
+    0x94 => LdelemI4 = "ldelem.i4",
+    0x95 => LdelemU4 = "ldelem.u4",
+    0x96 => LdelemI8 = "ldelem.i8",
+    0x97 => LdelemI = "ldelem.i",
+    0x98 => LdelemR4 = "ldelem.r4",
+    0x99 => LdelemR8 = "ldelem.r8",
+    0x9A => LdelemRef = "ldelem.ref",
+    0x9B => StelemI = "stelem.i",
+    0x9C => StelemI1 = "stelem.i1",
+    0x9D => StelemI2 = "stelem.i2",
+    0x9E => StelemI4 = "stelem.i4",
+    0x9F => StelemI8 = "stelem.i8",
+    0xA0 => StelemR4 = "stelem.r4",
+    0xA1 => StelemR8 = "stelem.r8",
+    0xA2 => StelemRef = "stelem.ref",
+    0xA3 => Ldelem(token) = "ldelem",
+    0xA4 => Stelem(token) = "stelem",
+    0xA5 => UnboxAny(token) = "unbox.any",
+    0xB3 => ConvOvfI1 = "conv.ovf.i1",
+    0xB4 => ConvOvfU1 = "conv.ovf.u1",
+    0xB5 => ConvOvfI2 = "conv.ovf.i2",
+    0xB6 => ConvOvfU2 = "conv.ovf.u2",
+    0xB7 => ConvOvfI4 = "conv.ovf.i4",
+    0xB8 => ConvOvfU4 = "conv.ovf.u4",
+    0xB9 => ConvOvfI8 = "conv.ovf.i8",
+    0xBA => ConvOvfU8 = "conv.ovf.u8",
+    0xC2 => Refanyval(token) = "refanyval",
+    0xC3 => Ckfinite = "ckfinite",
+    0xC6 => Mkrefany(token) = "mkrefany",
+    0xD0 => Ldtoken(token) = "ldtoken",
+    0xD1 => ConvU2 = "conv.u2",
+    0xD2 => ConvU1 = "conv.u1",
+    0xD3 => ConvI = "conv.i",
+    0xD4 => ConvOvfI = "conv.ovf.i",
+    0xD5 => ConvOvfU = "conv.ovf.u",
+    0xD6 => AddOvf = "add.ovf",
+    0xD7 => AddOvfUn = "add.ovf.un",
+    0xD8 => MulOvf = "mul.ovf",
+    0xD9 => MulOvfUn = "mul.ovf.un",
+    0xDA => SubOvf = "sub.ovf",
+    0xDB => SubOvfUn = "sub.ovf.un",
+    0xDC => Endfinally = "endfinally",
+    0xDD => Leave(target32) = "leave",
+    0xDE => LeaveS(target8) = "leave.s",
+    0xDF => StindI = "stind.i",
+    0xE0 => ConvU = "conv.u"
+  ],
+  two_byte: [
+    0x00 => Arglist = "arglist",
+    0x01 => Ceq = "ceq",
+    0x02 => Cgt = "cgt",
+    0x03 => CgtUn = "cgt.un",
+    0x04 => Clt = "clt",
+    0x05 => CltUn = "clt.un",
+    0x06 => Ldftn(token) = "ldftn",
+    0x07 => Ldvirtftn(token) = "ldvirtftn",
+    0x09 => Ldarg(u16) = "ldarg",
+    0x0A => Ldarga(u16) = "ldarga",
+    0x0B => Starg(u16) = "starg",
+    0x0C => Ldloc(u16) = "ldloc",
+    0x0D => Ldloca(u16) = "ldloca",
+    0x0E => Stloc(u16) = "stloc",
+    0x0F => Localloc = "localloc",
+    0x11 => Endfilter = "endfilter",
+    0x12 => Unaligned(u8) = "unaligned.",
+    0x13 => Volatile = "volatile.",
+    0x14 => Tail = "tail.",
+    0x15 => Initobj(token) = "initobj",
+    0x16 => Constrained(token) = "constrained.",
+    0x17 => Cpblk = "cpblk",
+    0x18 => Initblk = "initblk",
+    0x19 => No(u8) = "no.",
+    0x1A => Rethrow = "rethrow",
+    0x1C => Sizeof(token) = "sizeof",
+    0x1D => Refanytype = "refanytype",
+    0x1E => Readonly = "readonly."
+  ]
+}
+
+impl Instruction {
+  /// Returns true for tail., volatile., unaligned., constrained., readonly. and no.,
+  /// which modify the instruction that follows them.
+  pub fn is_prefix(&self) -> bool {
+    match *self {
+      Instruction::Unaligned(_) | Instruction::Volatile | Instruction::Tail | Instruction::Constrained(_) |
+      Instruction::No(_) | Instruction::Readonly => true,
+      _ => false
+    }
+  }
+
+  /// Returns the target of branch and leave instructions. Switch targets aren't included.
+  pub fn branch_target(&self) -> Option<u32> {
+    match *self {
+      Instruction::BrS(target) | Instruction::BrfalseS(target) | Instruction::BrtrueS(target) |
+      Instruction::BeqS(target) | Instruction::BgeS(target) | Instruction::BgtS(target) |
+      Instruction::BleS(target) | Instruction::BltS(target) | Instruction::BneUnS(target) |
+      Instruction::BgeUnS(target) | Instruction::BgtUnS(target) | Instruction::BleUnS(target) |
+      Instruction::BltUnS(target) | Instruction::Br(target) | Instruction::Brfalse(target) |
+      Instruction::Brtrue(target) | Instruction::Beq(target) | Instruction::Bge(target) |
+      Instruction::Bgt(target) | Instruction::Ble(target) | Instruction::Blt(target) |
+      Instruction::BneUn(target) | Instruction::BgeUn(target) | Instruction::BgtUn(target) |
+      Instruction::BleUn(target) | Instruction::BltUn(target) | Instruction::Leave(target) |
+      Instruction::LeaveS(target) => Some(target),
+      _ => None
+    }
+  }
+
+  /// Returns the metadata token operand of the instruction, if it has one.
+  pub fn token(&self) -> Option<&Token> {
+    match *self {
+      Instruction::Jmp(ref token) | Instruction::Call(ref token) | Instruction::Calli(ref token) |
+      Instruction::Callvirt(ref token) | Instruction::Cpobj(ref token) | Instruction::Ldobj(ref token) |
+      Instruction::Ldstr(ref token) | Instruction::Newobj(ref token) | Instruction::Castclass(ref token) |
+      Instruction::Isinst(ref token) | Instruction::Unbox(ref token) | Instruction::Ldfld(ref token) |
+      Instruction::Ldflda(ref token) | Instruction::Stfld(ref token) | Instruction::Ldsfld(ref token) |
+      Instruction::Ldsflda(ref token) | Instruction::Stsfld(ref token) | Instruction::Stobj(ref token) |
+      Instruction::Box(ref token) | Instruction::Newarr(ref token) | Instruction::Ldelema(ref token) |
+      Instruction::Ldelem(ref token) | Instruction::Stelem(ref token) | Instruction::UnboxAny(ref token) |
+      Instruction::Refanyval(ref token) | Instruction::Mkrefany(ref token) | Instruction::Ldtoken(ref token) |
+      Instruction::Ldftn(ref token) | Instruction::Ldvirtftn(ref token) | Instruction::Initobj(ref token) |
+      Instruction::Constrained(ref token) | Instruction::Sizeof(ref token) => Some(token),
+      _ => None
+    }
+  }
+}
+
+/// Iterates over the instructions of a method body, yielding their offsets along with them.
+/// Stops after the first decoding error.
+pub struct Instructions<'a> {
+  reader: Cursor<&'a [u8]>,
+  failed: bool
+}
+
+impl<'a> Instructions<'a> {
+  pub fn new(code: &'a [u8]) -> Instructions<'a> {
+    Instructions { reader: Cursor::new(code), failed: false }
+  }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+  type Item = Result<(u32, Instruction)>;
+
+  fn next(&mut self) -> Option<Result<(u32, Instruction)>> {
+    if self.failed || self.reader.position() as usize >= self.reader.get_ref().len() {
+      return None;
+    }
+
+    let offset = self.reader.position() as u32;
+    match Instruction::read(&mut self.reader) {
+      Ok(instruction) => Some(Ok((offset, instruction))),
+      Err(error) => {
+        self.failed = true;
+        Some(Err(error))
+      }
+    }
+  }
+}
+
+impl MethodBody {
+  pub fn instructions(&self) -> Instructions {
+    Instructions::new(&self.code)
+  }
+}
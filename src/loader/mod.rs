@@ -2,4 +2,5 @@
 pub mod pe;
 pub mod stream;
 pub mod code;
+pub mod instructions;
 pub mod clr;
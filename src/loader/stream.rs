@@ -7,7 +7,7 @@ use byteorder::{ReadBytesExt, LittleEndian};
 use typemap::{Key, DebugMap, TypeMap};
 
 use utils::stream::*;
-use metadata::heap::{StringHeap, UserStringHeap, UserString};
+use metadata::heap::{StringHeap, UserStringHeap, BlobHeap};
 use metadata::tables::*;
 use metadata::{Metadata, MetadataTable};
 
@@ -151,6 +151,72 @@ impl From<u8> for HeapOffsetSizes {
   }
 }
 
+impl FieldSizes {
+  fn heap_index_size(size: IndexSize) -> u64 {
+    match size {
+      IndexSize::Word => 2,
+      IndexSize::Dword => 4
+    }
+  }
+
+  fn table_index_size(&self, table_id: TableId) -> u64 {
+    FieldSizes::heap_index_size(*self.index_sizes.get(&table_id).unwrap_or(&IndexSize::Word))
+  }
+
+  /// Returns the size of a single row of the given table in bytes, as specified in ECMA 335 II.22.
+  pub fn row_size(&self, table_id: TableId) -> u64 {
+    let string = FieldSizes::heap_index_size(self.heap_sizes.string_index);
+    let guid = FieldSizes::heap_index_size(self.heap_sizes.guid_index);
+    let blob = FieldSizes::heap_index_size(self.heap_sizes.blob_index);
+    let table = |id| self.table_index_size(id);
+    let coded = FieldSizes::heap_index_size;
+    let rows = &self.row_counts;
+
+    match table_id {
+      TableId::Module => 2 + string + guid * 3,
+      TableId::TypeRef => coded(ResolutionScope::index_size(rows)) + string * 2,
+      TableId::TypeDef => 4 + string * 2 + coded(TypeDefOrRef::index_size(rows))
+        + table(TableId::Field) + table(TableId::MethodDef),
+      TableId::Field => 2 + string + blob,
+      TableId::MethodDef => 4 + 2 + 2 + string + blob + table(TableId::Param),
+      TableId::Param => 2 + 2 + string,
+      TableId::InterfaceImpl => table(TableId::TypeDef) + coded(TypeDefOrRef::index_size(rows)),
+      TableId::MemberRef => coded(MemberRefParent::index_size(rows)) + string + blob,
+      TableId::Constant => 2 + coded(HasConstant::index_size(rows)) + blob,
+      TableId::CustomAttribute => coded(HasCustomAttribute::index_size(rows))
+        + coded(CustomAttributeType::index_size(rows)) + blob,
+      TableId::FieldMarshal => coded(HasFieldMarshall::index_size(rows)) + blob,
+      TableId::DeclSecurity => 2 + coded(HasDeclSecurity::index_size(rows)) + blob,
+      TableId::ClassLayout => 2 + 4 + table(TableId::TypeDef),
+      TableId::FieldLayout => 4 + table(TableId::Field),
+      TableId::StandAloneSig => blob,
+      TableId::EventMap => table(TableId::TypeDef) + table(TableId::Event),
+      TableId::Event => 2 + string + coded(TypeDefOrRef::index_size(rows)),
+      TableId::PropertyMap => table(TableId::TypeDef) + table(TableId::Property),
+      TableId::Property => 2 + string + blob,
+      TableId::MethodSemantics => 2 + table(TableId::MethodDef) + coded(HasSemantics::index_size(rows)),
+      TableId::MethodImpl => table(TableId::TypeDef) + coded(MethodDefOrRef::index_size(rows)) * 2,
+      TableId::ModuleRef => string,
+      TableId::TypeSpec => blob,
+      TableId::ImplMap => 2 + coded(MemberForwarded::index_size(rows)) + string + table(TableId::ModuleRef),
+      TableId::FieldRVA => 4 + table(TableId::Field),
+      TableId::Assembly => 4 + 2 * 4 + 4 + blob + string * 2,
+      TableId::AssemblyProcessor => 4,
+      TableId::AssemblyOS => 4 * 3,
+      TableId::AssemblyRef => 2 * 4 + 4 + blob * 2 + string * 2,
+      TableId::AssemblyRefProcessor => 4 + table(TableId::AssemblyRef),
+      TableId::AssemblyRefOS => 4 * 3 + table(TableId::AssemblyRef),
+      TableId::File => 4 + string + blob,
+      TableId::ExportedType => 4 + 4 + string * 2 + coded(Implementation::index_size(rows)),
+      TableId::ManifestResource => 4 + 4 + string + coded(Implementation::index_size(rows)),
+      TableId::NestedClass => table(TableId::TypeDef) * 2,
+      TableId::GenericParam => 2 + 2 + coded(TypeOrMethodDef::index_size(rows)) + string,
+      TableId::MethodSpec => coded(MethodDefOrRef::index_size(rows)) + blob,
+      TableId::GenericParamConstraint => table(TableId::GenericParam) + coded(TypeDefOrRef::index_size(rows))
+    }
+  }
+}
+
 impl MetaDataTablesStream {
   fn get_index_sizes(row_counts: &HashMap<TableId, u32>) -> IndexSizes {
     row_counts.iter().map(|(&k, &v)|
@@ -205,15 +271,35 @@ impl StreamReader for MetaDataTablesStream {
 
     struct TableReader<'a, R: 'a + Read + Seek> {
       reader: &'a mut R,
-      sizes: &'a FieldSizes
+      sizes: &'a FieldSizes,
+      // The id of the first table which hasn't been read or skipped yet
+      next_table: u8
     }
 
     impl<'a, R: Read + Seek> TableReader<'a, R> {
       fn new(reader: &'a mut R, sizes: &'a FieldSizes) -> TableReader<'a, R> {
-        TableReader { reader, sizes }
+        // The module table is read separately
+        TableReader { reader, sizes, next_table: TableId::TypeRef as u8 }
+      }
+
+      // Tables are stored in the order of their ids, so tables we don't parse (yet) must be skipped over.
+      fn skip_to(&mut self, table_id: TableId) -> Result<()> {
+        for id in self.next_table .. table_id as u8 {
+          if let Some(skipped_table) = TableId::from_u8(id) {
+            let row_count = *self.sizes.row_counts.get(&skipped_table).unwrap_or(&0) as u64;
+            if row_count > 0 {
+              println!("Skipping {} rows of {:?}", row_count, skipped_table);
+              self.reader.skip((row_count * self.sizes.row_size(skipped_table)) as i64)?;
+            }
+          }
+        }
+
+        self.next_table = table_id as u8 + 1;
+        Ok(())
       }
 
       fn read<T: TableEntryReader + MetadataTable + Debug>(&mut self) -> Result<Vec<T>> {
+        self.skip_to(T::TABLE_ID)?;
         let row_count = *self.sizes.row_counts.get(&T::TABLE_ID).unwrap_or(&0) as usize;
         let mut entries = Vec::with_capacity(row_count);
         
@@ -231,32 +317,92 @@ impl StreamReader for MetaDataTablesStream {
 
     let type_refs: Vec<TypeRefEntry>;
     let type_defs: Vec<TypeDefEntry>;
+    let fields: Vec<FieldEntry>;
     let method_defs: Vec<MethodDefEntry>;
+    let params: Vec<ParamEntry>;
+    let interface_impls: Vec<InterfaceImplEntry>;
     let member_refs: Vec<MemberRefEntry>;
+    let constants: Vec<ConstantEntry>;
     let custom_attributes: Vec<CustomAttributeEntry>;
+    let field_marshals: Vec<FieldMarshalEntry>;
+    let decl_securities: Vec<DeclSecurityEntry>;
+    let class_layouts: Vec<ClassLayoutEntry>;
+    let stand_alone_sigs: Vec<StandAloneSigEntry>;
+    let event_maps: Vec<EventMapEntry>;
+    let events: Vec<EventEntry>;
+    let property_maps: Vec<PropertyMapEntry>;
+    let properties: Vec<PropertyEntry>;
+    let method_semantics: Vec<MethodSemanticsEntry>;
+    let method_impls: Vec<MethodImplEntry>;
+    let type_specs: Vec<TypeSpecEntry>;
+    let field_rvas: Vec<FieldRVAEntry>;
     let assembly: Vec<AssemblyEntry>;
     let assembly_refs: Vec<AssemblyRefEntry>;
+    let nested_classes: Vec<NestedClassEntry>;
+    let generic_params: Vec<GenericParamEntry>;
+    let method_specs: Vec<MethodSpecEntry>;
+    let generic_param_constraints: Vec<GenericParamConstraintEntry>;
     
     {
       let mut table_reader = TableReader::new(reader, &sizes);
       type_refs = table_reader.read()?;
       type_defs = table_reader.read()?;
+      fields = table_reader.read()?;
       method_defs = table_reader.read()?;
+      params = table_reader.read()?;
+      interface_impls = table_reader.read()?;
       member_refs = table_reader.read()?;
+      constants = table_reader.read()?;
       custom_attributes = table_reader.read()?;
+      field_marshals = table_reader.read()?;
+      decl_securities = table_reader.read()?;
+      class_layouts = table_reader.read()?;
+      stand_alone_sigs = table_reader.read()?;
+      event_maps = table_reader.read()?;
+      events = table_reader.read()?;
+      property_maps = table_reader.read()?;
+      properties = table_reader.read()?;
+      method_semantics = table_reader.read()?;
+      method_impls = table_reader.read()?;
+      type_specs = table_reader.read()?;
+      field_rvas = table_reader.read()?;
       assembly = table_reader.read()?;
       assembly_refs = table_reader.read()?;
+      nested_classes = table_reader.read()?;
+      generic_params = table_reader.read()?;
+      method_specs = table_reader.read()?;
+      generic_param_constraints = table_reader.read()?;
     }
 
     let mut tables = TypeMap::custom();
     tables.insert::<ModuleEntry>(vec![module]);
     tables.insert::<TypeRefEntry>(type_refs);
     tables.insert::<TypeDefEntry>(type_defs);
+    tables.insert::<FieldEntry>(fields);
     tables.insert::<MethodDefEntry>(method_defs);
+    tables.insert::<ParamEntry>(params);
+    tables.insert::<InterfaceImplEntry>(interface_impls);
     tables.insert::<MemberRefEntry>(member_refs);
+    tables.insert::<ConstantEntry>(constants);
     tables.insert::<CustomAttributeEntry>(custom_attributes);
+    tables.insert::<FieldMarshalEntry>(field_marshals);
+    tables.insert::<DeclSecurityEntry>(decl_securities);
+    tables.insert::<ClassLayoutEntry>(class_layouts);
+    tables.insert::<StandAloneSigEntry>(stand_alone_sigs);
+    tables.insert::<EventMapEntry>(event_maps);
+    tables.insert::<EventEntry>(events);
+    tables.insert::<PropertyMapEntry>(property_maps);
+    tables.insert::<PropertyEntry>(properties);
+    tables.insert::<MethodSemanticsEntry>(method_semantics);
+    tables.insert::<MethodImplEntry>(method_impls);
+    tables.insert::<TypeSpecEntry>(type_specs);
+    tables.insert::<FieldRVAEntry>(field_rvas);
     tables.insert::<AssemblyEntry>(assembly);
     tables.insert::<AssemblyRefEntry>(assembly_refs);
+    tables.insert::<NestedClassEntry>(nested_classes);
+    tables.insert::<GenericParamEntry>(generic_params);
+    tables.insert::<MethodSpecEntry>(method_specs);
+    tables.insert::<GenericParamConstraintEntry>(generic_param_constraints);
 
     Ok(MetaDataTablesStream { tables })
   }
@@ -286,7 +432,7 @@ impl StreamReader for StringHeap {
   }
 }
 
-struct StreamUtils { }
+pub struct StreamUtils { }
 
 pub struct CompressedUint {
   pub value: u32,
@@ -297,7 +443,7 @@ impl StreamUtils {
   // ECMA 335, page 272
   // Inspired by
   // https://github.com/jbevain/cecil/blob/505b07d6974d8405a63124139733c6fdc0e67bc7/Mono.Cecil.PE/ByteBuffer.cs#L101
-  pub fn decode_compressed_int<R: Read + Seek>(reader: &mut R) -> Result<CompressedUint> {
+  pub fn decode_compressed_int<R: Read>(reader: &mut R) -> Result<CompressedUint> {
     let first_byte = reader.read_u8()?;
 
     // Starts with a zero bit -> bits 1-7 are the length
@@ -318,20 +464,28 @@ impl StreamUtils {
 
     Ok(CompressedUint { value, compressed_size })
   }
-}
 
-impl UserString {
-  pub fn from_utf16(buffer: &[u16]) -> UserString {
-    match String::from_utf16(buffer) {
-      Ok(string) => UserString::Valid(string),
-      _ => UserString::Garbage
+  // ECMA 335, page 273
+  // The sign bit is rotated to the least significant bit.
+  pub fn decode_compressed_signed_int<R: Read>(reader: &mut R) -> Result<i32> {
+    let decoded = StreamUtils::decode_compressed_int(reader)?;
+    let value = (decoded.value >> 1) as i32;
+
+    if decoded.value & 1 == 0 {
+      return Ok(value);
     }
+
+    Ok(match decoded.compressed_size {
+      1 => value - 0x40,
+      2 => value - 0x2000,
+      _ => value - 0x1000_0000
+    })
   }
 }
 
 impl StreamReader for UserStringHeap {
   fn read_from<R: Read + Seek>(reader: &mut R, header: &StreamHeader) -> Result<UserStringHeap> {
-    let mut strings: Vec<(u32, UserString)> = vec![];
+    let mut strings: Vec<(u32, Vec<u16>)> = vec![];
     let mut bytes_read: usize = 0;
 
     // Always starts with a null byte, which is handled like any other table entry
@@ -342,13 +496,13 @@ impl StreamReader for UserStringHeap {
       bytes_read += decoded.compressed_size as usize;
 
       if decoded.value == 0 {
-        strings.push((start as u32, UserString::Valid("".to_string())));
+        strings.push((start as u32, vec![]));
         continue;
       }
 
       let mut string_buffer = vec![0u16; (decoded.value / 2) as usize];
       reader.read_exact_16(&mut string_buffer)?;
-      strings.push((start as u32, UserString::from_utf16(&string_buffer)));
+      strings.push((start as u32, string_buffer));
 
       let is_ascii = reader.read_u8()?;
       bytes_read += decoded.value as usize;
@@ -361,3 +515,30 @@ impl StreamReader for UserStringHeap {
     Ok(string_heap)
   }
 }
+
+impl StreamReader for BlobHeap {
+  fn read_from<R: Read + Seek>(reader: &mut R, header: &StreamHeader) -> Result<BlobHeap> {
+    let mut blobs: Vec<(u32, Vec<u8>)> = vec![];
+    let mut bytes_read: usize = 0;
+
+    // Like #US, always starts with an empty blob
+
+    while bytes_read < header.size as usize {
+      let start = bytes_read;
+      let decoded = StreamUtils::decode_compressed_int(reader)?;
+      bytes_read += decoded.compressed_size as usize;
+
+      let mut blob = vec![0u8; decoded.value as usize];
+      reader.read_exact(&mut blob)?;
+      bytes_read += decoded.value as usize;
+
+      blobs.push((start as u32, blob));
+    }
+
+    let blob_heap = BlobHeap {
+      blobs: blobs.into_iter().collect()
+    };
+
+    Ok(blob_heap)
+  }
+}
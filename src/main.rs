@@ -3,6 +3,7 @@
 #![feature(box_syntax)]
 #![feature(associated_type_defaults)]
 #![feature(associated_consts)]
+#![feature(try_reserve)]
 
 #![allow(dead_code)]
 
@@ -14,69 +15,58 @@ extern crate typemap;
 #[macro_use] mod utils;
 mod metadata;
 mod loader;
+mod analysis;
 mod runtime;
 
 #[cfg(test)]
 mod tests;
 
 use loader::pe;
-use std::marker::PhantomData;
-
-#[allow(non_snake_case)]
-pub mod Corlib {
-  pub mod Console {
-    pub fn WriteLine(str: String) {
-      println!("{}", str);
-    }
-  }
-}
-
-struct ManagedObject { }
-
-enum StackValue {
-  Int64(i64),
-  Int32(i32),
-  Float(f64),
-  Object(Box<ManagedObject>)
-}
-
-struct MethodInfo { }
-
-struct MethodState<'a> {
-  ip: u32,
-  stack: Vec<u8>,
-  local_variables: Vec<u8>,
-  local_heap: Vec<u8>,
-  arguments: Vec<u8>,
-  method_info: Box<MethodInfo>,
-  return_state: (),
-  phantom: PhantomData<&'a u8>
-}
-
-impl<'a> MethodState<'a> {
-  pub fn new() -> MethodState<'a> {
-    MethodState {
-      ip: 0,
-      stack: vec![],
-      local_variables: vec![],
-      local_heap: vec![],
-      arguments: vec![],
-      method_info: box (MethodInfo {}),
-      return_state: (),
-      phantom: PhantomData
-    }
-  }
-}
+use metadata::signature::TypeSig;
+use runtime::interpreter::Interpreter;
+use runtime::value::StackValue;
 
 fn main() {
   println!("CLRi 0.1");
-  let file = std::fs::File::open("sample/helloworld/HelloWorld.exe").unwrap();
+  let args = std::env::args().skip(1).collect::<Vec<_>>();
+  let disassemble = args.iter().any(|arg| arg == "--disassemble");
+  let mut positional = args.iter().filter(|arg| !arg.starts_with("--"));
+  let path = positional.next().map(|path| path.as_str()).unwrap_or("sample/helloworld/HelloWorld.exe");
+  // The arguments after the path are passed to the program
+  let program_args = positional.collect::<Vec<_>>();
+
+  let file = std::fs::File::open(path).unwrap();
   let mut file_reader = std::io::BufReader::new(file);
   let pe_file = pe::PEFile::read_from(&mut file_reader).unwrap();
   let image = loader::clr::CLRImage::from_pe(&pe_file).unwrap();
 
-  use metadata::tables::*;
-  use metadata::debug::*;
+  if disassemble {
+    println!("{}", image.disassemble());
+    return;
+  }
+
+  let entry_point = match image.entry_point() {
+    Some(entry_point) => entry_point,
+    None => {
+      println!("{} has no entry point", path);
+      return;
+    }
+  };
+
+  let mut interpreter = Interpreter::new(&image.metadata);
 
-  println!("{}", (*image.metadata.tables.get::<ModuleEntry>().unwrap())[0].as_debug(&image.metadata));
+  // Main either takes no arguments or a string[] of the program's arguments
+  let arg_count = image.metadata.get_method_def_signature(&entry_point).map(|signature| signature.params.len()).unwrap_or(0);
+  let args = if arg_count == 0 {
+    vec![]
+  } else {
+    let heap = interpreter.heap_mut();
+    let strings = program_args.iter().map(|arg| StackValue::Object(Some(heap.alloc_string(arg)))).collect();
+    vec![StackValue::Object(Some(heap.alloc_array(TypeSig::String, strings)))]
+  };
+
+  if let Err(error) = interpreter.run(&entry_point, args) {
+    println!("{}", error);
+    std::process::exit(1);
+  }
 }
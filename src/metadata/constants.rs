@@ -0,0 +1,143 @@
+use std::char;
+use std::f64;
+use std::io::{Cursor, Result};
+use byteorder::{ReadBytesExt, LittleEndian};
+
+use metadata::Metadata;
+use metadata::signature::{ElementType, invalid_signature};
+use metadata::tables::*;
+
+/// The value of a literal field, a parameter default or a property default (ECMA 335, II.22.9).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantValue {
+  Bool(bool),
+  // A UTF-16 code unit
+  Char(u16),
+  I1(i8),
+  U1(u8),
+  I2(i16),
+  U2(u16),
+  I4(i32),
+  U4(u32),
+  I8(i64),
+  U8(u64),
+  R4(f32),
+  R8(f64),
+  /// The UTF-16 code units of the string, which may contain lone surrogates.
+  String(Vec<u16>),
+  /// The null reference, stored as ELEMENT_TYPE_CLASS with a zero value.
+  Null
+}
+
+impl ConstantValue {
+  pub fn decode(type_: ElementType, blob: &[u8]) -> Result<ConstantValue> {
+    let mut reader = Cursor::new(blob);
+
+    Ok(match type_ {
+      ElementType::Boolean => ConstantValue::Bool(reader.read_u8()? != 0),
+      ElementType::Char => ConstantValue::Char(reader.read_u16::<LittleEndian>()?),
+      ElementType::I1 => ConstantValue::I1(reader.read_i8()?),
+      ElementType::U1 => ConstantValue::U1(reader.read_u8()?),
+      ElementType::I2 => ConstantValue::I2(reader.read_i16::<LittleEndian>()?),
+      ElementType::U2 => ConstantValue::U2(reader.read_u16::<LittleEndian>()?),
+      ElementType::I4 => ConstantValue::I4(reader.read_i32::<LittleEndian>()?),
+      ElementType::U4 => ConstantValue::U4(reader.read_u32::<LittleEndian>()?),
+      ElementType::I8 => ConstantValue::I8(reader.read_i64::<LittleEndian>()?),
+      ElementType::U8 => ConstantValue::U8(reader.read_u64::<LittleEndian>()?),
+      ElementType::R4 => ConstantValue::R4(reader.read_f32::<LittleEndian>()?),
+      ElementType::R8 => ConstantValue::R8(reader.read_f64::<LittleEndian>()?),
+      ElementType::String => {
+        // The string is stored as UTF-16 without a terminator, so its length must be even
+        if blob.len() % 2 != 0 {
+          return Err(invalid_signature(format!("String constant has an odd length of {} bytes", blob.len())));
+        }
+        let units = blob.chunks(2)
+          .map(|chunk| (chunk[0] as u16) | ((chunk[1] as u16) << 8))
+          .collect::<Vec<_>>();
+        ConstantValue::String(units)
+      },
+      ElementType::Class => ConstantValue::Null,
+      otherwise => return Err(invalid_signature(format!("Invalid constant type: {:?}", otherwise)))
+    })
+  }
+
+  /// Formats the value as a C# literal.
+  pub fn as_csharp(&self) -> String {
+    match *self {
+      ConstantValue::Bool(value) => value.to_string(),
+      ConstantValue::Char(value) => match ::std::char::from_u32(value as u32) {
+        Some(c) => format!("'{}'", escape_csharp(c, '\'')),
+        None => format!("'\\u{:04x}'", value)
+      },
+      ConstantValue::I1(value) => value.to_string(),
+      ConstantValue::U1(value) => value.to_string(),
+      ConstantValue::I2(value) => value.to_string(),
+      ConstantValue::U2(value) => value.to_string(),
+      ConstantValue::I4(value) => value.to_string(),
+      ConstantValue::U4(value) => format!("{}u", value),
+      ConstantValue::I8(value) => format!("{}L", value),
+      ConstantValue::U8(value) => format!("{}UL", value),
+      ConstantValue::R4(value) => match float_name(value as f64) {
+        Some(name) => format!("float.{}", name),
+        None => format!("{:?}f", value)
+      },
+      ConstantValue::R8(value) => match float_name(value) {
+        Some(name) => format!("double.{}", name),
+        None => format!("{:?}", value)
+      },
+      ConstantValue::String(ref value) => {
+        let escaped = char::decode_utf16(value.iter().cloned())
+          .map(|c| match c {
+            Ok(c) => escape_csharp(c, '"'),
+            Err(error) => format!("\\u{:04x}", error.unpaired_surrogate())
+          })
+          .collect::<String>();
+        format!("\"{}\"", escaped)
+      },
+      ConstantValue::Null => "null".to_string()
+    }
+  }
+}
+
+// The name of the float or double constant for values without a literal form
+fn float_name(value: f64) -> Option<&'static str> {
+  if value.is_nan() {
+    Some("NaN")
+  } else if value == f64::INFINITY {
+    Some("PositiveInfinity")
+  } else if value == f64::NEG_INFINITY {
+    Some("NegativeInfinity")
+  } else {
+    None
+  }
+}
+
+// Escapes a character of a C# character or string literal delimited by `quote`
+fn escape_csharp(c: char, quote: char) -> String {
+  match c {
+    '\\' => "\\\\".to_string(),
+    '\0' => "\\0".to_string(),
+    '\n' => "\\n".to_string(),
+    '\r' => "\\r".to_string(),
+    '\t' => "\\t".to_string(),
+    c if c == quote => format!("\\{}", c),
+    c if c.is_control() => format!("\\u{:04x}", c as u32),
+    c => c.to_string()
+  }
+}
+
+impl Metadata {
+  /// Returns the constant value of a field, parameter or property, or None if it has no default.
+  pub fn get_constant(&self, parent: HasConstant) -> Result<Option<ConstantValue>> {
+    let entry = self.get_table::<ConstantEntry>()
+      .and_then(|constants| constants.iter().find(|entry| entry.parent == parent));
+
+    match entry {
+      Some(entry) => {
+        let blob = self.get_blob(&entry.value).map(|blob| &blob[..]).unwrap_or(&[]);
+        ConstantValue::decode(entry.type_, blob).map(Some)
+      },
+      None => Ok(None)
+    }
+  }
+}
@@ -0,0 +1,336 @@
+use std::cmp;
+use std::io::{Read, Cursor, Result};
+use byteorder::{ReadBytesExt, LittleEndian};
+
+use metadata::Metadata;
+use metadata::signature::*;
+use metadata::tables::*;
+
+/// A decoded fixed or named custom attribute argument (ECMA 335, II.23.3).
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustomAttributeValue {
+  Bool(bool),
+  // A UTF-16 code unit
+  Char(u16),
+  I1(i8),
+  U1(u8),
+  I2(i16),
+  U2(u16),
+  I4(i32),
+  U4(u32),
+  I8(i64),
+  U8(u64),
+  R4(f32),
+  R8(f64),
+  String(Option<String>),
+  /// A System.Type, stored as its assembly-qualified name.
+  Type(Option<String>),
+  /// An enum value with the name of the enum type, and the value as its underlying type.
+  Enum(String, Box<CustomAttributeValue>),
+  Array(Option<Vec<CustomAttributeValue>>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamedArgumentKind {
+  Field,
+  Property
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedArgument {
+  pub kind: NamedArgumentKind,
+  pub name: String,
+  pub value: CustomAttributeValue
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomAttribute {
+  pub constructor: CustomAttributeType,
+  /// The namespace-qualified name of the attribute class.
+  pub attribute_type: String,
+  pub fixed_args: Vec<CustomAttributeValue>,
+  pub named_args: Vec<NamedArgument>
+}
+
+impl CustomAttribute {
+  pub fn get_named_arg(&self, name: &str) -> Option<&CustomAttributeValue> {
+    self.named_args.iter().find(|arg| arg.name == name).map(|arg| &arg.value)
+  }
+}
+
+// How a single value is serialized. Mirrors FieldOrPropType, but enums also carry their underlying type.
+#[derive(Debug, Clone)]
+enum SerializationType {
+  Primitive(ElementType),
+  String,
+  Type,
+  Boxed,
+  Enum(String, ElementType),
+  SzArray(Box<SerializationType>)
+}
+
+struct CustomAttributeReader<'a> {
+  meta: &'a Metadata,
+  references: &'a [&'a Metadata],
+  cursor: Cursor<&'a [u8]>
+}
+
+impl<'a> CustomAttributeReader<'a> {
+  fn peek_u8(&self) -> Option<u8> {
+    let position = self.cursor.position() as usize;
+    self.cursor.get_ref().get(position).cloned()
+  }
+
+  // SerString: a compressed length followed by UTF-8, or 0xFF for null
+  fn read_ser_string(&mut self) -> Result<Option<String>> {
+    if self.peek_u8() == Some(0xFF) {
+      self.cursor.read_u8()?;
+      return Ok(None);
+    }
+
+    let length = read_compressed_u32(&mut self.cursor)?;
+    let mut buffer = vec![0u8; length as usize];
+    self.cursor.read_exact(&mut buffer)?;
+
+    String::from_utf8(buffer)
+      .map(Some)
+      .map_err(|_| invalid_signature("Invalid UTF-8 in custom attribute string".to_string()))
+  }
+
+  fn type_from_signature(&self, type_sig: &TypeSig) -> Result<SerializationType> {
+    Ok(match *type_sig {
+      TypeSig::Boolean => SerializationType::Primitive(ElementType::Boolean),
+      TypeSig::Char => SerializationType::Primitive(ElementType::Char),
+      TypeSig::I1 => SerializationType::Primitive(ElementType::I1),
+      TypeSig::U1 => SerializationType::Primitive(ElementType::U1),
+      TypeSig::I2 => SerializationType::Primitive(ElementType::I2),
+      TypeSig::U2 => SerializationType::Primitive(ElementType::U2),
+      TypeSig::I4 => SerializationType::Primitive(ElementType::I4),
+      TypeSig::U4 => SerializationType::Primitive(ElementType::U4),
+      TypeSig::I8 => SerializationType::Primitive(ElementType::I8),
+      TypeSig::U8 => SerializationType::Primitive(ElementType::U8),
+      TypeSig::R4 => SerializationType::Primitive(ElementType::R4),
+      TypeSig::R8 => SerializationType::Primitive(ElementType::R8),
+      TypeSig::String => SerializationType::String,
+      TypeSig::Object => SerializationType::Boxed,
+      TypeSig::Class(ref type_) if self.meta.get_type_name(type_) == "System.Type" => SerializationType::Type,
+      TypeSig::ValueType(ref type_) => {
+        let underlying_type = self.enum_underlying_type(type_)?;
+        SerializationType::Enum(self.meta.full_type_name(type_).as_reflection(), underlying_type)
+      },
+      TypeSig::SzArray(ref element) => SerializationType::SzArray(box self.type_from_signature(element)?),
+      ref otherwise => return Err(invalid_signature(format!("Invalid custom attribute parameter type: {:?}", otherwise)))
+    })
+  }
+
+  // FieldOrPropType (ECMA 335, II.23.3)
+  fn read_field_or_prop_type(&mut self) -> Result<SerializationType> {
+    Ok(match read_element_type(&mut self.cursor)? {
+      ElementType::String => SerializationType::String,
+      ElementType::Type => SerializationType::Type,
+      ElementType::Boxed => SerializationType::Boxed,
+      ElementType::SzArray => SerializationType::SzArray(box self.read_field_or_prop_type()?),
+      ElementType::Enum => {
+        let name = self.read_ser_string()?.unwrap_or_default();
+        let underlying_type = self.enum_underlying_type_by_name(&name)?;
+        SerializationType::Enum(name, underlying_type)
+      },
+      primitive => SerializationType::Primitive(primitive)
+    })
+  }
+
+  fn read_primitive(&mut self, element_type: ElementType) -> Result<CustomAttributeValue> {
+    let reader = &mut self.cursor;
+
+    Ok(match element_type {
+      ElementType::Boolean => CustomAttributeValue::Bool(reader.read_u8()? != 0),
+      ElementType::Char => CustomAttributeValue::Char(reader.read_u16::<LittleEndian>()?),
+      ElementType::I1 => CustomAttributeValue::I1(reader.read_i8()?),
+      ElementType::U1 => CustomAttributeValue::U1(reader.read_u8()?),
+      ElementType::I2 => CustomAttributeValue::I2(reader.read_i16::<LittleEndian>()?),
+      ElementType::U2 => CustomAttributeValue::U2(reader.read_u16::<LittleEndian>()?),
+      ElementType::I4 => CustomAttributeValue::I4(reader.read_i32::<LittleEndian>()?),
+      ElementType::U4 => CustomAttributeValue::U4(reader.read_u32::<LittleEndian>()?),
+      ElementType::I8 => CustomAttributeValue::I8(reader.read_i64::<LittleEndian>()?),
+      ElementType::U8 => CustomAttributeValue::U8(reader.read_u64::<LittleEndian>()?),
+      ElementType::R4 => CustomAttributeValue::R4(reader.read_f32::<LittleEndian>()?),
+      ElementType::R8 => CustomAttributeValue::R8(reader.read_f64::<LittleEndian>()?),
+      otherwise => return Err(invalid_signature(format!("Invalid custom attribute element type: {:?}", otherwise)))
+    })
+  }
+
+  fn read_value(&mut self, type_: &SerializationType) -> Result<CustomAttributeValue> {
+    Ok(match *type_ {
+      SerializationType::Primitive(element_type) => self.read_primitive(element_type)?,
+      SerializationType::String => CustomAttributeValue::String(self.read_ser_string()?),
+      SerializationType::Type => CustomAttributeValue::Type(self.read_ser_string()?),
+      SerializationType::Boxed => {
+        let boxed_type = self.read_field_or_prop_type()?;
+        self.read_value(&boxed_type)?
+      },
+      SerializationType::Enum(ref name, underlying_type) =>
+        CustomAttributeValue::Enum(name.clone(), box self.read_primitive(underlying_type)?),
+      SerializationType::SzArray(ref element_type) => {
+        let count = self.cursor.read_u32::<LittleEndian>()?;
+
+        if count == 0xFFFF_FFFF {
+          CustomAttributeValue::Array(None)
+        } else {
+          let mut elements = Vec::with_capacity(cmp::min(count, 1024) as usize);
+          for _ in 0 .. count {
+            elements.push(self.read_value(element_type)?);
+          }
+          CustomAttributeValue::Array(Some(elements))
+        }
+      }
+    })
+  }
+
+  fn read_named_arg(&mut self) -> Result<NamedArgument> {
+    let kind = match read_element_type(&mut self.cursor)? {
+      ElementType::Field => NamedArgumentKind::Field,
+      ElementType::Property => NamedArgumentKind::Property,
+      otherwise => return Err(invalid_signature(format!("Invalid named argument kind: {:?}", otherwise)))
+    };
+
+    let type_ = self.read_field_or_prop_type()?;
+    let name = self.read_ser_string()?.unwrap_or_default();
+    let value = self.read_value(&type_)?;
+
+    Ok(NamedArgument { kind, name, value })
+  }
+
+  fn enum_underlying_type(&self, type_: &TypeDefOrRef) -> Result<ElementType> {
+    let local = match *type_ {
+      TypeDefOrRef::TypeDef(ref type_def) => enum_underlying_type_of(self.meta, type_def),
+      _ => None
+    };
+
+    match local {
+      Some(underlying_type) => Ok(underlying_type),
+      None => self.enum_underlying_type_by_name(&self.meta.full_type_name(type_).as_reflection())
+    }
+  }
+
+  // The name may be assembly-qualified, e.g. "System.AttributeTargets, mscorlib, Version=4.0.0.0",
+  // and uses '+' to separate nested types. The size of the values depends on the underlying type,
+  // so the rest of the blob can't be read if the enum isn't found.
+  fn enum_underlying_type_by_name(&self, qualified_name: &str) -> Result<ElementType> {
+    let full_name = qualified_name.split(',').next().unwrap_or("").trim();
+    let candidates = Some(self.meta).into_iter().chain(self.references.iter().cloned());
+
+    for meta in candidates {
+      if let Some(type_def) = meta.find_type_def_by_full_name(full_name) {
+        if let Some(underlying_type) = enum_underlying_type_of(meta, &type_def) {
+          return Ok(underlying_type);
+        }
+      }
+    }
+
+    Err(invalid_signature(format!("Can't find the underlying type of enum {}", qualified_name)))
+  }
+}
+
+/// Reads a sequence of named arguments, which are also used by permission sets.
+pub fn decode_named_args(meta: &Metadata, references: &[&Metadata], blob: &[u8], count: u32) -> Result<Vec<NamedArgument>> {
+  let mut reader = CustomAttributeReader { meta, references, cursor: Cursor::new(blob) };
+  (0 .. count).map(|_| reader.read_named_arg()).collect()
+}
+
+// The underlying type of an enum is the type of its only instance field, "value__"
+fn enum_underlying_type_of(meta: &Metadata, type_def: &Index<TypeDefEntry>) -> Option<ElementType> {
+  const FIELD_STATIC: u16 = 0x0010;
+
+  meta.get_field_range(type_def)
+    .map(|field| Index::<FieldEntry>::new(field))
+    .filter(|field| meta.get_entry(field).map(|entry| entry.flags & FIELD_STATIC == 0).unwrap_or(false))
+    .filter_map(|field| meta.get_field_signature(&field).ok())
+    .next()
+    .and_then(|field_sig| match field_sig.type_ {
+      TypeSig::Boolean => Some(ElementType::Boolean),
+      TypeSig::Char => Some(ElementType::Char),
+      TypeSig::I1 => Some(ElementType::I1),
+      TypeSig::U1 => Some(ElementType::U1),
+      TypeSig::I2 => Some(ElementType::I2),
+      TypeSig::U2 => Some(ElementType::U2),
+      TypeSig::I4 => Some(ElementType::I4),
+      TypeSig::U4 => Some(ElementType::U4),
+      TypeSig::I8 => Some(ElementType::I8),
+      TypeSig::U8 => Some(ElementType::U8),
+      _ => None
+    })
+}
+
+impl Metadata {
+  /// Returns the namespace-qualified name of the attribute class of a constructor.
+  pub fn get_attribute_type_name(&self, constructor: &CustomAttributeType) -> String {
+    match *constructor {
+      CustomAttributeType::MethodDef(ref method) => self.get_method_owner(method)
+        .map(|type_def| self.get_type_name(&TypeDefOrRef::TypeDef(type_def)))
+        .unwrap_or_default(),
+      CustomAttributeType::MemberRef(ref member) => match self.get_entry(member).map(|entry| entry.class) {
+        Some(MemberRefParent::TypeDef(type_def)) => self.get_type_name(&TypeDefOrRef::TypeDef(type_def)),
+        Some(MemberRefParent::TypeRef(type_ref)) => self.get_type_name(&TypeDefOrRef::TypeRef(type_ref)),
+        Some(MemberRefParent::TypeSpec(type_spec)) => self.get_type_name(&TypeDefOrRef::TypeSpec(type_spec)),
+        _ => String::new()
+      }
+    }
+  }
+
+  /// Decodes a custom attribute value blob, using the constructor's signature to parse the fixed arguments.
+  /// The underlying types of enums defined in other assemblies are looked up from `references`.
+  pub fn decode_custom_attribute(&self, entry: &CustomAttributeEntry, references: &[&Metadata]) -> Result<CustomAttribute> {
+    let signature = match entry.constructor {
+      CustomAttributeType::MethodDef(ref method) => self.get_method_def_signature(method)?,
+      CustomAttributeType::MemberRef(ref member) => self.get_member_ref_signature(member)?
+    };
+
+    let blob = self.get_blob(&entry.value).map(|blob| &blob[..]).unwrap_or(&[]);
+    let mut reader = CustomAttributeReader { meta: self, references, cursor: Cursor::new(blob) };
+
+    let mut fixed_args = vec![];
+    let mut named_args = vec![];
+
+    // An attribute without arguments may have an empty blob
+    if !blob.is_empty() {
+      let prolog = reader.cursor.read_u16::<LittleEndian>()?;
+      if prolog != 0x0001 {
+        return Err(invalid_signature(format!("Invalid custom attribute prolog: {:04x}", prolog)));
+      }
+
+      for param in &signature.params {
+        let type_ = reader.type_from_signature(param)?;
+        fixed_args.push(reader.read_value(&type_)?);
+      }
+
+      let named_arg_count = reader.cursor.read_u16::<LittleEndian>()?;
+      for _ in 0 .. named_arg_count {
+        named_args.push(reader.read_named_arg()?);
+      }
+    }
+
+    Ok(CustomAttribute {
+      constructor: entry.constructor,
+      attribute_type: self.get_attribute_type_name(&entry.constructor),
+      fixed_args,
+      named_args
+    })
+  }
+
+  /// Returns the decoded custom attributes applied to a metadata entity.
+  pub fn custom_attributes_of(&self, parent: HasCustomAttribute) -> Result<Vec<CustomAttribute>> {
+    self.custom_attributes_of_with_references(parent, &[])
+  }
+
+  pub fn custom_attributes_of_with_references(&self, parent: HasCustomAttribute, references: &[&Metadata]) -> Result<Vec<CustomAttribute>> {
+    let entries = match self.get_table::<CustomAttributeEntry>() {
+      Some(entries) => entries,
+      None => return Ok(vec![])
+    };
+
+    entries.iter()
+      .filter(|entry| entry.parent == parent)
+      .map(|entry| self.decode_custom_attribute(entry, references))
+      .collect()
+  }
+}
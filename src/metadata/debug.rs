@@ -1,8 +1,14 @@
-
 use std::fmt::Write;
 
+use loader::clr::CLRImage;
+use loader::code::{ExceptionClauseKind, InitLocals};
+use loader::instructions::{Instruction, Token};
+use loader::stream::TableId;
 use metadata::tables::*;
 use metadata::Metadata;
+use metadata::constants::ConstantValue;
+use metadata::generics::Variance;
+use metadata::signature::{TypeSig, MethodSignature, CallingConvention, FieldSig};
 
 impl ModuleEntry {
   pub fn as_debug(&self, meta: &Metadata) -> String {
@@ -27,4 +33,853 @@ impl MemberRefEntry {
 
     res
   }
-}
\ No newline at end of file
+}
+
+// The rest of this file is an ILDasm-like disassembler. The output aims to be close enough
+// to what ildasm prints for the two to be diffed, not to be valid ILAsm input in every case.
+
+// Names which aren't plain identifiers are quoted, e.g. '<>c__DisplayClass0_0'
+fn il_identifier(name: &str) -> String {
+  let is_plain = name == ".ctor" || name == ".cctor" || (!name.is_empty() &&
+    !name.starts_with(|c: char| c.is_digit(10)) &&
+    name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$' || c == '@' || c == '`' || c == '.'));
+
+  if is_plain {
+    name.to_string()
+  } else {
+    format!("'{}'", name.replace('\'', "\\'"))
+  }
+}
+
+fn il_string_literal(value: &str) -> String {
+  let mut res = String::from("\"");
+
+  for c in value.chars() {
+    match c {
+      '"' => res.push_str("\\\""),
+      '\\' => res.push_str("\\\\"),
+      '\n' => res.push_str("\\n"),
+      '\r' => res.push_str("\\r"),
+      '\t' => res.push_str("\\t"),
+      c if (c as u32) < 0x20 => write!(&mut res, "\\{:03o}", c as u32).unwrap(),
+      c => res.push(c)
+    }
+  }
+
+  res.push('"');
+  res
+}
+
+fn il_bytes(bytes: &[u8]) -> String {
+  let mut res = String::from("(");
+  for byte in bytes {
+    write!(&mut res, " {:02X}", byte).unwrap();
+  }
+  res.push_str(" )");
+  res
+}
+
+impl TypeSig {
+  /// Formats the type using ILAsm syntax, e.g. `class [mscorlib]System.Collections.Generic.List`1<int32>`.
+  pub fn as_il(&self, meta: &Metadata) -> String {
+    match *self {
+      TypeSig::Void => "void".to_string(),
+      TypeSig::Boolean => "bool".to_string(),
+      TypeSig::Char => "char".to_string(),
+      TypeSig::I1 => "int8".to_string(),
+      TypeSig::U1 => "uint8".to_string(),
+      TypeSig::I2 => "int16".to_string(),
+      TypeSig::U2 => "uint16".to_string(),
+      TypeSig::I4 => "int32".to_string(),
+      TypeSig::U4 => "uint32".to_string(),
+      TypeSig::I8 => "int64".to_string(),
+      TypeSig::U8 => "uint64".to_string(),
+      TypeSig::R4 => "float32".to_string(),
+      TypeSig::R8 => "float64".to_string(),
+      TypeSig::I => "native int".to_string(),
+      TypeSig::U => "native uint".to_string(),
+      TypeSig::String => "string".to_string(),
+      TypeSig::Object => "object".to_string(),
+      TypeSig::TypedByRef => "typedref".to_string(),
+      TypeSig::Class(ref type_) => format!("class {}", meta.get_il_type_name(type_)),
+      TypeSig::ValueType(ref type_) => format!("valuetype {}", meta.get_il_type_name(type_)),
+      TypeSig::Ptr(ref inner) => format!("{}*", inner.as_il(meta)),
+      TypeSig::ByRef(ref inner) => format!("{}&", inner.as_il(meta)),
+      TypeSig::SzArray(ref inner) => format!("{}[]", inner.as_il(meta)),
+      TypeSig::Array(ref inner, ref shape) => {
+        let dimensions = (0 .. shape.rank as usize).map(|dimension| {
+          match (shape.lower_bounds.get(dimension), shape.sizes.get(dimension)) {
+            (Some(&lower), Some(&size)) => format!("{}...{}", lower, lower + size as i32 - 1),
+            (Some(&lower), None) => format!("{}...", lower),
+            (None, Some(&size)) => size.to_string(),
+            (None, None) => String::new()
+          }
+        }).collect::<Vec<_>>();
+        format!("{}[{}]", inner.as_il(meta), dimensions.join(","))
+      },
+      TypeSig::GenericInst(ref generic_type, ref args) => {
+        let args = args.iter().map(|arg| arg.as_il(meta)).collect::<Vec<_>>();
+        format!("{}<{}>", generic_type.as_il(meta), args.join(","))
+      },
+      TypeSig::Var(number) => format!("!{}", number),
+      TypeSig::MVar(number) => format!("!!{}", number),
+      TypeSig::FnPtr(ref signature) => format!("method {}", signature.as_il(meta, "*", &[])),
+      TypeSig::CModReqd(ref modifier, ref inner) =>
+        format!("{} modreq({})", inner.as_il(meta), meta.get_il_type_name(modifier)),
+      TypeSig::CModOpt(ref modifier, ref inner) =>
+        format!("{} modopt({})", inner.as_il(meta), meta.get_il_type_name(modifier)),
+      TypeSig::Pinned(ref inner) => format!("{} pinned", inner.as_il(meta)),
+      TypeSig::Sentinel => "...".to_string()
+    }
+  }
+}
+
+impl MethodSignature {
+  /// Formats the signature using ILAsm syntax, e.g. `instance void Sample::Run(int32 count)`.
+  /// Parameters without a name in `param_names` only show their type. Names are expected to be quoted already.
+  pub fn as_il(&self, meta: &Metadata, name: &str, param_names: &[String]) -> String {
+    let mut res = String::new();
+
+    if self.has_this() {
+      res.push_str("instance ");
+    }
+
+    match self.calling_convention {
+      CallingConvention::VarArg => res.push_str("vararg "),
+      CallingConvention::C => res.push_str("unmanaged cdecl "),
+      CallingConvention::StdCall => res.push_str("unmanaged stdcall "),
+      CallingConvention::ThisCall => res.push_str("unmanaged thiscall "),
+      CallingConvention::FastCall => res.push_str("unmanaged fastcall "),
+      _ => ()
+    }
+
+    let mut params = self.params.iter().enumerate().map(|(position, param)| {
+      match param_names.get(position) {
+        // Markers like [out] go before the type
+        Some(name) if !name.is_empty() => match name.rfind("] ") {
+          Some(split) => format!("{} {} {}", &name[.. split + 1], param.as_il(meta), &name[split + 2 ..]),
+          None => format!("{} {}", param.as_il(meta), name)
+        },
+        _ => param.as_il(meta)
+      }
+    }).collect::<Vec<_>>();
+
+    if let Some(sentinel) = self.sentinel {
+      params.insert(sentinel, "...".to_string());
+    }
+
+    write!(&mut res, "{} {}({})", self.return_type.as_il(meta), name, params.join(", ")).unwrap();
+    res
+  }
+}
+
+fn method_access(flags: u16) -> &'static str {
+  match flags & 0x0007 {
+    0x0001 => "private",
+    0x0002 => "famandassem",
+    0x0003 => "assembly",
+    0x0004 => "family",
+    0x0005 => "famorassem",
+    0x0006 => "public",
+    _ => "privatescope"
+  }
+}
+
+fn type_attributes_as_il(flags: TypeAttributes) -> String {
+  let mut res = vec![];
+
+  if flags.contains(tdInterface) {
+    res.push("interface");
+  }
+
+  res.push(match (flags & tdVisibilityMask).bits() {
+    0x1 => "public",
+    0x2 => "nested public",
+    0x3 => "nested private",
+    0x4 => "nested family",
+    0x5 => "nested assembly",
+    0x6 => "nested famandassem",
+    0x7 => "nested famorassem",
+    _ => "private"
+  });
+
+  if flags.contains(tdAbstract) { res.push("abstract"); }
+
+  res.push(match (flags & tdLayoutMask).bits() {
+    0x08 => "sequential",
+    0x10 => "explicit",
+    _ => "auto"
+  });
+
+  res.push(match (flags & tdStringFormatMask).bits() {
+    0x10000 => "unicode",
+    0x20000 => "autochar",
+    _ => "ansi"
+  });
+
+  if flags.contains(tdSealed) { res.push("sealed"); }
+  if flags.contains(tdSpecialName) { res.push("specialname"); }
+  if flags.contains(tdRTSpecialName) { res.push("rtspecialname"); }
+  if flags.contains(tdImport) { res.push("import"); }
+  if flags.contains(tdSerializable) { res.push("serializable"); }
+  if flags.contains(tdBeforeFieldInit) { res.push("beforefieldinit"); }
+
+  res.join(" ")
+}
+
+fn method_attributes_as_il(flags: u16) -> String {
+  let mut res = vec![method_access(flags)];
+
+  if flags & 0x0080 != 0 { res.push("hidebysig"); }
+  if flags & 0x0100 != 0 { res.push("newslot"); }
+  if flags & 0x0800 != 0 { res.push("specialname"); }
+  if flags & 0x1000 != 0 { res.push("rtspecialname"); }
+  if flags & 0x0400 != 0 { res.push("abstract"); }
+  if flags & 0x0040 != 0 { res.push("virtual"); }
+  if flags & 0x0020 != 0 { res.push("final"); }
+  if flags & 0x0010 != 0 { res.push("static"); }
+  if flags & 0x2000 != 0 { res.push("pinvokeimpl"); }
+
+  res.join(" ")
+}
+
+fn method_impl_attributes_as_il(impl_flags: u16) -> String {
+  let mut res = vec![match impl_flags & 0x0003 {
+    0x0001 => "native",
+    0x0002 => "optil",
+    0x0003 => "runtime",
+    _ => "cil"
+  }];
+
+  res.push(if impl_flags & 0x0004 != 0 { "unmanaged" } else { "managed" });
+
+  if impl_flags & 0x0080 != 0 { res.push("preservesig"); }
+  if impl_flags & 0x1000 != 0 { res.push("internalcall"); }
+  if impl_flags & 0x0020 != 0 { res.push("synchronized"); }
+  if impl_flags & 0x0008 != 0 { res.push("noinlining"); }
+  if impl_flags & 0x0100 != 0 { res.push("aggressiveinlining"); }
+
+  res.join(" ")
+}
+
+fn field_attributes_as_il(flags: u16) -> String {
+  let mut res = vec![method_access(flags)];
+
+  if flags & 0x0010 != 0 { res.push("static"); }
+  if flags & 0x0020 != 0 { res.push("initonly"); }
+  if flags & 0x0040 != 0 { res.push("literal"); }
+  if flags & 0x0080 != 0 { res.push("notserialized"); }
+  if flags & 0x0200 != 0 { res.push("specialname"); }
+  if flags & 0x0400 != 0 { res.push("rtspecialname"); }
+
+  res.join(" ")
+}
+
+fn constant_as_il(value: &ConstantValue) -> String {
+  match *value {
+    ConstantValue::Bool(value) => format!("bool({})", value),
+    ConstantValue::Char(value) => format!("char(0x{:04X})", value),
+    ConstantValue::I1(value) => format!("int8(0x{:02X})", value),
+    ConstantValue::U1(value) => format!("uint8(0x{:02X})", value),
+    ConstantValue::I2(value) => format!("int16(0x{:04X})", value),
+    ConstantValue::U2(value) => format!("uint16(0x{:04X})", value),
+    ConstantValue::I4(value) => format!("int32(0x{:08X})", value),
+    ConstantValue::U4(value) => format!("uint32(0x{:08X})", value),
+    ConstantValue::I8(value) => format!("int64(0x{:016X})", value),
+    ConstantValue::U8(value) => format!("uint64(0x{:016X})", value),
+    ConstantValue::R4(value) => format!("float32({:?})", value),
+    ConstantValue::R8(value) => format!("float64({:?})", value),
+    ConstantValue::String(ref value) => il_string_literal(&String::from_utf16_lossy(value)),
+    ConstantValue::Null => "nullref".to_string()
+  }
+}
+
+// The opening and closing lines of a .try, handler or filter block
+struct Block {
+  start: u32,
+  end: u32,
+  header: String,
+  footer: &'static str
+}
+
+impl Metadata {
+  /// Returns the name of a type in ILAsm syntax, e.g. `[mscorlib]System.Console` or `Sample.Outer/Inner`.
+  pub fn get_il_type_name(&self, type_: &TypeDefOrRef) -> String {
+    let scope = match *type_ {
+      TypeDefOrRef::TypeSpec(ref index) => return self.get_entry(index)
+        .and_then(|entry| self.get_blob(&entry.signature))
+        .and_then(|blob| TypeSig::from_blob(blob).ok())
+        .map(|type_sig| type_sig.as_il(self))
+        .unwrap_or_else(|| format!("TypeSpec#{}", index.0)),
+      TypeDefOrRef::TypeRef(mut index) => {
+        // Nested type references are scoped by their enclosing type, so follow the chain to the outermost one
+        let mut scope = None;
+        for _ in 0 .. 64 {
+          match self.get_entry(&index).map(|entry| entry.resolution_scope) {
+            Some(ResolutionScope::TypeRef(enclosing)) => index = enclosing,
+            other => {
+              scope = other;
+              break
+            }
+          }
+        }
+
+        match scope {
+          Some(ResolutionScope::AssemblyRef(assembly_ref)) => self.get_entry(&assembly_ref)
+            .and_then(|entry| self.get_string(&entry.name))
+            .map(|name| format!("[{}]", il_identifier(name)))
+            .unwrap_or_default(),
+          _ => String::new()
+        }
+      },
+      TypeDefOrRef::TypeDef(_) => String::new()
+    };
+
+    let name = self.full_type_name(type_);
+    let names = name.names.iter().map(|name| il_identifier(name)).collect::<Vec<_>>();
+
+    if name.namespace.is_empty() {
+      format!("{}{}", scope, names.join("/"))
+    } else {
+      format!("{}{}.{}", scope, name.namespace, names.join("/"))
+    }
+  }
+
+  fn get_il_member_parent_name(&self, parent: &MemberRefParent) -> Option<String> {
+    match *parent {
+      MemberRefParent::TypeDef(type_def) => Some(self.get_il_type_name(&TypeDefOrRef::TypeDef(type_def))),
+      MemberRefParent::TypeRef(type_ref) => Some(self.get_il_type_name(&TypeDefOrRef::TypeRef(type_ref))),
+      MemberRefParent::TypeSpec(type_spec) => Some(self.get_il_type_name(&TypeDefOrRef::TypeSpec(type_spec))),
+      _ => None
+    }
+  }
+
+  /// Formats a reference to a method, as used by call instructions and custom attributes.
+  pub fn get_il_method_ref(&self, method: &MethodDefOrRef, type_args: Option<&[TypeSig]>) -> String {
+    let (owner, name, signature) = match *method {
+      MethodDefOrRef::MethodDef(ref index) => (
+        self.get_method_owner(index).map(|owner| self.get_il_type_name(&TypeDefOrRef::TypeDef(owner))),
+        self.get_entry(index).and_then(|entry| self.get_string(&entry.name)).cloned().unwrap_or_default(),
+        self.get_method_def_signature(index)
+      ),
+      MethodDefOrRef::MethodRef(ref index) => match self.get_entry(index) {
+        Some(entry) => (
+          self.get_il_member_parent_name(&entry.class),
+          self.get_string(&entry.name).cloned().unwrap_or_default(),
+          self.get_member_ref_signature(index)
+        ),
+        None => return format!("MemberRef#{}", index.0)
+      }
+    };
+
+    let mut name = match owner {
+      Some(owner) => format!("{}::{}", owner, il_identifier(&name)),
+      None => il_identifier(&name)
+    };
+
+    if let Some(type_args) = type_args {
+      let type_args = type_args.iter().map(|type_arg| type_arg.as_il(self)).collect::<Vec<_>>();
+      write!(&mut name, "<{}>", type_args.join(",")).unwrap();
+    }
+
+    match signature {
+      Ok(signature) => signature.as_il(self, &name, &[]),
+      Err(_) => name
+    }
+  }
+
+  fn get_il_field_ref(&self, owner: Option<String>, name: &str, signature: Option<&Vec<u8>>) -> String {
+    let type_ = signature
+      .and_then(|blob| FieldSig::from_blob(blob).ok())
+      .map(|field_sig| field_sig.type_.as_il(self))
+      .unwrap_or_default();
+
+    match owner {
+      Some(owner) => format!("{} {}::{}", type_, owner, il_identifier(name)),
+      None => format!("{} {}", type_, il_identifier(name))
+    }
+  }
+
+  /// Formats the operand of an instruction which references a metadata token.
+  pub fn get_il_token(&self, token: &Token) -> String {
+    match *token {
+      Token::UserString(index) => self.get_user_string(index)
+        .map(|value| il_string_literal(&value))
+        .unwrap_or_else(|| format!("/* invalid string {:08x} */", token.to_raw())),
+      Token::Table(TableId::TypeDef, index) => self.get_il_type_name(&TypeDefOrRef::TypeDef(Index::new(index))),
+      Token::Table(TableId::TypeRef, index) => self.get_il_type_name(&TypeDefOrRef::TypeRef(Index::new(index))),
+      Token::Table(TableId::TypeSpec, index) => self.get_il_type_name(&TypeDefOrRef::TypeSpec(Index::new(index))),
+      Token::Table(TableId::MethodDef, index) => self.get_il_method_ref(&MethodDefOrRef::MethodDef(Index::new(index)), None),
+      Token::Table(TableId::Field, index) => {
+        let field = Index::<FieldEntry>::new(index);
+        match self.get_entry(&field) {
+          Some(entry) => {
+            let owner = self.get_field_owner(&field).map(|owner| self.get_il_type_name(&TypeDefOrRef::TypeDef(owner)));
+            let name = self.get_string(&entry.name).cloned().unwrap_or_default();
+            self.get_il_field_ref(owner, &name, self.get_blob(&entry.signature))
+          },
+          None => format!("Field#{}", index)
+        }
+      },
+      Token::Table(TableId::MemberRef, index) => {
+        let member = Index::<MemberRefEntry>::new(index);
+        match self.get_entry(&member) {
+          // Field signatures start with 0x06, anything else is a method
+          Some(entry) if self.get_blob(&entry.signature).and_then(|blob| blob.first()) == Some(&0x06) => {
+            let owner = self.get_il_member_parent_name(&entry.class);
+            let name = self.get_string(&entry.name).cloned().unwrap_or_default();
+            self.get_il_field_ref(owner, &name, self.get_blob(&entry.signature))
+          },
+          _ => self.get_il_method_ref(&MethodDefOrRef::MethodRef(member), None)
+        }
+      },
+      Token::Table(TableId::MethodSpec, index) => match self.get_method_spec(&Index::new(index)) {
+        Ok(method_spec) => self.get_il_method_ref(&method_spec.method, Some(&method_spec.type_args)),
+        Err(_) => format!("MethodSpec#{}", index)
+      },
+      Token::Table(TableId::StandAloneSig, index) => self.get_entry(&Index::<StandAloneSigEntry>::new(index))
+        .and_then(|entry| self.get_blob(&entry.signature))
+        .and_then(|blob| MethodSignature::from_blob(blob).ok())
+        .map(|signature| signature.as_il(self, "*", &[]))
+        .unwrap_or_else(|| format!("StandAloneSig#{}", index)),
+      Token::Table(table_id, index) => format!("{:?}#{}", table_id, index)
+    }
+  }
+
+  fn get_il_generic_params(&self, owner: TypeOrMethodDef) -> String {
+    let params = self.generic_params_of(owner);
+
+    if params.is_empty() {
+      return String::new();
+    }
+
+    let params = params.iter().map(|param| {
+      let mut res = String::new();
+      if param.reference_type { res.push_str("class "); }
+      if param.value_type { res.push_str("valuetype "); }
+      if param.default_constructor { res.push_str(".ctor "); }
+
+      match param.variance {
+        Variance::Covariant => res.push('+'),
+        Variance::Contravariant => res.push('-'),
+        Variance::Invariant => ()
+      }
+
+      if !param.constraints.is_empty() {
+        let constraints = param.constraints.iter().map(|constraint| self.get_il_type_name(constraint)).collect::<Vec<_>>();
+        write!(&mut res, "({}) ", constraints.join(", ")).unwrap();
+      }
+
+      res.push_str(&il_identifier(&param.name));
+      res
+    }).collect::<Vec<_>>();
+
+    format!("<{}>", params.join(","))
+  }
+
+  fn write_il_custom_attributes(&self, res: &mut String, parent: HasCustomAttribute, indent: &str) {
+    let entries = match self.get_table::<CustomAttributeEntry>() {
+      Some(entries) => entries,
+      None => return
+    };
+
+    for entry in entries.iter().filter(|entry| entry.parent == parent) {
+      let constructor = match entry.constructor {
+        CustomAttributeType::MethodDef(method) => MethodDefOrRef::MethodDef(method),
+        CustomAttributeType::MemberRef(member) => MethodDefOrRef::MethodRef(member)
+      };
+
+      let value = self.get_blob(&entry.value).map(|blob| &blob[..]).unwrap_or(&[]);
+      writeln!(res, "{}.custom {} = {}", indent, self.get_il_method_ref(&constructor, None), il_bytes(value)).unwrap();
+    }
+  }
+
+  fn get_param_names(&self, method: &Index<MethodDefEntry>, param_count: usize) -> Vec<String> {
+    let mut names = vec![String::new(); param_count];
+
+    for param in self.get_param_range(method) {
+      if let Some(entry) = self.get_entry(&Index::<ParamEntry>::new(param)) {
+        // Sequence 0 is the return value
+        let position = entry.sequence as usize;
+        if position >= 1 && position <= param_count {
+          let mut name = String::new();
+          if entry.flags & 0x0001 != 0 { name.push_str("[in] "); }
+          if entry.flags & 0x0002 != 0 { name.push_str("[out] "); }
+          if entry.flags & 0x0010 != 0 { name.push_str("[opt] "); }
+          name.push_str(&il_identifier(self.get_string(&entry.name).map(|x| &x[..]).unwrap_or("")));
+          names[position - 1] = name;
+        }
+      }
+    }
+
+    names
+  }
+
+  fn get_il_operand(&self, instruction: &Instruction, arg_names: &[String]) -> Option<String> {
+    let label = |offset: u32| format!("IL_{:04x}", offset);
+    // Parameter names may carry [in]/[out] markers, which aren't part of the operand
+    let arg = |index: u16| match arg_names.get(index as usize).and_then(|name| name.rsplit(' ').next()) {
+      Some(name) if !name.is_empty() => name.to_string(),
+      _ => index.to_string()
+    };
+
+    Some(match *instruction {
+      Instruction::LdargS(index) | Instruction::LdargaS(index) | Instruction::StargS(index) => arg(index as u16),
+      Instruction::Ldarg(index) | Instruction::Ldarga(index) | Instruction::Starg(index) => arg(index),
+      Instruction::LdlocS(index) | Instruction::LdlocaS(index) | Instruction::StlocS(index) => format!("V_{}", index),
+      Instruction::Ldloc(index) | Instruction::Ldloca(index) | Instruction::Stloc(index) => format!("V_{}", index),
+      Instruction::LdcI4S(value) => value.to_string(),
+      Instruction::LdcI4(value) => value.to_string(),
+      Instruction::LdcI8(value) => format!("0x{:x}", value),
+      Instruction::LdcR4(value) => format!("{:?}", value),
+      Instruction::LdcR8(value) => format!("{:?}", value),
+      Instruction::Unaligned(value) | Instruction::No(value) => value.to_string(),
+      Instruction::Switch(ref targets) =>
+        format!("({})", targets.iter().map(|&target| label(target)).collect::<Vec<_>>().join(", ")),
+      Instruction::Ldtoken(ref token) => match *token {
+        Token::Table(TableId::MethodDef, _) | Token::Table(TableId::MethodSpec, _) => format!("method {}", self.get_il_token(token)),
+        Token::Table(TableId::Field, _) => format!("field {}", self.get_il_token(token)),
+        Token::Table(TableId::MemberRef, index) => {
+          let is_field = self.get_entry(&Index::<MemberRefEntry>::new(index))
+            .and_then(|entry| self.get_blob(&entry.signature))
+            .and_then(|blob| blob.first()) == Some(&0x06);
+          format!("{} {}", if is_field { "field" } else { "method" }, self.get_il_token(token))
+        },
+        _ => self.get_il_token(token)
+      },
+      ref other => match other.branch_target() {
+        Some(target) => label(target),
+        None => match other.token() {
+          Some(token) => self.get_il_token(token),
+          None => return None
+        }
+      }
+    })
+  }
+
+  /// Disassembles a single method, including its header, locals and exception handling blocks.
+  pub fn disassemble_method(&self, method: &Index<MethodDefEntry>, is_entry_point: bool) -> String {
+    let mut res = String::new();
+
+    let entry = match self.get_entry(method) {
+      Some(entry) => entry,
+      None => return res
+    };
+
+    let name = self.get_string(&entry.name).cloned().unwrap_or_default();
+    let signature = match self.get_method_def_signature(method) {
+      Ok(signature) => signature,
+      Err(error) => {
+        writeln!(&mut res, "  // Invalid signature of {}: {}", name, error).unwrap();
+        return res;
+      }
+    };
+
+    let param_names = self.get_param_names(method, signature.params.len());
+    let generic_params = self.get_il_generic_params(TypeOrMethodDef::MethodDef(*method));
+    let declared_name = format!(" {}{}", il_identifier(&name), generic_params);
+
+    writeln!(&mut res, "  .method {} {} {}",
+      method_attributes_as_il(entry.flags),
+      signature.as_il(self, &declared_name, &param_names),
+      method_impl_attributes_as_il(entry.impl_flags)).unwrap();
+    res.push_str("  {\n");
+
+    self.write_il_custom_attributes(&mut res, HasCustomAttribute::MethodDef(*method), "    ");
+
+    if is_entry_point {
+      res.push_str("    .entrypoint\n");
+    }
+
+    if let Some(body) = self.method_bodies.get(&(method.0 - 1)) {
+      writeln!(&mut res, "    // Code size       {} (0x{:x})", body.code.len(), body.code.len()).unwrap();
+      writeln!(&mut res, "    .maxstack  {}", body.header.max_stack).unwrap();
+
+      match self.get_local_var_signature(&body.header) {
+        Ok(Some(locals)) => {
+          let locals = locals.locals.iter().enumerate().map(|(position, local)| {
+            let mut type_ = local.type_.as_il(self);
+            if local.by_ref { type_.push('&'); }
+            if local.pinned { type_.push_str(" pinned"); }
+            format!("{} V_{}", type_, position)
+          }).collect::<Vec<_>>();
+
+          let init = if body.header.flags.contains(InitLocals) { "init " } else { "" };
+          writeln!(&mut res, "    .locals {}({})", init, locals.join(",\n             ")).unwrap();
+        },
+        Ok(None) => (),
+        Err(error) => writeln!(&mut res, "    // Invalid locals: {}", error).unwrap()
+      }
+
+      // Argument 0 of instance methods is `this`, which ildasm never names
+      let mut arg_names = if signature.has_this() { vec![String::new()] } else { vec![] };
+      arg_names.extend(param_names.iter().cloned());
+
+      let mut blocks = vec![];
+      for clause in &body.exception_clauses {
+        let try_end = clause.try_offset + clause.try_length;
+        let handler_end = clause.handler_offset + clause.handler_length;
+
+        // Clauses protecting the same region share a single .try block
+        if !blocks.iter().any(|block: &Block| block.start == clause.try_offset && block.end == try_end && block.header == ".try") {
+          blocks.push(Block { start: clause.try_offset, end: try_end, header: ".try".to_string(), footer: "// end .try" });
+        }
+
+        let header = match clause.kind {
+          ExceptionClauseKind::Catch(token) => match Token::from_raw(token) {
+            Ok(token) => format!("catch {}", self.get_il_token(&token)),
+            Err(_) => format!("catch /* invalid token {:08x} */", token)
+          },
+          ExceptionClauseKind::Filter(filter_offset) => {
+            blocks.push(Block { start: filter_offset, end: clause.handler_offset, header: "filter".to_string(), footer: "// end filter" });
+            String::new()
+          },
+          ExceptionClauseKind::Finally => "finally".to_string(),
+          ExceptionClauseKind::Fault => "fault".to_string()
+        };
+
+        blocks.push(Block { start: clause.handler_offset, end: handler_end, header, footer: "// end handler" });
+      }
+
+      // Outer blocks are opened first
+      blocks.sort_by(|a, b| (a.start, b.end).cmp(&(b.start, a.end)));
+
+      let mut open_blocks: Vec<&Block> = vec![];
+      let mut next_block = 0;
+
+      for instruction in body.instructions() {
+        let (offset, instruction) = match instruction {
+          Ok(instruction) => instruction,
+          Err(error) => {
+            writeln!(&mut res, "    // {}", error).unwrap();
+            break;
+          }
+        };
+
+        while open_blocks.last().map(|block| block.end <= offset).unwrap_or(false) {
+          let block = open_blocks.pop().unwrap();
+          let indent = "  ".repeat(open_blocks.len() + 2);
+          writeln!(&mut res, "{}}}  {}", indent, block.footer).unwrap();
+        }
+
+        while next_block < blocks.len() && blocks[next_block].start <= offset {
+          let block = &blocks[next_block];
+          let indent = "  ".repeat(open_blocks.len() + 2);
+          if block.header.is_empty() {
+            writeln!(&mut res, "{}{{  // handler", indent).unwrap();
+          } else {
+            writeln!(&mut res, "{}{}\n{}{{", indent, block.header, indent).unwrap();
+          }
+          open_blocks.push(block);
+          next_block += 1;
+        }
+
+        let indent = "  ".repeat(open_blocks.len() + 2);
+        match self.get_il_operand(&instruction, &arg_names) {
+          Some(operand) => writeln!(&mut res, "{}IL_{:04x}:  {:<10} {}", indent, offset, instruction.mnemonic(), operand).unwrap(),
+          None => writeln!(&mut res, "{}IL_{:04x}:  {}", indent, offset, instruction.mnemonic()).unwrap()
+        }
+      }
+
+      while let Some(block) = open_blocks.pop() {
+        let indent = "  ".repeat(open_blocks.len() + 2);
+        writeln!(&mut res, "{}}}  {}", indent, block.footer).unwrap();
+      }
+    }
+
+    let owner = self.get_method_owner(method)
+      .map(|owner| self.full_type_name(&TypeDefOrRef::TypeDef(owner)).names.join("/"))
+      .unwrap_or_default();
+    writeln!(&mut res, "  }} // end of method {}::{}", owner, name).unwrap();
+
+    res
+  }
+
+  fn disassemble_field(&self, field: &Index<FieldEntry>) -> String {
+    let entry = match self.get_entry(field) {
+      Some(entry) => entry,
+      None => return String::new()
+    };
+
+    let name = self.get_string(&entry.name).cloned().unwrap_or_default();
+    let type_ = self.get_field_signature(field).map(|field_sig| field_sig.type_.as_il(self)).unwrap_or_default();
+    let mut res = format!("  .field {} {} {}", field_attributes_as_il(entry.flags), type_, il_identifier(&name));
+
+    if let Ok(Some(value)) = self.get_constant(HasConstant::Field(*field)) {
+      write!(&mut res, " = {}", constant_as_il(&value)).unwrap();
+    }
+
+    res.push('\n');
+    self.write_il_custom_attributes(&mut res, HasCustomAttribute::Field(*field), "  ");
+    res
+  }
+
+  fn disassemble_type(&self, type_def: &Index<TypeDefEntry>, entry_point: Option<Index<MethodDefEntry>>) -> String {
+    let mut res = String::new();
+
+    let entry = match self.get_entry(type_def) {
+      Some(entry) => entry,
+      None => return res
+    };
+
+    let type_name = self.full_type_name(&TypeDefOrRef::TypeDef(*type_def));
+    // Nested types are declared by their simple name inside the enclosing class
+    let declared_name = if self.get_enclosing_type(type_def).is_some() {
+      il_identifier(type_name.names.last().map(|x| &x[..]).unwrap_or(""))
+    } else {
+      let name = il_identifier(type_name.names.last().map(|x| &x[..]).unwrap_or(""));
+      if type_name.namespace.is_empty() { name } else { format!("{}.{}", type_name.namespace, name) }
+    };
+
+    writeln!(&mut res, ".class {} {}{}", type_attributes_as_il(entry.flags), declared_name,
+      self.get_il_generic_params(TypeOrMethodDef::TypeDef(*type_def))).unwrap();
+
+    let has_base_type = match entry.extends {
+      TypeDefOrRef::TypeDef(index) => index.0 != 0,
+      TypeDefOrRef::TypeRef(index) => index.0 != 0,
+      TypeDefOrRef::TypeSpec(index) => index.0 != 0
+    };
+    if has_base_type {
+      writeln!(&mut res, "       extends {}", self.get_il_type_name(&entry.extends)).unwrap();
+    }
+
+    let interfaces = self.get_interfaces(type_def).iter().map(|interface| self.get_il_type_name(interface)).collect::<Vec<_>>();
+    if !interfaces.is_empty() {
+      writeln!(&mut res, "       implements {}", interfaces.join(",\n                  ")).unwrap();
+    }
+
+    res.push_str("{\n");
+    self.write_il_custom_attributes(&mut res, HasCustomAttribute::TypeDef(*type_def), "  ");
+
+    for nested in self.get_nested_types(type_def) {
+      for line in self.disassemble_type(&nested, entry_point).lines() {
+        writeln!(&mut res, "  {}", line).unwrap();
+      }
+    }
+
+    let members = self.type_members(type_def);
+
+    for field in &members.fields {
+      res.push_str(&self.disassemble_field(field));
+    }
+
+    for method in &members.methods {
+      res.push_str(&self.disassemble_method(method, Some(*method) == entry_point));
+      res.push('\n');
+    }
+
+    let owner = type_name.names.join("/");
+
+    for property in &members.properties {
+      let signature = self.get_property_signature(&property.property);
+      let (instance, type_, params) = match signature {
+        Ok(ref signature) => (
+          if signature.has_this { "instance " } else { "" },
+          signature.type_.as_il(self),
+          signature.params.iter().map(|param| param.as_il(self)).collect::<Vec<_>>().join(", ")
+        ),
+        Err(_) => ("", String::new(), String::new())
+      };
+
+      writeln!(&mut res, "  .property {}{} {}({})", instance, type_, il_identifier(&property.name), params).unwrap();
+      res.push_str("  {\n");
+      if let Some(getter) = property.getter {
+        writeln!(&mut res, "    .get {}", self.get_il_method_ref(&MethodDefOrRef::MethodDef(getter), None)).unwrap();
+      }
+      if let Some(setter) = property.setter {
+        writeln!(&mut res, "    .set {}", self.get_il_method_ref(&MethodDefOrRef::MethodDef(setter), None)).unwrap();
+      }
+      for other in &property.others {
+        writeln!(&mut res, "    .other {}", self.get_il_method_ref(&MethodDefOrRef::MethodDef(*other), None)).unwrap();
+      }
+      writeln!(&mut res, "  }} // end of property {}::{}", owner, property.name).unwrap();
+    }
+
+    for event in &members.events {
+      writeln!(&mut res, "  .event {} {}", self.get_il_type_name(&event.event_type), il_identifier(&event.name)).unwrap();
+      res.push_str("  {\n");
+      let accessors = [(".addon", event.add), (".removeon", event.remove), (".fire", event.raise)];
+      for &(directive, method) in &accessors {
+        if let Some(method) = method {
+          writeln!(&mut res, "    {} {}", directive, self.get_il_method_ref(&MethodDefOrRef::MethodDef(method), None)).unwrap();
+        }
+      }
+      for other in &event.others {
+        writeln!(&mut res, "    .other {}", self.get_il_method_ref(&MethodDefOrRef::MethodDef(*other), None)).unwrap();
+      }
+      writeln!(&mut res, "  }} // end of event {}::{}", owner, event.name).unwrap();
+    }
+
+    writeln!(&mut res, "}} // end of class {}", owner).unwrap();
+    res
+  }
+
+  /// Disassembles the whole module: assembly references, the assembly manifest, global members and all types.
+  pub fn disassemble(&self, entry_point: Option<Index<MethodDefEntry>>) -> String {
+    let mut res = String::new();
+
+    for entry in self.get_table::<AssemblyRefEntry>().map(|x| &x[..]).unwrap_or(&[]) {
+      let name = self.get_string(&entry.name).map(|x| &x[..]).unwrap_or("");
+      writeln!(&mut res, ".assembly extern {}\n{{", il_identifier(name)).unwrap();
+
+      let key = self.get_blob(&entry.public_key_or_token).map(|x| &x[..]).unwrap_or(&[]);
+      if !key.is_empty() {
+        // The PublicKey flag tells whether this is the full key or just its token
+        let directive = if entry.flags & 0x0001 != 0 { ".publickey" } else { ".publickeytoken" };
+        writeln!(&mut res, "  {} = {}", directive, il_bytes(key)).unwrap();
+      }
+
+      writeln!(&mut res, "  .ver {}:{}:{}:{}\n}}", entry.major_version, entry.minor_version, entry.build_number, entry.revision_number).unwrap();
+    }
+
+    for (position, entry) in self.get_table::<AssemblyEntry>().map(|x| &x[..]).unwrap_or(&[]).iter().enumerate() {
+      let name = self.get_string(&entry.name).map(|x| &x[..]).unwrap_or("");
+      writeln!(&mut res, ".assembly {}\n{{", il_identifier(name)).unwrap();
+      self.write_il_custom_attributes(&mut res, HasCustomAttribute::Assembly(Index::new(position as u32 + 1)), "  ");
+
+      let key = self.get_blob(&entry.public_key).map(|x| &x[..]).unwrap_or(&[]);
+      if !key.is_empty() {
+        writeln!(&mut res, "  .publickey = {}", il_bytes(key)).unwrap();
+      }
+
+      writeln!(&mut res, "  .hash algorithm 0x{:08x}", entry.hash_algorithm).unwrap();
+      writeln!(&mut res, "  .ver {}:{}:{}:{}\n}}", entry.major_version, entry.minor_version, entry.build_number, entry.revision_number).unwrap();
+    }
+
+    if let Some(module) = self.get_table::<ModuleEntry>().and_then(|modules| modules.first()) {
+      let name = self.get_string(&module.name).map(|x| &x[..]).unwrap_or("");
+      writeln!(&mut res, ".module {}", il_identifier(name)).unwrap();
+    }
+
+    let type_count = self.get_table::<TypeDefEntry>().map(|x| x.len()).unwrap_or(0) as u32;
+
+    for type_def in (1 .. type_count + 1).map(Index::<TypeDefEntry>::new) {
+      if self.get_enclosing_type(&type_def).is_some() {
+        continue;
+      }
+
+      res.push('\n');
+
+      // The first type is <Module>, whose members are global and aren't shown in a class
+      if type_def.0 == 1 {
+        let members = self.type_members(&type_def);
+        for field in &members.fields {
+          res.push_str(&self.disassemble_field(field));
+        }
+        for method in &members.methods {
+          res.push_str(&self.disassemble_method(method, Some(*method) == entry_point));
+        }
+      } else {
+        res.push_str(&self.disassemble_type(&type_def, entry_point));
+      }
+    }
+
+    res
+  }
+}
+
+impl CLRImage {
+  pub fn disassemble(&self) -> String {
+    self.metadata.disassemble(self.entry_point())
+  }
+}
@@ -0,0 +1,175 @@
+use std::io::Result;
+
+use metadata::Metadata;
+use metadata::signature::{MethodSpecSig, TypeSig, invalid_signature};
+use metadata::tables::*;
+
+// GenericParamAttributes (ECMA 335, II.23.1.7)
+const VARIANCE_MASK: u16                     = 0x0003;
+const COVARIANT: u16                         = 0x0001;
+const CONTRAVARIANT: u16                     = 0x0002;
+const REFERENCE_TYPE_CONSTRAINT: u16         = 0x0004;
+const NOT_NULLABLE_VALUE_TYPE_CONSTRAINT: u16 = 0x0008;
+const DEFAULT_CONSTRUCTOR_CONSTRAINT: u16    = 0x0010;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variance {
+  Invariant,
+  /// `out T`
+  Covariant,
+  /// `in T`
+  Contravariant
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenericParam {
+  pub index: Index<GenericParamEntry>,
+  pub number: u16,
+  pub name: String,
+  pub variance: Variance,
+  /// `where T : class`
+  pub reference_type: bool,
+  /// `where T : struct`
+  pub value_type: bool,
+  /// `where T : new()`
+  pub default_constructor: bool,
+  pub constraints: Vec<TypeDefOrRef>
+}
+
+/// A generic method instantiated with concrete type arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSpec {
+  pub method: MethodDefOrRef,
+  pub type_args: Vec<TypeSig>
+}
+
+impl GenericParam {
+  /// Formats the parameter as it appears in a C# type parameter list, e.g. `out T`.
+  pub fn as_csharp(&self) -> String {
+    match self.variance {
+      Variance::Invariant => self.name.clone(),
+      Variance::Covariant => format!("out {}", self.name),
+      Variance::Contravariant => format!("in {}", self.name)
+    }
+  }
+
+  /// Formats the constraints as a C# where clause, or returns None if the parameter is unconstrained.
+  pub fn constraint_clause(&self, meta: &Metadata) -> Option<String> {
+    let mut constraints = vec![];
+
+    if self.reference_type {
+      constraints.push("class".to_string());
+    }
+
+    // `struct` implies `new()` and the System.ValueType constraint, which C# doesn't show
+    if self.value_type {
+      constraints.push("struct".to_string());
+    }
+
+    constraints.extend(self.constraints.iter()
+      .map(|constraint| meta.get_type_name(constraint))
+      .filter(|name| !self.value_type || name != "System.ValueType"));
+
+    if self.default_constructor && !self.value_type {
+      constraints.push("new()".to_string());
+    }
+
+    if constraints.is_empty() {
+      None
+    } else {
+      Some(format!("where {} : {}", self.name, constraints.join(", ")))
+    }
+  }
+}
+
+impl MethodSpec {
+  /// Formats the instantiation like C#, e.g. `System.Linq.Enumerable.Select<int, string>`.
+  pub fn as_csharp(&self, meta: &Metadata) -> String {
+    let type_args = self.type_args.iter().map(|type_arg| type_arg.as_csharp(meta)).collect::<Vec<_>>();
+    format!("{}<{}>", meta.get_method_full_name(&self.method), type_args.join(", "))
+  }
+}
+
+impl Metadata {
+  /// Returns the generic parameters of a type or a method, ordered by their position.
+  pub fn generic_params_of(&self, owner: TypeOrMethodDef) -> Vec<GenericParam> {
+    let entries = match self.get_table::<GenericParamEntry>() {
+      Some(entries) => entries,
+      None => return vec![]
+    };
+
+    let mut params = entries.iter()
+      .enumerate()
+      .filter(|&(_, entry)| entry.owner == owner)
+      .map(|(position, entry)| {
+        let index = Index::new(position as u32 + 1);
+        let variance = match entry.flags & VARIANCE_MASK {
+          COVARIANT => Variance::Covariant,
+          CONTRAVARIANT => Variance::Contravariant,
+          _ => Variance::Invariant
+        };
+
+        GenericParam {
+          index,
+          number: entry.number,
+          name: self.get_string(&entry.name).cloned().unwrap_or_default(),
+          variance,
+          reference_type: entry.flags & REFERENCE_TYPE_CONSTRAINT != 0,
+          value_type: entry.flags & NOT_NULLABLE_VALUE_TYPE_CONSTRAINT != 0,
+          default_constructor: entry.flags & DEFAULT_CONSTRUCTOR_CONSTRAINT != 0,
+          constraints: self.generic_param_constraints_of(&index)
+        }
+      })
+      .collect::<Vec<_>>();
+
+    params.sort_by_key(|param| param.number);
+    params
+  }
+
+  pub fn generic_param_constraints_of(&self, param: &Index<GenericParamEntry>) -> Vec<TypeDefOrRef> {
+    self.get_table::<GenericParamConstraintEntry>()
+      .map(|entries| entries.iter()
+        .filter(|entry| entry.owner == *param)
+        .map(|entry| entry.constraint)
+        .collect())
+      .unwrap_or_default()
+  }
+
+  pub fn get_method_spec(&self, index: &Index<MethodSpecEntry>) -> Result<MethodSpec> {
+    let entry = self.get_entry(index).ok_or_else(|| invalid_signature(format!("No such MethodSpec: {}", index.0)))?;
+    let blob = self.get_blob(&entry.instantiation).ok_or_else(|| invalid_signature(format!("Invalid MethodSpec blob: {}", index.0)))?;
+    let signature = MethodSpecSig::from_blob(blob)?;
+
+    Ok(MethodSpec { method: entry.method, type_args: signature.type_args })
+  }
+
+  /// Returns the name of a method qualified with the name of its declaring type.
+  pub fn get_method_full_name(&self, method: &MethodDefOrRef) -> String {
+    match *method {
+      MethodDefOrRef::MethodDef(ref index) => {
+        let name = self.get_entry(index).and_then(|entry| self.get_string(&entry.name)).cloned().unwrap_or_default();
+        match self.get_method_owner(index) {
+          Some(owner) => format!("{}.{}", self.get_type_name(&TypeDefOrRef::TypeDef(owner)), name),
+          None => name
+        }
+      },
+      MethodDefOrRef::MethodRef(ref index) => match self.get_entry(index) {
+        Some(entry) => {
+          let name = self.get_string(&entry.name).cloned().unwrap_or_default();
+          let owner = match entry.class {
+            MemberRefParent::TypeDef(type_def) => Some(self.get_type_name(&TypeDefOrRef::TypeDef(type_def))),
+            MemberRefParent::TypeRef(type_ref) => Some(self.get_type_name(&TypeDefOrRef::TypeRef(type_ref))),
+            MemberRefParent::TypeSpec(type_spec) => Some(self.get_type_name(&TypeDefOrRef::TypeSpec(type_spec))),
+            _ => None
+          };
+
+          match owner {
+            Some(owner) => format!("{}.{}", owner, name),
+            None => name
+          }
+        },
+        None => format!("MemberRef#{}", index.0)
+      }
+    }
+  }
+}
@@ -1,20 +1,15 @@
 
 use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
-pub enum UserString {
-  Valid(String),
-  Garbage
-}
-
 #[derive(Debug)]
 pub struct StringHeap {
   pub strings: HashMap<u32, String>
 }
 
+/// The #US heap. Literals are kept as UTF-16 code units, as they may contain lone surrogates.
 #[derive(Debug)]
 pub struct UserStringHeap {
-  pub strings: HashMap<u32, UserString>
+  pub strings: HashMap<u32, Vec<u16>>
 }
 
 #[derive(Debug)]
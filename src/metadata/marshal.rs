@@ -0,0 +1,170 @@
+use std::io::{Read, Cursor, Result};
+use byteorder::ReadBytesExt;
+use enum_primitive::FromPrimitive;
+
+use metadata::Metadata;
+use metadata::signature::{read_compressed_u32, invalid_signature};
+use metadata::tables::*;
+
+enum_from_primitive! {
+  // ECMA 335, II.23.4, extended with the values from CoreCLR's corhdr.h
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum NativeType {
+    Boolean          = 0x02,
+    I1               = 0x03,
+    U1               = 0x04,
+    I2               = 0x05,
+    U2               = 0x06,
+    I4               = 0x07,
+    U4               = 0x08,
+    I8               = 0x09,
+    U8               = 0x0a,
+    R4               = 0x0b,
+    R8               = 0x0c,
+    Currency         = 0x0f,
+    BStr             = 0x13,
+    LPStr            = 0x14,
+    LPWStr           = 0x15,
+    LPTStr           = 0x16,
+    FixedSysString   = 0x17,
+    IUnknown         = 0x19,
+    IDispatch        = 0x1a,
+    Struct           = 0x1b,
+    Interface        = 0x1c,
+    SafeArray        = 0x1d,
+    FixedArray       = 0x1e,
+    Int              = 0x1f,
+    UInt             = 0x20,
+    ByValStr         = 0x22,
+    AnsiBStr         = 0x23,
+    TBStr            = 0x24,
+    VariantBool      = 0x25,
+    FunctionPtr      = 0x26,
+    AsAny            = 0x28,
+    Array            = 0x2a,
+    LPStruct         = 0x2b,
+    CustomMarshaler  = 0x2c,
+    Error            = 0x2d,
+    IInspectable     = 0x2e,
+    HString          = 0x2f,
+    LPUTF8Str        = 0x30,
+    // Used as the element type of arrays, when it isn't specified
+    Max              = 0x50
+  }
+}
+
+/// A decoded marshalling descriptor of a field or a parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarshalSpec {
+  /// A native type without any additional information, e.g. LPStr, LPWStr, LPUTF8Str or FunctionPtr.
+  Native(NativeType),
+  /// ByValTStr, an inline string of a fixed number of characters.
+  FixedSysString { size: u32 },
+  /// ByValArray, an inline array of a fixed number of elements.
+  ByValArray { size: u32, element_type: Option<NativeType> },
+  /// LPArray, a pointer to an array whose length is given by a constant and/or another parameter.
+  LPArray { element_type: Option<NativeType>, size_param_index: Option<u32>, size_const: Option<u32> },
+  SafeArray { variant_type: Option<u32>, user_defined_subtype: Option<String> },
+  /// Interface, IUnknown, IDispatch or IInspectable, optionally with the index of the iid_is parameter.
+  Interface { kind: NativeType, iid_param_index: Option<u32> },
+  CustomMarshaler { guid: String, native_type_name: String, marshaler_type: String, cookie: String }
+}
+
+fn read_native_type<R: Read>(reader: &mut R) -> Result<NativeType> {
+  let byte = reader.read_u8()?;
+  NativeType::from_u8(byte).ok_or_else(|| invalid_signature(format!("Unknown native type: {:02x}", byte)))
+}
+
+fn read_optional_u32(reader: &mut Cursor<&[u8]>) -> Result<Option<u32>> {
+  if reader.position() as usize >= reader.get_ref().len() {
+    return Ok(None);
+  }
+
+  read_compressed_u32(reader).map(Some)
+}
+
+fn read_optional_native_type(reader: &mut Cursor<&[u8]>) -> Result<Option<NativeType>> {
+  if reader.position() as usize >= reader.get_ref().len() {
+    return Ok(None);
+  }
+
+  read_native_type(reader).map(|native_type| if native_type == NativeType::Max { None } else { Some(native_type) })
+}
+
+// Strings in marshalling descriptors are prefixed with a compressed length
+fn read_string(reader: &mut Cursor<&[u8]>) -> Result<String> {
+  let length = read_compressed_u32(reader)?;
+  let mut buffer = vec![0u8; length as usize];
+  reader.read_exact(&mut buffer)?;
+  String::from_utf8(buffer).map_err(|_| invalid_signature("Invalid UTF-8 in marshalling descriptor".to_string()))
+}
+
+impl MarshalSpec {
+  pub fn from_blob(blob: &[u8]) -> Result<MarshalSpec> {
+    let mut reader = Cursor::new(blob);
+    let native_type = read_native_type(&mut reader)?;
+
+    Ok(match native_type {
+      NativeType::FixedSysString => MarshalSpec::FixedSysString {
+        size: read_optional_u32(&mut reader)?.unwrap_or(0)
+      },
+      NativeType::FixedArray => {
+        let size = read_optional_u32(&mut reader)?.unwrap_or(0);
+        let element_type = read_optional_native_type(&mut reader)?;
+        MarshalSpec::ByValArray { size, element_type }
+      },
+      NativeType::Array => {
+        let element_type = read_optional_native_type(&mut reader)?;
+        let size_param_index = read_optional_u32(&mut reader)?;
+        let size_const = read_optional_u32(&mut reader)?;
+        // The runtime emits a final flag telling whether SizeParamIndex was actually specified
+        let size_param_index = match read_optional_u32(&mut reader)? {
+          Some(0) => None,
+          _ => size_param_index
+        };
+        MarshalSpec::LPArray { element_type, size_param_index, size_const }
+      },
+      NativeType::SafeArray => {
+        let variant_type = read_optional_u32(&mut reader)?;
+        let user_defined_subtype = if (reader.position() as usize) < blob.len() {
+          Some(read_string(&mut reader)?)
+        } else {
+          None
+        };
+        MarshalSpec::SafeArray { variant_type, user_defined_subtype }
+      },
+      NativeType::Interface | NativeType::IUnknown | NativeType::IDispatch | NativeType::IInspectable =>
+        MarshalSpec::Interface { kind: native_type, iid_param_index: read_optional_u32(&mut reader)? },
+      NativeType::CustomMarshaler => MarshalSpec::CustomMarshaler {
+        guid: read_string(&mut reader)?,
+        native_type_name: read_string(&mut reader)?,
+        marshaler_type: read_string(&mut reader)?,
+        cookie: read_string(&mut reader)?
+      },
+      otherwise => MarshalSpec::Native(otherwise)
+    })
+  }
+}
+
+impl Metadata {
+  pub fn get_marshal_spec(&self, parent: HasFieldMarshall) -> Result<Option<MarshalSpec>> {
+    let entry = self.get_table::<FieldMarshalEntry>()
+      .and_then(|entries| entries.iter().find(|entry| entry.parent == parent));
+
+    match entry {
+      Some(entry) => {
+        let blob = self.get_blob(&entry.native_type).map(|blob| &blob[..]).unwrap_or(&[]);
+        MarshalSpec::from_blob(blob).map(Some)
+      },
+      None => Ok(None)
+    }
+  }
+
+  pub fn get_field_marshal_spec(&self, field: &Index<FieldEntry>) -> Result<Option<MarshalSpec>> {
+    self.get_marshal_spec(HasFieldMarshall::Field(*field))
+  }
+
+  pub fn get_param_marshal_spec(&self, param: &Index<ParamEntry>) -> Result<Option<MarshalSpec>> {
+    self.get_marshal_spec(HasFieldMarshall::Param(*param))
+  }
+}
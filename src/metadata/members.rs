@@ -0,0 +1,103 @@
+use metadata::Metadata;
+use metadata::tables::*;
+
+// CorMethodSemanticsAttr (ECMA 335, II.23.1.12)
+const SETTER: u16    = 0x0001;
+const GETTER: u16    = 0x0002;
+const ADD_ON: u16    = 0x0008;
+const REMOVE_ON: u16 = 0x0010;
+const FIRE: u16      = 0x0020;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyMember {
+  pub property: Index<PropertyEntry>,
+  pub name: String,
+  pub getter: Option<Index<MethodDefEntry>>,
+  pub setter: Option<Index<MethodDefEntry>>,
+  pub others: Vec<Index<MethodDefEntry>>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventMember {
+  pub event: Index<EventEntry>,
+  pub name: String,
+  pub event_type: TypeDefOrRef,
+  pub add: Option<Index<MethodDefEntry>>,
+  pub remove: Option<Index<MethodDefEntry>>,
+  pub raise: Option<Index<MethodDefEntry>>,
+  pub others: Vec<Index<MethodDefEntry>>
+}
+
+/// Everything declared by a type, not including inherited members.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeMembers {
+  pub fields: Vec<Index<FieldEntry>>,
+  pub methods: Vec<Index<MethodDefEntry>>,
+  pub properties: Vec<PropertyMember>,
+  pub events: Vec<EventMember>
+}
+
+impl Metadata {
+  pub fn type_members(&self, type_def: &Index<TypeDefEntry>) -> TypeMembers {
+    let fields = self.get_field_range(type_def).map(Index::new).collect();
+    let methods = self.get_method_range(type_def).map(Index::new).collect();
+
+    let properties = self.get_property_range(type_def)
+      .map(|property| {
+        let property = Index::new(property);
+        let mut member = PropertyMember {
+          property,
+          name: self.get_entry(&property).and_then(|entry| self.get_string(&entry.name)).cloned().unwrap_or_default(),
+          getter: None,
+          setter: None,
+          others: vec![]
+        };
+
+        for entry in self.method_semantics_of(HasSemantics::Property(property)) {
+          match entry.semantics {
+            GETTER => member.getter = Some(entry.method),
+            SETTER => member.setter = Some(entry.method),
+            _ => member.others.push(entry.method)
+          }
+        }
+
+        member
+      })
+      .collect();
+
+    let events = self.get_event_range(type_def)
+      .map(|event| {
+        let event = Index::new(event);
+        let entry = self.get_entry(&event);
+        let mut member = EventMember {
+          event,
+          name: entry.and_then(|entry| self.get_string(&entry.name)).cloned().unwrap_or_default(),
+          event_type: entry.map(|entry| entry.event_type).unwrap_or(TypeDefOrRef::TypeDef(Index::new(0))),
+          add: None,
+          remove: None,
+          raise: None,
+          others: vec![]
+        };
+
+        for entry in self.method_semantics_of(HasSemantics::Event(event)) {
+          match entry.semantics {
+            ADD_ON => member.add = Some(entry.method),
+            REMOVE_ON => member.remove = Some(entry.method),
+            FIRE => member.raise = Some(entry.method),
+            _ => member.others.push(entry.method)
+          }
+        }
+
+        member
+      })
+      .collect();
+
+    TypeMembers { fields, methods, properties, events }
+  }
+
+  fn method_semantics_of(&self, association: HasSemantics) -> Vec<&MethodSemanticsEntry> {
+    self.get_table::<MethodSemanticsEntry>()
+      .map(|entries| entries.iter().filter(|entry| entry.association == association).collect())
+      .unwrap_or_default()
+  }
+}
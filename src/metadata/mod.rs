@@ -8,6 +8,14 @@ use loader::code::MethodBody;
 pub mod heap;
 pub mod tables;
 pub mod debug;
+pub mod signature;
+pub mod types;
+pub mod custom_attributes;
+pub mod constants;
+pub mod marshal;
+pub mod security;
+pub mod generics;
+pub mod members;
 
 use loader::stream::TableId;
 use metadata::heap::Heaps;
@@ -16,13 +24,30 @@ use metadata::heap::Heaps;
 pub struct Metadata {
   pub tables: DebugMap,
   pub heaps: Heaps,
-  pub method_bodies: HashMap<u32, MethodBody>
+  pub method_bodies: HashMap<u32, MethodBody>,
+  /// The data stored in the image for fields with an RVA, by Field index - 1
+  pub field_data: HashMap<u32, Vec<u8>>
 }
 
 impl Metadata {
   pub fn get_string(&self, index: &Index<StringHeap>) -> Option<&String> {
     self.heaps.strings.strings.get(&index.0)
   }
+
+  pub fn get_blob(&self, index: &Index<BlobHeap>) -> Option<&Vec<u8>> {
+    self.heaps.blobs.blobs.get(&index.0)
+  }
+
+  /// Returns the string literal at an offset into the #US heap, as referenced by ldstr. None if the
+  /// index is invalid or the literal isn't valid UTF-16.
+  pub fn get_user_string(&self, index: u32) -> Option<String> {
+    self.get_user_string_utf16(index).and_then(|units| String::from_utf16(units).ok())
+  }
+
+  /// Returns the UTF-16 code units of a string literal, which needn't be valid UTF-16.
+  pub fn get_user_string_utf16(&self, index: u32) -> Option<&[u16]> {
+    self.heaps.user_strings.strings.get(&index).map(|units| &units[..])
+  }
 }
 
 struct KeyType;
@@ -51,11 +76,31 @@ metadata_tables! [
   ModuleEntry = Module,
   TypeRefEntry = TypeRef,
   TypeDefEntry = TypeDef,
+  FieldEntry = Field,
   MethodDefEntry = MethodDef,
+  ParamEntry = Param,
+  InterfaceImplEntry = InterfaceImpl,
   MemberRefEntry = MemberRef,
+  ConstantEntry = Constant,
   CustomAttributeEntry = CustomAttribute,
+  FieldMarshalEntry = FieldMarshal,
+  DeclSecurityEntry = DeclSecurity,
+  ClassLayoutEntry = ClassLayout,
+  StandAloneSigEntry = StandAloneSig,
+  EventMapEntry = EventMap,
+  EventEntry = Event,
+  PropertyMapEntry = PropertyMap,
+  PropertyEntry = Property,
+  MethodSemanticsEntry = MethodSemantics,
+  MethodImplEntry = MethodImpl,
+  TypeSpecEntry = TypeSpec,
+  FieldRVAEntry = FieldRVA,
   AssemblyEntry = Assembly,
-  AssemblyRefEntry = AssemblyRef
+  AssemblyRefEntry = AssemblyRef,
+  NestedClassEntry = NestedClass,
+  GenericParamEntry = GenericParam,
+  MethodSpecEntry = MethodSpec,
+  GenericParamConstraintEntry = GenericParamConstraint
 ];
 
 impl Metadata {
@@ -66,4 +111,13 @@ impl Metadata {
   pub fn get_table<T: Key + Debug>(&self) -> Option<&T::Value> where T::Value : Debug {
     self.tables.get::<T>()
   }
+
+  /// Returns the row referenced by a (1-based) table index, or None for null and out-of-bounds indices.
+  pub fn get_entry<T: Key<Value = Vec<T>> + Debug>(&self, index: &Index<T>) -> Option<&T> {
+    if index.0 == 0 {
+      return None;
+    }
+
+    self.get_table::<T>().and_then(|table| table.get(index.0 as usize - 1))
+  }
 }
@@ -0,0 +1,240 @@
+use std::cmp;
+use std::io::{Read, Cursor, Result};
+use byteorder::ReadBytesExt;
+use enum_primitive::FromPrimitive;
+
+use metadata::Metadata;
+use metadata::custom_attributes::{NamedArgument, NamedArgumentKind, CustomAttributeValue, decode_named_args};
+use metadata::signature::{read_compressed_u32, invalid_signature};
+use metadata::tables::*;
+
+enum_from_primitive! {
+  // System.Security.Permissions.SecurityAction, including the values only used by the runtime
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum SecurityAction {
+    Request              = 1,
+    Demand               = 2,
+    Assert               = 3,
+    Deny                 = 4,
+    PermitOnly           = 5,
+    LinkDemand           = 6,
+    InheritanceDemand    = 7,
+    RequestMinimum       = 8,
+    RequestOptional      = 9,
+    RequestRefuse        = 10,
+    PrejitGrant          = 11,
+    PrejitDenied         = 12,
+    NonCasDemand         = 13,
+    NonCasLinkDemand     = 14,
+    NonCasInheritance    = 15
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionSetFormat {
+  /// The .NET 2.0 binary format, which starts with a '.'.
+  Binary,
+  /// The legacy XML format.
+  Xml
+}
+
+/// A single permission of a permission set, e.g. SecurityPermission with Flags = UnmanagedCode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Permission {
+  /// The assembly-qualified name of the permission attribute (binary format) or the permission class (XML).
+  pub type_name: String,
+  /// In the XML format, all attributes except class and version are stored as string properties.
+  pub named_args: Vec<NamedArgument>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PermissionSet {
+  pub action: SecurityAction,
+  pub format: PermissionSetFormat,
+  /// Only XML permission sets can be unrestricted as a whole.
+  pub unrestricted: bool,
+  pub permissions: Vec<Permission>
+}
+
+fn read_ser_string(reader: &mut Cursor<&[u8]>) -> Result<String> {
+  let length = read_compressed_u32(reader)?;
+  let mut buffer = vec![0u8; length as usize];
+  reader.read_exact(&mut buffer)?;
+  String::from_utf8(buffer).map_err(|_| invalid_signature("Invalid UTF-8 in permission set".to_string()))
+}
+
+fn decode_binary(meta: &Metadata, references: &[&Metadata], blob: &[u8]) -> Result<Vec<Permission>> {
+  let mut reader = Cursor::new(blob);
+  // Skip the '.'
+  reader.read_u8()?;
+
+  let attribute_count = read_compressed_u32(&mut reader)?;
+  let mut permissions = Vec::with_capacity(cmp::min(attribute_count, 1024) as usize);
+
+  for _ in 0 .. attribute_count {
+    let type_name = read_ser_string(&mut reader)?;
+    let length = read_compressed_u32(&mut reader)? as usize;
+
+    let start = reader.position() as usize;
+    if start + length > blob.len() {
+      return Err(invalid_signature(format!("Permission {} overflows the permission set", type_name)));
+    }
+
+    let mut arguments = Cursor::new(&blob[start .. start + length]);
+    let named_arg_count = read_compressed_u32(&mut arguments)?;
+    let position = arguments.position() as usize;
+    let named_args = decode_named_args(meta, references, &blob[start + position .. start + length], named_arg_count)?;
+
+    reader.set_position((start + length) as u64);
+    permissions.push(Permission { type_name, named_args });
+  }
+
+  Ok(permissions)
+}
+
+// A minimal XML reader, which only understands the elements and attributes used by permission sets.
+struct XmlElement {
+  name: String,
+  attributes: Vec<(String, String)>
+}
+
+impl XmlElement {
+  fn get_attribute(&self, name: &str) -> Option<&str> {
+    self.attributes.iter().find(|&&(ref key, _)| key == name).map(|&(_, ref value)| &value[..])
+  }
+}
+
+fn unescape_xml(value: &str) -> String {
+  value.replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
+fn parse_xml_elements(xml: &str) -> Vec<XmlElement> {
+  let mut elements = vec![];
+  let mut rest = xml;
+
+  while let Some(start) = rest.find('<') {
+    let end = match rest[start ..].find('>') {
+      Some(end) => start + end,
+      None => break
+    };
+
+    let tag = rest[start + 1 .. end].trim_right_matches('/').trim();
+    rest = &rest[end + 1 ..];
+
+    // Skip closing tags, comments and processing instructions
+    if tag.starts_with('/') || tag.starts_with('!') || tag.starts_with('?') {
+      continue;
+    }
+
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = tag[.. name_end].to_string();
+
+    let mut attributes = vec![];
+    let mut attribute_text = &tag[name_end ..];
+
+    while let Some(equals) = attribute_text.find('=') {
+      let key = attribute_text[.. equals].trim().to_string();
+      let value_text = attribute_text[equals + 1 ..].trim_left();
+
+      let quote = match value_text.chars().next() {
+        Some(quote) if quote == '"' || quote == '\'' => quote,
+        _ => break
+      };
+
+      let value_end = match value_text[1 ..].find(quote) {
+        Some(value_end) => value_end + 1,
+        None => break
+      };
+
+      attributes.push((key, unescape_xml(&value_text[1 .. value_end])));
+      attribute_text = &value_text[value_end + 1 ..];
+    }
+
+    elements.push(XmlElement { name, attributes });
+  }
+
+  elements
+}
+
+fn decode_xml(blob: &[u8]) -> (bool, Vec<Permission>) {
+  // Legacy permission sets are stored as UTF-16, with or without a byte order mark, but tolerate UTF-8
+  // as well. Without a BOM, UTF-16 is recognized by the opening '<' of the XML.
+  let utf16 = if blob.starts_with(&[0xFF, 0xFE]) {
+    Some(&blob[2 ..])
+  } else if blob.starts_with(&[b'<', 0]) {
+    Some(blob)
+  } else {
+    None
+  };
+  let xml = if let Some(blob) = utf16 {
+    let units = blob.chunks(2)
+      .filter(|chunk| chunk.len() == 2)
+      .map(|chunk| (chunk[0] as u16) | ((chunk[1] as u16) << 8))
+      .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+  } else {
+    String::from_utf8_lossy(blob).into_owned()
+  };
+
+  let mut unrestricted = false;
+  let mut permissions = vec![];
+
+  for element in parse_xml_elements(xml.trim_left_matches('\u{feff}')) {
+    match &element.name[..] {
+      "PermissionSet" => unrestricted = element.get_attribute("Unrestricted") == Some("true"),
+      "IPermission" | "Permission" => {
+        let type_name = element.get_attribute("class").unwrap_or("").to_string();
+        let named_args = element.attributes.iter()
+          .filter(|&&(ref key, _)| key != "class" && key != "version")
+          .map(|&(ref key, ref value)| NamedArgument {
+            kind: NamedArgumentKind::Property,
+            name: key.clone(),
+            value: CustomAttributeValue::String(Some(value.clone()))
+          })
+          .collect();
+        permissions.push(Permission { type_name, named_args });
+      },
+      _ => ()
+    }
+  }
+
+  (unrestricted, permissions)
+}
+
+impl Metadata {
+  /// Decodes a permission set, looking up the enums of named arguments in `references` as well.
+  pub fn decode_permission_set(&self, entry: &DeclSecurityEntry, references: &[&Metadata]) -> Result<PermissionSet> {
+    let action = SecurityAction::from_u16(entry.action)
+      .ok_or_else(|| invalid_signature(format!("Unknown security action: {}", entry.action)))?;
+    let blob = self.get_blob(&entry.permission_set).map(|blob| &blob[..]).unwrap_or(&[]);
+
+    if blob.first() == Some(&b'.') {
+      let permissions = decode_binary(self, references, blob)?;
+      Ok(PermissionSet { action, format: PermissionSetFormat::Binary, unrestricted: false, permissions })
+    } else {
+      let (unrestricted, permissions) = decode_xml(blob);
+      Ok(PermissionSet { action, format: PermissionSetFormat::Xml, unrestricted, permissions })
+    }
+  }
+
+  /// Returns the decoded declarative security attributes of a type, a method or the assembly.
+  pub fn declarative_security_of(&self, parent: HasDeclSecurity) -> Result<Vec<PermissionSet>> {
+    self.declarative_security_of_with_references(parent, &[])
+  }
+
+  pub fn declarative_security_of_with_references(&self, parent: HasDeclSecurity, references: &[&Metadata]) -> Result<Vec<PermissionSet>> {
+    let entries = match self.get_table::<DeclSecurityEntry>() {
+      Some(entries) => entries,
+      None => return Ok(vec![])
+    };
+
+    entries.iter()
+      .filter(|entry| entry.parent == parent)
+      .map(|entry| self.decode_permission_set(entry, references))
+      .collect()
+  }
+}
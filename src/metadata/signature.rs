@@ -0,0 +1,575 @@
+#![allow(non_upper_case_globals)]
+
+use std::cmp;
+use std::io::{Read, Cursor, Result, Error, ErrorKind};
+use std::fmt::Write;
+use byteorder::ReadBytesExt;
+use enum_primitive::FromPrimitive;
+
+use loader::stream::{StreamUtils, TableId};
+use loader::code::MethodHeader;
+use metadata::Metadata;
+use metadata::tables::*;
+
+enum_from_primitive! {
+  // ECMA 335, II.23.1.16
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum ElementType {
+    End         = 0x00,
+    Void        = 0x01,
+    Boolean     = 0x02,
+    Char        = 0x03,
+    I1          = 0x04,
+    U1          = 0x05,
+    I2          = 0x06,
+    U2          = 0x07,
+    I4          = 0x08,
+    U4          = 0x09,
+    I8          = 0x0a,
+    U8          = 0x0b,
+    R4          = 0x0c,
+    R8          = 0x0d,
+    String      = 0x0e,
+    Ptr         = 0x0f,
+    ByRef       = 0x10,
+    ValueType   = 0x11,
+    Class       = 0x12,
+    Var         = 0x13,
+    Array       = 0x14,
+    GenericInst = 0x15,
+    TypedByRef  = 0x16,
+    I           = 0x18,
+    U           = 0x19,
+    FnPtr       = 0x1b,
+    Object      = 0x1c,
+    SzArray     = 0x1d,
+    MVar        = 0x1e,
+    CModReqd    = 0x1f,
+    CModOpt     = 0x20,
+    Internal    = 0x21,
+    Modifier    = 0x40,
+    Sentinel    = 0x41,
+    Pinned      = 0x45,
+    // Only used in custom attribute blobs
+    Type        = 0x50,
+    Boxed       = 0x51,
+    Field       = 0x53,
+    Property    = 0x54,
+    Enum        = 0x55
+  }
+}
+
+enum_from_primitive! {
+  // ECMA 335, II.23.2.1 - II.23.2.3. Stored in the lower 4 bits of the first byte of a signature.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum CallingConvention {
+    Default     = 0x0,
+    C           = 0x1,
+    StdCall     = 0x2,
+    ThisCall    = 0x3,
+    FastCall    = 0x4,
+    VarArg      = 0x5,
+    Field       = 0x6,
+    LocalSig    = 0x7,
+    Property    = 0x8,
+    Unmanaged   = 0x9,
+    GenericInst = 0xa
+  }
+}
+
+bitflags! {
+  // The upper 4 bits of the first byte of a signature.
+  pub flags SignatureFlags: u8 {
+    const Generic      = 0x10,
+    const HasThis      = 0x20,
+    const ExplicitThis = 0x40
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayShape {
+  pub rank: u32,
+  pub sizes: Vec<u32>,
+  pub lower_bounds: Vec<i32>
+}
+
+/// A type as it appears inside a signature blob.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeSig {
+  Void,
+  Boolean,
+  Char,
+  I1,
+  U1,
+  I2,
+  U2,
+  I4,
+  U4,
+  I8,
+  U8,
+  R4,
+  R8,
+  I,
+  U,
+  String,
+  Object,
+  TypedByRef,
+  Class(TypeDefOrRef),
+  ValueType(TypeDefOrRef),
+  Ptr(Box<TypeSig>),
+  ByRef(Box<TypeSig>),
+  SzArray(Box<TypeSig>),
+  Array(Box<TypeSig>, ArrayShape),
+  // The generic type is always either Class or ValueType
+  GenericInst(Box<TypeSig>, Vec<TypeSig>),
+  Var(u32),
+  MVar(u32),
+  FnPtr(Box<MethodSignature>),
+  CModReqd(TypeDefOrRef, Box<TypeSig>),
+  CModOpt(TypeDefOrRef, Box<TypeSig>),
+  Pinned(Box<TypeSig>),
+  Sentinel
+}
+
+/// MethodDefSig, MethodRefSig and StandAloneMethodSig (ECMA 335, II.23.2.1 - II.23.2.3).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSignature {
+  pub flags: SignatureFlags,
+  pub calling_convention: CallingConvention,
+  pub generic_param_count: u32,
+  pub return_type: TypeSig,
+  pub params: Vec<TypeSig>,
+  /// For vararg call sites, the index of the first parameter after the sentinel.
+  pub sentinel: Option<usize>
+}
+
+/// FieldSig (ECMA 335, II.23.2.4). Custom modifiers are kept as CModReqd and CModOpt wrappers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSig {
+  pub type_: TypeSig
+}
+
+/// PropertySig (ECMA 335, II.23.2.5).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertySig {
+  pub has_this: bool,
+  pub type_: TypeSig,
+  pub params: Vec<TypeSig>
+}
+
+/// A single local variable of a LocalVarSig. PINNED and BYREF are stripped from the type, while custom
+/// modifiers are kept as CModReqd and CModOpt wrappers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalVariable {
+  pub type_: TypeSig,
+  pub pinned: bool,
+  pub by_ref: bool
+}
+
+/// LocalVarSig (ECMA 335, II.23.2.6).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalVarSig {
+  pub locals: Vec<LocalVariable>
+}
+
+/// The instantiation of a generic method, stored in MethodSpec rows (ECMA 335, II.23.2.15).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSpecSig {
+  pub type_args: Vec<TypeSig>
+}
+
+pub fn invalid_signature(message: String) -> Error {
+  Error::new(ErrorKind::InvalidData, message)
+}
+
+pub fn read_compressed_u32<R: Read>(reader: &mut R) -> Result<u32> {
+  StreamUtils::decode_compressed_int(reader).map(|x| x.value)
+}
+
+pub fn read_element_type<R: Read>(reader: &mut R) -> Result<ElementType> {
+  let byte = reader.read_u8()?;
+  ElementType::from_u8(byte).ok_or_else(|| invalid_signature(format!("Unknown element type: {:02x}", byte)))
+}
+
+/// Reads a TypeDefOrRefOrSpecEncoded token (ECMA 335, II.23.2.8).
+pub fn read_type_def_or_ref_encoded<R: Read>(reader: &mut R) -> Result<TypeDefOrRef> {
+  let encoded = read_compressed_u32(reader)?;
+  let index = encoded >> 2;
+
+  match encoded & 0b11 {
+    0b00 => Ok(TypeDefOrRef::TypeDef(Index::new(index))),
+    0b01 => Ok(TypeDefOrRef::TypeRef(Index::new(index))),
+    0b10 => Ok(TypeDefOrRef::TypeSpec(Index::new(index))),
+    otherwise => Err(invalid_signature(format!("Invalid TypeDefOrRefOrSpecEncoded tag: {}", otherwise)))
+  }
+}
+
+impl TypeSig {
+  pub fn read<R: Read>(reader: &mut R) -> Result<TypeSig> {
+    let element_type = read_element_type(reader)?;
+    TypeSig::read_with_element_type(reader, element_type)
+  }
+
+  pub fn read_with_element_type<R: Read>(reader: &mut R, element_type: ElementType) -> Result<TypeSig> {
+    let type_sig = match element_type {
+      ElementType::Void => TypeSig::Void,
+      ElementType::Boolean => TypeSig::Boolean,
+      ElementType::Char => TypeSig::Char,
+      ElementType::I1 => TypeSig::I1,
+      ElementType::U1 => TypeSig::U1,
+      ElementType::I2 => TypeSig::I2,
+      ElementType::U2 => TypeSig::U2,
+      ElementType::I4 => TypeSig::I4,
+      ElementType::U4 => TypeSig::U4,
+      ElementType::I8 => TypeSig::I8,
+      ElementType::U8 => TypeSig::U8,
+      ElementType::R4 => TypeSig::R4,
+      ElementType::R8 => TypeSig::R8,
+      ElementType::I => TypeSig::I,
+      ElementType::U => TypeSig::U,
+      ElementType::String => TypeSig::String,
+      ElementType::Object => TypeSig::Object,
+      ElementType::TypedByRef => TypeSig::TypedByRef,
+      ElementType::Class => TypeSig::Class(read_type_def_or_ref_encoded(reader)?),
+      ElementType::ValueType => TypeSig::ValueType(read_type_def_or_ref_encoded(reader)?),
+      ElementType::Ptr => TypeSig::Ptr(box TypeSig::read(reader)?),
+      ElementType::ByRef => TypeSig::ByRef(box TypeSig::read(reader)?),
+      ElementType::SzArray => TypeSig::SzArray(box TypeSig::read(reader)?),
+      ElementType::Array => {
+        let element = TypeSig::read(reader)?;
+        let rank = read_compressed_u32(reader)?;
+
+        // Counts read from the blob only reserve so much up front, as a malformed one could be huge
+        let size_count = read_compressed_u32(reader)?;
+        let mut sizes = Vec::with_capacity(cmp::min(size_count, 1024) as usize);
+        for _ in 0 .. size_count {
+          sizes.push(read_compressed_u32(reader)?);
+        }
+
+        let lower_bound_count = read_compressed_u32(reader)?;
+        let mut lower_bounds = Vec::with_capacity(cmp::min(lower_bound_count, 1024) as usize);
+        for _ in 0 .. lower_bound_count {
+          lower_bounds.push(StreamUtils::decode_compressed_signed_int(reader)?);
+        }
+
+        TypeSig::Array(box element, ArrayShape { rank, sizes, lower_bounds })
+      },
+      ElementType::GenericInst => {
+        let generic_type = match read_element_type(reader)? {
+          ElementType::Class => TypeSig::Class(read_type_def_or_ref_encoded(reader)?),
+          ElementType::ValueType => TypeSig::ValueType(read_type_def_or_ref_encoded(reader)?),
+          otherwise => return Err(invalid_signature(format!("Invalid generic type in GENERICINST: {:?}", otherwise)))
+        };
+
+        let arg_count = read_compressed_u32(reader)?;
+        let mut args = Vec::with_capacity(cmp::min(arg_count, 1024) as usize);
+        for _ in 0 .. arg_count {
+          args.push(TypeSig::read(reader)?);
+        }
+
+        TypeSig::GenericInst(box generic_type, args)
+      },
+      ElementType::Var => TypeSig::Var(read_compressed_u32(reader)?),
+      ElementType::MVar => TypeSig::MVar(read_compressed_u32(reader)?),
+      ElementType::FnPtr => TypeSig::FnPtr(box MethodSignature::read(reader)?),
+      ElementType::CModReqd => {
+        let modifier = read_type_def_or_ref_encoded(reader)?;
+        TypeSig::CModReqd(modifier, box TypeSig::read(reader)?)
+      },
+      ElementType::CModOpt => {
+        let modifier = read_type_def_or_ref_encoded(reader)?;
+        TypeSig::CModOpt(modifier, box TypeSig::read(reader)?)
+      },
+      ElementType::Pinned => TypeSig::Pinned(box TypeSig::read(reader)?),
+      ElementType::Sentinel => TypeSig::Sentinel,
+      otherwise => return Err(invalid_signature(format!("Unexpected element type in signature: {:?}", otherwise)))
+    };
+
+    Ok(type_sig)
+  }
+
+  pub fn from_blob(blob: &[u8]) -> Result<TypeSig> {
+    TypeSig::read(&mut Cursor::new(blob))
+  }
+
+  /// Formats the type using C#-like syntax, resolving type names from the metadata.
+  pub fn as_csharp(&self, meta: &Metadata) -> String {
+    let mut res = String::new();
+
+    match *self {
+      TypeSig::Void => res.push_str("void"),
+      TypeSig::Boolean => res.push_str("bool"),
+      TypeSig::Char => res.push_str("char"),
+      TypeSig::I1 => res.push_str("sbyte"),
+      TypeSig::U1 => res.push_str("byte"),
+      TypeSig::I2 => res.push_str("short"),
+      TypeSig::U2 => res.push_str("ushort"),
+      TypeSig::I4 => res.push_str("int"),
+      TypeSig::U4 => res.push_str("uint"),
+      TypeSig::I8 => res.push_str("long"),
+      TypeSig::U8 => res.push_str("ulong"),
+      TypeSig::R4 => res.push_str("float"),
+      TypeSig::R8 => res.push_str("double"),
+      TypeSig::I => res.push_str("IntPtr"),
+      TypeSig::U => res.push_str("UIntPtr"),
+      TypeSig::String => res.push_str("string"),
+      TypeSig::Object => res.push_str("object"),
+      TypeSig::TypedByRef => res.push_str("TypedReference"),
+      TypeSig::Class(ref type_) | TypeSig::ValueType(ref type_) => res.push_str(&meta.get_type_name(type_)),
+      TypeSig::Ptr(ref inner) => write!(&mut res, "{}*", inner.as_csharp(meta)).unwrap(),
+      TypeSig::ByRef(ref inner) => write!(&mut res, "ref {}", inner.as_csharp(meta)).unwrap(),
+      TypeSig::SzArray(ref inner) => write!(&mut res, "{}[]", inner.as_csharp(meta)).unwrap(),
+      TypeSig::Array(ref inner, ref shape) => {
+        let commas = (1 .. shape.rank).map(|_| ",").collect::<String>();
+        write!(&mut res, "{}[{}]", inner.as_csharp(meta), commas).unwrap()
+      },
+      TypeSig::GenericInst(ref generic_type, ref args) => {
+        let name = generic_type.as_csharp(meta);
+        // Strip the arity suffix, e.g. List`1
+        let name = match name.rfind('`') {
+          Some(position) => &name[.. position],
+          None => &name[..]
+        };
+        let args = args.iter().map(|arg| arg.as_csharp(meta)).collect::<Vec<_>>();
+        write!(&mut res, "{}<{}>", name, args.join(", ")).unwrap()
+      },
+      TypeSig::Var(number) => write!(&mut res, "!{}", number).unwrap(),
+      TypeSig::MVar(number) => write!(&mut res, "!!{}", number).unwrap(),
+      TypeSig::FnPtr(ref signature) => {
+        let mut types = signature.params.iter().map(|param| param.as_csharp(meta)).collect::<Vec<_>>();
+        types.push(signature.return_type.as_csharp(meta));
+        write!(&mut res, "delegate*<{}>", types.join(", ")).unwrap()
+      },
+      TypeSig::CModReqd(ref modifier, ref inner) =>
+        write!(&mut res, "modreq({}) {}", meta.get_type_name(modifier), inner.as_csharp(meta)).unwrap(),
+      TypeSig::CModOpt(ref modifier, ref inner) =>
+        write!(&mut res, "modopt({}) {}", meta.get_type_name(modifier), inner.as_csharp(meta)).unwrap(),
+      TypeSig::Pinned(ref inner) => write!(&mut res, "pinned {}", inner.as_csharp(meta)).unwrap(),
+      TypeSig::Sentinel => res.push_str("...")
+    }
+
+    res
+  }
+}
+
+fn expect_calling_convention<R: Read>(reader: &mut R, expected: CallingConvention) -> Result<SignatureFlags> {
+  let first_byte = reader.read_u8()?;
+
+  if first_byte & 0x0F != expected as u8 {
+    return Err(invalid_signature(format!("Expected a {:?} signature, got {:02x}", expected, first_byte)));
+  }
+
+  Ok(SignatureFlags::from_bits_truncate(first_byte))
+}
+
+impl FieldSig {
+  pub fn read<R: Read>(reader: &mut R) -> Result<FieldSig> {
+    expect_calling_convention(reader, CallingConvention::Field)?;
+    Ok(FieldSig { type_: TypeSig::read(reader)? })
+  }
+
+  pub fn from_blob(blob: &[u8]) -> Result<FieldSig> {
+    FieldSig::read(&mut Cursor::new(blob))
+  }
+}
+
+impl PropertySig {
+  pub fn read<R: Read>(reader: &mut R) -> Result<PropertySig> {
+    let flags = expect_calling_convention(reader, CallingConvention::Property)?;
+    let param_count = read_compressed_u32(reader)?;
+    let type_ = TypeSig::read(reader)?;
+
+    let mut params = Vec::with_capacity(cmp::min(param_count, 1024) as usize);
+    for _ in 0 .. param_count {
+      params.push(TypeSig::read(reader)?);
+    }
+
+    Ok(PropertySig { has_this: flags.contains(HasThis), type_, params })
+  }
+
+  pub fn from_blob(blob: &[u8]) -> Result<PropertySig> {
+    PropertySig::read(&mut Cursor::new(blob))
+  }
+}
+
+impl LocalVariable {
+  pub fn from_type_sig(type_sig: TypeSig) -> LocalVariable {
+    let mut current = type_sig;
+    let mut pinned = false;
+    let mut by_ref = false;
+    // The custom modifiers before PINNED and BYREF, as (required, modifier)
+    let mut modifiers = vec![];
+
+    // PINNED may appear among the custom modifiers, and always precedes BYREF
+    let mut type_ = loop {
+      current = match current {
+        TypeSig::Pinned(inner) => {
+          pinned = true;
+          *inner
+        },
+        TypeSig::ByRef(inner) if !by_ref => {
+          by_ref = true;
+          *inner
+        },
+        TypeSig::CModReqd(modifier, inner) if !by_ref => {
+          modifiers.push((true, modifier));
+          *inner
+        },
+        TypeSig::CModOpt(modifier, inner) if !by_ref => {
+          modifiers.push((false, modifier));
+          *inner
+        },
+        otherwise => break otherwise
+      }
+    };
+
+    // Put the modifiers back around the type, the first one outermost
+    for (required, modifier) in modifiers.into_iter().rev() {
+      type_ = if required { TypeSig::CModReqd(modifier, box type_) } else { TypeSig::CModOpt(modifier, box type_) };
+    }
+    LocalVariable { type_, pinned, by_ref }
+  }
+}
+
+impl LocalVarSig {
+  pub fn read<R: Read>(reader: &mut R) -> Result<LocalVarSig> {
+    expect_calling_convention(reader, CallingConvention::LocalSig)?;
+    let count = read_compressed_u32(reader)?;
+
+    let mut locals = Vec::with_capacity(cmp::min(count, 1024) as usize);
+    for _ in 0 .. count {
+      locals.push(LocalVariable::from_type_sig(TypeSig::read(reader)?));
+    }
+
+    Ok(LocalVarSig { locals })
+  }
+
+  pub fn from_blob(blob: &[u8]) -> Result<LocalVarSig> {
+    LocalVarSig::read(&mut Cursor::new(blob))
+  }
+}
+
+impl MethodSpecSig {
+  pub fn read<R: Read>(reader: &mut R) -> Result<MethodSpecSig> {
+    expect_calling_convention(reader, CallingConvention::GenericInst)?;
+    let count = read_compressed_u32(reader)?;
+
+    let mut type_args = Vec::with_capacity(cmp::min(count, 1024) as usize);
+    for _ in 0 .. count {
+      type_args.push(TypeSig::read(reader)?);
+    }
+
+    Ok(MethodSpecSig { type_args })
+  }
+
+  pub fn from_blob(blob: &[u8]) -> Result<MethodSpecSig> {
+    MethodSpecSig::read(&mut Cursor::new(blob))
+  }
+}
+
+impl Metadata {
+  pub fn get_field_signature(&self, index: &Index<FieldEntry>) -> Result<FieldSig> {
+    let entry = self.get_entry(index).ok_or_else(|| invalid_signature(format!("No such field: {}", index.0)))?;
+    let blob = self.get_blob(&entry.signature).ok_or_else(|| invalid_signature(format!("Invalid signature blob of field {}", index.0)))?;
+    FieldSig::from_blob(blob)
+  }
+
+  pub fn get_method_def_signature(&self, index: &Index<MethodDefEntry>) -> Result<MethodSignature> {
+    let entry = self.get_entry(index).ok_or_else(|| invalid_signature(format!("No such method: {}", index.0)))?;
+    let blob = self.get_blob(&entry.signature).ok_or_else(|| invalid_signature(format!("Invalid signature blob of method {}", index.0)))?;
+    MethodSignature::from_blob(blob)
+  }
+
+  pub fn get_member_ref_signature(&self, index: &Index<MemberRefEntry>) -> Result<MethodSignature> {
+    let entry = self.get_entry(index).ok_or_else(|| invalid_signature(format!("No such member: {}", index.0)))?;
+    let blob = self.get_blob(&entry.signature).ok_or_else(|| invalid_signature(format!("Invalid signature blob of member {}", index.0)))?;
+    MethodSignature::from_blob(blob)
+  }
+
+  pub fn get_property_signature(&self, index: &Index<PropertyEntry>) -> Result<PropertySig> {
+    let entry = self.get_entry(index).ok_or_else(|| invalid_signature(format!("No such property: {}", index.0)))?;
+    let blob = self.get_blob(&entry.signature).ok_or_else(|| invalid_signature(format!("Invalid signature blob of property {}", index.0)))?;
+    PropertySig::from_blob(blob)
+  }
+
+  pub fn get_type_spec_signature(&self, index: &Index<TypeSpecEntry>) -> Result<TypeSig> {
+    let entry = self.get_entry(index).ok_or_else(|| invalid_signature(format!("No such type spec: {}", index.0)))?;
+    let blob = self.get_blob(&entry.signature).ok_or_else(|| invalid_signature(format!("Invalid signature blob of type spec {}", index.0)))?;
+    TypeSig::from_blob(blob)
+  }
+
+  /// Resolves the local variable signature of a method through the StandAloneSig table.
+  /// Returns None for methods without locals, including all methods with a tiny header.
+  pub fn get_local_var_signature(&self, header: &MethodHeader) -> Result<Option<LocalVarSig>> {
+    let token = header.local_var_signature_token;
+
+    if token == 0 {
+      return Ok(None);
+    }
+
+    if (token >> 24) as u8 != TableId::StandAloneSig as u8 {
+      return Err(invalid_signature(format!("Invalid local variable signature token: {:08x}", token)));
+    }
+
+    let index = Index::<StandAloneSigEntry>::new(token & 0x00FF_FFFF);
+    let entry = self.get_entry(&index).ok_or_else(|| invalid_signature(format!("No such StandAloneSig: {:08x}", token)))?;
+    let blob = self.get_blob(&entry.signature).ok_or_else(|| invalid_signature(format!("Invalid StandAloneSig blob: {:08x}", token)))?;
+    LocalVarSig::from_blob(blob).map(Some)
+  }
+}
+
+impl MethodSignature {
+  pub fn read<R: Read>(reader: &mut R) -> Result<MethodSignature> {
+    let first_byte = reader.read_u8()?;
+    let flags = SignatureFlags::from_bits_truncate(first_byte);
+    let calling_convention = CallingConvention::from_u8(first_byte & 0x0F)
+      .ok_or_else(|| invalid_signature(format!("Unknown calling convention: {:02x}", first_byte)))?;
+
+    let generic_param_count = if flags.contains(Generic) {
+      read_compressed_u32(reader)?
+    } else {
+      0
+    };
+
+    let param_count = read_compressed_u32(reader)?;
+    let return_type = TypeSig::read(reader)?;
+
+    let mut params = Vec::with_capacity(cmp::min(param_count, 1024) as usize);
+    let mut sentinel = None;
+
+    while params.len() < param_count as usize {
+      match TypeSig::read(reader)? {
+        TypeSig::Sentinel => sentinel = Some(params.len()),
+        param => params.push(param)
+      }
+    }
+
+    Ok(MethodSignature { flags, calling_convention, generic_param_count, return_type, params, sentinel })
+  }
+
+  pub fn from_blob(blob: &[u8]) -> Result<MethodSignature> {
+    MethodSignature::read(&mut Cursor::new(blob))
+  }
+
+  pub fn has_this(&self) -> bool {
+    self.flags.contains(HasThis)
+  }
+
+  /// Formats the signature as a C#-like method declaration with the given name.
+  pub fn as_csharp(&self, meta: &Metadata, name: &str) -> String {
+    let mut params = self.params.iter().map(|param| param.as_csharp(meta)).collect::<Vec<_>>();
+
+    if let Some(sentinel) = self.sentinel {
+      params.insert(sentinel, "...".to_string());
+    }
+
+    let generic_params = if self.generic_param_count > 0 {
+      format!("<{}>", (0 .. self.generic_param_count).map(|i| format!("!!{}", i)).collect::<Vec<_>>().join(", "))
+    } else {
+      String::new()
+    };
+
+    let static_ = if self.has_this() { "" } else { "static " };
+    format!("{}{} {}{}({})", static_, self.return_type.as_csharp(meta), name, generic_params, params.join(", "))
+  }
+}
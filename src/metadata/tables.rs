@@ -1,11 +1,14 @@
 
 #![allow(non_upper_case_globals)]
 
-use std::io::{Read, Result};
+use std::io::{Read, Result, Error, ErrorKind};
 use std::marker::PhantomData;
+use std::hash::{Hash, Hasher};
 use byteorder::{ReadBytesExt, LittleEndian};
+use enum_primitive::FromPrimitive;
 
 use loader::stream::{TableId, IndexSize, RowCounts, FieldSizes};
+use metadata::signature::ElementType;
 
 pub trait ReadIndexSizeExt {
   fn read_index<T>(&mut self, size: IndexSize) -> Result<Index<T>>;
@@ -40,19 +43,42 @@ impl<T: Read> ReadIndexSizeExt for T {
   }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug)]
 pub struct Index<T>(pub u32, PhantomData<T>);
 
+// Implemented by hand, because deriving would require T to implement these as well
+impl<T> Clone for Index<T> {
+  fn clone(&self) -> Index<T> {
+    Index::new(self.0)
+  }
+}
+
+impl<T> Copy for Index<T> { }
+
+impl<T> PartialEq for Index<T> {
+  fn eq(&self, other: &Index<T>) -> bool {
+    self.0 == other.0
+  }
+}
+
+impl<T> Eq for Index<T> { }
+
+impl<T> Hash for Index<T> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.0.hash(state)
+  }
+}
+
 pub trait TableEntryReader {
   fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<Self> where Self : Sized;
 }
 
 impl<T> Index<T> {
-  fn new(index: u32) -> Index<T> {
+  pub fn new(index: u32) -> Index<T> {
     Index(index, PhantomData::<T>)
   }
 
-  fn to_u32(self) -> u32 {
+  pub fn to_u32(self) -> u32 {
     self.0
   }
 }
@@ -70,18 +96,28 @@ pub struct ModuleEntry {
 pub struct ModuleRefEntry { }
 
 #[derive(Debug)]
-pub struct FieldEntry { }
+pub struct FieldEntry {
+  // CorFieldAttr
+  pub flags: u16,
+  pub name: Index<StringHeap>,
+  pub signature: Index<BlobHeap>
+}
 
 #[derive(Debug)]
 pub struct ParamEntry {
   // CorParamAttr
-  flags: u16,
-  sequence: u16,
-  name: Index<StringHeap>
+  pub flags: u16,
+  pub sequence: u16,
+  pub name: Index<StringHeap>
 }
 
 #[derive(Debug)]
-pub struct PropertyEntry { }
+pub struct PropertyEntry {
+  // CorPropertyAttr
+  pub flags: u16,
+  pub name: Index<StringHeap>,
+  pub signature: Index<BlobHeap>
+}
 
 #[derive(Debug)]
 pub struct AssemblyEntry {
@@ -112,18 +148,21 @@ pub struct AssemblyRefEntry {
 }
 
 #[derive(Debug)]
-pub struct InterfaceImplEntry { }
+pub struct InterfaceImplEntry {
+  pub class: Index<TypeDefEntry>,
+  pub interface: TypeDefOrRef
+}
 
 #[derive(Debug)]
 pub struct MethodDefEntry {
   pub rva: u32,
   // CorMethodImpl
-  impl_flags: u16,
+  pub impl_flags: u16,
   // CorMethodAttr
-  flags: u16,
-  name: Index<StringHeap>,
-  signature: Index<BlobHeap>,
-  param_list: Index<ParamEntry>
+  pub flags: u16,
+  pub name: Index<StringHeap>,
+  pub signature: Index<BlobHeap>,
+  pub param_list: Index<ParamEntry>
 }
 
 #[derive(Debug)]
@@ -134,13 +173,82 @@ pub struct MemberRefEntry {
 }
 
 #[derive(Debug)]
-pub struct StandAloneSigEntry { }
+pub struct ConstantEntry {
+  pub type_: ElementType,
+  pub parent: HasConstant,
+  pub value: Index<BlobHeap>
+}
+
+#[derive(Debug)]
+pub struct FieldMarshalEntry {
+  pub parent: HasFieldMarshall,
+  pub native_type: Index<BlobHeap>
+}
+
+#[derive(Debug)]
+pub struct DeclSecurityEntry {
+  // SecurityAction
+  pub action: u16,
+  pub parent: HasDeclSecurity,
+  pub permission_set: Index<BlobHeap>
+}
+
+#[derive(Debug)]
+pub struct ClassLayoutEntry {
+  pub packing_size: u16,
+  /// The size of instances in bytes, or 0 to let the runtime choose it
+  pub class_size: u32,
+  pub parent: Index<TypeDefEntry>
+}
+
+#[derive(Debug)]
+pub struct StandAloneSigEntry {
+  pub signature: Index<BlobHeap>
+}
+
+#[derive(Debug)]
+pub struct EventMapEntry {
+  pub parent: Index<TypeDefEntry>,
+  pub event_list: Index<EventEntry>
+}
+
+#[derive(Debug)]
+pub struct EventEntry {
+  // CorEventAttr
+  pub flags: u16,
+  pub name: Index<StringHeap>,
+  pub event_type: TypeDefOrRef
+}
+
+#[derive(Debug)]
+pub struct PropertyMapEntry {
+  pub parent: Index<TypeDefEntry>,
+  pub property_list: Index<PropertyEntry>
+}
+
+#[derive(Debug)]
+pub struct MethodSemanticsEntry {
+  // CorMethodSemanticsAttr
+  pub semantics: u16,
+  pub method: Index<MethodDefEntry>,
+  pub association: HasSemantics
+}
 
 #[derive(Debug)]
-pub struct EventEntry { }
+pub struct MethodImplEntry {
+  pub class: Index<TypeDefEntry>,
+  /// The method implementing the declaration
+  pub method_body: MethodDefOrRef,
+  /// The overridden method, or the implemented interface method
+  pub method_declaration: MethodDefOrRef
+}
 
 #[derive(Debug)]
-pub struct PermissionEntry { }
+pub struct FieldRVAEntry {
+  /// Where the initial value of the field is stored in the image, e.g. the data of an array initializer
+  pub rva: u32,
+  pub field: Index<FieldEntry>
+}
 
 #[derive(Debug)]
 pub struct FileEntry { }
@@ -151,26 +259,54 @@ pub struct ExportedTypeEntry { }
 #[derive(Debug)]
 pub struct ManifestResourceEntry { }
 
+#[derive(Debug)]
+pub struct NestedClassEntry {
+  pub nested_class: Index<TypeDefEntry>,
+  pub enclosing_class: Index<TypeDefEntry>
+}
+
+#[derive(Debug)]
+pub struct GenericParamEntry {
+  // The position of the parameter, starting at 0
+  pub number: u16,
+  // GenericParamAttributes
+  pub flags: u16,
+  pub owner: TypeOrMethodDef,
+  pub name: Index<StringHeap>
+}
+
+#[derive(Debug)]
+pub struct MethodSpecEntry {
+  pub method: MethodDefOrRef,
+  pub instantiation: Index<BlobHeap>
+}
+
+#[derive(Debug)]
+pub struct GenericParamConstraintEntry {
+  pub owner: Index<GenericParamEntry>,
+  pub constraint: TypeDefOrRef
+}
+
 #[derive(Debug)]
 pub struct TypeRefEntry {
-  resolution_scope: ResolutionScope,
-  name: Index<StringHeap>,
-  namespace: Index<StringHeap>
+  pub resolution_scope: ResolutionScope,
+  pub name: Index<StringHeap>,
+  pub namespace: Index<StringHeap>
 }
 
 #[derive(Debug)]
 pub struct TypeDefEntry {
-  flags: TypeAttributes,
-  name: Index<StringHeap>,
-  namespace: Index<StringHeap>,
-  extends: TypeDefOrRef,
-  fields: Index<FieldEntry>,
-  methods: Index<MethodDefEntry>
+  pub flags: TypeAttributes,
+  pub name: Index<StringHeap>,
+  pub namespace: Index<StringHeap>,
+  pub extends: TypeDefOrRef,
+  pub fields: Index<FieldEntry>,
+  pub methods: Index<MethodDefEntry>
 }
 
 #[derive(Debug)]
 pub struct TypeSpecEntry {
-  signature: Index<BlobHeap>
+  pub signature: Index<BlobHeap>
 }
 
 #[derive(Debug)]
@@ -216,6 +352,16 @@ impl TableEntryReader for TypeDefEntry {
   }
 }
 
+impl TableEntryReader for FieldEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<FieldEntry> {
+    let flags = reader.read_u16::<LittleEndian>()?;
+    let name = reader.read_string(sizes)?;
+    let signature = reader.read_blob(sizes)?;
+
+    Ok(FieldEntry { flags, name, signature })
+  }
+}
+
 impl TableEntryReader for MethodDefEntry {
   fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<MethodDefEntry> {
     let rva = reader.read_u32::<LittleEndian>()?;
@@ -229,6 +375,25 @@ impl TableEntryReader for MethodDefEntry {
   }
 }
 
+impl TableEntryReader for ParamEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<ParamEntry> {
+    let flags = reader.read_u16::<LittleEndian>()?;
+    let sequence = reader.read_u16::<LittleEndian>()?;
+    let name = reader.read_string(sizes)?;
+
+    Ok(ParamEntry { flags, sequence, name })
+  }
+}
+
+impl TableEntryReader for InterfaceImplEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<InterfaceImplEntry> {
+    let class = reader.read_table_index(sizes, TableId::TypeDef)?;
+    let interface = TypeDefOrRef::read_from(reader, &sizes.row_counts)?;
+
+    Ok(InterfaceImplEntry { class, interface })
+  }
+}
+
 impl TableEntryReader for MemberRefEntry {
   fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<MemberRefEntry> {
     let class = MemberRefParent::read_from(reader, &sizes.row_counts)?;
@@ -239,6 +404,20 @@ impl TableEntryReader for MemberRefEntry {
   }
 }
 
+impl TableEntryReader for ConstantEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<ConstantEntry> {
+    let type_encoded = reader.read_u8()?;
+    let type_ = ElementType::from_u8(type_encoded)
+      .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Invalid constant type: {:02x}", type_encoded)))?;
+    // Padding
+    reader.read_u8()?;
+    let parent = HasConstant::read_from(reader, &sizes.row_counts)?;
+    let value = reader.read_blob(sizes)?;
+
+    Ok(ConstantEntry { type_, parent, value })
+  }
+}
+
 impl TableEntryReader for CustomAttributeEntry {
   fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<CustomAttributeEntry> {
     let parent = HasCustomAttribute::read_from(reader, &sizes.row_counts)?;
@@ -249,6 +428,118 @@ impl TableEntryReader for CustomAttributeEntry {
   }
 }
 
+impl TableEntryReader for FieldMarshalEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<FieldMarshalEntry> {
+    let parent = HasFieldMarshall::read_from(reader, &sizes.row_counts)?;
+    let native_type = reader.read_blob(sizes)?;
+
+    Ok(FieldMarshalEntry { parent, native_type })
+  }
+}
+
+impl TableEntryReader for DeclSecurityEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<DeclSecurityEntry> {
+    let action = reader.read_u16::<LittleEndian>()?;
+    let parent = HasDeclSecurity::read_from(reader, &sizes.row_counts)?;
+    let permission_set = reader.read_blob(sizes)?;
+
+    Ok(DeclSecurityEntry { action, parent, permission_set })
+  }
+}
+
+impl TableEntryReader for ClassLayoutEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<ClassLayoutEntry> {
+    let packing_size = reader.read_u16::<LittleEndian>()?;
+    let class_size = reader.read_u32::<LittleEndian>()?;
+    let parent = reader.read_table_index(sizes, TableId::TypeDef)?;
+
+    Ok(ClassLayoutEntry { packing_size, class_size, parent })
+  }
+}
+
+impl TableEntryReader for StandAloneSigEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<StandAloneSigEntry> {
+    let signature = reader.read_blob(sizes)?;
+
+    Ok(StandAloneSigEntry { signature })
+  }
+}
+
+impl TableEntryReader for EventMapEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<EventMapEntry> {
+    let parent = reader.read_table_index(sizes, TableId::TypeDef)?;
+    let event_list = reader.read_table_index(sizes, TableId::Event)?;
+
+    Ok(EventMapEntry { parent, event_list })
+  }
+}
+
+impl TableEntryReader for EventEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<EventEntry> {
+    let flags = reader.read_u16::<LittleEndian>()?;
+    let name = reader.read_string(sizes)?;
+    let event_type = TypeDefOrRef::read_from(reader, &sizes.row_counts)?;
+
+    Ok(EventEntry { flags, name, event_type })
+  }
+}
+
+impl TableEntryReader for PropertyMapEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<PropertyMapEntry> {
+    let parent = reader.read_table_index(sizes, TableId::TypeDef)?;
+    let property_list = reader.read_table_index(sizes, TableId::Property)?;
+
+    Ok(PropertyMapEntry { parent, property_list })
+  }
+}
+
+impl TableEntryReader for PropertyEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<PropertyEntry> {
+    let flags = reader.read_u16::<LittleEndian>()?;
+    let name = reader.read_string(sizes)?;
+    let signature = reader.read_blob(sizes)?;
+
+    Ok(PropertyEntry { flags, name, signature })
+  }
+}
+
+impl TableEntryReader for MethodSemanticsEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<MethodSemanticsEntry> {
+    let semantics = reader.read_u16::<LittleEndian>()?;
+    let method = reader.read_table_index(sizes, TableId::MethodDef)?;
+    let association = HasSemantics::read_from(reader, &sizes.row_counts)?;
+
+    Ok(MethodSemanticsEntry { semantics, method, association })
+  }
+}
+
+impl TableEntryReader for MethodImplEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<MethodImplEntry> {
+    let class = reader.read_table_index(sizes, TableId::TypeDef)?;
+    let method_body = MethodDefOrRef::read_from(reader, &sizes.row_counts)?;
+    let method_declaration = MethodDefOrRef::read_from(reader, &sizes.row_counts)?;
+
+    Ok(MethodImplEntry { class, method_body, method_declaration })
+  }
+}
+
+impl TableEntryReader for TypeSpecEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<TypeSpecEntry> {
+    let signature = reader.read_blob(sizes)?;
+
+    Ok(TypeSpecEntry { signature })
+  }
+}
+
+impl TableEntryReader for FieldRVAEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<FieldRVAEntry> {
+    let rva = reader.read_u32::<LittleEndian>()?;
+    let field = reader.read_table_index(sizes, TableId::Field)?;
+
+    Ok(FieldRVAEntry { rva, field })
+  }
+}
+
 impl TableEntryReader for AssemblyEntry {
   fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<AssemblyEntry> {
     let hash_algorithm = reader.read_u32::<LittleEndian>()?;
@@ -300,6 +591,44 @@ impl TableEntryReader for AssemblyRefEntry {
   }
 }
 
+impl TableEntryReader for NestedClassEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<NestedClassEntry> {
+    let nested_class = reader.read_table_index(sizes, TableId::TypeDef)?;
+    let enclosing_class = reader.read_table_index(sizes, TableId::TypeDef)?;
+
+    Ok(NestedClassEntry { nested_class, enclosing_class })
+  }
+}
+
+impl TableEntryReader for GenericParamEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<GenericParamEntry> {
+    let number = reader.read_u16::<LittleEndian>()?;
+    let flags = reader.read_u16::<LittleEndian>()?;
+    let owner = TypeOrMethodDef::read_from(reader, &sizes.row_counts)?;
+    let name = reader.read_string(sizes)?;
+
+    Ok(GenericParamEntry { number, flags, owner, name })
+  }
+}
+
+impl TableEntryReader for MethodSpecEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<MethodSpecEntry> {
+    let method = MethodDefOrRef::read_from(reader, &sizes.row_counts)?;
+    let instantiation = reader.read_blob(sizes)?;
+
+    Ok(MethodSpecEntry { method, instantiation })
+  }
+}
+
+impl TableEntryReader for GenericParamConstraintEntry {
+  fn read_entry<R: Read>(reader: &mut R, sizes: &FieldSizes) -> Result<GenericParamConstraintEntry> {
+    let owner = reader.read_table_index(sizes, TableId::GenericParam)?;
+    let constraint = TypeDefOrRef::read_from(reader, &sizes.row_counts)?;
+
+    Ok(GenericParamConstraintEntry { owner, constraint })
+  }
+}
+
 pub struct TaggedIndex {
   pub tag: u8,
   pub index: u32
@@ -321,16 +650,16 @@ impl TaggedIndex {
     }
   }
 
-  pub fn read_from<R: Read>(reader: &mut R, tag_bits_length: u8, row_count: u32) -> Result<TaggedIndex> {
+  pub fn index_size(tag_bits_length: u8, row_count: u32) -> IndexSize {
     // The maximum number of rows that can be encoded with a 16-bit tagged index
     let max_length_for_word_index = 2u32.pow(16 - (tag_bits_length as u32)) - 1;
+    IndexSize::from(row_count > max_length_for_word_index)
+  }
+
+  pub fn read_from<R: Read>(reader: &mut R, tag_bits_length: u8, row_count: u32) -> Result<TaggedIndex> {
     let tag_mask = TaggedIndex::tag_mask_from_len(tag_bits_length);
 
-    let tagged_index = if row_count > max_length_for_word_index {
-      reader.read_u32::<LittleEndian>()
-    } else {
-      reader.read_u16::<LittleEndian>().map(|x| x as u32)
-    }?;
+    let tagged_index = reader.read_index::<TaggedIndex>(TaggedIndex::index_size(tag_bits_length, row_count))?.0;
 
     let tag = (tagged_index & (tag_mask as u32)) as u8;
     let index = (tagged_index & !(tag_mask as u32)) >> tag_bits_length;
@@ -371,9 +700,14 @@ macro_rules! tagged_index_parser {
     patterns: [$($pattern: expr => $case: ident),*]
   } => {
     impl $type_ {
+      pub fn index_size(row_counts: &RowCounts) -> IndexSize {
+        let max_size = max_table_entries!(row_counts, [$($table_id),*]);
+        TaggedIndex::index_size($tag_length, max_size)
+      }
+
       pub fn read_from<R: Read>(reader: &mut R, row_counts: &RowCounts) -> Result<$type_> {
         let max_size = max_table_entries!(row_counts, [$($table_id),*]);
-        let tagged_index = TaggedIndex::read_from(reader, 2, max_size)?;
+        let tagged_index = TaggedIndex::read_from(reader, $tag_length, max_size)?;
         match tagged_index.tag {
           $(
             $pattern => Ok($type_::$case(Index::new(tagged_index.index)))
@@ -385,7 +719,7 @@ macro_rules! tagged_index_parser {
   };
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TypeDefOrRef {
   TypeDef(Index<TypeDefEntry>),
   TypeRef(Index<TypeRefEntry>),
@@ -402,7 +736,7 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HasConstant {
   Field(Index<FieldEntry>),
   Param(Index<ParamEntry>),
@@ -419,7 +753,7 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HasCustomAttribute {
   MethodDef(Index<MethodDefEntry>),
   Field(Index<FieldEntry>),
@@ -427,9 +761,9 @@ pub enum HasCustomAttribute {
   TypeDef(Index<TypeDefEntry>),
   Param(Index<ParamEntry>),
   InterfaceImpl(Index<InterfaceImplEntry>),
-  MemberRef(Index<MethodDefEntry>),
+  MemberRef(Index<MemberRefEntry>),
   Module(Index<ModuleEntry>),
-  //Permission(Index<PermissionEntry>),
+  Permission(Index<DeclSecurityEntry>),
   Property(Index<PropertyEntry>),
   Event(Index<EventEntry>),
   StandAloneSig(Index<StandAloneSigEntry>),
@@ -439,12 +773,20 @@ pub enum HasCustomAttribute {
   AssemblyRef(Index<AssemblyRefEntry>),
   File(Index<FileEntry>),
   ExportedType(Index<ExportedTypeEntry>),
-  ManifestResource(Index<ManifestResourceEntry>)
+  ManifestResource(Index<ManifestResourceEntry>),
+  GenericParam(Index<GenericParamEntry>),
+  GenericParamConstraint(Index<GenericParamConstraintEntry>),
+  MethodSpec(Index<MethodSpecEntry>)
 }
 
 tagged_index_parser! {
   type: HasCustomAttribute,
   tag_length: 5,
+  tables: [
+    MethodDef, Field, TypeRef, TypeDef, Param, InterfaceImpl, MemberRef, Module, DeclSecurity, Property, Event,
+    StandAloneSig, ModuleRef, TypeSpec, Assembly, AssemblyRef, File, ExportedType, ManifestResource, GenericParam,
+    GenericParamConstraint, MethodSpec
+  ],
   patterns: [
     0b00000 => MethodDef,
     0b00001 => Field,
@@ -454,7 +796,7 @@ tagged_index_parser! {
     0b00101 => InterfaceImpl,
     0b00110 => MemberRef,
     0b00111 => Module,
-    //0b01000 => Permission,
+    0b01000 => Permission,
     0b01001 => Property,
     0b01010 => Event,
     0b01011 => StandAloneSig,
@@ -464,11 +806,14 @@ tagged_index_parser! {
     0b01111 => AssemblyRef,
     0b10000 => File,
     0b10001 => ExportedType,
-    0b10010 => ManifestResource
+    0b10010 => ManifestResource,
+    0b10011 => GenericParam,
+    0b10100 => GenericParamConstraint,
+    0b10101 => MethodSpec
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HasFieldMarshall {
   Field(Index<FieldEntry>),
   Param(Index<ParamEntry>)
@@ -483,7 +828,7 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HasDeclSecurity {
   TypeDef(Index<TypeDefEntry>),
   MethodDef(Index<MethodDefEntry>),
@@ -500,7 +845,7 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MemberRefParent {
   TypeDef(Index<TypeDefEntry>),
   TypeRef(Index<TypeRefEntry>),
@@ -521,7 +866,7 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HasSemantics {
   Event(Index<EventEntry>),
   Property(Index<PropertyEntry>)
@@ -536,7 +881,7 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MethodDefOrRef {
   MethodDef(Index<MethodDefEntry>),
   MethodRef(Index<MemberRefEntry>)
@@ -552,7 +897,7 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MemberForwarded {
   Field(Index<FieldEntry>),
   MethodDef(Index<MemberRefEntry>)
@@ -567,7 +912,7 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Implementation {
   File(Index<FileEntry>),
   AssemblyRef(Index<AssemblyRefEntry>),
@@ -584,7 +929,7 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CustomAttributeType {
   MethodDef(Index<MethodDefEntry>),
   MemberRef(Index<MemberRefEntry>)
@@ -599,12 +944,12 @@ tagged_index_parser! {
   ]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ResolutionScope {
   Module(Index<ModuleEntry>),
-  ModuleRef(Index<TypeDefEntry>),
-  AssemblyRef(Index<TypeDefEntry>),
-  TypeRef(Index<TypeDefEntry>)
+  ModuleRef(Index<ModuleRefEntry>),
+  AssemblyRef(Index<AssemblyRefEntry>),
+  TypeRef(Index<TypeRefEntry>)
 }
 
 tagged_index_parser! {
@@ -618,6 +963,7 @@ tagged_index_parser! {
   ]
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TypeOrMethodDef {
   TypeDef(Index<TypeDefEntry>),
   MethodDef(Index<MethodDefEntry>)
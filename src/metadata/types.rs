@@ -0,0 +1,265 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::ops::Range;
+use typemap::Key;
+
+use metadata::Metadata;
+use metadata::signature::TypeSig;
+use metadata::tables::*;
+
+pub fn join_namespace(namespace: &str, name: &str) -> String {
+  if namespace.is_empty() {
+    name.to_string()
+  } else {
+    format!("{}.{}", namespace, name)
+  }
+}
+
+/// The name of a type definition or reference, including the names of its enclosing types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FullTypeName {
+  /// The namespace of the outermost type.
+  pub namespace: String,
+  /// The names of the enclosing types, outermost first, followed by the name of the type itself.
+  pub names: Vec<String>
+}
+
+impl FullTypeName {
+  /// Formats the name like System.Type.FullName, e.g. `Ns.Outer+Inner`.
+  pub fn as_reflection(&self) -> String {
+    join_namespace(&self.namespace, &self.names.join("+"))
+  }
+
+  /// Formats the name like C#, e.g. `Ns.Outer.Inner`.
+  pub fn as_csharp(&self) -> String {
+    join_namespace(&self.namespace, &self.names.join("."))
+  }
+}
+
+impl Metadata {
+  /// Returns the namespace-qualified name of a type in C# syntax, or a placeholder if the index is invalid.
+  /// Type specs are formatted using C#-like syntax.
+  pub fn get_type_name(&self, type_: &TypeDefOrRef) -> String {
+    match *type_ {
+      TypeDefOrRef::TypeSpec(ref index) => self.get_entry(index)
+        .and_then(|entry| self.get_blob(&entry.signature))
+        .and_then(|blob| TypeSig::from_blob(blob).ok())
+        .map(|type_sig| type_sig.as_csharp(self))
+        .unwrap_or_else(|| format!("TypeSpec#{}", index.0)),
+      _ => self.full_type_name(type_).as_csharp()
+    }
+  }
+
+  /// Builds the name of a type, following NestedClass rows for type definitions and TypeRef
+  /// resolution scopes for type references.
+  pub fn full_type_name(&self, type_: &TypeDefOrRef) -> FullTypeName {
+    let mut names = vec![];
+    let mut namespace = String::new();
+
+    match *type_ {
+      TypeDefOrRef::TypeDef(index) => {
+        let mut current = Some(index);
+
+        // Bounded by the number of types, so that malformed metadata can't loop forever
+        let type_count = self.get_table::<TypeDefEntry>().map(|x| x.len()).unwrap_or(0);
+        while let Some(index) = current.take() {
+          let entry = match self.get_entry(&index) {
+            Some(entry) => entry,
+            None => {
+              names.push(format!("TypeDef#{}", index.0));
+              break
+            }
+          };
+
+          names.push(self.get_string(&entry.name).cloned().unwrap_or_default());
+          namespace = self.get_string(&entry.namespace).cloned().unwrap_or_default();
+
+          if names.len() <= type_count {
+            current = self.get_enclosing_type(&index);
+          }
+        }
+      },
+      TypeDefOrRef::TypeRef(index) => {
+        let mut current = Some(index);
+
+        let type_ref_count = self.get_table::<TypeRefEntry>().map(|x| x.len()).unwrap_or(0);
+        while let Some(index) = current.take() {
+          let entry = match self.get_entry(&index) {
+            Some(entry) => entry,
+            None => {
+              names.push(format!("TypeRef#{}", index.0));
+              break
+            }
+          };
+
+          names.push(self.get_string(&entry.name).cloned().unwrap_or_default());
+          namespace = self.get_string(&entry.namespace).cloned().unwrap_or_default();
+
+          if let ResolutionScope::TypeRef(enclosing) = entry.resolution_scope {
+            if names.len() <= type_ref_count {
+              current = Some(enclosing);
+            }
+          }
+        }
+      },
+      TypeDefOrRef::TypeSpec(index) => names.push(self.get_type_name(&TypeDefOrRef::TypeSpec(index)))
+    }
+
+    names.reverse();
+    FullTypeName { namespace, names }
+  }
+
+  /// Returns the type a nested type is declared in, or None for top-level types.
+  pub fn get_enclosing_type(&self, type_def: &Index<TypeDefEntry>) -> Option<Index<TypeDefEntry>> {
+    self.get_table::<NestedClassEntry>()
+      .and_then(|entries| entries.iter().find(|entry| entry.nested_class == *type_def))
+      .map(|entry| entry.enclosing_class)
+  }
+
+  pub fn get_nested_types(&self, type_def: &Index<TypeDefEntry>) -> Vec<Index<TypeDefEntry>> {
+    self.get_table::<NestedClassEntry>()
+      .map(|entries| entries.iter()
+        .filter(|entry| entry.enclosing_class == *type_def)
+        .map(|entry| entry.nested_class)
+        .collect())
+      .unwrap_or_default()
+  }
+
+  /// Returns the interfaces directly implemented by a type, in declaration order.
+  pub fn get_interfaces(&self, type_def: &Index<TypeDefEntry>) -> Vec<TypeDefOrRef> {
+    self.get_table::<InterfaceImplEntry>()
+      .map(|entries| entries.iter()
+        .filter(|entry| entry.class == *type_def)
+        .map(|entry| entry.interface)
+        .collect())
+      .unwrap_or_default()
+  }
+
+  /// Finds a top-level type by its namespace and name.
+  pub fn find_type_def(&self, namespace: &str, name: &str) -> Option<Index<TypeDefEntry>> {
+    self.get_table::<TypeDefEntry>().and_then(|type_defs|
+      type_defs.iter().enumerate().position(|(position, entry)|
+        self.get_string(&entry.namespace).map(|x| &x[..]) == Some(namespace) &&
+        self.get_string(&entry.name).map(|x| &x[..]) == Some(name) &&
+        self.get_enclosing_type(&Index::new(position as u32 + 1)).is_none()))
+      .map(|position| Index::new(position as u32 + 1))
+  }
+
+  /// Finds a type by its reflection-style name, e.g. `Ns.Outer+Inner`.
+  pub fn find_type_def_by_full_name(&self, full_name: &str) -> Option<Index<TypeDefEntry>> {
+    let mut names = full_name.split('+');
+    let outermost = names.next().unwrap_or("");
+
+    let (namespace, name) = match outermost.rfind('.') {
+      Some(position) => (&outermost[.. position], &outermost[position + 1 ..]),
+      None => ("", outermost)
+    };
+
+    let mut current = self.find_type_def(namespace, name);
+    for nested_name in names {
+      current = current.and_then(|enclosing| self.get_nested_types(&enclosing).into_iter().find(|nested|
+        self.get_entry(nested).and_then(|entry| self.get_string(&entry.name)).map(|x| &x[..]) == Some(nested_name)));
+    }
+
+    current
+  }
+
+  /// Returns the range of Field indices owned by a TypeDef.
+  /// Each type owns the fields up to the start of the next type's field list.
+  pub fn get_field_range(&self, type_def: &Index<TypeDefEntry>) -> Range<u32> {
+    self.list_range_of::<TypeDefEntry, _>(type_def.0, self.row_count::<FieldEntry>(), |entry| entry.fields.0)
+  }
+
+  /// Returns the range of MethodDef indices owned by a TypeDef.
+  pub fn get_method_range(&self, type_def: &Index<TypeDefEntry>) -> Range<u32> {
+    self.list_range_of::<TypeDefEntry, _>(type_def.0, self.row_count::<MethodDefEntry>(), |entry| entry.methods.0)
+  }
+
+  /// Returns the range of Param indices owned by a MethodDef.
+  pub fn get_param_range(&self, method: &Index<MethodDefEntry>) -> Range<u32> {
+    self.list_range_of::<MethodDefEntry, _>(method.0, self.row_count::<ParamEntry>(), |entry| entry.param_list.0)
+  }
+
+  /// Returns the range of Property indices owned by a TypeDef through its PropertyMap row.
+  pub fn get_property_range(&self, type_def: &Index<TypeDefEntry>) -> Range<u32> {
+    let maps = match self.get_table::<PropertyMapEntry>() {
+      Some(maps) => maps,
+      None => return 0 .. 0
+    };
+
+    match maps.iter().position(|map| map.parent == *type_def) {
+      Some(position) => {
+        self.list_range_of::<PropertyMapEntry, _>(position as u32 + 1, self.row_count::<PropertyEntry>(), |map| map.property_list.0)
+      },
+      None => 0 .. 0
+    }
+  }
+
+  /// Returns the range of Event indices owned by a TypeDef through its EventMap row.
+  pub fn get_event_range(&self, type_def: &Index<TypeDefEntry>) -> Range<u32> {
+    let maps = match self.get_table::<EventMapEntry>() {
+      Some(maps) => maps,
+      None => return 0 .. 0
+    };
+
+    match maps.iter().position(|map| map.parent == *type_def) {
+      Some(position) => {
+        self.list_range_of::<EventMapEntry, _>(position as u32 + 1, self.row_count::<EventEntry>(), |map| map.event_list.0)
+      },
+      None => 0 .. 0
+    }
+  }
+
+  fn row_count<T: Key<Value = Vec<T>> + Debug>(&self) -> u32 {
+    self.get_table::<T>().map(|x| x.len()).unwrap_or(0) as u32
+  }
+
+  // The members a row of an owner table like TypeDef owns, given where each row's member list starts
+  fn list_range_of<T: Key<Value = Vec<T>> + Debug, F: Fn(&T) -> u32>(&self, row: u32, member_count: u32, start_of: F) -> Range<u32> {
+    let owners = match self.get_table::<T>() {
+      Some(owners) => owners,
+      None => return 0 .. 0
+    };
+    let start = match owners.get((row as usize).wrapping_sub(1)) {
+      Some(owner) => start_of(owner),
+      None => return 0 .. 0
+    };
+
+    let end = owners.get(row as usize).map(|owner| start_of(owner)).unwrap_or(member_count + 1);
+    // An index past the end of the member table means the row has no members
+    start .. ::std::cmp::max(start, ::std::cmp::min(end, member_count + 1))
+  }
+
+  // The row of an owner table whose member list contains a member. The lists start in ascending order,
+  // so the owner is the last row starting at or before the member, which is found by binary search.
+  fn list_owner<T: Key<Value = Vec<T>> + Debug, F: Fn(&T) -> u32>(&self, member: u32, member_count: u32, start_of: F) -> Option<u32> {
+    let owners = match self.get_table::<T>() {
+      Some(owners) => owners,
+      None => return None
+    };
+    if member == 0 || member > member_count {
+      return None;
+    }
+
+    let position = match owners.binary_search_by(|owner| if start_of(owner) <= member { Ordering::Less } else { Ordering::Greater }) {
+      Ok(position) | Err(position) => position as u32
+    };
+    let range = self.list_range_of::<T, _>(position, member_count, start_of);
+    if range.start <= member && member < range.end { Some(position) } else { None }
+  }
+
+  /// Returns the TypeDef which owns the given method.
+  pub fn get_method_owner(&self, method: &Index<MethodDefEntry>) -> Option<Index<TypeDefEntry>> {
+    self.list_owner::<TypeDefEntry, _>(method.0, self.row_count::<MethodDefEntry>(), |entry| entry.methods.0).map(Index::new)
+  }
+
+  /// Returns the TypeDef which owns the given field.
+  pub fn get_field_owner(&self, field: &Index<FieldEntry>) -> Option<Index<TypeDefEntry>> {
+    self.list_owner::<TypeDefEntry, _>(field.0, self.row_count::<FieldEntry>(), |entry| entry.fields.0).map(Index::new)
+  }
+
+  /// Returns the MethodDef which owns the given parameter.
+  pub fn get_param_owner(&self, param: &Index<ParamEntry>) -> Option<Index<MethodDefEntry>> {
+    self.list_owner::<MethodDefEntry, _>(param.0, self.row_count::<ParamEntry>(), |entry| entry.param_list.0).map(Index::new)
+  }
+}
@@ -0,0 +1,138 @@
+use std::io::Cursor;
+use byteorder::{ReadBytesExt, LittleEndian};
+
+use metadata::Metadata;
+use metadata::signature::TypeSig;
+use metadata::tables::TypeDefOrRef;
+use runtime::class::{is_reference_type, runtime_type};
+use runtime::error::ExecutionError;
+use runtime::exceptions::core_type_derives_from;
+use runtime::value::{StackValue, ValueTypeInstance};
+
+/// The maximum number of elements of an array, as in .NET's Array.MaxLength. Larger arrays throw an
+/// OutOfMemoryException, like arrays the host can't allocate.
+pub const MAX_ARRAY_LENGTH: usize = 0x7FFF_FFC7;
+
+// The core library types all arrays derive from or implement
+const ARRAY_BASE_TYPES: &'static [&'static str] = &["System.Object", "System.Array", "System.ICloneable",
+  "System.Collections.IEnumerable", "System.Collections.ICollection", "System.Collections.IList"];
+
+/// The element type of an array type, or None for other types.
+pub fn element_type(array_type: &TypeSig) -> Option<&TypeSig> {
+  match *array_type {
+    TypeSig::SzArray(ref element) | TypeSig::Array(ref element, _) => Some(element),
+    _ => None
+  }
+}
+
+pub fn argument_null(param: &str) -> ExecutionError {
+  ExecutionError::exception("System.ArgumentNullException", &format!("Value cannot be null.\nParameter name: {}", param))
+}
+
+pub fn index_out_of_range() -> ExecutionError {
+  ExecutionError::exception("System.IndexOutOfRangeException", "Index was outside the bounds of the array.")
+}
+
+pub fn type_mismatch() -> ExecutionError {
+  ExecutionError::exception("System.ArrayTypeMismatchException", "Attempted to access an element as a type incompatible with the array.")
+}
+
+/// The position of an element in row-major order, from an index for each dimension. None if an
+/// index is out of bounds.
+pub fn linear_index(lengths: &[usize], lower_bounds: &[i32], indices: &[i64]) -> Option<usize> {
+  if indices.len() != lengths.len() || lower_bounds.len() != lengths.len() {
+    return None;
+  }
+
+  let mut position = 0;
+  for ((&length, &lower_bound), &index) in lengths.iter().zip(lower_bounds).zip(indices) {
+    let offset = index - lower_bound as i64;
+    if offset < 0 || offset >= length as i64 {
+      return None;
+    }
+    position = position * length + offset as usize;
+  }
+  Some(position)
+}
+
+/// The zero value of the type of a value, which Array.Clear stores.
+pub fn zeroed(value: &StackValue) -> StackValue {
+  match *value {
+    StackValue::Int32(_) => StackValue::Int32(0),
+    StackValue::Int64(_) => StackValue::Int64(0),
+    StackValue::NativeInt(_) | StackValue::FunctionPointer(..) => StackValue::NativeInt(0),
+    StackValue::Float(_) => StackValue::Float(0.0),
+    StackValue::ValueType(ref instance) => StackValue::ValueType(ValueTypeInstance {
+      type_: instance.type_.clone(),
+      fields: instance.fields.iter().map(zeroed).collect()
+    }),
+    _ => StackValue::Object(None)
+  }
+}
+
+/// Decodes the data of an array initializer, which stores elements of a primitive type in little-endian
+/// order. None if the element type isn't primitive or there isn't enough data.
+pub fn decode_elements(element_type: &TypeSig, data: &[u8], count: usize) -> Option<Vec<StackValue>> {
+  let mut reader = Cursor::new(data);
+  let mut elements = vec![];
+  for _ in 0 .. count {
+    let element = match *element_type {
+      TypeSig::Boolean | TypeSig::U1 => reader.read_u8().map(|value| StackValue::Int32(value as i32)),
+      TypeSig::I1 => reader.read_i8().map(|value| StackValue::Int32(value as i32)),
+      TypeSig::Char | TypeSig::U2 => reader.read_u16::<LittleEndian>().map(|value| StackValue::Int32(value as i32)),
+      TypeSig::I2 => reader.read_i16::<LittleEndian>().map(|value| StackValue::Int32(value as i32)),
+      TypeSig::I4 | TypeSig::U4 => reader.read_i32::<LittleEndian>().map(StackValue::Int32),
+      TypeSig::I8 | TypeSig::U8 => reader.read_i64::<LittleEndian>().map(StackValue::Int64),
+      TypeSig::R4 => reader.read_f32::<LittleEndian>().map(|value| StackValue::Float(value as f64)),
+      TypeSig::R8 => reader.read_f64::<LittleEndian>().map(StackValue::Float),
+      _ => return None
+    };
+    match element {
+      Ok(element) => elements.push(element),
+      Err(_) => return None
+    }
+  }
+  Some(elements)
+}
+
+/// Whether a value of type `from` can be stored in a location of type `to` as it is. Arrays of
+/// reference types are covariant, so a `string[]` can be stored as an `object[]`.
+pub fn is_assignable(meta: &Metadata, from: &TypeSig, to: &TypeSig) -> bool {
+  let (from, to) = (runtime_type(meta, from), runtime_type(meta, to));
+  if from == to || (to == TypeSig::Object && is_reference_type(&from)) {
+    return true;
+  }
+
+  match (&from, &to) {
+    (&TypeSig::SzArray(ref from_element), &TypeSig::SzArray(ref to_element)) => elements_assignable(meta, from_element, to_element),
+    (&TypeSig::Array(ref from_element, ref from_shape), &TypeSig::Array(ref to_element, ref to_shape)) =>
+      from_shape.rank == to_shape.rank && elements_assignable(meta, from_element, to_element),
+    (&TypeSig::SzArray(_), &TypeSig::Class(ref base)) | (&TypeSig::Array(_, _), &TypeSig::Class(ref base)) =>
+      ARRAY_BASE_TYPES.contains(&meta.get_type_name(base).as_str()),
+    (&TypeSig::String, &TypeSig::Class(ref base)) => meta.get_type_name(base) == "System.Object",
+    (&TypeSig::Class(TypeDefOrRef::TypeDef(class)), &TypeSig::Class(ref base)) => {
+      // Follow the base classes, which end with a class from outside the assembly
+      let mut current = class;
+      for _ in 0 .. 256 {
+        if TypeDefOrRef::TypeDef(current) == *base {
+          return true;
+        }
+        match meta.get_entry(&current).map(|entry| entry.extends) {
+          Some(TypeDefOrRef::TypeDef(parent)) if parent.0 != 0 => current = parent,
+          Some(extends @ TypeDefOrRef::TypeRef(_)) => return match *base {
+            TypeDefOrRef::TypeRef(_) => core_type_derives_from(&meta.get_type_name(&extends), &meta.get_type_name(base)),
+            _ => false
+          },
+          _ => return false
+        }
+      }
+      false
+    },
+    _ => false
+  }
+}
+
+// Elements of arrays are assignable if they're of the same type, or of reference types which are assignable
+fn elements_assignable(meta: &Metadata, from: &TypeSig, to: &TypeSig) -> bool {
+  runtime_type(meta, from) == runtime_type(meta, to) || (is_reference_type(from) && is_reference_type(to) && is_assignable(meta, from, to))
+}
@@ -0,0 +1,266 @@
+use std::io::{Error, ErrorKind, Result};
+use std::rc::Rc;
+
+use metadata::Metadata;
+use metadata::signature::TypeSig;
+use metadata::tables::*;
+use runtime::generics::GenericContext;
+use runtime::method_table::{MethodImpls, MethodTable};
+
+// CorFieldAttr
+const FD_STATIC: u16 = 0x0010;
+// CorMethodAttr
+const MD_STATIC: u16 = 0x0010;
+
+/// An instance field of a class.
+#[derive(Debug, Clone)]
+pub struct FieldInfo {
+  pub field: Index<FieldEntry>,
+  pub name: String,
+  pub type_: TypeSig
+}
+
+/// A class loaded by the interpreter, with the layout of its instances.
+#[derive(Debug)]
+pub struct Class {
+  /// The position of the class in the interpreter's class table, which object headers refer to
+  pub id: usize,
+  pub type_def: Index<TypeDefEntry>,
+  /// The type arguments of an instantiation of a generic type, which gets a class of its own
+  pub type_args: Vec<TypeSig>,
+  /// The reflection-style name, e.g. `Sample.Outer+Inner` or `Sample.List`1[System.Int32]`
+  pub name: String,
+  pub parent: Option<Rc<Class>>,
+  /// The full name of the base class if it's defined outside the assembly, e.g. `System.Exception`.
+  /// Classes deriving from another class of the assembly inherit it from their parent.
+  pub external_base: Option<String>,
+  /// The instance fields, starting with the ones inherited from the base classes. Their types are
+  /// the ones returned by `runtime_type`.
+  pub fields: Vec<FieldInfo>,
+  /// The virtual methods and interface implementations used by `callvirt`
+  pub methods: MethodTable
+}
+
+impl Class {
+  /// Lays out a class defined in the metadata, instantiated with the given type arguments if it's generic.
+  /// `parent` is the loaded base class, or None if the base class is System.Object or another type from
+  /// outside the assembly.
+  pub fn new(meta: &Metadata, impls: &MethodImpls, id: usize, type_def: &Index<TypeDefEntry>, type_args: Vec<TypeSig>, parent: Option<Rc<Class>>)
+    -> Result<Class> {
+    let mut fields = parent.as_ref().map(|parent| parent.fields.clone()).unwrap_or_default();
+    let context = GenericContext::new(type_args.clone(), vec![]);
+
+    for index in meta.get_field_range(type_def) {
+      let field = Index::<FieldEntry>::new(index);
+      let entry = meta.get_entry(&field).ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("No such field: {}", index)))?;
+      if entry.flags & FD_STATIC != 0 {
+        continue;
+      }
+
+      let name = meta.get_string(&entry.name).cloned().unwrap_or_default();
+      let type_ = runtime_type(meta, &context.substitute(&meta.get_field_signature(&field)?.type_));
+      fields.push(FieldInfo { field, name, type_ });
+    }
+
+    let external_base = match parent {
+      Some(ref parent) => parent.external_base.clone(),
+      None => external_base_name(meta, type_def)
+    };
+
+    let methods = MethodTable::new(meta, impls, type_def, parent.as_ref().map(|parent| &parent.methods))?;
+
+    let name = meta.full_type_name(&TypeDefOrRef::TypeDef(*type_def)).as_reflection();
+    let name = if type_args.is_empty() { name } else { format!("{}[{}]", name, reflection_names(meta, &type_args)) };
+    Ok(Class { id, type_def: *type_def, type_args, name, parent, external_base, fields, methods })
+  }
+
+  /// The position of an instance field in the objects of this class.
+  pub fn field_slot(&self, field: &Index<FieldEntry>) -> Option<usize> {
+    self.fields.iter().position(|info| info.field == *field)
+  }
+
+  /// Whether the class is the given type or derives from it.
+  pub fn is_subclass_of(&self, type_def: &Index<TypeDefEntry>) -> bool {
+    let mut class = Some(self);
+    while let Some(current) = class {
+      if current.type_def == *type_def {
+        return true;
+      }
+      class = current.parent.as_ref().map(|parent| &**parent);
+    }
+    false
+  }
+
+  /// Whether the class implements an interface, itself or through its base classes. Interfaces from
+  /// other assemblies are compared by name.
+  pub fn implements(&self, meta: &Metadata, interface: &TypeDefOrRef) -> bool {
+    self.methods.interfaces.iter().any(|other| match (*other, *interface) {
+      (TypeDefOrRef::TypeDef(other), TypeDefOrRef::TypeDef(interface)) => other == interface,
+      (TypeDefOrRef::TypeRef(_), TypeDefOrRef::TypeRef(_)) => meta.get_type_name(other) == meta.get_type_name(interface),
+      _ => false
+    })
+  }
+
+  /// Whether the class is the given instantiation of a generic type, derives from it or implements it.
+  pub fn is_subclass_of_instance(&self, type_def: &Index<TypeDefEntry>, type_args: &[TypeSig]) -> bool {
+    let mut class = Some(self);
+    while let Some(current) = class {
+      if current.type_def == *type_def && current.type_args == type_args {
+        return true;
+      }
+      class = current.parent.as_ref().map(|parent| &**parent);
+    }
+
+    // The method table gives the arguments of generic interfaces in terms of the class's type parameters
+    let context = GenericContext::new(self.type_args.clone(), vec![]);
+    self.methods.interfaces.contains(&TypeDefOrRef::TypeDef(*type_def)) &&
+      context.substitute_all(self.methods.type_args_of(type_def)) == type_args
+  }
+}
+
+/// Whether a field is static.
+pub fn is_static_field(meta: &Metadata, field: &Index<FieldEntry>) -> bool {
+  meta.get_entry(field).map(|entry| entry.flags & FD_STATIC != 0).unwrap_or(false)
+}
+
+/// The type initializer of a type, its static `.cctor` method, if it has one.
+pub fn type_initializer(meta: &Metadata, type_def: &Index<TypeDefEntry>) -> Option<Index<MethodDefEntry>> {
+  meta.get_method_range(type_def).map(|method| Index::<MethodDefEntry>::new(method)).find(|method| {
+    meta.get_entry(method)
+      .map(|entry| entry.flags & MD_STATIC != 0 && meta.get_string(&entry.name).map(|name| name == ".cctor").unwrap_or(false))
+      .unwrap_or(false)
+  })
+}
+
+/// Whether a method is static.
+pub fn is_static_method(meta: &Metadata, method: &Index<MethodDefEntry>) -> bool {
+  meta.get_entry(method).map(|entry| entry.flags & MD_STATIC != 0).unwrap_or(false)
+}
+
+// The name of the base type of a type definition, if it's defined outside the assembly
+fn external_base_name(meta: &Metadata, type_def: &Index<TypeDefEntry>) -> Option<String> {
+  meta.get_entry(type_def).and_then(|entry| match entry.extends {
+    TypeDefOrRef::TypeDef(_) => None,
+    ref extends => Some(meta.get_type_name(extends))
+  })
+}
+
+/// Whether a type defined in the metadata is a value type, i.e. a struct or an enum.
+pub fn is_value_type(meta: &Metadata, type_def: &Index<TypeDefEntry>) -> bool {
+  match external_base_name(meta, type_def) {
+    Some(ref name) => name == "System.ValueType" || name == "System.Enum",
+    None => false
+  }
+}
+
+/// The underlying type of an enum, which is the type of its only instance field, `value__`.
+/// None if the type isn't an enum.
+pub fn enum_underlying_type(meta: &Metadata, type_def: &Index<TypeDefEntry>) -> Option<TypeSig> {
+  if external_base_name(meta, type_def).as_ref().map(|name| name.as_str()) != Some("System.Enum") {
+    return None;
+  }
+
+  meta.get_field_range(type_def)
+    .map(|index| Index::<FieldEntry>::new(index))
+    .find(|field| !is_static_field(meta, field))
+    .and_then(|field| meta.get_field_signature(&field).ok())
+    .map(|signature| signature.type_)
+}
+
+/// The primitive type of a core library type referenced by name, e.g. I4 for System.Int32.
+pub fn primitive_type(name: &str) -> Option<TypeSig> {
+  Some(match name {
+    "System.Boolean" => TypeSig::Boolean,
+    "System.Char" => TypeSig::Char,
+    "System.SByte" => TypeSig::I1,
+    "System.Byte" => TypeSig::U1,
+    "System.Int16" => TypeSig::I2,
+    "System.UInt16" => TypeSig::U2,
+    "System.Int32" => TypeSig::I4,
+    "System.UInt32" => TypeSig::U4,
+    "System.Int64" => TypeSig::I8,
+    "System.UInt64" => TypeSig::U8,
+    "System.Single" => TypeSig::R4,
+    "System.Double" => TypeSig::R8,
+    "System.IntPtr" => TypeSig::I,
+    "System.UIntPtr" => TypeSig::U,
+    _ => return None
+  })
+}
+
+/// The reflection name of a primitive type, the inverse of `primitive_type`.
+pub fn primitive_type_name(type_: &TypeSig) -> Option<&'static str> {
+  Some(match *type_ {
+    TypeSig::Boolean => "System.Boolean",
+    TypeSig::Char => "System.Char",
+    TypeSig::I1 => "System.SByte",
+    TypeSig::U1 => "System.Byte",
+    TypeSig::I2 => "System.Int16",
+    TypeSig::U2 => "System.UInt16",
+    TypeSig::I4 => "System.Int32",
+    TypeSig::U4 => "System.UInt32",
+    TypeSig::I8 => "System.Int64",
+    TypeSig::U8 => "System.UInt64",
+    TypeSig::R4 => "System.Single",
+    TypeSig::R8 => "System.Double",
+    TypeSig::I => "System.IntPtr",
+    TypeSig::U => "System.UIntPtr",
+    _ => return None
+  })
+}
+
+/// The reflection name of a type, like `System.Int32[]` or `System.String[,]`.
+pub fn reflection_name(meta: &Metadata, type_: &TypeSig) -> String {
+  if let Some(name) = primitive_type_name(type_) {
+    return name.to_string();
+  }
+  match *type_ {
+    TypeSig::String => "System.String".to_string(),
+    TypeSig::Object => "System.Object".to_string(),
+    TypeSig::Class(ref type_) | TypeSig::ValueType(ref type_) => meta.full_type_name(type_).as_reflection(),
+    TypeSig::SzArray(ref element) => format!("{}[]", reflection_name(meta, element)),
+    // Arrays of rank 1 which aren't zero-based vectors are written with a star
+    TypeSig::Array(ref element, ref shape) if shape.rank == 1 => format!("{}[*]", reflection_name(meta, element)),
+    TypeSig::Array(ref element, ref shape) => format!("{}[{}]", reflection_name(meta, element), ",".repeat(shape.rank as usize - 1)),
+    TypeSig::GenericInst(ref generic_type, ref args) => format!("{}[{}]", reflection_name(meta, generic_type), reflection_names(meta, args)),
+    _ => type_.as_csharp(meta)
+  }
+}
+
+// The type arguments of an instantiation, as they appear in its reflection name
+fn reflection_names(meta: &Metadata, types: &[TypeSig]) -> String {
+  types.iter().map(|type_| reflection_name(meta, type_)).collect::<Vec<_>>().join(",")
+}
+
+/// The type the interpreter uses for values of the given type: enums are replaced by their underlying
+/// type, references to primitive types like System.Int32 by the primitive type, and custom modifiers are dropped.
+/// Enums defined in other assemblies are left alone, as their underlying type isn't known.
+pub fn runtime_type(meta: &Metadata, type_: &TypeSig) -> TypeSig {
+  match *type_ {
+    TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)) => enum_underlying_type(meta, &type_def).unwrap_or_else(|| type_.clone()),
+    TypeSig::ValueType(ref type_ref) => primitive_type(&meta.get_type_name(type_ref)).unwrap_or_else(|| type_.clone()),
+    TypeSig::CModReqd(_, ref inner) | TypeSig::CModOpt(_, ref inner) | TypeSig::Pinned(ref inner) => runtime_type(meta, inner),
+    _ => type_.clone()
+  }
+}
+
+/// The type argument of an instantiation of `System.Nullable<T>`, or None for other types.
+pub fn nullable_type(meta: &Metadata, type_: &TypeSig) -> Option<TypeSig> {
+  match *type_ {
+    TypeSig::GenericInst(ref generic_type, ref args) if args.len() == 1 => match **generic_type {
+      TypeSig::ValueType(ref generic_type) if meta.full_type_name(generic_type).as_reflection() == "System.Nullable`1" =>
+        Some(args[0].clone()),
+      _ => None
+    },
+    _ => None
+  }
+}
+
+/// Whether values of the given type are object references, which box and unbox.any leave alone.
+pub fn is_reference_type(type_: &TypeSig) -> bool {
+  match *type_ {
+    TypeSig::String | TypeSig::Object | TypeSig::Class(_) | TypeSig::SzArray(_) | TypeSig::Array(_, _) => true,
+    TypeSig::GenericInst(ref generic_type, _) => is_reference_type(generic_type),
+    _ => false
+  }
+}
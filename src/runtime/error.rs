@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::fmt;
+
+/// A managed exception which wasn't caught.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagedException {
+  /// The full name of the exception type, e.g. `System.OverflowException`.
+  pub type_name: String,
+  pub message: String,
+  /// Where the exception was thrown, starting with the innermost frame. Empty until the exception is thrown.
+  pub stack_trace: Vec<StackFrame>
+}
+
+/// A frame of a managed stack trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+  /// The full name of the method, e.g. `Sample.Program.Main`
+  pub method: String,
+  /// The offset of the instruction that was running
+  pub offset: u32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionError {
+  /// The IL is invalid, or uses something the interpreter doesn't support.
+  InvalidProgram { method: String, offset: u32, reason: String },
+  UnhandledException(ManagedException)
+}
+
+impl ExecutionError {
+  /// An unhandled exception of the given type, e.g. `System.NullReferenceException`.
+  pub fn exception(type_name: &str, message: &str) -> ExecutionError {
+    ExecutionError::UnhandledException(ManagedException {
+      type_name: type_name.to_string(),
+      message: message.to_string(),
+      stack_trace: vec![]
+    })
+  }
+
+  pub fn null_reference() -> ExecutionError {
+    ExecutionError::exception("System.NullReferenceException", "Object reference not set to an instance of an object.")
+  }
+}
+
+impl fmt::Display for StackFrame {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "at {}, IL_{:04x}", self.method, self.offset)
+  }
+}
+
+impl fmt::Display for ExecutionError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      ExecutionError::InvalidProgram { ref method, offset, ref reason } =>
+        write!(f, "Invalid program: {}, IL_{:04x}: {}", method, offset, reason),
+      ExecutionError::UnhandledException(ref exception) => {
+        write!(f, "Unhandled exception. {}: {}", exception.type_name, exception.message)?;
+        for frame in &exception.stack_trace {
+          write!(f, "\n   {}", frame)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl Error for ExecutionError {
+  fn description(&self) -> &str {
+    match *self {
+      ExecutionError::InvalidProgram { ref reason, .. } => reason,
+      ExecutionError::UnhandledException(ref exception) => &exception.message
+    }
+  }
+}
@@ -0,0 +1,66 @@
+use runtime::error::StackFrame;
+use runtime::value::ObjectRef;
+
+/// What System.Exception keeps for an exception object, which is either a core library exception
+/// or an instance of a class deriving from one.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ExceptionState {
+  /// None for exceptions constructed without a message, which get a default one
+  pub message: Option<String>,
+  pub inner: Option<ObjectRef>,
+  /// Where the exception was last thrown, starting with the innermost frame. Rethrowing keeps it.
+  pub stack_trace: Vec<StackFrame>
+}
+
+/// The base class of a core library exception type, e.g. System.ArithmeticException for
+/// System.OverflowException. None if the type isn't a known exception type.
+pub fn core_exception_base(type_name: &str) -> Option<&'static str> {
+  Some(match type_name {
+    "System.Exception" => "System.Object",
+    "System.SystemException" | "System.ApplicationException" => "System.Exception",
+    "System.ArithmeticException" | "System.ArgumentException" | "System.ArrayTypeMismatchException" |
+    "System.FormatException" | "System.IndexOutOfRangeException" | "System.InvalidCastException" |
+    "System.InvalidOperationException" | "System.MemberAccessException" | "System.NotImplementedException" |
+    "System.NotSupportedException" | "System.NullReferenceException" | "System.OutOfMemoryException" |
+    "System.RankException" | "System.StackOverflowException" | "System.TypeInitializationException" |
+    "System.TypeLoadException" | "System.IO.IOException" | "System.Collections.Generic.KeyNotFoundException" =>
+      "System.SystemException",
+    "System.DivideByZeroException" | "System.OverflowException" => "System.ArithmeticException",
+    "System.ArgumentNullException" | "System.ArgumentOutOfRangeException" => "System.ArgumentException",
+    "System.ObjectDisposedException" => "System.InvalidOperationException",
+    "System.MissingMemberException" => "System.MemberAccessException",
+    "System.MissingMethodException" | "System.MissingFieldException" => "System.MissingMemberException",
+    _ => return None
+  })
+}
+
+/// Whether a core library type is the given type or derives from it. Types other than the known
+/// exception types only match themselves.
+pub fn core_type_derives_from(type_name: &str, base: &str) -> bool {
+  let mut current = type_name;
+  loop {
+    if current == base {
+      return true;
+    }
+    match core_exception_base(current) {
+      Some(parent) => current = parent,
+      None => return false
+    }
+  }
+}
+
+/// The message of exceptions constructed without one.
+pub fn default_message(type_name: &str) -> String {
+  match type_name {
+    "System.ArgumentException" => "Value does not fall within the expected range.".to_string(),
+    "System.ArgumentNullException" => "Value cannot be null.".to_string(),
+    "System.ArgumentOutOfRangeException" => "Specified argument was out of the range of valid values.".to_string(),
+    _ => format!("Exception of type '{}' was thrown.", type_name)
+  }
+}
+
+/// The message of an argument exception constructed with the name of the parameter, which follows the
+/// message, or the default one of the type.
+pub fn argument_message(type_name: &str, message: Option<String>, param_name: &str) -> String {
+  format!("{}\nParameter name: {}", message.unwrap_or_else(|| default_message(type_name)), param_name)
+}
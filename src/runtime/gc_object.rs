@@ -1,9 +1,12 @@
-
-struct GcObject<T: Sized> {
-  marked: bool,
-  value: T
+#[derive(Debug)]
+pub struct GcObject<T: Sized> {
+  pub marked: bool,
+  pub value: T
 }
 
-struct BaseObject {
-  vtable: usize
+/// The header of an instance of a class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaseObject {
+  /// The id of the object's class in the interpreter's class table
+  pub vtable: usize
 }
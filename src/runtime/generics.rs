@@ -0,0 +1,140 @@
+use metadata::Metadata;
+use metadata::signature::{TypeSig, MethodSignature};
+use metadata::tables::*;
+use runtime::class::{is_reference_type, is_value_type, runtime_type};
+
+/// The type arguments a generic type or method is instantiated with, which replace the type
+/// variables `!n` and `!!n` in its signatures and instructions.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GenericContext {
+  /// The arguments of the generic type declaring the method, for `!n`
+  pub type_args: Vec<TypeSig>,
+  /// The arguments of a generic method, for `!!n`
+  pub method_args: Vec<TypeSig>
+}
+
+impl GenericContext {
+  pub fn new(type_args: Vec<TypeSig>, method_args: Vec<TypeSig>) -> GenericContext {
+    GenericContext { type_args, method_args }
+  }
+
+  /// Replaces the type variables in a type with the type arguments. Variables without an argument are left alone.
+  pub fn substitute(&self, type_: &TypeSig) -> TypeSig {
+    let substitute = |inner: &TypeSig| Box::new(self.substitute(inner));
+    match *type_ {
+      TypeSig::Var(number) => self.type_args.get(number as usize).cloned().unwrap_or_else(|| type_.clone()),
+      TypeSig::MVar(number) => self.method_args.get(number as usize).cloned().unwrap_or_else(|| type_.clone()),
+      TypeSig::Ptr(ref inner) => TypeSig::Ptr(substitute(inner)),
+      TypeSig::ByRef(ref inner) => TypeSig::ByRef(substitute(inner)),
+      TypeSig::SzArray(ref element) => TypeSig::SzArray(substitute(element)),
+      TypeSig::Array(ref element, ref shape) => TypeSig::Array(substitute(element), shape.clone()),
+      TypeSig::GenericInst(ref generic_type, ref args) => TypeSig::GenericInst(generic_type.clone(), self.substitute_all(args)),
+      TypeSig::CModReqd(ref modifier, ref inner) => TypeSig::CModReqd(*modifier, substitute(inner)),
+      TypeSig::CModOpt(ref modifier, ref inner) => TypeSig::CModOpt(*modifier, substitute(inner)),
+      TypeSig::Pinned(ref inner) => TypeSig::Pinned(substitute(inner)),
+      _ => type_.clone()
+    }
+  }
+
+  pub fn substitute_all(&self, types: &[TypeSig]) -> Vec<TypeSig> {
+    types.iter().map(|type_| self.substitute(type_)).collect()
+  }
+
+  /// The signature of an instantiation of a method.
+  pub fn substitute_signature(&self, signature: &MethodSignature) -> MethodSignature {
+    MethodSignature {
+      return_type: self.substitute(&signature.return_type),
+      params: self.substitute_all(&signature.params),
+      ..signature.clone()
+    }
+  }
+
+  /// The instantiation whose code runs for this one. As in the CLR, instantiations over reference types
+  /// share their code, which only needs to know that the arguments are object references: they're
+  /// replaced by System.Object. Instructions naming a type variable still see the actual argument.
+  pub fn canonical(&self, meta: &Metadata) -> GenericContext {
+    let canonical = |args: &[TypeSig]| args.iter()
+      .map(|arg| if is_reference_type(arg) { TypeSig::Object } else { runtime_type(meta, arg) })
+      .collect();
+    GenericContext { type_args: canonical(&self.type_args), method_args: canonical(&self.method_args) }
+  }
+}
+
+/// The generic type definition of the assembly a type instantiates, with the type arguments, e.g.
+/// `Sample.List`1` and `int32` for `Sample.List`1<int32>`. None for other types.
+pub fn generic_instance(type_: &TypeSig) -> Option<(Index<TypeDefEntry>, &[TypeSig])> {
+  match *type_ {
+    TypeSig::GenericInst(ref generic_type, ref args) => match **generic_type {
+      TypeSig::Class(TypeDefOrRef::TypeDef(type_def)) | TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)) => Some((type_def, args)),
+      _ => None
+    },
+    _ => None
+  }
+}
+
+/// Like `generic_instance`, for a type spec used as the base class or an interface of a type.
+pub fn generic_type_spec(meta: &Metadata, type_: &TypeDefOrRef) -> Option<(Index<TypeDefEntry>, Vec<TypeSig>)> {
+  let type_ = match *type_ {
+    TypeDefOrRef::TypeSpec(ref type_spec) => match meta.get_type_spec_signature(type_spec) {
+      Ok(type_) => type_,
+      Err(_) => return None
+    },
+    _ => return None
+  };
+  generic_instance(&type_).map(|(type_def, args)| (type_def, args.to_vec()))
+}
+
+/// The type of the instances of a type of the assembly, which is an instantiation if it's given type arguments.
+pub fn instance_type(meta: &Metadata, type_def: &Index<TypeDefEntry>, type_args: &[TypeSig]) -> TypeSig {
+  let type_ = if is_value_type(meta, type_def) {
+    TypeSig::ValueType(TypeDefOrRef::TypeDef(*type_def))
+  } else {
+    TypeSig::Class(TypeDefOrRef::TypeDef(*type_def))
+  };
+
+  if type_args.is_empty() { type_ } else { TypeSig::GenericInst(Box::new(type_), type_args.to_vec()) }
+}
+
+/// The generic type of the assembly declaring a referenced member, with the type arguments the reference
+/// gives it, e.g. for `Sample.List`1<int32>::Add`. The arguments may refer to the caller's type variables.
+pub fn member_instance(meta: &Metadata, member: &Index<MemberRefEntry>) -> Option<(Index<TypeDefEntry>, Vec<TypeSig>)> {
+  match meta.get_entry(member).map(|entry| entry.class) {
+    Some(MemberRefParent::TypeSpec(type_spec)) => generic_type_spec(meta, &TypeDefOrRef::TypeSpec(type_spec)),
+    _ => None
+  }
+}
+
+/// The definition of a method referenced through an instantiation of a generic type of the assembly, with the
+/// type arguments of the instantiation. The reference's signature refers to the type's parameters as `!n`,
+/// like the definition's, so the method is found by its name and signature.
+pub fn instantiated_method(meta: &Metadata, member: &Index<MemberRefEntry>) -> Option<(Index<MethodDefEntry>, Vec<TypeSig>)> {
+  let (type_def, type_args) = match member_instance(meta, member) {
+    Some(instance) => instance,
+    None => return None
+  };
+  let name = meta.get_entry(member).and_then(|entry| meta.get_string(&entry.name));
+  let signature = meta.get_member_ref_signature(member).ok();
+
+  meta.get_method_range(&type_def)
+    .map(|index| Index::<MethodDefEntry>::new(index))
+    .find(|method| {
+      name.is_some() && meta.get_entry(method).and_then(|entry| meta.get_string(&entry.name)) == name &&
+        signature.is_some() && meta.get_method_def_signature(method).ok() == signature
+    })
+    .map(|method| (method, type_args))
+}
+
+/// The definition of a field referenced through an instantiation of a generic type of the assembly, with the
+/// type arguments of the instantiation.
+pub fn instantiated_field(meta: &Metadata, member: &Index<MemberRefEntry>) -> Option<(Index<FieldEntry>, Index<TypeDefEntry>, Vec<TypeSig>)> {
+  let (type_def, type_args) = match member_instance(meta, member) {
+    Some(instance) => instance,
+    None => return None
+  };
+  let name = meta.get_entry(member).and_then(|entry| meta.get_string(&entry.name));
+
+  meta.get_field_range(&type_def)
+    .map(|index| Index::<FieldEntry>::new(index))
+    .find(|field| name.is_some() && meta.get_entry(field).and_then(|entry| meta.get_string(&entry.name)) == name)
+    .map(|field| (field, type_def, type_args))
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use metadata::signature::TypeSig;
+use runtime::exceptions::ExceptionState;
+use runtime::gc_object::{GcObject, BaseObject};
+use runtime::value::{ObjectRef, StackValue};
+
+/// The contents of an object on the managed heap.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ManagedObject {
+  /// A System.String, as UTF-16 code units
+  String(Vec<u16>),
+  /// An instance of a class, with a value for each field of the class layout
+  Instance { header: BaseObject, fields: Vec<StackValue> },
+  /// A boxed value type, with the type named by the box instruction, e.g. an enum rather than its underlying type
+  Boxed { type_: TypeSig, value: StackValue },
+  /// An instance of a core library exception class, e.g. System.NullReferenceException, given by its full name
+  Exception { type_name: String },
+  /// An array of the given type, e.g. `int32[]` or `int32[0...,0...]`, with the length and lower bound of
+  /// each dimension and the elements in row-major order
+  Array { type_: TypeSig, lengths: Vec<usize>, lower_bounds: Vec<i32>, elements: Vec<StackValue> },
+  /// The System.Type object of a type, as returned by typeof, with the reflection name of the type
+  Type { type_: TypeSig, name: String }
+}
+
+/// The managed heap. Objects aren't collected yet, and an `ObjectRef` is an index into `objects`.
+#[derive(Debug)]
+pub struct Heap {
+  objects: Vec<GcObject<ManagedObject>>,
+  // The intern pool, which makes equal string literals the same object
+  interned: HashMap<Vec<u16>, ObjectRef>,
+  // The state of exception objects, including instances of classes deriving from System.Exception, by object
+  exceptions: HashMap<usize, ExceptionState>,
+  // The type objects, by the reflection name of their type
+  types: HashMap<String, ObjectRef>
+}
+
+impl Heap {
+  pub fn new() -> Heap {
+    Heap { objects: vec![], interned: HashMap::new(), exceptions: HashMap::new(), types: HashMap::new() }
+  }
+
+  pub fn alloc(&mut self, value: ManagedObject) -> ObjectRef {
+    self.objects.push(GcObject { marked: false, value });
+    ObjectRef(self.objects.len() - 1)
+  }
+
+  pub fn get(&self, object: ObjectRef) -> Option<&ManagedObject> {
+    self.objects.get(object.0).map(|object| &object.value)
+  }
+
+  pub fn get_mut(&mut self, object: ObjectRef) -> Option<&mut ManagedObject> {
+    self.objects.get_mut(object.0).map(|object| &mut object.value)
+  }
+
+  pub fn alloc_string(&mut self, value: &str) -> ObjectRef {
+    self.alloc(ManagedObject::String(value.encode_utf16().collect()))
+  }
+
+  /// Allocates a single-dimensional array with a lower bound of zero, like the ones created by newarr.
+  pub fn alloc_array(&mut self, element_type: TypeSig, elements: Vec<StackValue>) -> ObjectRef {
+    let lengths = vec![elements.len()];
+    self.alloc(ManagedObject::Array { type_: TypeSig::SzArray(Box::new(element_type)), lengths, lower_bounds: vec![0], elements })
+  }
+
+  /// Returns the interned string with the given value, allocating it the first time.
+  pub fn intern(&mut self, value: Vec<u16>) -> ObjectRef {
+    if let Some(&object) = self.interned.get(&value) {
+      return object;
+    }
+
+    let object = self.alloc(ManagedObject::String(value.clone()));
+    self.interned.insert(value, object);
+    object
+  }
+
+  /// Returns the interned string equal to the given one, if there is one.
+  pub fn is_interned(&self, value: &[u16]) -> Option<ObjectRef> {
+    self.interned.get(value).cloned()
+  }
+
+  /// Returns the type object of a type, allocating it the first time, so that types have a single
+  /// type object and compare by reference.
+  pub fn type_object(&mut self, type_: TypeSig, name: String) -> ObjectRef {
+    if let Some(&object) = self.types.get(&name) {
+      return object;
+    }
+
+    let object = self.alloc(ManagedObject::Type { type_, name: name.clone() });
+    self.types.insert(name, object);
+    object
+  }
+
+  /// Returns the UTF-16 code units of a System.String. None if the object isn't a string.
+  pub fn get_utf16(&self, object: ObjectRef) -> Option<&[u16]> {
+    match self.get(object) {
+      Some(&ManagedObject::String(ref value)) => Some(value),
+      _ => None
+    }
+  }
+
+  /// Returns the value of a System.String, replacing unpaired surrogates. None if the object isn't a string.
+  pub fn get_string(&self, object: ObjectRef) -> Option<String> {
+    self.get_utf16(object).map(String::from_utf16_lossy)
+  }
+
+  /// The message, inner exception and stack trace of an exception. None if the object was never
+  /// constructed or thrown as an exception.
+  pub fn exception_state(&self, object: ObjectRef) -> Option<&ExceptionState> {
+    self.exceptions.get(&object.0)
+  }
+
+  pub fn exception_state_mut(&mut self, object: ObjectRef) -> &mut ExceptionState {
+    self.exceptions.entry(object.0).or_insert_with(ExceptionState::default)
+  }
+}
@@ -0,0 +1,245 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use metadata::Metadata;
+use metadata::signature::MethodSignature;
+use metadata::tables::*;
+use runtime::error::ExecutionError;
+use runtime::heap::{Heap, ManagedObject};
+use runtime::strings;
+use runtime::value::{ObjectRef, StackValue};
+
+/// A method implemented in Rust. It gets the arguments of the call, including `this`, and returns
+/// the return value, or None for void methods.
+pub type InternalCall = Box<Fn(&mut Heap, &[StackValue]) -> Result<Option<StackValue>, ExecutionError>>;
+
+/// Identifies a method by its assembly, type, name and signature, e.g.
+/// `[System.Console]System.Console::WriteLine void(string)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodKey {
+  pub assembly: String,
+  pub namespace: String,
+  pub type_name: String,
+  pub name: String,
+  /// The return and parameter types in ILAsm syntax, e.g. `void(string)` or `instance int32(int32, object)`
+  pub signature: String
+}
+
+impl MethodKey {
+  pub fn new(assembly: &str, namespace: &str, type_name: &str, name: &str, signature: &str) -> MethodKey {
+    MethodKey {
+      assembly: assembly.to_string(),
+      namespace: namespace.to_string(),
+      type_name: type_name.to_string(),
+      name: name.to_string(),
+      signature: signature.to_string()
+    }
+  }
+
+  fn from_parts(meta: &Metadata, assembly: &str, namespace: &Index<StringHeap>, type_name: &Index<StringHeap>,
+    name: &Index<StringHeap>, signature: &MethodSignature) -> MethodKey {
+    let string = |index| meta.get_string(index).map(|value| value.as_str()).unwrap_or("");
+    MethodKey::new(assembly, string(namespace), string(type_name), string(name), &signature_key(meta, signature))
+  }
+
+  /// The key of a method referenced from another assembly, or None if it isn't a method of a type reference.
+  pub fn from_member_ref(meta: &Metadata, member: &Index<MemberRefEntry>) -> Option<MethodKey> {
+    let (entry, type_ref) = match meta.get_entry(member) {
+      Some(entry) => match entry.class {
+        MemberRefParent::TypeRef(type_ref) => (entry, type_ref),
+        _ => return None
+      },
+      None => return None
+    };
+    let (type_entry, signature) = match (meta.get_entry(&type_ref), meta.get_member_ref_signature(member)) {
+      (Some(type_entry), Ok(signature)) => (type_entry, signature),
+      _ => return None
+    };
+
+    // Nested type references are scoped by their enclosing type
+    let mut scope = Some(type_entry.resolution_scope);
+    for _ in 0 .. 64 {
+      match scope {
+        Some(ResolutionScope::TypeRef(enclosing)) => scope = meta.get_entry(&enclosing).map(|entry| entry.resolution_scope),
+        _ => break
+      }
+    }
+    let assembly = match scope {
+      Some(ResolutionScope::AssemblyRef(assembly_ref)) => meta.get_entry(&assembly_ref).and_then(|entry| meta.get_string(&entry.name)),
+      _ => None
+    };
+
+    assembly.map(|assembly| MethodKey::from_parts(meta, assembly, &type_entry.namespace, &type_entry.name, &entry.name, &signature))
+  }
+
+  /// The key of a method defined in the assembly being run.
+  pub fn from_method_def(meta: &Metadata, method: &Index<MethodDefEntry>) -> Option<MethodKey> {
+    let owner = meta.get_method_owner(method).and_then(|owner| meta.get_entry(&owner));
+    let (entry, owner, signature) = match (meta.get_entry(method), owner, meta.get_method_def_signature(method)) {
+      (Some(entry), Some(owner), Ok(signature)) => (entry, owner, signature),
+      _ => return None
+    };
+    let assembly = meta.get_table::<AssemblyEntry>()
+      .and_then(|assemblies| assemblies.first())
+      .and_then(|assembly| meta.get_string(&assembly.name))
+      .map(|name| name.as_str())
+      .unwrap_or("");
+
+    Some(MethodKey::from_parts(meta, assembly, &owner.namespace, &owner.name, &entry.name, &signature))
+  }
+}
+
+/// The signature part of a method key, e.g. `instance int32(int32, object)`.
+pub fn signature_key(meta: &Metadata, signature: &MethodSignature) -> String {
+  let params = signature.params.iter().map(|param| param.as_il(meta)).collect::<Vec<_>>();
+  let instance = if signature.has_this() { "instance " } else { "" };
+  format!("{}{}({})", instance, signature.return_type.as_il(meta), params.join(", "))
+}
+
+impl fmt::Display for MethodKey {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.namespace.is_empty() {
+      write!(f, "[{}]{}::{} {}", self.assembly, self.type_name, self.name, self.signature)
+    } else {
+      write!(f, "[{}]{}.{}::{} {}", self.assembly, self.namespace, self.type_name, self.name, self.signature)
+    }
+  }
+}
+
+/// The assemblies defining the core types, on .NET Framework, in .NET Core reference assemblies and in the .NET Core implementation.
+pub const CORLIB_ASSEMBLIES: &'static [&'static str] = &["mscorlib", "System.Runtime", "System.Private.CoreLib"];
+
+/// Console lives in mscorlib on .NET Framework and in its own assembly on .NET Core.
+pub const CONSOLE_ASSEMBLIES: &'static [&'static str] = &["mscorlib", "System.Console"];
+
+/// The registry of internal calls, which binds methods without IL to Rust implementations.
+pub struct InternalCalls {
+  calls: HashMap<MethodKey, InternalCall>
+}
+
+impl InternalCalls {
+  /// An empty registry.
+  pub fn new() -> InternalCalls {
+    InternalCalls { calls: HashMap::new() }
+  }
+
+  /// The bindings for the base class library, with console output going to `output`.
+  pub fn corlib<W: Write + 'static>(output: W) -> InternalCalls {
+    let mut calls = InternalCalls::new();
+    let output = Rc::new(RefCell::new(output));
+
+    for &(name, newline) in &[("Write", false), ("WriteLine", true)] {
+      for &param in &["string", "object", "int32", "int64", "char", "bool"] {
+        let output = output.clone();
+        calls.register_in(CONSOLE_ASSEMBLIES, "System", "Console", name, &format!("void({})", param), move |heap, args| {
+          let text = match (args.get(0), param) {
+            (Some(&StackValue::Object(Some(object))), "string") => heap.get_string(object).unwrap_or_default(),
+            (Some(&StackValue::Object(None)), "string") => String::new(),
+            (Some(value), "object") => match strings::to_display_string(heap, value) {
+              Some(text) => text,
+              None => return Err(invalid_argument(&format!("Console.{}", name)))
+            },
+            (Some(&StackValue::Int32(value)), "char") => String::from_utf16_lossy(&[value as u16]),
+            (Some(&StackValue::Int32(value)), "bool") => if value != 0 { "True" } else { "False" }.to_string(),
+            (Some(&StackValue::Int32(value)), _) => value.to_string(),
+            (Some(&StackValue::Int64(value)), _) => value.to_string(),
+            _ => return Err(invalid_argument(&format!("Console.{}", name)))
+          };
+          write_console(&output, &text, newline)
+        });
+      }
+    }
+
+    calls.register_in(CONSOLE_ASSEMBLIES, "System", "Console", "WriteLine", "void()",
+      move |_, _| write_console(&output, "", true));
+
+    // Constructors of classes deriving from System.Object call its constructor, which does nothing
+    calls.register_in(CORLIB_ASSEMBLIES, "System", "Object", ".ctor", "instance void()", |_, _| Ok(None));
+    // Objects which don't override Equals and GetHashCode, like strings and boxed primitives, which generic
+    // collections compare through constrained calls
+    calls.register_in(CORLIB_ASSEMBLIES, "System", "Object", "Equals", "instance bool(object)", |heap, args| {
+      match (args.get(0), args.get(1)) {
+        (Some(&StackValue::Object(Some(this))), Some(&StackValue::Object(other))) =>
+          Ok(Some(StackValue::Int32(object_equals(heap, this, other) as i32))),
+        _ => Err(invalid_argument("Object.Equals"))
+      }
+    });
+    calls.register_in(CORLIB_ASSEMBLIES, "System", "Object", "GetHashCode", "instance int32()", |heap, args| {
+      match args.get(0) {
+        Some(&StackValue::Object(Some(this))) => Ok(Some(StackValue::Int32(hash_code(heap, this)))),
+        _ => Err(invalid_argument("Object.GetHashCode"))
+      }
+    });
+
+    strings::register(&mut calls);
+    calls
+  }
+
+  /// Binds a method to a Rust implementation, replacing any previous binding.
+  pub fn register<F>(&mut self, key: MethodKey, call: F)
+    where F: Fn(&mut Heap, &[StackValue]) -> Result<Option<StackValue>, ExecutionError> + 'static {
+    self.calls.insert(key, Box::new(call));
+  }
+
+  /// Binds a method to the same implementation in each of the given assemblies. This is for types
+  /// which live in different assemblies depending on the framework.
+  pub fn register_in<F>(&mut self, assemblies: &[&str], namespace: &str, type_name: &str, name: &str, signature: &str, call: F)
+    where F: Fn(&mut Heap, &[StackValue]) -> Result<Option<StackValue>, ExecutionError> + 'static {
+    let call = Rc::new(call);
+    for assembly in assemblies {
+      let call = call.clone();
+      self.register(MethodKey::new(assembly, namespace, type_name, name, signature), move |heap, args| call(heap, args));
+    }
+  }
+
+  pub fn get(&self, key: &MethodKey) -> Option<&InternalCall> {
+    self.calls.get(key)
+  }
+}
+
+pub fn invalid_argument(method: &str) -> ExecutionError {
+  ExecutionError::exception("System.ArgumentException", &format!("Invalid argument for {}", method))
+}
+
+// Strings and boxed values are equal to the ones with the same contents, other objects only to themselves
+fn object_equals(heap: &Heap, this: ObjectRef, other: Option<ObjectRef>) -> bool {
+  let other = match other {
+    Some(other) => other,
+    None => return false
+  };
+  if this == other {
+    return true;
+  }
+
+  match (heap.get(this), heap.get(other)) {
+    (Some(&ManagedObject::String(ref a)), Some(&ManagedObject::String(ref b))) => a == b,
+    (Some(&ManagedObject::Boxed { type_: ref a_type, value: ref a }), Some(&ManagedObject::Boxed { type_: ref b_type, value: ref b })) =>
+      a_type == b_type && a == b,
+    _ => false
+  }
+}
+
+// Hash codes agree with object_equals. Floats hash by their integral part, which equal values share.
+fn hash_code(heap: &Heap, this: ObjectRef) -> i32 {
+  match heap.get(this) {
+    Some(&ManagedObject::String(ref value)) => value.iter().fold(5381i32, |hash, &unit| hash.wrapping_mul(33) ^ unit as i32),
+    Some(&ManagedObject::Boxed { ref value, .. }) => match *value {
+      StackValue::Int32(value) => value,
+      StackValue::Int64(value) => (value ^ (value >> 32)) as i32,
+      StackValue::NativeInt(value) => value as i32,
+      StackValue::Float(value) => value as i64 as i32,
+      _ => this.0 as i32
+    },
+    _ => this.0 as i32
+  }
+}
+
+fn write_console<W: Write>(output: &Rc<RefCell<W>>, text: &str, newline: bool) -> Result<Option<StackValue>, ExecutionError> {
+  let mut output = output.borrow_mut();
+  let result = if newline { writeln!(output, "{}", text) } else { write!(output, "{}", text) };
+  result.and_then(|_| output.flush()).map(|_| None)
+    .map_err(|error: io::Error| ExecutionError::exception("System.IO.IOException", &error.to_string()))
+}
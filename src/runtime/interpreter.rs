@@ -0,0 +1,2695 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::io::{self, Cursor};
+use std::mem;
+use std::rc::Rc;
+
+use loader::code::{ExceptionClause, ExceptionClauseKind};
+use loader::instructions::{Instruction, Token};
+use loader::stream::TableId;
+use metadata::Metadata;
+use metadata::signature::{TypeSig, MethodSignature};
+use metadata::tables::*;
+use runtime::arrays::*;
+use runtime::class::*;
+use runtime::error::{ExecutionError, ManagedException, StackFrame};
+use runtime::exceptions::*;
+use runtime::gc_object::BaseObject;
+use runtime::generics::*;
+use runtime::heap::{Heap, ManagedObject};
+use runtime::internal_calls::{InternalCalls, MethodKey};
+use runtime::method_table::{is_abstract, MethodImpls};
+use runtime::value::*;
+
+/// A decoded method body, shared by all frames running the method.
+pub struct MethodCode {
+  pub method: Index<MethodDefEntry>,
+  pub name: String,
+  /// The canonical instantiation the code is for, which is empty unless the method or its type is generic
+  pub context: GenericContext,
+  /// The signature, with the type arguments of `context` filled in
+  pub signature: MethodSignature,
+  pub instructions: Vec<(u32, Instruction)>,
+  /// Maps IL offsets to positions in `instructions`.
+  pub positions: HashMap<u32, usize>,
+  /// The types of the arguments, including `this`.
+  pub arg_types: Vec<TypeSig>,
+  pub local_types: Vec<TypeSig>,
+  pub exception_clauses: Vec<ExceptionClause>
+}
+
+pub struct Frame {
+  pub code: Rc<MethodCode>,
+  /// The position of the current instruction in `code.instructions`.
+  pub ip: usize,
+  pub stack: Vec<StackValue>,
+  pub locals: Vec<StackValue>,
+  pub args: Vec<StackValue>,
+  /// The type arguments the method runs with, which the type tokens of its instructions are instantiated with
+  pub context: GenericContext,
+  /// What to push on return instead of the return value, for constructors called by newobj
+  pub result: Option<Constructed>,
+  /// The type given by a constrained. prefix, for the callvirt following it
+  pub constrained: Option<TypeSig>,
+  /// The type whose initializer the frame runs, with the class of the instantiation for generic types. The
+  /// instruction which needed the type runs again once it returns.
+  pub initializing: Option<(Index<TypeDefEntry>, Option<usize>)>,
+  /// Whether a readonly. prefix precedes the ldelema following it, which then skips its type check
+  pub readonly: bool,
+  /// The finally and fault handlers running in the frame, innermost last
+  pub handlers: Vec<RunningHandler>,
+  /// The exceptions caught by the catch and filter handlers running in the frame, for rethrow
+  pub caught: Vec<(ExceptionClause, ObjectRef)>,
+  /// For a frame running a filter on a copy of the method's locals and arguments, the depth of the
+  /// method's frame, which gets them back at endfilter
+  pub filter_of: Option<usize>
+}
+
+/// A finally or fault handler being run, with what to do once it reaches endfinally.
+#[derive(Debug, Clone, Copy)]
+pub struct RunningHandler {
+  /// The position of the handler's clause in the method's exception clauses
+  pub clause: usize,
+  pub then: AfterHandler
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AfterHandler {
+  /// Continue the leave instruction at `from` to `target`, running the remaining finally handlers on the way
+  Leave { from: u32, target: u32 },
+  /// Continue unwinding an exception which passed through `from` to its handler, the clause `handler`
+  /// of the frame at `depth`, or the end of the type initializer the frame runs if there's no clause
+  Unwind { exception: ObjectRef, from: u32, depth: usize, handler: Option<usize> }
+}
+
+/// What newobj pushes once the constructor returns.
+#[derive(Debug, Clone)]
+pub enum Constructed {
+  Object(ObjectRef),
+  /// A value type instance, which the constructor initialized through `this` at the given location
+  Value(Pointer)
+}
+
+/// How far the type initializer of a type has got.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TypeInit {
+  Running,
+  Done,
+  /// The initializer threw an exception, and every use of the type throws this TypeInitializationException
+  Failed(ObjectRef)
+}
+
+// What to do after an instruction
+enum Flow {
+  Next,
+  Branch(u32),
+  // The method, its type arguments, its arguments and what to push when it returns instead of its return value
+  Call(Index<MethodDefEntry>, GenericContext, Vec<StackValue>, Option<Constructed>),
+  // Runs the type initializer of a type or an instantiation of a generic type, then the current instruction again
+  Initialize(Index<TypeDefEntry>, Option<usize>, Index<MethodDefEntry>),
+  Return(Option<StackValue>),
+  // The exception, and whether it's rethrown, which keeps its stack trace
+  Throw(ObjectRef, bool)
+}
+
+/// The default limit on the number of frames, which is far below what a real stack allows
+/// but keeps runaway recursion from using all of the host's memory.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// Runs IL with an explicit stack of frames, so that managed recursion doesn't use the native stack.
+pub struct Interpreter<'a> {
+  meta: &'a Metadata,
+  frames: Vec<Frame>,
+  // The code of methods, with one entry per canonical instantiation for generic methods, by MethodDef index
+  methods: HashMap<u32, Vec<Rc<MethodCode>>>,
+  max_depth: usize,
+  heap: Heap,
+  internal_calls: InternalCalls,
+  classes: Vec<Rc<Class>>,
+  // Maps TypeDef indices to positions in `classes`
+  class_ids: HashMap<u32, usize>,
+  // The instantiations of generic types, as their type arguments and position in `classes`, by TypeDef index
+  instantiations: HashMap<u32, Vec<(Vec<TypeSig>, usize)>>,
+  // The TypeDefs whose classes are being loaded, which are waiting for their base classes
+  loading: Vec<u32>,
+  // The explicit overrides of the assembly, which the method tables of classes are built from
+  method_impls: MethodImpls,
+  // The definitions of the methods and fields referenced through instantiations of generic types, by MemberRef index
+  instance_methods: HashMap<u32, Option<(Index<MethodDefEntry>, Vec<TypeSig>)>>,
+  instance_fields: HashMap<u32, Option<(Index<FieldEntry>, Index<TypeDefEntry>, Vec<TypeSig>)>>,
+  // The types and values of static fields, by Field index and the class of the instantiation for generic types
+  statics: HashMap<(u32, Option<usize>), (TypeSig, StackValue)>,
+  // How far the type initializers of types have got, by TypeDef index and the class of the instantiation for generic types
+  type_inits: HashMap<(u32, Option<usize>), TypeInit>
+}
+
+impl<'a> Interpreter<'a> {
+  /// Creates an interpreter with the base class library bindings, writing console output to stdout.
+  pub fn new(meta: &'a Metadata) -> Interpreter<'a> {
+    Interpreter::with_internal_calls(meta, InternalCalls::corlib(io::stdout()))
+  }
+
+  pub fn with_internal_calls(meta: &'a Metadata, internal_calls: InternalCalls) -> Interpreter<'a> {
+    Interpreter {
+      meta,
+      frames: vec![],
+      methods: HashMap::new(),
+      max_depth: DEFAULT_MAX_DEPTH,
+      heap: Heap::new(),
+      internal_calls,
+      classes: vec![],
+      class_ids: HashMap::new(),
+      instantiations: HashMap::new(),
+      loading: vec![],
+      method_impls: MethodImpls::new(meta),
+      instance_methods: HashMap::new(),
+      instance_fields: HashMap::new(),
+      statics: HashMap::new(),
+      type_inits: HashMap::new()
+    }
+  }
+
+  /// The registry of internal calls, for registering more of them.
+  pub fn internal_calls(&mut self) -> &mut InternalCalls {
+    &mut self.internal_calls
+  }
+
+  pub fn heap(&self) -> &Heap {
+    &self.heap
+  }
+
+  pub fn heap_mut(&mut self) -> &mut Heap {
+    &mut self.heap
+  }
+
+  /// Sets the maximum number of frames on the call stack. Calls beyond it throw a StackOverflowException.
+  pub fn set_max_depth(&mut self, max_depth: usize) {
+    self.max_depth = max_depth;
+  }
+
+  fn method_name(&self, method: &Index<MethodDefEntry>) -> String {
+    self.meta.get_method_full_name(&MethodDefOrRef::MethodDef(*method))
+  }
+
+  fn load_method(&mut self, method: &Index<MethodDefEntry>, context: &GenericContext) -> Result<Rc<MethodCode>, ExecutionError> {
+    let context = context.canonical(self.meta);
+    let loaded = self.methods.get(&method.0).and_then(|codes| codes.iter().find(|code| code.context == context).cloned());
+    if let Some(code) = loaded {
+      return Ok(code);
+    }
+
+    let name = self.method_name(method);
+    let invalid = |offset: u32, reason: String| ExecutionError::InvalidProgram { method: name.clone(), offset, reason };
+
+    let body = self.meta.method_bodies.get(&(method.0 - 1)).ok_or_else(|| invalid(0, "The method has no body".to_string()))?;
+    let signature = self.meta.get_method_def_signature(method).map_err(|error| invalid(0, error.to_string()))?;
+    let signature = context.substitute_signature(&signature);
+    let locals = self.meta.get_local_var_signature(&body.header).map_err(|error| invalid(0, error.to_string()))?;
+
+    let mut instructions = vec![];
+    let mut positions = HashMap::new();
+    let mut reader = Cursor::new(&body.code[..]);
+    while (reader.position() as usize) < body.code.len() {
+      let offset = reader.position() as u32;
+      let instruction = Instruction::read(&mut reader).map_err(|error| invalid(offset, error.to_string()))?;
+      positions.insert(offset, instructions.len());
+      instructions.push((offset, instruction));
+    }
+
+    // `this` is a managed pointer for methods of value types
+    let mut arg_types = vec![];
+    if signature.has_this() {
+      let this_type = match self.meta.get_method_owner(method) {
+        Some(owner) if is_value_type(self.meta, &owner) => TypeSig::ByRef(Box::new(instance_type(self.meta, &owner, &context.type_args))),
+        Some(owner) => instance_type(self.meta, &owner, &context.type_args),
+        None => TypeSig::Object
+      };
+      arg_types.push(this_type);
+    }
+    arg_types.extend(signature.params.iter().map(|param| runtime_type(self.meta, param)));
+
+    let meta = self.meta;
+    let local_types = locals.map(|locals| locals.locals.into_iter()
+      .map(|local| {
+        let local_type = context.substitute(&local.type_);
+        if local.by_ref { TypeSig::ByRef(Box::new(local_type)) } else { runtime_type(meta, &local_type) }
+      })
+      .collect())
+      .unwrap_or_default();
+
+    let exception_clauses = body.exception_clauses.clone();
+    let code = Rc::new(MethodCode {
+      method: *method, name, context, signature, instructions, positions, arg_types, local_types, exception_clauses
+    });
+    self.methods.entry(method.0).or_insert_with(Vec::new).push(code.clone());
+    Ok(code)
+  }
+
+  fn push_frame(&mut self, method: &Index<MethodDefEntry>, context: GenericContext, args: Vec<StackValue>, result: Option<Constructed>)
+    -> Result<(), ExecutionError> {
+    let code = self.load_method(method, &context)?;
+
+    if args.len() != code.arg_types.len() {
+      return Err(ExecutionError::InvalidProgram {
+        method: code.name.clone(),
+        offset: 0,
+        reason: format!("Expected {} arguments, got {}", code.arg_types.len(), args.len())
+      });
+    }
+
+    let mut locals = vec![];
+    for local_type in &code.local_types {
+      match self.default_value(local_type) {
+        Ok(value) => locals.push(value),
+        Err(ExecutionError::InvalidProgram { reason, .. }) =>
+          return Err(ExecutionError::InvalidProgram { method: code.name.clone(), offset: 0, reason }),
+        Err(error) => return Err(error)
+      }
+    }
+
+    let args = args.into_iter().zip(code.arg_types.iter()).map(|(arg, arg_type)| arg.store_as(arg_type)).collect();
+    self.frames.push(Frame {
+      code, ip: 0, stack: vec![], locals, args, context, result, constrained: None, initializing: None, readonly: false,
+      handlers: vec![], caught: vec![], filter_of: None
+    });
+    Ok(())
+  }
+
+  /// Runs a method to completion, returning its return value.
+  pub fn run(&mut self, method: &Index<MethodDefEntry>, args: Vec<StackValue>) -> Result<Option<StackValue>, ExecutionError> {
+    let base_depth = self.frames.len();
+
+    // The type of the method may need initializing first. Its initializer runs before the method's frame is pushed,
+    // so that an exception escaping it is thrown to the caller rather than from inside the method.
+    let result = match self.initialize_for_method(&MethodDefOrRef::MethodDef(*method), &GenericContext::default()) {
+      Ok(Some(Flow::Initialize(type_def, instantiation, initializer))) =>
+        self.push_initializer(type_def, instantiation, &initializer).and_then(|_| self.run_frames(base_depth)).map(|_| ()),
+      Ok(Some(Flow::Throw(exception, _))) => Err(self.unhandled(exception)),
+      Ok(_) => Ok(()),
+      Err(error) => Err(error)
+    };
+    let result = result
+      .and_then(|_| self.push_frame(method, GenericContext::default(), args, None))
+      .and_then(|_| self.run_frames(base_depth));
+    // After an error, the frames of the failed call are left behind
+    self.frames.truncate(base_depth);
+    result
+  }
+
+  fn run_frames(&mut self, base_depth: usize) -> Result<Option<StackValue>, ExecutionError> {
+    // The outcome of handling an instruction's outcome, like the handler a thrown exception goes to
+    let mut pending = None;
+    loop {
+      let flow = match pending.take() {
+        Some(flow) => flow,
+        None => self.step()
+      };
+
+      match flow {
+        Ok(Flow::Next) => self.frame().ip += 1,
+        Ok(Flow::Branch(target)) => {
+          let position = self.frame().code.positions.get(&target).cloned();
+          match position {
+            Some(position) => self.frame().ip = position,
+            None => return Err(self.invalid(format!("Branch target IL_{:04x} is not the start of an instruction", target)))
+          }
+        },
+        Ok(Flow::Call(method, context, args, result)) => {
+          if self.frames.len() >= self.max_depth {
+            pending = Some(Err(self.exception("System.StackOverflowException", "Operation caused a stack overflow.")));
+          } else if let Err(error) = self.push_frame(&method, context, args, result) {
+            pending = Some(Err(error));
+          }
+        },
+        Ok(Flow::Initialize(type_def, instantiation, initializer)) => {
+          if let Err(error) = self.push_initializer(type_def, instantiation, &initializer) {
+            pending = Some(Err(error));
+          }
+        },
+        Ok(Flow::Return(value)) => {
+          let frame = self.frames.pop().unwrap();
+          // The instruction which needed the type runs again, now that it's initialized
+          if let Some((type_def, instantiation)) = frame.initializing {
+            self.type_inits.insert((type_def.0, instantiation), TypeInit::Done);
+            // Unless nothing needed it but `run`, before calling the method
+            if self.frames.len() == base_depth {
+              return Ok(None);
+            }
+            continue;
+          }
+
+          let value = match frame.result {
+            Some(Constructed::Object(object)) => Some(StackValue::Object(Some(object))),
+            Some(Constructed::Value(pointer)) => Some(self.load(&pointer)?),
+            None => value
+          };
+          if self.frames.len() == base_depth {
+            return Ok(value);
+          }
+
+          if let Some(value) = value {
+            self.push(value);
+          }
+          self.frame().ip += 1;
+        },
+        Ok(Flow::Throw(exception, rethrow)) => pending = Some(Ok(self.throw(exception, rethrow, base_depth)?)),
+        Err(ExecutionError::UnhandledException(exception)) => pending = Some(Ok(self.raise(exception, base_depth)?)),
+        Err(error) => return Err(error)
+      }
+    }
+  }
+
+  // The initializers of generic types run once per instantiation, with its type arguments
+  fn push_initializer(&mut self, type_def: Index<TypeDefEntry>, instantiation: Option<usize>, initializer: &Index<MethodDefEntry>)
+    -> Result<(), ExecutionError> {
+    if self.frames.len() >= self.max_depth {
+      return Err(self.exception("System.StackOverflowException", "Operation caused a stack overflow."));
+    }
+    let type_args = instantiation.map(|id| self.classes[id].type_args.clone()).unwrap_or_default();
+    self.push_frame(initializer, GenericContext::new(type_args, vec![]), vec![], None)?;
+    self.frame().initializing = Some((type_def, instantiation));
+    Ok(())
+  }
+
+  // Starts the type initializer of a type unless it has already started. Types being initialized count as
+  // initialized, so initializers which depend on each other see the fields the other hasn't set yet, as in
+  // the CLR. Types whose initializer failed throw their TypeInitializationException again.
+  fn initialize(&mut self, type_def: Index<TypeDefEntry>, instantiation: Option<usize>) -> Option<Flow> {
+    match self.type_inits.get(&(type_def.0, instantiation)).cloned() {
+      Some(TypeInit::Failed(exception)) => return Some(Flow::Throw(exception, false)),
+      Some(_) => return None,
+      None => ()
+    }
+
+    match type_initializer(self.meta, &type_def) {
+      Some(initializer) => {
+        self.type_inits.insert((type_def.0, instantiation), TypeInit::Running);
+        Some(Flow::Initialize(type_def, instantiation, initializer))
+      },
+      None => {
+        self.type_inits.insert((type_def.0, instantiation), TypeInit::Done);
+        None
+      }
+    }
+  }
+
+  // Static field accesses initialize the type declaring the field. Once one of its static fields has been
+  // stored, the type's initializer has already started.
+  fn initialize_for_field(&mut self, token: &Token) -> Result<Option<Flow>, ExecutionError> {
+    let (field, instantiation) = self.field_token(token)?;
+    if self.statics.contains_key(&(field.0, instantiation)) {
+      return Ok(None);
+    }
+    Ok(self.meta.get_field_owner(&field).and_then(|owner| self.initialize(owner, instantiation)))
+  }
+
+  // Calls of static methods and constructors initialize the type declaring them, and so do calls of instance
+  // methods of value types. beforefieldinit types are only initialized by static field accesses.
+  fn initialize_for_method(&mut self, method: &MethodDefOrRef, context: &GenericContext) -> Result<Option<Flow>, ExecutionError> {
+    let method = match *method {
+      MethodDefOrRef::MethodDef(method) => method,
+      MethodDefOrRef::MethodRef(_) => return Ok(None)
+    };
+    let owner = match self.meta.get_method_owner(&method) {
+      Some(owner) => owner,
+      None => return Ok(None)
+    };
+    let instantiation = self.instantiation(&owner, &context.type_args)?;
+    if self.type_inits.get(&(owner.0, instantiation)) == Some(&TypeInit::Done) {
+      return Ok(None);
+    }
+
+    let before_field_init = self.meta.get_entry(&owner).map(|entry| entry.flags.contains(tdBeforeFieldInit)).unwrap_or(false);
+    let is_constructor = self.meta.get_entry(&method).and_then(|entry| self.meta.get_string(&entry.name)).map(|name| name == ".ctor").unwrap_or(false);
+    let triggers = is_static_method(self.meta, &method) || is_constructor || is_value_type(self.meta, &owner);
+    if before_field_init || !triggers {
+      return Ok(None);
+    }
+    Ok(self.initialize(owner, instantiation))
+  }
+
+  // An exception escaping a type initializer fails the type for good. The initializer's frame is dropped, and
+  // the exception is thrown again from the instruction which needed the type, inside a TypeInitializationException.
+  fn fail_initialization(&mut self, exception: ObjectRef) -> Result<Flow, ExecutionError> {
+    let (type_def, instantiation) = match self.frames.pop().and_then(|frame| frame.initializing) {
+      Some(initializing) => initializing,
+      None => return Err(self.invalid("Expected the frame of a type initializer".to_string()))
+    };
+
+    let type_name = match instantiation {
+      Some(id) => self.classes[id].name.clone(),
+      None => self.meta.full_type_name(&TypeDefOrRef::TypeDef(type_def)).as_reflection()
+    };
+    let wrapper = self.heap.alloc(ManagedObject::Exception { type_name: "System.TypeInitializationException".to_string() });
+    {
+      let state = self.heap.exception_state_mut(wrapper);
+      state.message = Some(format!("The type initializer for '{}' threw an exception.", type_name));
+      state.inner = Some(exception);
+    }
+    self.type_inits.insert((type_def.0, instantiation), TypeInit::Failed(wrapper));
+    // Accesses to the fields the initializer did set must throw too
+    for field in self.meta.get_field_range(&type_def) {
+      self.statics.remove(&(field, instantiation));
+    }
+    Ok(Flow::Throw(wrapper, false))
+  }
+
+  fn frame(&mut self) -> &mut Frame {
+    self.frames.last_mut().unwrap()
+  }
+
+  fn offset(&self) -> u32 {
+    self.frames.last().map(frame_offset).unwrap_or(0)
+  }
+
+  // Errors while setting up the first frame have no method to blame
+  fn invalid(&self, reason: String) -> ExecutionError {
+    let method = self.frames.last().map(|frame| frame.code.name.clone()).unwrap_or_default();
+    ExecutionError::InvalidProgram { method, offset: self.offset(), reason }
+  }
+
+  fn exception(&self, type_name: &str, message: &str) -> ExecutionError {
+    ExecutionError::exception(type_name, message)
+  }
+
+  fn arithmetic_error(&self, error: ArithmeticError) -> ExecutionError {
+    match error {
+      ArithmeticError::InvalidOperands => self.invalid("Invalid operand types".to_string()),
+      ArithmeticError::Overflow => self.exception("System.OverflowException", "Arithmetic operation resulted in an overflow."),
+      ArithmeticError::DivideByZero => self.exception("System.DivideByZeroException", "Attempted to divide by zero.")
+    }
+  }
+
+  fn push(&mut self, value: StackValue) {
+    self.frame().stack.push(value);
+  }
+
+  fn pop(&mut self) -> Result<StackValue, ExecutionError> {
+    match self.frame().stack.pop() {
+      Some(value) => Ok(value),
+      None => Err(self.invalid("Stack underflow".to_string()))
+    }
+  }
+
+  fn load_arg(&mut self, index: u16) -> Result<(), ExecutionError> {
+    let value = self.frame().args.get(index as usize).cloned();
+    match value {
+      Some(value) => Ok(self.push(value)),
+      None => Err(self.invalid(format!("Argument index {} is out of range", index)))
+    }
+  }
+
+  fn load_local(&mut self, index: u16) -> Result<(), ExecutionError> {
+    let value = self.frame().locals.get(index as usize).cloned();
+    match value {
+      Some(value) => Ok(self.push(value)),
+      None => Err(self.invalid(format!("Local variable index {} is out of range", index)))
+    }
+  }
+
+  // The type of a value type instance stored at a location
+  fn value_type_at(&mut self, pointer: &Pointer) -> Option<TypeSig> {
+    match location_mut(&mut self.frames, &mut self.heap, &mut self.statics, pointer) {
+      Some(&mut StackValue::ValueType(ref instance)) => Some(instance.type_.clone()),
+      _ => None
+    }
+  }
+
+  // The type of a field of a value type, by its position in the layout
+  fn value_field_type(&self, value_type: &TypeSig, slot: usize) -> Option<TypeSig> {
+    if let Some(inner) = nullable_type(self.meta, value_type) {
+      return match slot {
+        0 => Some(TypeSig::Boolean),
+        1 => Some(runtime_type(self.meta, &inner)),
+        _ => None
+      };
+    }
+
+    let id = match *value_type {
+      TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)) => self.class_ids.get(&type_def.0).cloned(),
+      ref instance => generic_instance(instance).and_then(|(type_def, type_args)| self.instantiation_id(&type_def, type_args))
+    };
+    id.and_then(|id| self.classes[id].fields.get(slot)).map(|info| info.type_.clone())
+  }
+
+  // The type of the values stored at a location, which stores narrow to
+  fn location_type(&mut self, pointer: &Pointer) -> Option<TypeSig> {
+    match *pointer {
+      Pointer::Local { frame, index } => self.frames.get(frame).and_then(|frame| frame.code.local_types.get(index as usize)).cloned(),
+      Pointer::Argument { frame, index } => self.frames.get(frame).and_then(|frame| frame.code.arg_types.get(index as usize)).cloned(),
+      Pointer::Field { object, slot } => match self.heap.get(object) {
+        Some(&ManagedObject::Instance { header, .. }) => self.classes.get(header.vtable)
+          .and_then(|class| class.fields.get(slot))
+          .map(|info| info.type_.clone()),
+        _ => None
+      },
+      Pointer::StaticField(field, instantiation) => self.statics.get(&(field.0, instantiation)).map(|&(ref field_type, _)| field_type.clone()),
+      Pointer::ValueField(ref inner, slot) => match self.value_type_at(inner) {
+        Some(value_type) => self.value_field_type(&value_type, slot),
+        None => None
+      },
+      Pointer::Boxed(object) => match self.heap.get(object) {
+        Some(&ManagedObject::Boxed { ref type_, .. }) => Some(runtime_type(self.meta, type_)),
+        _ => None
+      },
+      Pointer::Element { array, .. } => match self.heap.get(array) {
+        Some(&ManagedObject::Array { ref type_, .. }) => element_type(type_).map(|element| runtime_type(self.meta, element)),
+        _ => None
+      }
+    }
+  }
+
+  fn store(&mut self, pointer: Pointer, value: StackValue) -> Result<(), ExecutionError> {
+    if let Pointer::StaticField(field, instantiation) = pointer {
+      self.init_static_field(&field, instantiation)?;
+    }
+
+    let value = match self.location_type(&pointer) {
+      Some(location_type) => value.store_as(&location_type),
+      None => return Err(self.invalid(format!("Invalid store to {:?}", pointer)))
+    };
+    let stored = match location_mut(&mut self.frames, &mut self.heap, &mut self.statics, &pointer) {
+      Some(target) => {
+        *target = value;
+        true
+      },
+      None => false
+    };
+
+    if stored { Ok(()) } else { Err(self.invalid(format!("Invalid store to {:?}", pointer))) }
+  }
+
+  fn load(&mut self, pointer: &Pointer) -> Result<StackValue, ExecutionError> {
+    let value = location_mut(&mut self.frames, &mut self.heap, &mut self.statics, pointer).map(|value| value.clone());
+    value.ok_or_else(|| self.invalid(format!("Invalid load from {:?}", pointer)))
+  }
+
+  fn pop_pointer(&mut self) -> Result<Pointer, ExecutionError> {
+    match self.pop()? {
+      StackValue::ManagedPointer(pointer) => Ok(pointer),
+      other => Err(self.invalid(format!("Expected a managed pointer, found {:?}", other)))
+    }
+  }
+
+  fn binary(&mut self, op: BinaryOp) -> Result<(), ExecutionError> {
+    let b = self.pop()?;
+    let a = self.pop()?;
+    match StackValue::binary(op, a, b) {
+      Ok(value) => Ok(self.push(value)),
+      Err(error) => Err(self.arithmetic_error(error))
+    }
+  }
+
+  fn compare(&mut self, op: CompareOp) -> Result<bool, ExecutionError> {
+    let b = self.pop()?;
+    let a = self.pop()?;
+    StackValue::compare(op, a, b).map_err(|error| self.arithmetic_error(error))
+  }
+
+  fn branch_if(&mut self, op: CompareOp, target: u32) -> Result<Flow, ExecutionError> {
+    Ok(if self.compare(op)? { Flow::Branch(target) } else { Flow::Next })
+  }
+
+  fn convert(&mut self, target: Conversion, checked: bool, unsigned_source: bool) -> Result<(), ExecutionError> {
+    let value = self.pop()?;
+    match value.convert(target, checked, unsigned_source) {
+      Ok(value) => Ok(self.push(value)),
+      Err(error) => Err(self.arithmetic_error(error))
+    }
+  }
+
+  fn load_indirect(&mut self, type_sig: TypeSig) -> Result<(), ExecutionError> {
+    let pointer = self.pop_pointer()?;
+    let value = self.load(&pointer)?;
+    self.push(extend(value, &type_sig));
+    Ok(())
+  }
+
+  fn store_indirect(&mut self, type_sig: TypeSig) -> Result<(), ExecutionError> {
+    let value = self.pop()?;
+    let pointer = self.pop_pointer()?;
+    self.store(pointer, value.store_as(&type_sig))
+  }
+
+  // Pops the arguments of a call, the first argument being the deepest on the stack
+  fn pop_args(&mut self, count: usize) -> Result<Vec<StackValue>, ExecutionError> {
+    let stack_len = self.frame().stack.len();
+    if stack_len < count {
+      return Err(self.invalid("Stack underflow".to_string()));
+    }
+    Ok(self.frame().stack.split_off(stack_len - count))
+  }
+
+  // The method a token names, with the type arguments it's instantiated with. Methods of generic types of the
+  // assembly are named through an instantiation like `List<!0>::Add`, which is resolved to the definition.
+  fn method_token(&mut self, token: &Token) -> Result<(MethodDefOrRef, GenericContext), ExecutionError> {
+    let (method, method_args) = match *token {
+      Token::Table(TableId::MethodDef, index) => (MethodDefOrRef::MethodDef(Index::new(index)), vec![]),
+      Token::Table(TableId::MemberRef, index) => (MethodDefOrRef::MethodRef(Index::new(index)), vec![]),
+      Token::Table(TableId::MethodSpec, index) => match self.meta.get_method_spec(&Index::new(index)) {
+        Ok(method_spec) => (method_spec.method, method_spec.type_args),
+        Err(error) => return Err(self.invalid(error.to_string()))
+      },
+      _ => return Err(self.invalid(format!("Can't resolve method token {:08x}", token.to_raw())))
+    };
+
+    // The type arguments may be the caller's own type parameters
+    let method_args = self.instantiate_all(&method_args);
+    Ok(match method {
+      MethodDefOrRef::MethodRef(member) => match self.instance_method(&member) {
+        Some((method, type_args)) => (MethodDefOrRef::MethodDef(method), GenericContext::new(self.instantiate_all(&type_args), method_args)),
+        None => (method, GenericContext::new(vec![], method_args))
+      },
+      method => (method, GenericContext::new(vec![], method_args))
+    })
+  }
+
+  // `instantiated_method`, which is only looked up once for each MemberRef
+  fn instance_method(&mut self, member: &Index<MemberRefEntry>) -> Option<(Index<MethodDefEntry>, Vec<TypeSig>)> {
+    let meta = self.meta;
+    self.instance_methods.entry(member.0).or_insert_with(|| instantiated_method(meta, member)).clone()
+  }
+
+  // `instantiated_field`, which is only looked up once for each MemberRef
+  fn instance_field(&mut self, member: &Index<MemberRefEntry>) -> Option<(Index<FieldEntry>, Index<TypeDefEntry>, Vec<TypeSig>)> {
+    let meta = self.meta;
+    self.instance_fields.entry(member.0).or_insert_with(|| instantiated_field(meta, member)).clone()
+  }
+
+  // Fills in the type arguments of the running method
+  fn instantiate(&self, type_: &TypeSig) -> TypeSig {
+    match self.frames.last() {
+      Some(frame) => frame.context.substitute(type_),
+      None => type_.clone()
+    }
+  }
+
+  fn instantiate_all(&self, types: &[TypeSig]) -> Vec<TypeSig> {
+    types.iter().map(|type_| self.instantiate(type_)).collect()
+  }
+
+  fn method_signature(&self, method: &MethodDefOrRef) -> Result<MethodSignature, ExecutionError> {
+    let signature = match *method {
+      MethodDefOrRef::MethodDef(ref method) => self.meta.get_method_def_signature(method),
+      MethodDefOrRef::MethodRef(ref member) => self.meta.get_member_ref_signature(member)
+    };
+    signature.map_err(|error| self.invalid(error.to_string()))
+  }
+
+  fn call(&mut self, token: &Token) -> Result<Flow, ExecutionError> {
+    let (method, context) = self.method_token(token)?;
+    if let Some(flow) = self.initialize_for_method(&method, &context)? {
+      return Ok(flow);
+    }
+    if let MethodDefOrRef::MethodRef(member) = method {
+      if let Some(nullable) = self.nullable_parent(&member) {
+        return self.call_nullable(&member, nullable);
+      }
+    }
+
+    let signature = self.method_signature(&method)?;
+    let args = self.pop_args(signature.params.len() + signature.has_this() as usize)?;
+    self.invoke(method, context, &signature, args)
+  }
+
+  // Calls a method with arguments already popped off the stack
+  fn invoke(&mut self, method: MethodDefOrRef, context: GenericContext, signature: &MethodSignature, args: Vec<StackValue>)
+    -> Result<Flow, ExecutionError> {
+    let method = match method {
+      MethodDefOrRef::MethodDef(method) => method,
+      MethodDefOrRef::MethodRef(member) => {
+        if let Some(array_type) = self.array_parent(&member) {
+          return self.call_array_accessor(&member, array_type, args);
+        }
+        let parent = self.external_parent(&member).unwrap_or_default();
+        match parent.as_str() {
+          "System.Array" => return self.call_array(&member, signature, args),
+          "System.Type" => return self.call_type(&member, signature, args),
+          "System.Runtime.CompilerServices.RuntimeHelpers" if self.member_name(&member) == "InitializeArray" =>
+            return self.initialize_array(&args),
+          _ => ()
+        }
+        if self.exception_parent(&member).is_some() {
+          return self.call_exception(&member, signature, args);
+        }
+        return self.internal_call(MethodKey::from_member_ref(self.meta, &member), signature, args);
+      }
+    };
+
+    if is_abstract(self.meta, &method) {
+      return Err(self.invalid(format!("{} is abstract", self.method_name(&method))));
+    }
+    // MethodImplAttributes.InternalCall
+    let is_internal_call = self.meta.get_entry(&method).map(|entry| entry.impl_flags & 0x1000 != 0).unwrap_or(false);
+    if is_internal_call {
+      return self.internal_call(MethodKey::from_method_def(self.meta, &method), signature, args);
+    }
+    Ok(Flow::Call(method, context, args, None))
+  }
+
+  // callvirt: the method that runs depends on the type of `this`
+  fn call_virtual(&mut self, token: &Token) -> Result<Flow, ExecutionError> {
+    let constrained = self.frame().constrained.take();
+    let (method, context) = self.method_token(token)?;
+    let signature = self.method_signature(&method)?;
+    if !signature.has_this() {
+      return Err(self.invalid(format!("callvirt can't call the static method {}", self.meta.get_method_full_name(&method))));
+    }
+    let mut args = self.pop_args(signature.params.len() + 1)?;
+
+    if let Some(constrained) = constrained {
+      if let Some((target, target_context)) = self.constrained_target(constrained, &method, &context, &mut args)? {
+        return self.invoke(MethodDefOrRef::MethodDef(target), target_context, &signature, args);
+      }
+    }
+
+    let this = match args[0] {
+      StackValue::Object(Some(object)) => object,
+      StackValue::Object(None) => return Err(ExecutionError::null_reference()),
+      // Methods of value types are called through pointers, and can't be overridden
+      _ => return self.invoke(method, context, &signature, args)
+    };
+    match self.resolve_virtual(this, &method, &context)? {
+      Some((target, target_context)) => {
+        // Methods of value types get a pointer to the boxed value
+        let owner = self.meta.get_method_owner(&target);
+        if owner.map(|owner| is_value_type(self.meta, &owner)).unwrap_or(false) {
+          args[0] = StackValue::ManagedPointer(Pointer::Boxed(this));
+        }
+        self.invoke(MethodDefOrRef::MethodDef(target), target_context, &signature, args)
+      },
+      // Strings, boxed primitives and objects inheriting the method from another assembly
+      None => self.invoke(method, context, &signature, args)
+    }
+  }
+
+  // The method a virtual call runs on an object and its type arguments, or None if its class doesn't override it
+  fn resolve_virtual(&mut self, object: ObjectRef, method: &MethodDefOrRef, context: &GenericContext)
+    -> Result<Option<(Index<MethodDefEntry>, GenericContext)>, ExecutionError> {
+    let boxed_type = match self.heap.get(object) {
+      Some(&ManagedObject::Instance { header, .. }) => return Ok(self.resolve_in(&self.classes[header.vtable], method, context)),
+      Some(&ManagedObject::Boxed { ref type_, .. }) => type_.clone(),
+      _ => return Ok(None)
+    };
+    match self.value_type_class(&boxed_type)? {
+      Some(class) => Ok(self.resolve_in(&class, method, context)),
+      None => Ok(None)
+    }
+  }
+
+  // The type arguments of the target come from the class, or the base class declaring it. Default implementations
+  // of interface methods keep the ones they were called with.
+  fn resolve_in(&self, class: &Class, method: &MethodDefOrRef, context: &GenericContext) -> Option<(Index<MethodDefEntry>, GenericContext)> {
+    let target = match *method {
+      MethodDefOrRef::MethodDef(ref method) => class.methods.resolve(method),
+      MethodDefOrRef::MethodRef(ref member) => class.methods.resolve_external(self.meta, member)
+    };
+    let target = match target {
+      Some(target) => target,
+      None => return None
+    };
+
+    let owner = self.meta.get_method_owner(&target);
+    let mut declaring = Some(class);
+    while let Some(current) = declaring {
+      if Some(current.type_def) == owner {
+        return Some((target, GenericContext::new(current.type_args.clone(), context.method_args.clone())));
+      }
+      declaring = current.parent.as_ref().map(|parent| &**parent);
+    }
+    Some((target, context.clone()))
+  }
+
+  // constrained. T callvirt, where `this` is a pointer to a T: reference types are dereferenced, value
+  // types implementing the method get called with the pointer, and other value types are boxed
+  fn constrained_target(&mut self, type_: TypeSig, method: &MethodDefOrRef, context: &GenericContext, args: &mut Vec<StackValue>)
+    -> Result<Option<(Index<MethodDefEntry>, GenericContext)>, ExecutionError> {
+    let pointer = match args[0] {
+      StackValue::ManagedPointer(ref pointer) => pointer.clone(),
+      ref other => return Err(self.invalid(format!("constrained. expects a managed pointer, found {:?}", other)))
+    };
+    if is_reference_type(&type_) {
+      args[0] = self.load(&pointer)?;
+      return Ok(None);
+    }
+
+    let is_enum = match type_ {
+      TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)) => enum_underlying_type(self.meta, &type_def).is_some(),
+      _ => false
+    };
+    let class = if is_enum { None } else { self.value_type_class(&type_)? };
+    if let Some(class) = class {
+      if let Some((target, target_context)) = self.resolve_in(&class, method, context) {
+        if self.meta.get_method_owner(&target) == Some(class.type_def) {
+          return Ok(Some((target, target_context)));
+        }
+      }
+    }
+
+    let value = self.load(&pointer)?;
+    args[0] = self.box_value(type_, value)?;
+    Ok(None)
+  }
+
+  // ldftn, and ldvirtftn which looks the method up in the class of an object like callvirt
+  fn load_function(&mut self, token: &Token, is_virtual: bool) -> Result<(), ExecutionError> {
+    let (method, context) = self.method_token(token)?;
+    let target = if is_virtual {
+      match self.pop_object()? {
+        Some(object) => self.resolve_virtual(object, &method, &context)?,
+        None => return Err(ExecutionError::null_reference())
+      }
+    } else {
+      None
+    };
+
+    match (target, method) {
+      (Some((target, target_context)), _) => Ok(self.push(StackValue::FunctionPointer(target, target_context))),
+      (None, MethodDefOrRef::MethodDef(target)) => Ok(self.push(StackValue::FunctionPointer(target, context))),
+      _ => Err(self.invalid(format!("Can't take the address of {}", self.meta.get_method_full_name(&method))))
+    }
+  }
+
+  // calli: the callee's own signature gives the arguments to pop, and the pointer the type arguments it runs with
+  fn call_indirect(&mut self) -> Result<Flow, ExecutionError> {
+    let (method, context) = match self.pop()? {
+      StackValue::FunctionPointer(method, context) => (MethodDefOrRef::MethodDef(method), context),
+      other => return Err(self.invalid(format!("Expected a function pointer, found {:?}", other)))
+    };
+    let signature = self.method_signature(&method)?;
+    let args = self.pop_args(signature.params.len() + signature.has_this() as usize)?;
+    self.invoke(method, context, &signature, args)
+  }
+
+  // The instantiation of Nullable<T> declaring a referenced method, if that's where it's declared
+  fn nullable_parent(&self, member: &Index<MemberRefEntry>) -> Option<TypeSig> {
+    let type_spec = match self.meta.get_entry(member).map(|entry| entry.class) {
+      Some(MemberRefParent::TypeSpec(type_spec)) => type_spec,
+      _ => return None
+    };
+    match self.meta.get_type_spec_signature(&type_spec) {
+      Ok(ref type_) if nullable_type(self.meta, type_).is_some() => Some(type_.clone()),
+      _ => None
+    }
+  }
+
+  // Loads the Nullable<T> instance `this` points to, returning whether it has a value and the value
+  fn pop_nullable_this(&mut self) -> Result<(bool, StackValue), ExecutionError> {
+    let pointer = self.pop_pointer()?;
+    match self.load(&pointer)? {
+      StackValue::ValueType(ValueTypeInstance { fields, .. }) => {
+        let mut fields = fields.into_iter();
+        match (fields.next(), fields.next()) {
+          (Some(StackValue::Int32(has_value)), Some(value)) => Ok((has_value != 0, value)),
+          _ => Err(self.invalid("Expected a Nullable<T> value".to_string()))
+        }
+      },
+      other => Err(self.invalid(format!("Expected a Nullable<T> value, found {:?}", other)))
+    }
+  }
+
+  // A Nullable<T> with the given value
+  fn nullable_value(&self, nullable: TypeSig, value: StackValue) -> Result<StackValue, ExecutionError> {
+    let value_type = self.value_field_type(&nullable, 1).unwrap_or(TypeSig::Object);
+    Ok(StackValue::ValueType(ValueTypeInstance { type_: nullable, fields: vec![StackValue::Int32(1), value.store_as(&value_type)] }))
+  }
+
+  // The methods of Nullable<T>, which live in the core library and are implemented here, as they
+  // access the instance through `this`
+  fn call_nullable(&mut self, member: &Index<MemberRefEntry>, nullable: TypeSig) -> Result<Flow, ExecutionError> {
+    let name = self.member_name(member);
+    let param_count = self.meta.get_member_ref_signature(member).map(|signature| signature.params.len()).unwrap_or(0);
+
+    match (name.as_str(), param_count) {
+      (".ctor", 1) => {
+        let value = self.pop()?;
+        let pointer = self.pop_pointer()?;
+        let value = self.nullable_value(nullable, value)?;
+        self.store(pointer, value)?;
+      },
+      ("get_HasValue", 0) => {
+        let (has_value, _) = self.pop_nullable_this()?;
+        self.push(StackValue::Int32(has_value as i32));
+      },
+      ("get_Value", 0) => match self.pop_nullable_this()? {
+        (true, value) => self.push(value),
+        (false, _) => return Err(self.exception("System.InvalidOperationException", "Nullable object must have a value."))
+      },
+      // Instances without a value keep the default value of T
+      ("GetValueOrDefault", 0) => {
+        let (_, value) = self.pop_nullable_this()?;
+        self.push(value);
+      },
+      _ => {
+        let message = format!("Method not found: '{}::{}'.", nullable.as_il(self.meta), name);
+        return Err(self.exception("System.MissingMethodException", &message));
+      }
+    }
+    Ok(Flow::Next)
+  }
+
+  fn internal_call(&mut self, key: Option<MethodKey>, signature: &MethodSignature, args: Vec<StackValue>) -> Result<Flow, ExecutionError> {
+    let key = match key {
+      Some(key) => key,
+      None => return Err(self.invalid("Can't resolve the called method".to_string()))
+    };
+
+    let result = match self.internal_calls.get(&key) {
+      Some(call) => Some(call(&mut self.heap, &args)),
+      None => None
+    };
+
+    match result {
+      Some(Ok(value)) => {
+        if let Some(value) = value {
+          self.push(value.store_as(&signature.return_type));
+        }
+        Ok(Flow::Next)
+      },
+      Some(Err(error)) => Err(error),
+      None => Err(self.exception("System.MissingMethodException", &format!("Method not found: '{}'.", key)))
+    }
+  }
+
+  // Throws a runtime-raised exception as an exception object
+  fn raise(&mut self, exception: ManagedException, base_depth: usize) -> Result<Flow, ExecutionError> {
+    let object = self.heap.alloc(ManagedObject::Exception { type_name: exception.type_name.clone() });
+    self.heap.exception_state_mut(object).message = Some(exception.message);
+
+    // Stack overflows can't be caught
+    if exception.type_name == "System.StackOverflowException" {
+      let stack_trace = self.stack_trace();
+      self.heap.exception_state_mut(object).stack_trace = stack_trace;
+      return Err(self.unhandled(object));
+    }
+    self.throw(object, false, base_depth)
+  }
+
+  // Exceptions are handled in two passes: the first one looks for a handler, running filters, and the
+  // second one unwinds to it, running finally and fault handlers. Handlers are only looked for in the
+  // frames above `base_depth`; nothing is unwound if there is none.
+  fn throw(&mut self, exception: ObjectRef, rethrow: bool, base_depth: usize) -> Result<Flow, ExecutionError> {
+    if !rethrow {
+      let stack_trace = self.stack_trace();
+      self.heap.exception_state_mut(exception).stack_trace = stack_trace;
+    }
+
+    match self.find_handler(exception, base_depth)? {
+      Some((depth, handler)) => {
+        let from = self.offset();
+        self.unwind(exception, from, 0, depth, handler)
+      },
+      None => Err(self.unhandled(exception))
+    }
+  }
+
+  // The first pass: the depth of the frame handling an exception and the position of the clause handling it,
+  // which is None if the exception ends the type initializer the frame runs
+  fn find_handler(&mut self, exception: ObjectRef, base_depth: usize) -> Result<Option<(usize, Option<usize>)>, ExecutionError> {
+    for depth in (base_depth .. self.frames.len()).rev() {
+      let (code, offset) = (self.frames[depth].code.clone(), frame_offset(&self.frames[depth]));
+
+      for (index, clause) in code.exception_clauses.iter().enumerate() {
+        if !clause.try_contains(offset) {
+          continue;
+        }
+
+        let handles = match clause.kind {
+          ExceptionClauseKind::Catch(token) => {
+            let token = Token::from_raw(token).map_err(|error| self.invalid(error.to_string()))?;
+            let type_ = self.type_token(&token)?;
+            self.is_instance_of(exception, &type_)?
+          },
+          ExceptionClauseKind::Filter(filter_offset) => self.run_filter(depth, filter_offset, exception)?,
+          ExceptionClauseKind::Finally | ExceptionClauseKind::Fault => false
+        };
+        if handles {
+          return Ok(Some((depth, Some(index))));
+        }
+      }
+
+      if self.frames[depth].initializing.is_some() {
+        return Ok(Some((depth, None)));
+      }
+    }
+    Ok(None)
+  }
+
+  // Runs a filter on a copy of the frame of its method, with the exception on the stack
+  fn run_filter(&mut self, depth: usize, filter_offset: u32, exception: ObjectRef) -> Result<bool, ExecutionError> {
+    let (code, locals, args, context) = {
+      let frame = &self.frames[depth];
+      (frame.code.clone(), frame.locals.clone(), frame.args.clone(), frame.context.clone())
+    };
+    let ip = match code.positions.get(&filter_offset) {
+      Some(&ip) => ip,
+      None => return Err(self.invalid(format!("Filter IL_{:04x} is not the start of an instruction", filter_offset)))
+    };
+
+    let base_depth = self.frames.len();
+    let stack = vec![StackValue::Object(Some(exception))];
+    self.frames.push(Frame {
+      code, ip, stack, locals, args, context, result: None, constrained: None, initializing: None, readonly: false,
+      handlers: vec![], caught: vec![], filter_of: Some(depth)
+    });
+    let result = self.run_frames(base_depth);
+    self.frames.truncate(base_depth);
+
+    match result {
+      Ok(Some(value)) => value.is_true().map_err(|error| self.arithmetic_error(error)),
+      Ok(None) => Err(self.invalid("endfilter needs a value".to_string())),
+      // Exceptions thrown by filters are swallowed, and the filter doesn't match
+      Err(ExecutionError::UnhandledException(_)) => Ok(false),
+      Err(error) => Err(error)
+    }
+  }
+
+  // The second pass: runs the finally and fault handlers between where the exception is and its
+  // handler, then enters the handler. `from` is where the exception is in the top frame, and `start`
+  // the first of its clauses left to check.
+  fn unwind(&mut self, exception: ObjectRef, from: u32, start: usize, depth: usize, handler: Option<usize>) -> Result<Flow, ExecutionError> {
+    let (mut from, mut start) = (from, start);
+    loop {
+      let code = self.frame().code.clone();
+      let is_handling_frame = self.frames.len() - 1 == depth;
+      let end = match (is_handling_frame, handler) {
+        (true, Some(handler)) => handler,
+        _ => code.exception_clauses.len()
+      };
+
+      for index in start .. end {
+        let clause = code.exception_clauses[index];
+        let is_cleanup = match clause.kind {
+          ExceptionClauseKind::Finally | ExceptionClauseKind::Fault => true,
+          _ => false
+        };
+        if is_cleanup && clause.try_contains(from) {
+          let frame = self.frame();
+          frame.stack.clear();
+          frame.handlers.push(RunningHandler { clause: index, then: AfterHandler::Unwind { exception, from, depth, handler } });
+          return Ok(Flow::Branch(clause.handler_offset));
+        }
+      }
+
+      if is_handling_frame {
+        let clause = match handler {
+          Some(handler) => code.exception_clauses[handler],
+          None => return self.fail_initialization(exception)
+        };
+        let frame = self.frame();
+        frame.stack.clear();
+        frame.stack.push(StackValue::Object(Some(exception)));
+        // The handlers the exception escaped from are done
+        frame.handlers.retain(|running| code.exception_clauses[running.clause].handler_contains(clause.handler_offset));
+        frame.caught.retain(|&(other, _)| other.handler_contains(clause.handler_offset));
+        frame.caught.push((clause, exception));
+        return Ok(Flow::Branch(clause.handler_offset));
+      }
+
+      self.frames.pop();
+      from = self.offset();
+      start = 0;
+    }
+  }
+
+  // leave: exits catch handlers and protected regions, running the finally handlers of the regions it exits
+  fn leave(&mut self, target: u32) -> Result<Flow, ExecutionError> {
+    let from = self.offset();
+    {
+      let frame = self.frame();
+      frame.stack.clear();
+      frame.caught.retain(|&(clause, _)| !clause.handler_contains(from) || clause.handler_contains(target));
+    }
+    self.next_finally(from, target, 0)
+  }
+
+  fn next_finally(&mut self, from: u32, target: u32, start: usize) -> Result<Flow, ExecutionError> {
+    let code = self.frame().code.clone();
+    for index in start .. code.exception_clauses.len() {
+      let clause = code.exception_clauses[index];
+      if clause.kind == ExceptionClauseKind::Finally && clause.try_contains(from) && !clause.try_contains(target) {
+        self.frame().handlers.push(RunningHandler { clause: index, then: AfterHandler::Leave { from, target } });
+        return Ok(Flow::Branch(clause.handler_offset));
+      }
+    }
+    Ok(Flow::Branch(target))
+  }
+
+  fn end_finally(&mut self) -> Result<Flow, ExecutionError> {
+    let running = self.frame().handlers.pop();
+    let running = match running {
+      Some(running) => running,
+      None => return Err(self.invalid("endfinally outside of a finally or fault handler".to_string()))
+    };
+
+    self.frame().stack.clear();
+    match running.then {
+      AfterHandler::Leave { from, target } => self.next_finally(from, target, running.clause + 1),
+      AfterHandler::Unwind { exception, from, depth, handler } => self.unwind(exception, from, running.clause + 1, depth, handler)
+    }
+  }
+
+  fn stack_trace(&self) -> Vec<StackFrame> {
+    self.frames.iter().rev().map(|frame| StackFrame { method: frame.code.name.clone(), offset: frame_offset(frame) }).collect()
+  }
+
+  fn unhandled(&self, exception: ObjectRef) -> ExecutionError {
+    let stack_trace = self.heap.exception_state(exception).map(|state| state.stack_trace.clone()).unwrap_or_default();
+    ExecutionError::UnhandledException(ManagedException {
+      type_name: self.object_type_name(exception),
+      message: self.exception_message(exception),
+      stack_trace
+    })
+  }
+
+  fn exception_message(&self, exception: ObjectRef) -> String {
+    match self.heap.exception_state(exception).and_then(|state| state.message.clone()) {
+      Some(message) => message,
+      None => default_message(&self.object_type_name(exception))
+    }
+  }
+
+  fn member_name(&self, member: &Index<MemberRefEntry>) -> String {
+    self.meta.get_entry(member).and_then(|entry| self.meta.get_string(&entry.name)).cloned().unwrap_or_default()
+  }
+
+  // The name of the type from another assembly declaring a referenced method, if that's where it's declared
+  fn external_parent(&self, member: &Index<MemberRefEntry>) -> Option<String> {
+    match self.meta.get_entry(member).map(|entry| entry.class) {
+      Some(MemberRefParent::TypeRef(type_ref)) => Some(self.meta.get_type_name(&TypeDefOrRef::TypeRef(type_ref))),
+      _ => None
+    }
+  }
+
+  // The core library exception type declaring a referenced method, if that's where it's declared
+  fn exception_parent(&self, member: &Index<MemberRefEntry>) -> Option<String> {
+    self.external_parent(member).and_then(|parent| if core_exception_base(&parent).is_some() { Some(parent) } else { None })
+  }
+
+  // The members of System.Exception, for core library exceptions and classes deriving from them
+  fn call_exception(&mut self, member: &Index<MemberRefEntry>, signature: &MethodSignature, args: Vec<StackValue>) -> Result<Flow, ExecutionError> {
+    let name = self.member_name(member);
+    let this = match args.first() {
+      Some(&StackValue::Object(Some(object))) => object,
+      Some(&StackValue::Object(None)) => return Err(ExecutionError::null_reference()),
+      _ => return self.internal_call(MethodKey::from_member_ref(self.meta, member), signature, args)
+    };
+
+    let result = match name.as_str() {
+      // Constructors take a message, an inner exception or both, and argument exceptions a parameter name
+      ".ctor" => {
+        let (mut strings, mut inner) = (vec![], None);
+        for (arg, param) in args[1 ..].iter().zip(&signature.params) {
+          match (param, arg) {
+            (&TypeSig::String, &StackValue::Object(object)) => strings.push(object.and_then(|object| self.heap.get_string(object))),
+            (_, &StackValue::Object(Some(object))) if self.heap.exception_state(object).is_some() => inner = Some(object),
+            _ => ()
+          }
+        }
+        let string = |position: usize| strings.get(position).cloned().and_then(|string| string);
+
+        // ArgumentNullException and ArgumentOutOfRangeException take the parameter name first, unless they're given
+        // an inner exception, and ArgumentException takes it after the message
+        let type_name = self.exception_parent(member).unwrap_or_default();
+        let (message, param_name) = match type_name.as_str() {
+          "System.ArgumentNullException" | "System.ArgumentOutOfRangeException" if inner.is_none() => (string(1), string(0)),
+          "System.ArgumentException" => (string(0), string(1)),
+          _ => (string(0), None)
+        };
+
+        let state = self.heap.exception_state_mut(this);
+        state.message = match param_name {
+          Some(param_name) => Some(argument_message(&type_name, message, &param_name)),
+          None => message
+        };
+        state.inner = inner;
+        None
+      },
+      "get_Message" => {
+        let message = self.exception_message(this);
+        Some(StackValue::Object(Some(self.heap.alloc_string(&message))))
+      },
+      "get_InnerException" => Some(StackValue::Object(self.heap.exception_state(this).and_then(|state| state.inner))),
+      "get_StackTrace" => {
+        let stack_trace = self.heap.exception_state(this).map(|state| state.stack_trace.clone()).unwrap_or_default();
+        let lines = stack_trace.iter().map(|frame| format!("   {}", frame)).collect::<Vec<_>>();
+        Some(StackValue::Object(if lines.is_empty() { None } else { Some(self.heap.alloc_string(&lines.join("\n"))) }))
+      },
+      _ => return self.internal_call(MethodKey::from_member_ref(self.meta, member), signature, args)
+    };
+
+    if let Some(value) = result {
+      self.push(value);
+    }
+    Ok(Flow::Next)
+  }
+
+  // The members of System.Type for the type objects of ldtoken and typeof
+  fn call_type(&mut self, member: &Index<MemberRefEntry>, signature: &MethodSignature, args: Vec<StackValue>) -> Result<Flow, ExecutionError> {
+    let type_name = |heap: &Heap, value: Option<&StackValue>| match value {
+      Some(&StackValue::Object(Some(object))) => match heap.get(object) {
+        Some(&ManagedObject::Type { ref type_, ref name }) => Some((type_.clone(), name.clone())),
+        _ => None
+      },
+      _ => None
+    };
+
+    let result = match (self.member_name(member).as_str(), args.first()) {
+      ("GetTypeFromHandle", Some(&StackValue::TypeHandle(ref type_))) => {
+        let name = reflection_name(self.meta, type_);
+        StackValue::Object(Some(self.heap.type_object(type_.clone(), name)))
+      },
+      ("op_Equality", _) | ("op_Inequality", _) => {
+        // Type objects are unique per type
+        let equal = match (args.get(0), args.get(1)) {
+          (Some(&StackValue::Object(a)), Some(&StackValue::Object(b))) => a == b,
+          _ => return Err(self.invalid("Type.op_Equality takes two objects".to_string()))
+        };
+        StackValue::Int32((equal == (self.member_name(member) == "op_Equality")) as i32)
+      },
+      (name @ "get_FullName", Some(&StackValue::Object(Some(_)))) | (name @ "ToString", Some(&StackValue::Object(Some(_)))) |
+        (name @ "get_Name", Some(&StackValue::Object(Some(_)))) => {
+        let (type_, full_name) = match type_name(&self.heap, args.first()) {
+          Some(type_name) => type_name,
+          None => return self.internal_call(MethodKey::from_member_ref(self.meta, member), signature, args)
+        };
+        // The name of an instantiation is the name of its generic type, like List`1
+        let name = if name == "get_Name" {
+          let definition = match type_ {
+            TypeSig::GenericInst(ref generic_type, _) => reflection_name(self.meta, generic_type),
+            _ => full_name
+          };
+          definition.rsplit(|c| c == '.' || c == '+').next().unwrap_or("").to_string()
+        } else {
+          full_name
+        };
+        StackValue::Object(Some(self.heap.alloc_string(&name)))
+      },
+      (_, Some(&StackValue::Object(None))) => return Err(ExecutionError::null_reference()),
+      _ => return self.internal_call(MethodKey::from_member_ref(self.meta, member), signature, args)
+    };
+    self.push(result);
+    Ok(Flow::Next)
+  }
+
+  // An array length or index, which is an int32 or a native int, or an int64 for the members of System.Array taking one
+  fn index_arg(&self, value: &StackValue) -> Result<i64, ExecutionError> {
+    match *value {
+      StackValue::Int32(value) => Ok(value as i64),
+      StackValue::Int64(value) => Ok(value),
+      StackValue::NativeInt(value) => Ok(value as i64),
+      ref other => Err(self.invalid(format!("Expected an index, found {:?}", other)))
+    }
+  }
+
+  fn pop_index(&mut self) -> Result<i64, ExecutionError> {
+    let value = self.pop()?;
+    self.index_arg(&value)
+  }
+
+  fn pop_array(&mut self) -> Result<ObjectRef, ExecutionError> {
+    self.pop_object()?.ok_or_else(ExecutionError::null_reference)
+  }
+
+  // Allocates an array with the default value of its element type in each element
+  fn new_array(&mut self, type_: TypeSig, lengths: Vec<usize>, lower_bounds: Vec<i32>) -> Result<ObjectRef, ExecutionError> {
+    let element = match element_type(&type_).cloned() {
+      Some(element) => self.default_value(&element)?,
+      None => return Err(self.invalid(format!("{} isn't an array type", type_.as_csharp(self.meta))))
+    };
+    let count = lengths.iter().fold(Some(1usize), |count, &length| count.and_then(|count| count.checked_mul(length)));
+    let count = match count {
+      Some(count) if count <= MAX_ARRAY_LENGTH => count,
+      _ => return Err(self.exception("System.OutOfMemoryException", "Array dimensions exceeded supported range."))
+    };
+    // Reserve the elements up front, so that running out of memory throws instead of aborting the host
+    let mut elements = Vec::new();
+    if elements.try_reserve_exact(count).is_err() {
+      return Err(self.exception("System.OutOfMemoryException", "Insufficient memory to continue the execution of the program."));
+    }
+    elements.resize(count, element);
+    Ok(self.heap.alloc(ManagedObject::Array { type_, lengths, lower_bounds, elements }))
+  }
+
+  // The type, lengths and lower bounds of an array
+  fn array_shape(&self, array: ObjectRef) -> Result<(TypeSig, Vec<usize>, Vec<i32>), ExecutionError> {
+    match self.heap.get(array) {
+      Some(&ManagedObject::Array { ref type_, ref lengths, ref lower_bounds, .. }) => Ok((type_.clone(), lengths.clone(), lower_bounds.clone())),
+      _ => Err(self.invalid(format!("Expected an array, found an instance of {}", self.object_type_name(array))))
+    }
+  }
+
+  // The location of an element, given an index for each dimension
+  fn element_pointer(&self, array: ObjectRef, indices: &[i64]) -> Result<Pointer, ExecutionError> {
+    let (_, lengths, lower_bounds) = self.array_shape(array)?;
+    match linear_index(&lengths, &lower_bounds, indices) {
+      Some(index) => Ok(Pointer::Element { array, index }),
+      None => Err(index_out_of_range())
+    }
+  }
+
+  // Arrays of reference types are covariant, so storing an object checks that it's an instance of the
+  // element type of the actual array
+  fn check_element_store(&self, array: ObjectRef, value: &StackValue) -> Result<(), ExecutionError> {
+    if let StackValue::Object(Some(object)) = *value {
+      let (type_, _, _) = self.array_shape(array)?;
+      let element = element_type(&type_).cloned().unwrap_or(TypeSig::Object);
+      if !self.is_instance_of_type(object, &element) {
+        return Err(type_mismatch());
+      }
+    }
+    Ok(())
+  }
+
+  // ldelem and ldelem.<type>, the type being None for ldelem.ref
+  fn load_element(&mut self, type_sig: Option<TypeSig>) -> Result<(), ExecutionError> {
+    let index = self.pop_index()?;
+    let array = self.pop_array()?;
+    let pointer = self.element_pointer(array, &[index])?;
+    let value = self.load(&pointer)?;
+    self.push(match type_sig {
+      Some(type_sig) => extend(value, &type_sig),
+      None => value
+    });
+    Ok(())
+  }
+
+  // stelem and stelem.<type>, the type being None for stelem.ref
+  fn store_element(&mut self, type_sig: Option<TypeSig>) -> Result<(), ExecutionError> {
+    let value = self.pop()?;
+    let index = self.pop_index()?;
+    let array = self.pop_array()?;
+    let pointer = self.element_pointer(array, &[index])?;
+    if type_sig.as_ref().map(is_reference_type).unwrap_or(true) {
+      self.check_element_store(array, &value)?;
+    }
+    let value = match type_sig {
+      Some(type_sig) => value.store_as(&type_sig),
+      None => value
+    };
+    self.store(pointer, value)
+  }
+
+  // ldelema: stores through the pointer aren't checked, so for reference types the element type must be
+  // exactly the given one, unless the pointer is only read from
+  fn element_address(&mut self, token: &Token) -> Result<(), ExecutionError> {
+    let type_ = runtime_type(self.meta, &self.token_type(token)?);
+    let readonly = mem::replace(&mut self.frame().readonly, false);
+    let index = self.pop_index()?;
+    let array = self.pop_array()?;
+    let pointer = self.element_pointer(array, &[index])?;
+    if is_reference_type(&type_) && !readonly && self.location_type(&pointer) != Some(type_) {
+      return Err(type_mismatch());
+    }
+    self.push(StackValue::ManagedPointer(pointer));
+    Ok(())
+  }
+
+  // The multi-dimensional array type declaring a referenced method, if that's where it's declared. Their
+  // constructors and accessors have no IL, the runtime provides them.
+  fn array_parent(&self, member: &Index<MemberRefEntry>) -> Option<TypeSig> {
+    let type_spec = match self.meta.get_entry(member).map(|entry| entry.class) {
+      Some(MemberRefParent::TypeSpec(type_spec)) => type_spec,
+      _ => return None
+    };
+    match self.meta.get_type_spec_signature(&type_spec) {
+      Ok(type_ @ TypeSig::Array(..)) => Some(type_),
+      _ => None
+    }
+  }
+
+  // The constructors of multi-dimensional arrays take a length for each dimension, or a lower bound and
+  // a length for each
+  fn construct_array(&mut self, array_type: TypeSig, args: &[StackValue]) -> Result<ObjectRef, ExecutionError> {
+    let rank = match array_type {
+      TypeSig::Array(_, ref shape) => shape.rank as usize,
+      _ => 1
+    };
+    let mut bounds = vec![];
+    for arg in args {
+      bounds.push(self.index_arg(arg)?);
+    }
+
+    let (lower_bounds, lengths) = if bounds.len() == rank {
+      (vec![0; rank], bounds)
+    } else if bounds.len() == rank * 2 {
+      (bounds.chunks(2).map(|pair| pair[0]).collect(), bounds.chunks(2).map(|pair| pair[1]).collect::<Vec<_>>())
+    } else {
+      return Err(self.invalid(format!("Can't construct {} with {} arguments", array_type.as_csharp(self.meta), bounds.len())));
+    };
+    if lengths.iter().any(|&length| length < 0) {
+      return Err(self.arithmetic_error(ArithmeticError::Overflow));
+    }
+    let lengths = lengths.iter().map(|&length| length as usize).collect();
+    self.new_array(array_type, lengths, lower_bounds.iter().map(|&lower_bound| lower_bound as i32).collect())
+  }
+
+  // Get, Set and Address of multi-dimensional arrays, which take an index for each dimension
+  fn call_array_accessor(&mut self, member: &Index<MemberRefEntry>, array_type: TypeSig, args: Vec<StackValue>) -> Result<Flow, ExecutionError> {
+    let name = self.member_name(member);
+    let rank = match array_type {
+      TypeSig::Array(_, ref shape) => shape.rank as usize,
+      _ => 1
+    };
+    let array = match args.first() {
+      Some(&StackValue::Object(Some(array))) => array,
+      Some(&StackValue::Object(None)) => return Err(ExecutionError::null_reference()),
+      _ => return Err(self.invalid(format!("Expected an array for {}::{}", array_type.as_csharp(self.meta), name)))
+    };
+    if args.len() < rank + 1 {
+      return Err(self.invalid(format!("{}::{} takes {} indices", array_type.as_csharp(self.meta), name, rank)));
+    }
+
+    let mut indices = vec![];
+    for arg in &args[1 .. rank + 1] {
+      indices.push(self.index_arg(arg)?);
+    }
+    let pointer = self.element_pointer(array, &indices)?;
+
+    match (name.as_str(), args.get(rank + 1)) {
+      ("Get", None) => {
+        let value = self.load(&pointer)?;
+        self.push(value);
+      },
+      ("Set", Some(value)) => {
+        self.check_element_store(array, value)?;
+        self.store(pointer, value.clone())?;
+      },
+      ("Address", None) => self.push(StackValue::ManagedPointer(pointer)),
+      _ => {
+        let message = format!("Method not found: '{}::{}'.", array_type.as_il(self.meta), name);
+        return Err(self.exception("System.MissingMethodException", &message));
+      }
+    }
+    Ok(Flow::Next)
+  }
+
+  // The members of System.Array used by compiled code
+  fn call_array(&mut self, member: &Index<MemberRefEntry>, signature: &MethodSignature, args: Vec<StackValue>) -> Result<Flow, ExecutionError> {
+    let name = self.member_name(member);
+    if name == "Resize" {
+      return self.resize_array(&args);
+    }
+
+    // The other members take the array as `this` or as their first argument
+    let array = match args.first() {
+      Some(&StackValue::Object(Some(array))) => array,
+      Some(&StackValue::Object(None)) if signature.has_this() => return Err(ExecutionError::null_reference()),
+      Some(&StackValue::Object(None)) => return Err(argument_null("array")),
+      _ => return self.internal_call(MethodKey::from_member_ref(self.meta, member), signature, args)
+    };
+    let (_, lengths, lower_bounds) = self.array_shape(array)?;
+    let length = lengths.iter().product::<usize>();
+
+    let result = match name.as_str() {
+      "get_Length" => Some(StackValue::Int32(length as i32)),
+      "get_LongLength" => Some(StackValue::Int64(length as i64)),
+      "get_Rank" => Some(StackValue::Int32(lengths.len() as i32)),
+      "GetLength" | "GetLowerBound" | "GetUpperBound" => {
+        let dimension = match args.get(1) {
+          Some(dimension) => self.index_arg(dimension)?,
+          None => return Err(self.invalid(format!("Array.{} takes a dimension", name)))
+        };
+        if dimension < 0 || dimension as usize >= lengths.len() {
+          return Err(index_out_of_range());
+        }
+        let (length, lower_bound) = (lengths[dimension as usize] as i32, lower_bounds[dimension as usize]);
+        Some(StackValue::Int32(match name.as_str() {
+          "GetLength" => length,
+          "GetLowerBound" => lower_bound,
+          _ => lower_bound + length - 1
+        }))
+      },
+      "Clear" => {
+        let (start, count) = match (args.get(1), args.get(2)) {
+          (Some(start), Some(count)) => (self.index_arg(start)? - lower_bounds[0] as i64, self.index_arg(count)?),
+          _ => (0, length as i64)
+        };
+        if start < 0 || count < 0 || start + count > length as i64 {
+          return Err(index_out_of_range());
+        }
+        if let Some(&mut ManagedObject::Array { ref mut elements, .. }) = self.heap.get_mut(array) {
+          for element in &mut elements[start as usize .. (start + count) as usize] {
+            let zero = zeroed(element);
+            *element = zero;
+          }
+        }
+        None
+      },
+      "Copy" => {
+        self.copy_array(array, &args)?;
+        None
+      },
+      _ => return self.internal_call(MethodKey::from_member_ref(self.meta, member), signature, args)
+    };
+
+    if let Some(value) = result {
+      self.push(value);
+    }
+    Ok(Flow::Next)
+  }
+
+  // Array.Copy(source, destination, length) and Array.Copy(source, sourceIndex, destination, destinationIndex, length).
+  // Elements are copied between arrays of the same element type, and between arrays of reference types if
+  // each copied element is an instance of the destination's element type.
+  fn copy_array(&mut self, source: ObjectRef, args: &[StackValue]) -> Result<(), ExecutionError> {
+    let (destination, source_index, destination_index, length) = match args.len() {
+      3 => (&args[1], None, None, &args[2]),
+      5 => (&args[2], Some(&args[1]), Some(&args[3]), &args[4]),
+      _ => return Err(self.invalid(format!("Array.Copy doesn't take {} arguments", args.len())))
+    };
+    let destination = match *destination {
+      StackValue::Object(Some(destination)) => destination,
+      StackValue::Object(None) => return Err(argument_null("destinationArray")),
+      ref other => return Err(self.invalid(format!("Expected an array, found {:?}", other)))
+    };
+
+    let (source_type, source_lengths, source_lower_bounds) = self.array_shape(source)?;
+    let (destination_type, destination_lengths, destination_lower_bounds) = self.array_shape(destination)?;
+    if source_lengths.len() != destination_lengths.len() {
+      return Err(self.exception("System.RankException", "Attempted to operate on an array with the incorrect number of dimensions."));
+    }
+
+    let source_index = match source_index {
+      Some(index) => self.index_arg(index)? - source_lower_bounds[0] as i64,
+      None => 0
+    };
+    let destination_index = match destination_index {
+      Some(index) => self.index_arg(index)? - destination_lower_bounds[0] as i64,
+      None => 0
+    };
+    let length = self.index_arg(length)?;
+    if source_index < 0 || destination_index < 0 || length < 0 {
+      return Err(self.exception("System.ArgumentOutOfRangeException", "Non-negative number required."));
+    }
+    if source_index + length > source_lengths.iter().product::<usize>() as i64 {
+      return Err(self.exception("System.ArgumentException",
+        "Source array was not long enough. Check the source index, length, and the array's lower bounds."));
+    }
+    if destination_index + length > destination_lengths.iter().product::<usize>() as i64 {
+      return Err(self.exception("System.ArgumentException",
+        "Destination array was not long enough. Check the destination index, length, and the array's lower bounds."));
+    }
+
+    let (source_index, destination_index, length) = (source_index as usize, destination_index as usize, length as usize);
+    let elements = match self.heap.get(source) {
+      Some(&ManagedObject::Array { ref elements, .. }) => elements[source_index .. source_index + length].to_vec(),
+      _ => vec![]
+    };
+
+    let source_element = element_type(&source_type).map(|element| runtime_type(self.meta, element));
+    let destination_element = element_type(&destination_type).map(|element| runtime_type(self.meta, element));
+    if source_element != destination_element {
+      let (source_element, destination_element) = (source_element.unwrap_or(TypeSig::Void), destination_element.unwrap_or(TypeSig::Void));
+      if !is_reference_type(&source_element) || !is_reference_type(&destination_element) {
+        return Err(self.exception("System.ArrayTypeMismatchException", "Source array type cannot be assigned to destination array type."));
+      }
+      for element in &elements {
+        if let StackValue::Object(Some(object)) = *element {
+          if !self.is_instance_of_type(object, &destination_element) {
+            return Err(self.exception("System.InvalidCastException",
+              "At least one element in the source array could not be cast down to the destination array type."));
+          }
+        }
+      }
+    }
+
+    if let Some(&mut ManagedObject::Array { elements: ref mut target, .. }) = self.heap.get_mut(destination) {
+      for (target, element) in target[destination_index .. destination_index + length].iter_mut().zip(elements) {
+        *target = element;
+      }
+    }
+    Ok(())
+  }
+
+  // Array.Resize<T>(ref T[], int), which replaces the array with a copy of the given length
+  fn resize_array(&mut self, args: &[StackValue]) -> Result<Flow, ExecutionError> {
+    let (pointer, new_size) = match (args.get(0), args.get(1)) {
+      (Some(&StackValue::ManagedPointer(ref pointer)), Some(new_size)) => (pointer.clone(), self.index_arg(new_size)?),
+      _ => return Err(self.invalid("Array.Resize takes a reference to an array and a length".to_string()))
+    };
+    if new_size < 0 {
+      return Err(self.exception("System.ArgumentOutOfRangeException", "Non-negative number required.\nParameter name: newSize"));
+    }
+
+    // The type of the referenced location gives the element type even if the array is null
+    let array_type = match self.location_type(&pointer) {
+      Some(array_type @ TypeSig::SzArray(_)) => array_type,
+      other => return Err(self.invalid(format!("Array.Resize expects a reference to an array, found {:?}", other)))
+    };
+    let old = match self.load(&pointer)? {
+      StackValue::Object(old) => old,
+      other => return Err(self.invalid(format!("Expected an array, found {:?}", other)))
+    };
+    let elements = match old.and_then(|old| self.heap.get(old)) {
+      Some(&ManagedObject::Array { ref elements, .. }) if elements.len() == new_size as usize => return Ok(Flow::Next),
+      Some(&ManagedObject::Array { ref elements, .. }) => elements.iter().take(new_size as usize).cloned().collect::<Vec<_>>(),
+      _ => vec![]
+    };
+
+    let resized = self.new_array(array_type, vec![new_size as usize], vec![0])?;
+    if let Some(&mut ManagedObject::Array { elements: ref mut target, .. }) = self.heap.get_mut(resized) {
+      for (target, element) in target.iter_mut().zip(elements) {
+        *target = element;
+      }
+    }
+    self.store(pointer, StackValue::Object(Some(resized)))?;
+    Ok(Flow::Next)
+  }
+
+  // RuntimeHelpers.InitializeArray, which copies the data of a field with an RVA into an array of a primitive type
+  fn initialize_array(&mut self, args: &[StackValue]) -> Result<Flow, ExecutionError> {
+    let (array, field) = match (args.get(0), args.get(1)) {
+      (Some(&StackValue::Object(Some(array))), Some(&StackValue::FieldHandle(field))) => (array, field),
+      (Some(&StackValue::Object(None)), _) => return Err(argument_null("array")),
+      _ => return Err(self.invalid("RuntimeHelpers.InitializeArray takes an array and a field handle".to_string()))
+    };
+    let (array_type, lengths, _) = self.array_shape(array)?;
+    let element = element_type(&array_type).map(|element| runtime_type(self.meta, element)).unwrap_or(TypeSig::Void);
+
+    let elements = match self.meta.field_data.get(&(field.0 - 1)) {
+      Some(data) => decode_elements(&element, data, lengths.iter().product()),
+      None => None
+    };
+    match elements {
+      Some(elements) => {
+        if let Some(&mut ManagedObject::Array { elements: ref mut target, .. }) = self.heap.get_mut(array) {
+          *target = elements;
+        }
+        Ok(Flow::Next)
+      },
+      None => Err(self.exception("System.ArgumentException", "The field is invalid for initializing array."))
+    }
+  }
+
+  fn load_string(&mut self, token: &Token) -> Result<(), ExecutionError> {
+    let value = match *token {
+      Token::UserString(index) => self.meta.get_user_string_utf16(index),
+      _ => None
+    };
+
+    match value {
+      // String literals are interned, so equal literals are the same object
+      Some(value) => {
+        let object = self.heap.intern(value.to_vec());
+        Ok(self.push(StackValue::Object(Some(object))))
+      },
+      None => Err(self.invalid(format!("Invalid string token {:08x}", token.to_raw())))
+    }
+  }
+
+  fn load_class(&mut self, type_def: &Index<TypeDefEntry>) -> Result<Rc<Class>, ExecutionError> {
+    self.load_instance(type_def, vec![])
+  }
+
+  // The position in `classes` of an instantiation of a generic type, if it's loaded
+  fn instantiation_id(&self, type_def: &Index<TypeDefEntry>, type_args: &[TypeSig]) -> Option<usize> {
+    self.instantiations.get(&type_def.0)
+      .and_then(|instantiations| instantiations.iter().find(|&&(ref args, _)| &args[..] == type_args))
+      .map(|&(_, id)| id)
+  }
+
+  // Loads a class, or the instantiation of a generic type with the given type arguments, which is a
+  // class of its own with its own layout, method table and statics
+  fn load_instance(&mut self, type_def: &Index<TypeDefEntry>, type_args: Vec<TypeSig>) -> Result<Rc<Class>, ExecutionError> {
+    let loaded = if type_args.is_empty() { self.class_ids.get(&type_def.0).cloned() } else { self.instantiation_id(type_def, &type_args) };
+    if let Some(id) = loaded {
+      return Ok(self.classes[id].clone());
+    }
+
+    let extends = self.meta.get_entry(type_def).map(|entry| entry.extends);
+    let parent = match extends {
+      Some(TypeDefOrRef::TypeDef(parent)) if parent.0 != 0 => Some((parent, vec![])),
+      // A generic base class gets its type arguments from the class's
+      Some(ref extends) => generic_type_spec(self.meta, extends)
+        .map(|(parent, parent_args)| (parent, GenericContext::new(type_args.clone(), vec![]).substitute_all(&parent_args))),
+      None => return Err(self.invalid(format!("No such type: {}", type_def.0)))
+    };
+    let parent = match parent {
+      // A base class which is still being loaded derives from the class, directly or not
+      Some((parent, _)) if parent == *type_def || self.loading.contains(&parent.0) =>
+        return Err(self.invalid(format!("{} derives from itself", self.meta.get_type_name(&TypeDefOrRef::TypeDef(parent))))),
+      Some((parent, parent_args)) => {
+        self.loading.push(type_def.0);
+        let parent = self.load_instance(&parent, parent_args);
+        self.loading.pop();
+        Some(parent?)
+      },
+      None => None
+    };
+
+    let id = self.classes.len();
+    let class = Class::new(self.meta, &self.method_impls, id, type_def, type_args.clone(), parent).map_err(|error| self.invalid(error.to_string()))?;
+    let class = Rc::new(class);
+    self.classes.push(class.clone());
+    if type_args.is_empty() {
+      self.class_ids.insert(type_def.0, id);
+    } else {
+      self.instantiations.entry(type_def.0).or_insert_with(Vec::new).push((type_args, id));
+    }
+    Ok(class)
+  }
+
+  // The class of the instantiation of a generic type declaring a member, None for types which aren't generic
+  fn instantiation(&mut self, type_def: &Index<TypeDefEntry>, type_args: &[TypeSig]) -> Result<Option<usize>, ExecutionError> {
+    if type_args.is_empty() {
+      return Ok(None);
+    }
+    Ok(Some(self.load_instance(type_def, type_args.to_vec())?.id))
+  }
+
+  // The class of a value type of the assembly or of an instantiation of a generic one, None for other types
+  fn value_type_class(&mut self, type_: &TypeSig) -> Result<Option<Rc<Class>>, ExecutionError> {
+    let (type_def, type_args) = match *type_ {
+      TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)) => (type_def, vec![]),
+      ref instance => match generic_instance(instance) {
+        Some((type_def, type_args)) if !is_reference_type(instance) => (type_def, type_args.to_vec()),
+        _ => return Ok(None)
+      }
+    };
+    self.load_instance(&type_def, type_args).map(Some)
+  }
+
+  // Like `value_type_class`, for classes which are already loaded
+  fn loaded_value_class(&self, type_: &TypeSig) -> Option<&Rc<Class>> {
+    let id = match *type_ {
+      TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)) => self.class_ids.get(&type_def.0).cloned(),
+      ref instance => match generic_instance(instance) {
+        Some((type_def, type_args)) if !is_reference_type(instance) => self.instantiation_id(&type_def, type_args),
+        _ => None
+      }
+    };
+    id.map(|id| &self.classes[id])
+  }
+
+  /// The zero value of a location of the given type. Value types get an instance with each field set
+  /// to its zero value.
+  fn default_value(&mut self, type_: &TypeSig) -> Result<StackValue, ExecutionError> {
+    let type_ = runtime_type(self.meta, type_);
+    if let Some(value) = StackValue::default_for(&type_) {
+      return Ok(value);
+    }
+
+    if let Some(inner) = nullable_type(self.meta, &type_) {
+      let value = self.default_value(&inner)?;
+      return Ok(StackValue::ValueType(ValueTypeInstance { type_, fields: vec![StackValue::Int32(0), value] }));
+    }
+
+    match self.value_type_class(&type_)? {
+      Some(class) => {
+        let mut fields = vec![];
+        for info in &class.fields {
+          fields.push(self.default_value(&info.type_)?);
+        }
+        Ok(StackValue::ValueType(ValueTypeInstance { type_, fields }))
+      },
+      None => Err(self.invalid(format!("Values of type {} aren't supported", type_.as_csharp(self.meta))))
+    }
+  }
+
+  fn alloc_instance(&mut self, class: &Class) -> Result<ObjectRef, ExecutionError> {
+    let mut fields = vec![];
+    for info in &class.fields {
+      fields.push(self.default_value(&info.type_)?);
+    }
+
+    Ok(self.heap.alloc(ManagedObject::Instance { header: BaseObject { vtable: class.id }, fields }))
+  }
+
+  fn new_object(&mut self, token: &Token) -> Result<Flow, ExecutionError> {
+    match *token {
+      Token::Table(TableId::MethodDef, _) => (),
+      Token::Table(TableId::MemberRef, index) => {
+        // new Nullable<T>(value) needs no constructor call
+        let member = Index::<MemberRefEntry>::new(index);
+        if let Some(nullable) = self.nullable_parent(&member) {
+          let value = self.pop()?;
+          let value = self.nullable_value(nullable, value)?;
+          self.push(value);
+          return Ok(Flow::Next);
+        }
+
+        if let Some(array_type) = self.array_parent(&member) {
+          let signature = self.method_signature(&MethodDefOrRef::MethodRef(member))?;
+          let args = self.pop_args(signature.params.len())?;
+          let array = self.construct_array(array_type, &args)?;
+          self.push(StackValue::Object(Some(array)));
+          return Ok(Flow::Next);
+        }
+
+        if let Some(type_name) = self.exception_parent(&member) {
+          let signature = self.method_signature(&MethodDefOrRef::MethodRef(member))?;
+          let mut args = self.pop_args(signature.params.len())?;
+          let object = self.heap.alloc(ManagedObject::Exception { type_name });
+          args.insert(0, StackValue::Object(Some(object)));
+          self.call_exception(&member, &signature, args)?;
+          self.push(StackValue::Object(Some(object)));
+          return Ok(Flow::Next);
+        }
+      },
+      _ => return Err(self.invalid(format!("Can't construct objects with constructor {:08x}", token.to_raw())))
+    }
+
+    // Constructors of generic types are called through an instantiation
+    let (constructor, context) = match self.method_token(token)? {
+      (MethodDefOrRef::MethodDef(constructor), context) => (constructor, context),
+      _ => return Err(self.invalid(format!("Can't construct objects with constructor {:08x}", token.to_raw())))
+    };
+    if let Some(flow) = self.initialize_for_method(&MethodDefOrRef::MethodDef(constructor), &context)? {
+      return Ok(flow);
+    }
+    let owner = match self.meta.get_method_owner(&constructor) {
+      Some(owner) => owner,
+      None => return Err(self.invalid(format!("The constructor {:08x} has no owner", token.to_raw())))
+    };
+
+    let class = self.load_instance(&owner, context.type_args.clone())?;
+    let callee = self.load_method(&constructor, &context)?;
+    if !callee.signature.has_this() {
+      return Err(self.invalid(format!("{} isn't an instance constructor", callee.name)));
+    }
+
+    let mut args = self.pop_args(callee.arg_types.len() - 1)?;
+    if is_value_type(self.meta, &owner) {
+      // The constructor initializes a temporary through `this`, which is then pushed by value
+      let type_ = instance_type(self.meta, &owner, &context.type_args);
+      let value = self.default_value(&type_)?;
+      let temporary = Pointer::Boxed(self.heap.alloc(ManagedObject::Boxed { type_, value }));
+      args.insert(0, StackValue::ManagedPointer(temporary.clone()));
+      return Ok(Flow::Call(constructor, context, args, Some(Constructed::Value(temporary))));
+    }
+
+    let object = self.alloc_instance(&class)?;
+    args.insert(0, StackValue::Object(Some(object)));
+    Ok(Flow::Call(constructor, context, args, Some(Constructed::Object(object))))
+  }
+
+  // The field a token names, with the class of the instantiation it belongs to for static fields of generic
+  // types. Fields of generic types are named through an instantiation like `List<!0>::count`.
+  fn field_token(&mut self, token: &Token) -> Result<(Index<FieldEntry>, Option<usize>), ExecutionError> {
+    let member = match *token {
+      Token::Table(TableId::Field, index) => return Ok((Index::new(index), None)),
+      Token::Table(TableId::MemberRef, index) => Index::<MemberRefEntry>::new(index),
+      _ => return Err(self.invalid(format!("Can't resolve field token {:08x}", token.to_raw())))
+    };
+
+    match self.instance_field(&member) {
+      // Instance fields have the same slot in all instantiations
+      Some((field, _, _)) if !is_static_field(self.meta, &field) => Ok((field, None)),
+      Some((field, owner, type_args)) => {
+        let type_args = self.instantiate_all(&type_args);
+        Ok((field, self.instantiation(&owner, &type_args)?))
+      },
+      None => Err(self.invalid(format!("Can't resolve field token {:08x}", token.to_raw())))
+    }
+  }
+
+  // The position of a field in the layout of a value type
+  fn value_field_slot(&mut self, value_type: &TypeSig, field: &Index<FieldEntry>) -> Option<usize> {
+    match self.value_type_class(value_type) {
+      Ok(Some(class)) => class.field_slot(field),
+      _ => None
+    }
+  }
+
+  // The location of an instance field of an object, or of the value type instance a managed pointer points to
+  fn field_pointer(&mut self, target: StackValue, field: &Index<FieldEntry>) -> Result<Pointer, ExecutionError> {
+    let slot = match target {
+      StackValue::Object(Some(object)) => {
+        let slot = match self.heap.get(object) {
+          Some(&ManagedObject::Instance { header, .. }) => self.classes[header.vtable].field_slot(field),
+          _ => None
+        };
+        slot.map(|slot| Pointer::Field { object, slot })
+      },
+      StackValue::Object(None) => return Err(ExecutionError::null_reference()),
+      StackValue::ManagedPointer(pointer) => {
+        let slot = match self.value_type_at(&pointer) {
+          Some(value_type) => self.value_field_slot(&value_type, field),
+          None => None
+        };
+        slot.map(|slot| Pointer::ValueField(Box::new(pointer), slot))
+      },
+      other => return Err(self.invalid(format!("Expected an object or a managed pointer, found {:?}", other)))
+    };
+
+    slot.ok_or_else(|| self.invalid(format!("The object has no field {}", field.0)))
+  }
+
+  fn pop_field_pointer(&mut self, token: &Token) -> Result<Pointer, ExecutionError> {
+    let (field, _) = self.field_token(token)?;
+    let target = self.pop()?;
+    self.field_pointer(target, &field)
+  }
+
+  // ldfld, which also reads fields of value type instances on the stack
+  fn load_field(&mut self, token: &Token) -> Result<(), ExecutionError> {
+    let (field, _) = self.field_token(token)?;
+    let value = match self.pop()? {
+      StackValue::ValueType(instance) => {
+        let slot = self.value_field_slot(&instance.type_, &field);
+        match slot.and_then(|slot| instance.fields.into_iter().nth(slot)) {
+          Some(value) => value,
+          None => return Err(self.invalid(format!("The value has no field {}", field.0)))
+        }
+      },
+      target => {
+        let pointer = self.field_pointer(target, &field)?;
+        self.load(&pointer)?
+      }
+    };
+    self.push(value);
+    Ok(())
+  }
+
+  fn init_static_field(&mut self, field: &Index<FieldEntry>, instantiation: Option<usize>) -> Result<(), ExecutionError> {
+    if self.statics.contains_key(&(field.0, instantiation)) {
+      return Ok(());
+    }
+
+    if !is_static_field(self.meta, field) {
+      return Err(self.invalid(format!("Field {} isn't static", field.0)));
+    }
+    let field_type = self.meta.get_field_signature(field).map_err(|error| self.invalid(error.to_string()))?.type_;
+    let type_args = instantiation.map(|id| self.classes[id].type_args.clone()).unwrap_or_default();
+    let field_type = runtime_type(self.meta, &GenericContext::new(type_args, vec![]).substitute(&field_type));
+    let value = self.default_value(&field_type)?;
+    self.statics.insert((field.0, instantiation), (field_type, value));
+    Ok(())
+  }
+
+  fn static_field_pointer(&mut self, token: &Token) -> Result<Pointer, ExecutionError> {
+    let (field, instantiation) = self.field_token(token)?;
+    self.init_static_field(&field, instantiation)?;
+    Ok(Pointer::StaticField(field, instantiation))
+  }
+
+  fn type_token(&self, token: &Token) -> Result<TypeDefOrRef, ExecutionError> {
+    match *token {
+      Token::Table(TableId::TypeDef, index) => Ok(TypeDefOrRef::TypeDef(Index::new(index))),
+      Token::Table(TableId::TypeRef, index) => Ok(TypeDefOrRef::TypeRef(Index::new(index))),
+      Token::Table(TableId::TypeSpec, index) => Ok(TypeDefOrRef::TypeSpec(Index::new(index))),
+      _ => Err(self.invalid(format!("Can't resolve type token {:08x}", token.to_raw())))
+    }
+  }
+
+  // The type named by a type token, as a signature. Type variables are replaced by the arguments of the running method.
+  fn token_type(&self, token: &Token) -> Result<TypeSig, ExecutionError> {
+    Ok(match self.type_token(token)? {
+      TypeDefOrRef::TypeDef(type_def) if is_value_type(self.meta, &type_def) => TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)),
+      TypeDefOrRef::TypeSpec(type_spec) => {
+        let type_ = self.meta.get_type_spec_signature(&type_spec).map_err(|error| self.invalid(error.to_string()))?;
+        self.instantiate(&type_)
+      },
+      // Types from other assemblies are only known to be value types if they're primitive types
+      type_ref @ TypeDefOrRef::TypeRef(_) => match self.meta.get_type_name(&type_ref).as_str() {
+        "System.Object" => TypeSig::Object,
+        "System.String" => TypeSig::String,
+        name => primitive_type(name).unwrap_or(TypeSig::Class(type_ref))
+      },
+      type_def => TypeSig::Class(type_def)
+    })
+  }
+
+  /// The reflection-style name of the type of an object.
+  pub fn object_type_name(&self, object: ObjectRef) -> String {
+    match self.heap.get(object) {
+      Some(&ManagedObject::String(_)) => "System.String".to_string(),
+      Some(&ManagedObject::Instance { header, .. }) => self.classes[header.vtable].name.clone(),
+      Some(&ManagedObject::Exception { ref type_name }) => type_name.clone(),
+      Some(&ManagedObject::Boxed { ref type_, .. }) | Some(&ManagedObject::Array { ref type_, .. }) => reflection_name(self.meta, type_),
+      Some(&ManagedObject::Type { .. }) => "System.RuntimeType".to_string(),
+      None => "System.Object".to_string()
+    }
+  }
+
+  // Whether an object can be cast to the given type
+  fn is_instance_of(&self, object: ObjectRef, type_: &TypeDefOrRef) -> Result<bool, ExecutionError> {
+    // Types from other assemblies can only be checked by name
+    let external_name = match *type_ {
+      TypeDefOrRef::TypeRef(_) => self.meta.get_type_name(type_),
+      TypeDefOrRef::TypeDef(_) => String::new(),
+      TypeDefOrRef::TypeSpec(type_spec) => return match self.meta.get_type_spec_signature(&type_spec) {
+        Ok(ref type_ @ TypeSig::SzArray(_)) | Ok(ref type_ @ TypeSig::Array(..)) | Ok(ref type_ @ TypeSig::GenericInst(..)) |
+          Ok(ref type_ @ TypeSig::Var(_)) | Ok(ref type_ @ TypeSig::MVar(_)) => Ok(self.is_instance_of_type(object, &self.instantiate(type_))),
+        Ok(_) => Err(self.invalid("Casts to type specs other than arrays and generic types aren't supported".to_string())),
+        Err(error) => Err(self.invalid(error.to_string()))
+      }
+    };
+    if external_name == "System.Object" {
+      return Ok(true);
+    }
+
+    let is_enum = |boxed_type: &TypeSig| match *boxed_type {
+      TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)) => enum_underlying_type(self.meta, &type_def).is_some(),
+      _ => false
+    };
+
+    // Value types of the assembly implement interfaces through their class, which is loaded with their first value
+    let implemented_by_value = |boxed_type: &TypeSig| match self.loaded_value_class(boxed_type) {
+      Some(class) => class.implements(self.meta, type_),
+      None => false
+    };
+
+    Ok(match (self.heap.get(object), *type_) {
+      (Some(&ManagedObject::String(_)), _) => external_name == "System.String",
+      (Some(&ManagedObject::Boxed { type_: ref boxed_type, .. }), TypeDefOrRef::TypeDef(type_def)) =>
+        *boxed_type == TypeSig::ValueType(TypeDefOrRef::TypeDef(type_def)) || implemented_by_value(boxed_type),
+      (Some(&ManagedObject::Boxed { type_: ref boxed_type, .. }), _) =>
+        external_name == "System.ValueType" || (external_name == "System.Enum" && is_enum(boxed_type)) ||
+          primitive_type(&external_name).as_ref() == Some(boxed_type) || implemented_by_value(boxed_type),
+      (Some(&ManagedObject::Instance { header, .. }), TypeDefOrRef::TypeDef(type_def)) =>
+        self.classes[header.vtable].is_subclass_of(&type_def) || self.classes[header.vtable].implements(self.meta, type_),
+      (Some(&ManagedObject::Instance { header, .. }), _) => self.classes[header.vtable].implements(self.meta, type_) ||
+        match self.classes[header.vtable].external_base {
+          Some(ref base) => core_type_derives_from(base, &external_name),
+          None => false
+        },
+      (Some(&ManagedObject::Exception { ref type_name }), _) => core_type_derives_from(type_name, &external_name),
+      (Some(&ManagedObject::Array { type_: ref array_type, .. }), _) => is_assignable(self.meta, array_type, &TypeSig::Class(*type_)),
+      (Some(&ManagedObject::Type { .. }), _) => ["System.Type", "System.Reflection.MemberInfo"].contains(&external_name.as_str()),
+      (None, _) => false
+    })
+  }
+
+  // Whether an object can be stored in a location of the given type, like an element of an array
+  fn is_instance_of_type(&self, object: ObjectRef, type_: &TypeSig) -> bool {
+    match (self.heap.get(object), runtime_type(self.meta, type_)) {
+      (_, TypeSig::Object) => true,
+      (Some(&ManagedObject::String(_)), TypeSig::String) => true,
+      (Some(&ManagedObject::Array { type_: ref array_type, .. }), ref type_) => is_assignable(self.meta, array_type, type_),
+      (Some(&ManagedObject::Boxed { type_: ref boxed_type, .. }), ref type_) if primitive_type_name(type_).is_some() =>
+        runtime_type(self.meta, boxed_type) == *type_,
+      // Instantiations of generic types are only compatible with the same instantiation, or a base class's
+      (Some(&ManagedObject::Instance { header, .. }), ref type_) if generic_instance(type_).is_some() => match generic_instance(type_) {
+        Some((type_def, type_args)) => self.classes[header.vtable].is_subclass_of_instance(&type_def, type_args),
+        None => false
+      },
+      (Some(&ManagedObject::Boxed { type_: ref boxed_type, .. }), ref type_ @ TypeSig::GenericInst(..)) => boxed_type == type_ ||
+        match (self.loaded_value_class(boxed_type), generic_instance(type_)) {
+          (Some(class), Some((type_def, type_args))) => class.is_subclass_of_instance(&type_def, type_args),
+          _ => false
+        },
+      (_, TypeSig::Class(ref class)) | (_, TypeSig::ValueType(ref class)) => self.is_instance_of(object, class).unwrap_or(false),
+      _ => false
+    }
+  }
+
+  fn cast(&mut self, token: &Token, throw: bool) -> Result<(), ExecutionError> {
+    let type_ = self.type_token(token)?;
+    let object = match self.pop()? {
+      StackValue::Object(Some(object)) => object,
+      // Null can be cast to any reference type
+      StackValue::Object(None) => return Ok(self.push(StackValue::Object(None))),
+      other => return Err(self.invalid(format!("Expected an object, found {:?}", other)))
+    };
+
+    if self.is_instance_of(object, &type_)? {
+      self.push(StackValue::Object(Some(object)));
+    } else if throw {
+      let message = format!("Unable to cast object of type '{}' to type '{}'.",
+        self.object_type_name(object), reflection_name(self.meta, &self.token_type(token)?));
+      return Err(self.exception("System.InvalidCastException", &message));
+    } else {
+      self.push(StackValue::Object(None));
+    }
+    Ok(())
+  }
+
+  fn pop_object(&mut self) -> Result<Option<ObjectRef>, ExecutionError> {
+    match self.pop()? {
+      StackValue::Object(object) => Ok(object),
+      other => Err(self.invalid(format!("Expected an object, found {:?}", other)))
+    }
+  }
+
+  // box: value types are copied into a new object, and a Nullable<T> boxes its value or becomes null
+  fn box_value(&mut self, type_: TypeSig, value: StackValue) -> Result<StackValue, ExecutionError> {
+    if is_reference_type(&type_) {
+      return Ok(value);
+    }
+
+    if let Some(inner) = nullable_type(self.meta, &type_) {
+      let mut fields = match value {
+        StackValue::ValueType(instance) => instance.fields.into_iter(),
+        other => return Err(self.invalid(format!("Expected a Nullable<T> value, found {:?}", other)))
+      };
+      return match (fields.next(), fields.next()) {
+        (Some(StackValue::Int32(0)), Some(_)) => Ok(StackValue::Object(None)),
+        (Some(StackValue::Int32(_)), Some(value)) => self.box_value(inner, value),
+        _ => Err(self.invalid("Expected a Nullable<T> value".to_string()))
+      };
+    }
+
+    let value = value.store_as(&runtime_type(self.meta, &type_));
+    Ok(StackValue::Object(Some(self.heap.alloc(ManagedObject::Boxed { type_, value }))))
+  }
+
+  // The location of the value in a boxed object, which must be of the given type. Enums and
+  // their underlying type can be unboxed as each other.
+  fn unboxed_pointer(&self, object: Option<ObjectRef>, type_: &TypeSig) -> Result<Pointer, ExecutionError> {
+    let object = object.ok_or_else(ExecutionError::null_reference)?;
+    let matches = match self.heap.get(object) {
+      Some(&ManagedObject::Boxed { type_: ref boxed_type, .. }) => runtime_type(self.meta, boxed_type) == runtime_type(self.meta, type_),
+      _ => false
+    };
+
+    if matches {
+      Ok(Pointer::Boxed(object))
+    } else {
+      Err(self.exception("System.InvalidCastException", "Specified cast is not valid."))
+    }
+  }
+
+  // unbox.any, which is castclass for reference types. A Nullable<T> can be unboxed from null or a boxed T.
+  fn unbox_any(&mut self, token: &Token) -> Result<(), ExecutionError> {
+    let type_ = self.token_type(token)?;
+    if is_reference_type(&type_) {
+      return self.cast(token, true);
+    }
+
+    let object = self.pop_object()?;
+    let value = match nullable_type(self.meta, &type_) {
+      Some(_) if object.is_none() => self.default_value(&type_)?,
+      Some(inner) => {
+        let pointer = self.unboxed_pointer(object, &inner)?;
+        let value = self.load(&pointer)?;
+        self.nullable_value(type_, value)?
+      },
+      None => {
+        let pointer = self.unboxed_pointer(object, &type_)?;
+        self.load(&pointer)?
+      }
+    };
+    self.push(value);
+    Ok(())
+  }
+
+  // The size and alignment of the values of a type, as sizeof gives them. Fields are laid out in
+  // order at their natural alignment, as with sequential layout.
+  fn size_of(&mut self, type_: &TypeSig) -> Result<(usize, usize), ExecutionError> {
+    let type_ = runtime_type(self.meta, type_);
+    let size = match type_ {
+      TypeSig::Boolean | TypeSig::I1 | TypeSig::U1 => 1,
+      TypeSig::Char | TypeSig::I2 | TypeSig::U2 => 2,
+      TypeSig::I4 | TypeSig::U4 | TypeSig::R4 => 4,
+      TypeSig::I8 | TypeSig::U8 | TypeSig::R8 => 8,
+      TypeSig::I | TypeSig::U | TypeSig::Ptr(_) | TypeSig::FnPtr(_) | TypeSig::ByRef(_) => mem::size_of::<usize>(),
+      ref reference if is_reference_type(reference) => mem::size_of::<usize>(),
+      _ => {
+        let field_types = match (nullable_type(self.meta, &type_), &type_) {
+          (Some(inner), _) => vec![TypeSig::Boolean, inner],
+          (None, _) if self.value_type_class(&type_)?.is_some() =>
+            self.value_type_class(&type_)?.map(|class| class.fields.iter().map(|info| info.type_.clone()).collect()).unwrap_or_default(),
+          _ => return Err(self.invalid(format!("The size of {} isn't known", type_.as_csharp(self.meta))))
+        };
+
+        let (mut size, mut alignment) = (0, 1);
+        for field_type in &field_types {
+          let (field_size, field_alignment) = self.size_of(field_type)?;
+          size = align(size, field_alignment) + field_size;
+          alignment = cmp::max(alignment, field_alignment);
+        }
+        // Structs without fields still take a byte
+        return Ok((cmp::max(align(size, alignment), 1), alignment));
+      }
+    };
+    Ok((size, size))
+  }
+
+  fn step(&mut self) -> Result<Flow, ExecutionError> {
+    let code = self.frame().code.clone();
+    let ip = self.frame().ip;
+    let depth = self.frames.len() - 1;
+
+    let instruction = match code.instructions.get(ip) {
+      Some(&(_, ref instruction)) => instruction,
+      None => return Err(self.invalid("Control fell through the end of the method".to_string()))
+    };
+
+    match *instruction {
+      Instruction::Ldsfld(ref token) | Instruction::Ldsflda(ref token) | Instruction::Stsfld(ref token) => {
+        if let Some(flow) = self.initialize_for_field(token)? {
+          return Ok(flow);
+        }
+      },
+      _ => ()
+    }
+
+    match *instruction {
+      Instruction::Nop | Instruction::Break => (),
+
+      Instruction::Ldarg0 => self.load_arg(0)?,
+      Instruction::Ldarg1 => self.load_arg(1)?,
+      Instruction::Ldarg2 => self.load_arg(2)?,
+      Instruction::Ldarg3 => self.load_arg(3)?,
+      Instruction::LdargS(index) => self.load_arg(index as u16)?,
+      Instruction::Ldarg(index) => self.load_arg(index)?,
+      Instruction::LdargaS(index) => self.push(StackValue::ManagedPointer(Pointer::Argument { frame: depth, index: index as u16 })),
+      Instruction::Ldarga(index) => self.push(StackValue::ManagedPointer(Pointer::Argument { frame: depth, index })),
+      Instruction::StargS(index) => {
+        let value = self.pop()?;
+        self.store(Pointer::Argument { frame: depth, index: index as u16 }, value)?;
+      },
+      Instruction::Starg(index) => {
+        let value = self.pop()?;
+        self.store(Pointer::Argument { frame: depth, index }, value)?;
+      },
+
+      Instruction::Ldloc0 => self.load_local(0)?,
+      Instruction::Ldloc1 => self.load_local(1)?,
+      Instruction::Ldloc2 => self.load_local(2)?,
+      Instruction::Ldloc3 => self.load_local(3)?,
+      Instruction::LdlocS(index) => self.load_local(index as u16)?,
+      Instruction::Ldloc(index) => self.load_local(index)?,
+      Instruction::LdlocaS(index) => self.push(StackValue::ManagedPointer(Pointer::Local { frame: depth, index: index as u16 })),
+      Instruction::Ldloca(index) => self.push(StackValue::ManagedPointer(Pointer::Local { frame: depth, index })),
+      Instruction::Stloc0 | Instruction::Stloc1 | Instruction::Stloc2 | Instruction::Stloc3 |
+      Instruction::StlocS(_) | Instruction::Stloc(_) => {
+        let index = match *instruction {
+          Instruction::Stloc0 => 0,
+          Instruction::Stloc1 => 1,
+          Instruction::Stloc2 => 2,
+          Instruction::Stloc3 => 3,
+          Instruction::StlocS(index) => index as u16,
+          Instruction::Stloc(index) => index,
+          _ => unreachable!()
+        };
+        let value = self.pop()?;
+        self.store(Pointer::Local { frame: depth, index }, value)?;
+      },
+
+      Instruction::Ldnull => self.push(StackValue::Object(None)),
+      Instruction::LdcI4M1 => self.push(StackValue::Int32(-1)),
+      Instruction::LdcI40 => self.push(StackValue::Int32(0)),
+      Instruction::LdcI41 => self.push(StackValue::Int32(1)),
+      Instruction::LdcI42 => self.push(StackValue::Int32(2)),
+      Instruction::LdcI43 => self.push(StackValue::Int32(3)),
+      Instruction::LdcI44 => self.push(StackValue::Int32(4)),
+      Instruction::LdcI45 => self.push(StackValue::Int32(5)),
+      Instruction::LdcI46 => self.push(StackValue::Int32(6)),
+      Instruction::LdcI47 => self.push(StackValue::Int32(7)),
+      Instruction::LdcI48 => self.push(StackValue::Int32(8)),
+      Instruction::LdcI4S(value) => self.push(StackValue::Int32(value as i32)),
+      Instruction::LdcI4(value) => self.push(StackValue::Int32(value)),
+      Instruction::LdcI8(value) => self.push(StackValue::Int64(value)),
+      Instruction::LdcR4(value) => self.push(StackValue::Float(value as f64)),
+      Instruction::LdcR8(value) => self.push(StackValue::Float(value)),
+
+      Instruction::Dup => {
+        let value = self.pop()?;
+        self.push(value.clone());
+        self.push(value);
+      },
+      Instruction::Pop => {
+        self.pop()?;
+      },
+
+      Instruction::Call(ref token) => return self.call(token),
+      Instruction::Callvirt(ref token) => return self.call_virtual(token),
+      Instruction::Calli(_) => return self.call_indirect(),
+      Instruction::Ldftn(ref token) => self.load_function(token, false)?,
+      Instruction::Ldvirtftn(ref token) => self.load_function(token, true)?,
+      Instruction::Constrained(ref token) => {
+        // The prefix only applies to the callvirt right after it, which takes it from the frame
+        match code.instructions.get(ip + 1) {
+          Some(&(_, Instruction::Callvirt(_))) => (),
+          _ => return Err(self.invalid("constrained. must be followed by callvirt".to_string()))
+        }
+        let type_ = self.token_type(token)?;
+        self.frame().constrained = Some(type_);
+      },
+      Instruction::Ldstr(ref token) => self.load_string(token)?,
+      Instruction::Newobj(ref token) => return self.new_object(token),
+
+      Instruction::Ldfld(ref token) => self.load_field(token)?,
+      Instruction::Ldflda(ref token) => {
+        let pointer = self.pop_field_pointer(token)?;
+        self.push(StackValue::ManagedPointer(pointer));
+      },
+      Instruction::Stfld(ref token) => {
+        let value = self.pop()?;
+        let pointer = self.pop_field_pointer(token)?;
+        self.store(pointer, value)?;
+      },
+      Instruction::Ldsfld(ref token) => {
+        let pointer = self.static_field_pointer(token)?;
+        let value = self.load(&pointer)?;
+        self.push(value);
+      },
+      Instruction::Ldsflda(ref token) => {
+        let pointer = self.static_field_pointer(token)?;
+        self.push(StackValue::ManagedPointer(pointer));
+      },
+      Instruction::Stsfld(ref token) => {
+        let value = self.pop()?;
+        let pointer = self.static_field_pointer(token)?;
+        self.store(pointer, value)?;
+      },
+      Instruction::Isinst(ref token) => self.cast(token, false)?,
+      Instruction::Castclass(ref token) => self.cast(token, true)?,
+
+      Instruction::Initobj(ref token) => {
+        let type_ = self.token_type(token)?;
+        let value = self.default_value(&type_)?;
+        let pointer = self.pop_pointer()?;
+        self.store(pointer, value)?;
+      },
+      Instruction::Ldobj(ref token) => {
+        self.token_type(token)?;
+        let pointer = self.pop_pointer()?;
+        let value = self.load(&pointer)?;
+        self.push(value);
+      },
+      Instruction::Stobj(ref token) => {
+        let type_ = runtime_type(self.meta, &self.token_type(token)?);
+        let value = self.pop()?;
+        let pointer = self.pop_pointer()?;
+        self.store(pointer, value.store_as(&type_))?;
+      },
+      Instruction::Cpobj(ref token) => {
+        self.token_type(token)?;
+        let source = self.pop_pointer()?;
+        let destination = self.pop_pointer()?;
+        let value = self.load(&source)?;
+        self.store(destination, value)?;
+      },
+      Instruction::Box(ref token) => {
+        let type_ = self.token_type(token)?;
+        let value = self.pop()?;
+        let boxed = self.box_value(type_, value)?;
+        self.push(boxed);
+      },
+      Instruction::Unbox(ref token) => {
+        let type_ = self.token_type(token)?;
+        if nullable_type(self.meta, &type_).is_some() {
+          return Err(self.invalid("Unboxing to a pointer to Nullable<T> isn't supported".to_string()));
+        }
+        let object = self.pop_object()?;
+        let pointer = self.unboxed_pointer(object, &type_)?;
+        self.push(StackValue::ManagedPointer(pointer));
+      },
+      Instruction::UnboxAny(ref token) => self.unbox_any(token)?,
+      Instruction::Ldtoken(ref token) => match *token {
+        Token::Table(TableId::Field, index) => self.push(StackValue::FieldHandle(Index::new(index))),
+        Token::Table(TableId::TypeDef, _) | Token::Table(TableId::TypeRef, _) | Token::Table(TableId::TypeSpec, _) => {
+          let type_ = self.token_type(token)?;
+          self.push(StackValue::TypeHandle(type_));
+        },
+        _ => return Err(self.invalid(format!("ldtoken of {:08x} isn't supported", token.to_raw())))
+      },
+
+      Instruction::Newarr(ref token) => {
+        let element_type = self.token_type(token)?;
+        let length = self.pop_index()?;
+        if length < 0 {
+          return Err(self.arithmetic_error(ArithmeticError::Overflow));
+        }
+        let array = self.new_array(TypeSig::SzArray(Box::new(element_type)), vec![length as usize], vec![0])?;
+        self.push(StackValue::Object(Some(array)));
+      },
+      Instruction::Ldlen => {
+        let array = self.pop_array()?;
+        let (_, lengths, _) = self.array_shape(array)?;
+        self.push(StackValue::NativeInt(lengths.iter().product::<usize>() as isize));
+      },
+      Instruction::Readonly => self.frame().readonly = true,
+      Instruction::Ldelema(ref token) => self.element_address(token)?,
+      Instruction::LdelemI1 => self.load_element(Some(TypeSig::I1))?,
+      Instruction::LdelemU1 => self.load_element(Some(TypeSig::U1))?,
+      Instruction::LdelemI2 => self.load_element(Some(TypeSig::I2))?,
+      Instruction::LdelemU2 => self.load_element(Some(TypeSig::U2))?,
+      Instruction::LdelemI4 => self.load_element(Some(TypeSig::I4))?,
+      Instruction::LdelemU4 => self.load_element(Some(TypeSig::U4))?,
+      Instruction::LdelemI8 => self.load_element(Some(TypeSig::I8))?,
+      Instruction::LdelemI => self.load_element(Some(TypeSig::I))?,
+      Instruction::LdelemR4 => self.load_element(Some(TypeSig::R4))?,
+      Instruction::LdelemR8 => self.load_element(Some(TypeSig::R8))?,
+      Instruction::LdelemRef => self.load_element(None)?,
+      Instruction::Ldelem(ref token) => {
+        let type_ = runtime_type(self.meta, &self.token_type(token)?);
+        self.load_element(Some(type_))?;
+      },
+      Instruction::StelemI => self.store_element(Some(TypeSig::I))?,
+      Instruction::StelemI1 => self.store_element(Some(TypeSig::I1))?,
+      Instruction::StelemI2 => self.store_element(Some(TypeSig::I2))?,
+      Instruction::StelemI4 => self.store_element(Some(TypeSig::I4))?,
+      Instruction::StelemI8 => self.store_element(Some(TypeSig::I8))?,
+      Instruction::StelemR4 => self.store_element(Some(TypeSig::R4))?,
+      Instruction::StelemR8 => self.store_element(Some(TypeSig::R8))?,
+      Instruction::StelemRef => self.store_element(None)?,
+      Instruction::Stelem(ref token) => {
+        let type_ = runtime_type(self.meta, &self.token_type(token)?);
+        self.store_element(Some(type_))?;
+      },
+      Instruction::Sizeof(ref token) => {
+        let type_ = self.token_type(token)?;
+        let (size, _) = self.size_of(&type_)?;
+        self.push(StackValue::Int32(size as i32));
+      },
+
+      Instruction::Ret => {
+        let value = if code.signature.return_type == TypeSig::Void {
+          None
+        } else {
+          let value = self.pop()?;
+          Some(value.store_as(&code.signature.return_type))
+        };
+
+        if !self.frame().stack.is_empty() {
+          return Err(self.invalid("The stack must be empty after ret".to_string()));
+        }
+        return Ok(Flow::Return(value));
+      },
+
+      Instruction::Br(target) | Instruction::BrS(target) => return Ok(Flow::Branch(target)),
+
+      Instruction::Throw => return match self.pop_object()? {
+        Some(exception) => Ok(Flow::Throw(exception, false)),
+        None => Err(ExecutionError::null_reference())
+      },
+      Instruction::Rethrow => {
+        let offset = self.offset();
+        let exception = self.frame().caught.iter().rev()
+          .find(|&&(clause, _)| clause.handler_contains(offset))
+          .map(|&(_, exception)| exception);
+        return match exception {
+          Some(exception) => Ok(Flow::Throw(exception, true)),
+          None => Err(self.invalid("rethrow outside of a catch handler".to_string()))
+        };
+      },
+      Instruction::Leave(target) | Instruction::LeaveS(target) => return self.leave(target),
+      Instruction::Endfinally => return self.end_finally(),
+      // Filters run in a frame of their own, which returns whether the filter accepts the exception
+      Instruction::Endfilter => {
+        let value = self.pop()?;
+        if let Some(method_depth) = self.frame().filter_of {
+          let (locals, args) = (self.frame().locals.clone(), self.frame().args.clone());
+          self.frames[method_depth].locals = locals;
+          self.frames[method_depth].args = args;
+        }
+        return Ok(Flow::Return(Some(value)));
+      },
+      Instruction::Brfalse(target) | Instruction::BrfalseS(target) | Instruction::Brtrue(target) | Instruction::BrtrueS(target) => {
+        let value = self.pop()?;
+        let is_true = value.is_true().map_err(|error| self.arithmetic_error(error))?;
+        let branch_if = match *instruction {
+          Instruction::Brtrue(_) | Instruction::BrtrueS(_) => true,
+          _ => false
+        };
+        if is_true == branch_if {
+          return Ok(Flow::Branch(target));
+        }
+      },
+      Instruction::Beq(target) | Instruction::BeqS(target) => return self.branch_if(CompareOp::Eq, target),
+      Instruction::BneUn(target) | Instruction::BneUnS(target) => {
+        let equal = self.compare(CompareOp::Eq)?;
+        if !equal {
+          return Ok(Flow::Branch(target));
+        }
+      },
+      Instruction::Bge(target) | Instruction::BgeS(target) => return self.branch_if(CompareOp::Ge, target),
+      Instruction::BgeUn(target) | Instruction::BgeUnS(target) => return self.branch_if(CompareOp::GeUn, target),
+      Instruction::Bgt(target) | Instruction::BgtS(target) => return self.branch_if(CompareOp::Gt, target),
+      Instruction::BgtUn(target) | Instruction::BgtUnS(target) => return self.branch_if(CompareOp::GtUn, target),
+      Instruction::Ble(target) | Instruction::BleS(target) => return self.branch_if(CompareOp::Le, target),
+      Instruction::BleUn(target) | Instruction::BleUnS(target) => return self.branch_if(CompareOp::LeUn, target),
+      Instruction::Blt(target) | Instruction::BltS(target) => return self.branch_if(CompareOp::Lt, target),
+      Instruction::BltUn(target) | Instruction::BltUnS(target) => return self.branch_if(CompareOp::LtUn, target),
+      Instruction::Switch(ref targets) => {
+        let value = match self.pop()? {
+          StackValue::Int32(value) => value as u32 as usize,
+          StackValue::NativeInt(value) => value as usize,
+          other => return Err(self.invalid(format!("Expected an integer for switch, found {:?}", other)))
+        };
+        // Out of range values, including negative ones, fall through
+        if let Some(&target) = targets.get(value) {
+          return Ok(Flow::Branch(target));
+        }
+      },
+
+      Instruction::Ceq | Instruction::Cgt | Instruction::CgtUn | Instruction::Clt | Instruction::CltUn => {
+        let op = match *instruction {
+          Instruction::Ceq => CompareOp::Eq,
+          Instruction::Cgt => CompareOp::Gt,
+          Instruction::CgtUn => CompareOp::GtUn,
+          Instruction::Clt => CompareOp::Lt,
+          _ => CompareOp::LtUn
+        };
+        let result = self.compare(op)?;
+        self.push(StackValue::Int32(result as i32));
+      },
+
+      Instruction::Add => self.binary(BinaryOp::Add)?,
+      Instruction::Sub => self.binary(BinaryOp::Sub)?,
+      Instruction::Mul => self.binary(BinaryOp::Mul)?,
+      Instruction::Div => self.binary(BinaryOp::Div)?,
+      Instruction::DivUn => self.binary(BinaryOp::DivUn)?,
+      Instruction::Rem => self.binary(BinaryOp::Rem)?,
+      Instruction::RemUn => self.binary(BinaryOp::RemUn)?,
+      Instruction::And => self.binary(BinaryOp::And)?,
+      Instruction::Or => self.binary(BinaryOp::Or)?,
+      Instruction::Xor => self.binary(BinaryOp::Xor)?,
+      Instruction::AddOvf => self.binary(BinaryOp::AddOvf)?,
+      Instruction::AddOvfUn => self.binary(BinaryOp::AddOvfUn)?,
+      Instruction::SubOvf => self.binary(BinaryOp::SubOvf)?,
+      Instruction::SubOvfUn => self.binary(BinaryOp::SubOvfUn)?,
+      Instruction::MulOvf => self.binary(BinaryOp::MulOvf)?,
+      Instruction::MulOvfUn => self.binary(BinaryOp::MulOvfUn)?,
+      Instruction::Shl | Instruction::Shr | Instruction::ShrUn => {
+        let op = match *instruction {
+          Instruction::Shl => ShiftOp::Shl,
+          Instruction::Shr => ShiftOp::Shr,
+          _ => ShiftOp::ShrUn
+        };
+        let amount = self.pop()?;
+        let value = self.pop()?;
+        let result = StackValue::shift(op, value, amount).map_err(|error| self.arithmetic_error(error))?;
+        self.push(result);
+      },
+      Instruction::Neg | Instruction::Not => {
+        let value = self.pop()?;
+        let result = match *instruction {
+          Instruction::Neg => value.neg(),
+          _ => value.not()
+        };
+        let result = result.map_err(|error| self.arithmetic_error(error))?;
+        self.push(result);
+      },
+      Instruction::Ckfinite => {
+        let value = self.pop()?;
+        match value {
+          StackValue::Float(value) if value.is_finite() => self.push(StackValue::Float(value)),
+          StackValue::Float(_) => return Err(self.exception("System.ArithmeticException", "Function does not accept floating point Not-a-Number values.")),
+          other => return Err(self.invalid(format!("Expected a float for ckfinite, found {:?}", other)))
+        }
+      },
+
+      Instruction::ConvI1 => self.convert(Conversion::I1, false, false)?,
+      Instruction::ConvI2 => self.convert(Conversion::I2, false, false)?,
+      Instruction::ConvI4 => self.convert(Conversion::I4, false, false)?,
+      Instruction::ConvI8 => self.convert(Conversion::I8, false, false)?,
+      Instruction::ConvU1 => self.convert(Conversion::U1, false, false)?,
+      Instruction::ConvU2 => self.convert(Conversion::U2, false, false)?,
+      Instruction::ConvU4 => self.convert(Conversion::U4, false, false)?,
+      Instruction::ConvU8 => self.convert(Conversion::U8, false, false)?,
+      Instruction::ConvI => self.convert(Conversion::I, false, false)?,
+      Instruction::ConvU => self.convert(Conversion::U, false, false)?,
+      Instruction::ConvR4 => self.convert(Conversion::R4, false, false)?,
+      Instruction::ConvR8 => self.convert(Conversion::R8, false, false)?,
+      Instruction::ConvRUn => self.convert(Conversion::RUn, false, true)?,
+      Instruction::ConvOvfI1 => self.convert(Conversion::I1, true, false)?,
+      Instruction::ConvOvfI2 => self.convert(Conversion::I2, true, false)?,
+      Instruction::ConvOvfI4 => self.convert(Conversion::I4, true, false)?,
+      Instruction::ConvOvfI8 => self.convert(Conversion::I8, true, false)?,
+      Instruction::ConvOvfU1 => self.convert(Conversion::U1, true, false)?,
+      Instruction::ConvOvfU2 => self.convert(Conversion::U2, true, false)?,
+      Instruction::ConvOvfU4 => self.convert(Conversion::U4, true, false)?,
+      Instruction::ConvOvfU8 => self.convert(Conversion::U8, true, false)?,
+      Instruction::ConvOvfI => self.convert(Conversion::I, true, false)?,
+      Instruction::ConvOvfU => self.convert(Conversion::U, true, false)?,
+      Instruction::ConvOvfI1Un => self.convert(Conversion::I1, true, true)?,
+      Instruction::ConvOvfI2Un => self.convert(Conversion::I2, true, true)?,
+      Instruction::ConvOvfI4Un => self.convert(Conversion::I4, true, true)?,
+      Instruction::ConvOvfI8Un => self.convert(Conversion::I8, true, true)?,
+      Instruction::ConvOvfU1Un => self.convert(Conversion::U1, true, true)?,
+      Instruction::ConvOvfU2Un => self.convert(Conversion::U2, true, true)?,
+      Instruction::ConvOvfU4Un => self.convert(Conversion::U4, true, true)?,
+      Instruction::ConvOvfU8Un => self.convert(Conversion::U8, true, true)?,
+      Instruction::ConvOvfIUn => self.convert(Conversion::I, true, true)?,
+      Instruction::ConvOvfUUn => self.convert(Conversion::U, true, true)?,
+
+      Instruction::LdindI1 => self.load_indirect(TypeSig::I1)?,
+      Instruction::LdindU1 => self.load_indirect(TypeSig::U1)?,
+      Instruction::LdindI2 => self.load_indirect(TypeSig::I2)?,
+      Instruction::LdindU2 => self.load_indirect(TypeSig::U2)?,
+      Instruction::LdindI4 => self.load_indirect(TypeSig::I4)?,
+      Instruction::LdindU4 => self.load_indirect(TypeSig::U4)?,
+      Instruction::LdindI8 => self.load_indirect(TypeSig::I8)?,
+      Instruction::LdindI => self.load_indirect(TypeSig::I)?,
+      Instruction::LdindR4 => self.load_indirect(TypeSig::R4)?,
+      Instruction::LdindR8 => self.load_indirect(TypeSig::R8)?,
+      Instruction::LdindRef => self.load_indirect(TypeSig::Object)?,
+      Instruction::StindI1 => self.store_indirect(TypeSig::I1)?,
+      Instruction::StindI2 => self.store_indirect(TypeSig::I2)?,
+      Instruction::StindI4 => self.store_indirect(TypeSig::I4)?,
+      Instruction::StindI8 => self.store_indirect(TypeSig::I8)?,
+      Instruction::StindI => self.store_indirect(TypeSig::I)?,
+      Instruction::StindR4 => self.store_indirect(TypeSig::R4)?,
+      Instruction::StindR8 => self.store_indirect(TypeSig::R8)?,
+      Instruction::StindRef => self.store_indirect(TypeSig::Object)?,
+
+      ref other => return Err(self.invalid(format!("{} isn't supported", other.mnemonic())))
+    }
+
+    Ok(Flow::Next)
+  }
+}
+
+// The offset of the instruction a frame is running, which is the call for frames below the top one
+fn frame_offset(frame: &Frame) -> u32 {
+  frame.code.instructions.get(frame.ip).map(|&(offset, _)| offset).unwrap_or(0)
+}
+
+// ldind and ldelem: small integers are sign or zero extended according to the instruction
+fn extend(value: StackValue, type_sig: &TypeSig) -> StackValue {
+  match (value, type_sig) {
+    (StackValue::Int32(value), &TypeSig::I1) => StackValue::Int32(value as i8 as i32),
+    (StackValue::Int32(value), &TypeSig::U1) => StackValue::Int32(value as u8 as i32),
+    (StackValue::Int32(value), &TypeSig::I2) => StackValue::Int32(value as i16 as i32),
+    (StackValue::Int32(value), &TypeSig::U2) => StackValue::Int32(value as u16 as i32),
+    (value, _) => value
+  }
+}
+
+// Rounds an offset up to a multiple of the alignment
+fn align(offset: usize, alignment: usize) -> usize {
+  (offset + alignment - 1) / alignment * alignment
+}
+
+// The storage a pointer refers to. This takes the parts of the interpreter it needs separately, so that
+// the result only borrows them.
+fn location_mut<'s>(frames: &'s mut [Frame], heap: &'s mut Heap, statics: &'s mut HashMap<(u32, Option<usize>), (TypeSig, StackValue)>,
+  pointer: &Pointer) -> Option<&'s mut StackValue> {
+  match *pointer {
+    Pointer::Local { frame, index } => frames.get_mut(frame).and_then(|frame| frame.locals.get_mut(index as usize)),
+    Pointer::Argument { frame, index } => frames.get_mut(frame).and_then(|frame| frame.args.get_mut(index as usize)),
+    Pointer::Field { object, slot } => match heap.get_mut(object) {
+      Some(&mut ManagedObject::Instance { ref mut fields, .. }) => fields.get_mut(slot),
+      _ => None
+    },
+    Pointer::StaticField(field, instantiation) => statics.get_mut(&(field.0, instantiation)).map(|&mut (_, ref mut value)| value),
+    Pointer::ValueField(ref inner, slot) => match location_mut(frames, heap, statics, inner) {
+      Some(&mut StackValue::ValueType(ref mut instance)) => instance.fields.get_mut(slot),
+      _ => None
+    },
+    Pointer::Boxed(object) => match heap.get_mut(object) {
+      Some(&mut ManagedObject::Boxed { ref mut value, .. }) => Some(value),
+      _ => None
+    },
+    Pointer::Element { array, index } => match heap.get_mut(array) {
+      Some(&mut ManagedObject::Array { ref mut elements, .. }) => elements.get_mut(index),
+      _ => None
+    }
+  }
+}
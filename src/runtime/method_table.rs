@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+
+use metadata::Metadata;
+use metadata::signature::{TypeSig, MethodSignature};
+use metadata::tables::*;
+use runtime::generics::*;
+use runtime::internal_calls::signature_key;
+
+// CorMethodAttr
+const MD_FINAL: u16 = 0x0020;
+const MD_VIRTUAL: u16 = 0x0040;
+const MD_NEW_SLOT: u16 = 0x0100;
+const MD_ABSTRACT: u16 = 0x0400;
+
+/// A virtual method slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+  /// The method which introduced the slot
+  pub declaration: Index<MethodDefEntry>,
+  /// The method which calls through the slot run on instances of the class
+  pub implementation: Index<MethodDefEntry>
+}
+
+/// The virtual methods of a class and the implementations of the interfaces it implements.
+#[derive(Debug, Clone, Default)]
+pub struct MethodTable {
+  /// The slots of the base classes come first, so that a slot has the same position in all derived classes
+  pub slots: Vec<Slot>,
+  // Maps each virtual method of the class and its base classes to its slot
+  slot_indices: HashMap<u32, usize>,
+  /// The implemented interfaces, including the ones of the base classes and the ones extended by other interfaces
+  pub interfaces: Vec<TypeDefOrRef>,
+  // Maps the methods of interfaces defined in the assembly to the methods implementing them
+  interface_methods: HashMap<u32, Index<MethodDefEntry>>,
+  // The explicit overrides of the MethodImpl table for the class and its base classes, by declaration
+  explicit_overrides: HashMap<MethodDefOrRef, Index<MethodDefEntry>>,
+  // The type arguments of the generic base classes and interfaces of the assembly, by TypeDef index. They're
+  // given in terms of the class's own type parameters, like `Base<!0>`, as all instantiations share the table.
+  type_args: HashMap<u32, Vec<TypeSig>>
+}
+
+fn method_flags(meta: &Metadata, method: &Index<MethodDefEntry>) -> u16 {
+  meta.get_entry(method).map(|entry| entry.flags).unwrap_or(0)
+}
+
+/// Whether a method is virtual, which includes all methods of interfaces except static ones.
+pub fn is_virtual(meta: &Metadata, method: &Index<MethodDefEntry>) -> bool {
+  method_flags(meta, method) & MD_VIRTUAL != 0
+}
+
+/// Whether a method is abstract, so that it has no body and can only be called through a method table.
+pub fn is_abstract(meta: &Metadata, method: &Index<MethodDefEntry>) -> bool {
+  method_flags(meta, method) & MD_ABSTRACT != 0
+}
+
+// Instantiations of generic types of the assembly are replaced by their definition and type arguments
+fn generic_definition(meta: &Metadata, type_: TypeDefOrRef) -> (TypeDefOrRef, Vec<TypeSig>) {
+  match generic_type_spec(meta, &type_) {
+    Some((type_def, type_args)) => (TypeDefOrRef::TypeDef(type_def), type_args),
+    None => (type_, vec![])
+  }
+}
+
+// The interfaces declared by a type, followed by the interfaces they extend, with their type arguments
+fn declared_interfaces(meta: &Metadata, type_def: &Index<TypeDefEntry>) -> Vec<(TypeDefOrRef, Vec<TypeSig>)> {
+  let mut interfaces = meta.get_interfaces(type_def).into_iter().map(|interface| generic_definition(meta, interface)).collect::<Vec<_>>();
+  let mut position = 0;
+  while position < interfaces.len() {
+    if let (TypeDefOrRef::TypeDef(interface), ref type_args) = interfaces[position].clone() {
+      // The interfaces extended by a generic interface refer to its type parameters
+      let context = GenericContext::new(type_args.clone(), vec![]);
+      for base in meta.get_interfaces(&interface) {
+        let (base, base_args) = generic_definition(meta, base);
+        if !interfaces.iter().any(|&(other, _)| other == base) {
+          interfaces.push((base, context.substitute_all(&base_args)));
+        }
+      }
+    }
+    position += 1;
+  }
+  interfaces
+}
+
+/// The rows of the MethodImpl table grouped by the type declaring them, so that building a method table
+/// doesn't scan the whole table for the class and each of its interfaces.
+#[derive(Debug, Clone, Default)]
+pub struct MethodImpls {
+  // (declaration, body) by TypeDef index
+  by_class: HashMap<u32, Vec<(MethodDefOrRef, MethodDefOrRef)>>
+}
+
+impl MethodImpls {
+  pub fn new(meta: &Metadata) -> MethodImpls {
+    let mut by_class = HashMap::new();
+    for entry in meta.get_table::<MethodImplEntry>().map(|entries| &entries[..]).unwrap_or(&[]) {
+      // Methods of generic interfaces are declared through an instantiation, like `IComparer<!0>::Compare`
+      let declaration = match entry.method_declaration {
+        MethodDefOrRef::MethodRef(member) => instantiated_method(meta, &member)
+          .map(|(method, _)| MethodDefOrRef::MethodDef(method))
+          .unwrap_or(entry.method_declaration),
+        declaration => declaration
+      };
+      by_class.entry(entry.class.0).or_insert_with(Vec::new).push((declaration, entry.method_body));
+    }
+    MethodImpls { by_class }
+  }
+
+  // The MethodImpl rows of a type, as (declaration, body)
+  fn of(&self, meta: &Metadata, type_def: &Index<TypeDefEntry>) -> Result<Vec<(MethodDefOrRef, Index<MethodDefEntry>)>> {
+    let mut res = vec![];
+    for &(declaration, body) in self.by_class.get(&type_def.0).map(|rows| &rows[..]).unwrap_or(&[]) {
+      match body {
+        MethodDefOrRef::MethodDef(body) => res.push((declaration, body)),
+        MethodDefOrRef::MethodRef(_) => return Err(Error::new(ErrorKind::InvalidData,
+          format!("The overrides of {} must be defined in the assembly", meta.get_type_name(&TypeDefOrRef::TypeDef(*type_def)))))
+      }
+    }
+    Ok(res)
+  }
+}
+
+// The names and signatures of the methods compared while building a table, which are only parsed once.
+// None for methods whose signature can't be read, which don't match any other method.
+type Signatures = HashMap<u32, Option<(String, MethodSignature)>>;
+
+impl MethodTable {
+  /// Builds the method table of a type from the one of its base class, which is None for types
+  /// deriving from a type outside the assembly.
+  pub fn new(meta: &Metadata, impls: &MethodImpls, type_def: &Index<TypeDefEntry>, parent: Option<&MethodTable>) -> Result<MethodTable> {
+    let mut table = parent.cloned().unwrap_or_default();
+    let mut signatures = Signatures::new();
+
+    // A generic base class gets its arguments from the class, and passes them on to the types it got its own from
+    let base = meta.get_entry(type_def).and_then(|entry| generic_type_spec(meta, &entry.extends));
+    if let Some((base, base_args)) = base {
+      let context = GenericContext::new(base_args.clone(), vec![]);
+      for type_args in table.type_args.values_mut() {
+        *type_args = context.substitute_all(type_args);
+      }
+      table.type_args.insert(base.0, base_args);
+    }
+
+    // Virtual methods override the slot of a method with the same name and signature, unless they're newslot
+    for index in meta.get_method_range(type_def) {
+      let method = Index::<MethodDefEntry>::new(index);
+      let flags = method_flags(meta, &method);
+      if flags & MD_VIRTUAL == 0 {
+        continue;
+      }
+
+      let overridden = if flags & MD_NEW_SLOT != 0 {
+        None
+      } else {
+        table.slots.iter().rposition(|slot|
+          table.same_name_and_signature(meta, &mut signatures, &slot.implementation, &method) ||
+            table.same_name_and_signature(meta, &mut signatures, &slot.declaration, &method))
+      };
+      match overridden {
+        Some(slot) => table.override_slot(meta, slot, method)?,
+        None => {
+          table.slot_indices.insert(method.0, table.slots.len());
+          table.slots.push(Slot { declaration: method, implementation: method });
+        }
+      }
+    }
+
+    // Explicit overrides, which can also override methods with another name
+    let own_overrides = impls.of(meta, type_def)?;
+    for &(declaration, body) in &own_overrides {
+      table.explicit_overrides.insert(declaration, body);
+      if let MethodDefOrRef::MethodDef(declaration) = declaration {
+        if let Some(&slot) = table.slot_indices.get(&declaration.0) {
+          table.override_slot(meta, slot, body)?;
+        }
+      }
+    }
+
+    let declared = declared_interfaces(meta, type_def);
+    for &(interface, ref type_args) in &declared {
+      if !table.interfaces.contains(&interface) {
+        table.interfaces.push(interface);
+      }
+      if let TypeDefOrRef::TypeDef(interface) = interface {
+        if !type_args.is_empty() {
+          table.type_args.insert(interface.0, type_args.clone());
+        }
+      }
+    }
+
+    let defaults = table.default_implementations(meta, impls)?;
+    for interface in table.interfaces.clone() {
+      let interface = match interface {
+        TypeDefOrRef::TypeDef(interface) => interface,
+        // The methods of other assemblies' interfaces are found by name when they're called
+        _ => continue
+      };
+      // Implicit implementations are only looked for again when the class declares the interface itself
+      let redeclared = declared.iter().any(|&(other, _)| other == TypeDefOrRef::TypeDef(interface));
+
+      for index in meta.get_method_range(&interface) {
+        let method = Index::<MethodDefEntry>::new(index);
+        if !is_virtual(meta, &method) {
+          continue;
+        }
+
+        let explicit = own_overrides.iter()
+          .find(|&&(declaration, _)| declaration == MethodDefOrRef::MethodDef(method))
+          .map(|&(_, body)| body);
+        let inherited = table.interface_methods.get(&method.0).cloned();
+
+        let implementation = match (explicit, inherited) {
+          (Some(body), _) => Some(body),
+          (None, Some(inherited)) if !redeclared => Some(inherited),
+          (None, inherited) => table.slots.iter().rev()
+            .find(|slot| table.same_name_and_signature(meta, &mut signatures, &slot.implementation, &method))
+            .map(|slot| slot.implementation)
+            .or(inherited)
+        };
+        let implementation = match implementation {
+          Some(implementation) => Some(implementation),
+          // The most specific override in an interface extending the method's interface, or its own body
+          None => match defaults.get(&MethodDefOrRef::MethodDef(method)) {
+            Some(&body) => Some(body),
+            None => if is_abstract(meta, &method) { None } else { Some(method) }
+          }
+        };
+        if let Some(implementation) = implementation {
+          table.interface_methods.insert(method.0, implementation);
+        }
+      }
+    }
+
+    Ok(table)
+  }
+
+  // The signature of a method of the class or one of its base classes or interfaces, in terms of the class's
+  // own type parameters
+  fn signature_of(&self, meta: &Metadata, method: &Index<MethodDefEntry>) -> Option<MethodSignature> {
+    let signature = match meta.get_method_def_signature(method) {
+      Ok(signature) => signature,
+      Err(_) => return None
+    };
+    if self.type_args.is_empty() {
+      return Some(signature);
+    }
+
+    match meta.get_method_owner(method).and_then(|owner| self.type_args.get(&owner.0)) {
+      Some(type_args) => Some(GenericContext::new(type_args.clone(), vec![]).substitute_signature(&signature)),
+      None => Some(signature)
+    }
+  }
+
+  // Whether two methods of the assembly have the same name and signature, which is how methods override
+  // the virtual methods of base classes and implement interface methods implicitly. Signatures are compared
+  // once the type arguments of generic base types are filled in, so `int Get()` implements `IGetter<int>.Get`.
+  fn same_name_and_signature(&self, meta: &Metadata, signatures: &mut Signatures, a: &Index<MethodDefEntry>, b: &Index<MethodDefEntry>) -> bool {
+    for method in &[*a, *b] {
+      if !signatures.contains_key(&method.0) {
+        let name = meta.get_entry(method).and_then(|entry| meta.get_string(&entry.name));
+        let signature = match (name, self.signature_of(meta, method)) {
+          (Some(name), Some(signature)) => Some((name.to_string(), signature)),
+          _ => None
+        };
+        signatures.insert(method.0, signature);
+      }
+    }
+    match (&signatures[&a.0], &signatures[&b.0]) {
+      (&Some(ref a), &Some(ref b)) => a == b,
+      _ => false
+    }
+  }
+
+  fn override_slot(&mut self, meta: &Metadata, slot: usize, method: Index<MethodDefEntry>) -> Result<()> {
+    let overridden = self.slots[slot].implementation;
+    if overridden != method && method_flags(meta, &overridden) & MD_FINAL != 0 {
+      return Err(Error::new(ErrorKind::InvalidData, format!("{} can't override the final method {}",
+        meta.get_method_full_name(&MethodDefOrRef::MethodDef(method)), meta.get_method_full_name(&MethodDefOrRef::MethodDef(overridden)))));
+    }
+
+    self.slots[slot].implementation = method;
+    self.slot_indices.insert(method.0, slot);
+    Ok(())
+  }
+
+  // The overrides of interface methods declared by the interfaces of the class, by the method they override.
+  // Interfaces come after the ones they extend, so the most specific override wins.
+  fn default_implementations(&self, meta: &Metadata, impls: &MethodImpls) -> Result<HashMap<MethodDefOrRef, Index<MethodDefEntry>>> {
+    let mut res = HashMap::new();
+    for interface in self.interfaces.iter().rev() {
+      if let TypeDefOrRef::TypeDef(interface) = *interface {
+        for (declaration, body) in impls.of(meta, &interface)? {
+          res.entry(declaration).or_insert(body);
+        }
+      }
+    }
+    Ok(res)
+  }
+
+  /// The type arguments of a generic base class or interface of the assembly, in terms of the class's own type
+  /// parameters. Empty for types which aren't generic.
+  pub fn type_args_of(&self, type_def: &Index<TypeDefEntry>) -> &[TypeSig] {
+    self.type_args.get(&type_def.0).map(|type_args| &type_args[..]).unwrap_or(&[])
+  }
+
+  // The method running for calls through the slot of the given method, if it has one
+  fn implementation_of(&self, method: Index<MethodDefEntry>) -> Index<MethodDefEntry> {
+    match self.slot_indices.get(&method.0) {
+      Some(&slot) => self.slots[slot].implementation,
+      None => method
+    }
+  }
+
+  /// The method which a virtual call to a method of the assembly runs on instances of the class.
+  /// None if the method isn't virtual, or is an interface method the class doesn't implement.
+  pub fn resolve(&self, method: &Index<MethodDefEntry>) -> Option<Index<MethodDefEntry>> {
+    if let Some(&implementation) = self.interface_methods.get(&method.0) {
+      return Some(self.implementation_of(implementation));
+    }
+    self.slot_indices.get(&method.0).map(|&slot| self.slots[slot].implementation)
+  }
+
+  /// The method overriding or implementing a method of another assembly, like System.Object::ToString or
+  /// System.IDisposable::Dispose. None if the class inherits the method from outside the assembly.
+  pub fn resolve_external(&self, meta: &Metadata, member: &Index<MemberRefEntry>) -> Option<Index<MethodDefEntry>> {
+    if let Some(&body) = self.explicit_overrides.get(&MethodDefOrRef::MethodRef(*member)) {
+      return Some(self.implementation_of(body));
+    }
+
+    let (name, signature) = match (meta.get_entry(member), meta.get_member_ref_signature(member)) {
+      (Some(entry), Ok(signature)) => (meta.get_string(&entry.name), signature_key(meta, &signature)),
+      _ => return None
+    };
+    self.slots.iter().rev()
+      .find(|slot| {
+        let entry = meta.get_entry(&slot.implementation);
+        entry.and_then(|entry| meta.get_string(&entry.name)) == name &&
+          meta.get_method_def_signature(&slot.implementation).map(|other| signature_key(meta, &other)).ok() == Some(signature.clone())
+      })
+      .map(|slot| slot.implementation)
+  }
+}
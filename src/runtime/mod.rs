@@ -1,2 +1,12 @@
-
 mod gc_object;
+pub mod value;
+pub mod error;
+pub mod exceptions;
+pub mod heap;
+pub mod class;
+pub mod generics;
+pub mod method_table;
+pub mod internal_calls;
+pub mod strings;
+pub mod arrays;
+pub mod interpreter;
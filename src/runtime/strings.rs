@@ -0,0 +1,216 @@
+use metadata::signature::TypeSig;
+use runtime::error::ExecutionError;
+use runtime::heap::{Heap, ManagedObject};
+use runtime::internal_calls::{InternalCalls, CORLIB_ASSEMBLIES, invalid_argument};
+use runtime::value::StackValue;
+
+fn format_error() -> ExecutionError {
+  ExecutionError::exception("System.FormatException", "Input string was not in a correct format.")
+}
+
+// A string argument, None being null
+fn string_arg<'a>(heap: &'a Heap, args: &[StackValue], index: usize) -> Result<Option<&'a [u16]>, ExecutionError> {
+  match args.get(index) {
+    Some(&StackValue::Object(None)) => Ok(None),
+    Some(&StackValue::Object(Some(object))) => heap.get_utf16(object).map(Some).ok_or_else(|| invalid_argument("System.String")),
+    _ => Err(invalid_argument("System.String"))
+  }
+}
+
+// The `this` argument of an instance method of String
+fn this_arg<'a>(heap: &'a Heap, args: &[StackValue]) -> Result<&'a [u16], ExecutionError> {
+  string_arg(heap, args, 0)?.ok_or_else(ExecutionError::null_reference)
+}
+
+fn bool_result(value: bool) -> Result<Option<StackValue>, ExecutionError> {
+  Ok(Some(StackValue::Int32(value as i32)))
+}
+
+// Concatenates the string arguments, treating null as empty
+fn concat(heap: &mut Heap, args: &[StackValue]) -> Result<Option<StackValue>, ExecutionError> {
+  let mut value = vec![];
+  for index in 0 .. args.len() {
+    if let Some(part) = string_arg(heap, args, index)? {
+      value.extend_from_slice(part);
+    }
+  }
+  Ok(Some(StackValue::Object(Some(heap.alloc(ManagedObject::String(value))))))
+}
+
+// Concatenates the arguments as Object.ToString would format them, treating null as empty
+fn concat_objects(heap: &mut Heap, args: &[StackValue]) -> Result<Option<StackValue>, ExecutionError> {
+  let mut value = vec![];
+  for index in 0 .. args.len() {
+    match string_arg(heap, args, index) {
+      Ok(Some(part)) => value.extend_from_slice(part),
+      _ => value.extend(to_display_string(heap, &args[index]).ok_or_else(|| invalid_argument("String.Concat"))?.encode_utf16())
+    }
+  }
+  Ok(Some(StackValue::Object(Some(heap.alloc(ManagedObject::String(value))))))
+}
+
+/// Formats a value as Object.ToString would, for the values which don't need a call into managed code.
+pub fn to_display_string(heap: &Heap, value: &StackValue) -> Option<String> {
+  match *value {
+    StackValue::Object(None) => Some(String::new()),
+    StackValue::Object(Some(object)) => match heap.get(object) {
+      Some(&ManagedObject::String(ref value)) => Some(String::from_utf16_lossy(value)),
+      // Boxed enums are formatted as their underlying value, as their names aren't known here
+      Some(&ManagedObject::Boxed { ref type_, ref value }) => match (type_, value) {
+        (&TypeSig::Boolean, &StackValue::Int32(value)) => Some(if value != 0 { "True" } else { "False" }.to_string()),
+        (&TypeSig::Char, &StackValue::Int32(value)) => Some(String::from_utf16_lossy(&[value as u16])),
+        (&TypeSig::U4, &StackValue::Int32(value)) => Some((value as u32).to_string()),
+        (&TypeSig::U8, &StackValue::Int64(value)) => Some((value as u64).to_string()),
+        (&TypeSig::R4, &StackValue::Float(value)) => Some((value as f32).to_string()),
+        (_, value) => to_display_string(heap, value)
+      },
+      Some(&ManagedObject::Type { ref name, .. }) => Some(name.clone()),
+      _ => None
+    },
+    StackValue::Int32(value) => Some(value.to_string()),
+    StackValue::Int64(value) => Some(value.to_string()),
+    StackValue::NativeInt(value) => Some(value.to_string()),
+    StackValue::Float(value) => Some(value.to_string()),
+    StackValue::ManagedPointer(_) | StackValue::ValueType(_) | StackValue::FunctionPointer(..) | StackValue::FieldHandle(_) |
+    StackValue::TypeHandle(_) => None
+  }
+}
+
+/// Implements the composite formatting of String.Format: `{index[,alignment][:format]}` items, with
+/// `{{` and `}}` as escapes. Format strings of the items are ignored.
+pub fn format(heap: &Heap, format: &str, args: &[StackValue]) -> Result<String, ExecutionError> {
+  let mut res = String::new();
+  let mut chars = format.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '{' if chars.peek() == Some(&'{') => {
+        chars.next();
+        res.push('{');
+      },
+      '}' if chars.peek() == Some(&'}') => {
+        chars.next();
+        res.push('}');
+      },
+      '}' => return Err(format_error()),
+      '{' => {
+        let mut item = String::new();
+        loop {
+          match chars.next() {
+            Some('}') => break,
+            Some(c) => item.push(c),
+            None => return Err(format_error())
+          }
+        }
+
+        // Split off the format string, then the alignment
+        let item = item.splitn(2, ':').next().unwrap_or("");
+        let mut parts = item.splitn(2, ',');
+        let index = parts.next().unwrap_or("").trim().parse::<usize>().map_err(|_| format_error())?;
+        let alignment = match parts.next() {
+          Some(alignment) => alignment.trim().parse::<i32>().map_err(|_| format_error())?,
+          None => 0
+        };
+        // Like .NET, refuse a width of a million or more rather than allocating the padding
+        if alignment <= -1_000_000 || alignment >= 1_000_000 {
+          return Err(format_error());
+        }
+
+        let value = match args.get(index) {
+          Some(value) => to_display_string(heap, value).ok_or_else(|| invalid_argument("String.Format"))?,
+          None => return Err(ExecutionError::exception("System.FormatException",
+            "Index (zero based) must be greater than or equal to zero and less than the size of the argument list."))
+        };
+
+        // A positive alignment pads on the left, a negative one on the right
+        let width = if alignment < 0 { -alignment as usize } else { alignment as usize };
+        let padding = " ".repeat(width.saturating_sub(value.chars().count()));
+        if alignment > 0 {
+          res.push_str(&padding);
+        }
+        res.push_str(&value);
+        if alignment < 0 {
+          res.push_str(&padding);
+        }
+      },
+      c => res.push(c)
+    }
+  }
+
+  Ok(res)
+}
+
+fn string<F>(calls: &mut InternalCalls, name: &str, signature: &str, call: F)
+  where F: Fn(&mut Heap, &[StackValue]) -> Result<Option<StackValue>, ExecutionError> + 'static {
+  calls.register_in(CORLIB_ASSEMBLIES, "System", "String", name, signature, call);
+}
+
+/// Registers the internal calls of System.String.
+pub fn register(calls: &mut InternalCalls) {
+  string(calls, "Concat", "string(string, string)", concat);
+  string(calls, "Concat", "string(string, string, string)", concat);
+  string(calls, "Concat", "string(string, string, string, string)", concat);
+  string(calls, "Concat", "string(object, object)", concat_objects);
+  string(calls, "Concat", "string(object, object, object)", concat_objects);
+  string(calls, "Concat", "string(string[])", |heap, args| {
+    let values = match args.get(0) {
+      Some(&StackValue::Object(None)) =>
+        return Err(ExecutionError::exception("System.ArgumentNullException", "Value cannot be null.\nParameter name: values")),
+      Some(&StackValue::Object(Some(array))) => match heap.get(array) {
+        Some(&ManagedObject::Array { ref elements, .. }) => elements.clone(),
+        _ => return Err(invalid_argument("String.Concat"))
+      },
+      _ => return Err(invalid_argument("String.Concat"))
+    };
+    concat(heap, &values)
+  });
+
+  string(calls, "get_Length", "instance int32()", |heap, args| {
+    Ok(Some(StackValue::Int32(this_arg(heap, args)?.len() as i32)))
+  });
+  string(calls, "get_Chars", "instance char(int32)", |heap, args| {
+    let value = this_arg(heap, args)?;
+    match args.get(1) {
+      Some(&StackValue::Int32(index)) if index >= 0 && (index as usize) < value.len() =>
+        Ok(Some(StackValue::Int32(value[index as usize] as i32))),
+      Some(&StackValue::Int32(_)) =>
+        Err(ExecutionError::exception("System.IndexOutOfRangeException", "Index was outside the bounds of the array.")),
+      _ => Err(invalid_argument("String.get_Chars"))
+    }
+  });
+
+  string(calls, "Equals", "instance bool(string)", |heap, args| {
+    let value = this_arg(heap, args)?;
+    bool_result(string_arg(heap, args, 1)? == Some(value))
+  });
+  string(calls, "Equals", "bool(string, string)", |heap, args| bool_result(string_arg(heap, args, 0)? == string_arg(heap, args, 1)?));
+  string(calls, "op_Equality", "bool(string, string)", |heap, args| bool_result(string_arg(heap, args, 0)? == string_arg(heap, args, 1)?));
+  string(calls, "op_Inequality", "bool(string, string)", |heap, args| bool_result(string_arg(heap, args, 0)? != string_arg(heap, args, 1)?));
+  string(calls, "IsNullOrEmpty", "bool(string)", |heap, args| {
+    bool_result(string_arg(heap, args, 0)?.map(|value| value.is_empty()).unwrap_or(true))
+  });
+
+  string(calls, "Intern", "string(string)", |heap, args| {
+    let value = string_arg(heap, args, 0)?.ok_or_else(|| {
+      ExecutionError::exception("System.ArgumentNullException", "Value cannot be null.\nParameter name: str")
+    })?.to_vec();
+    Ok(Some(StackValue::Object(Some(heap.intern(value)))))
+  });
+  string(calls, "IsInterned", "string(string)", |heap, args| {
+    let value = string_arg(heap, args, 0)?.ok_or_else(|| {
+      ExecutionError::exception("System.ArgumentNullException", "Value cannot be null.\nParameter name: str")
+    })?;
+    Ok(Some(StackValue::Object(heap.is_interned(value))))
+  });
+
+  for signature in &["string(string, object)", "string(string, object, object)", "string(string, object, object, object)"] {
+    string(calls, "Format", signature, |heap, args| {
+      let format_string = match string_arg(heap, args, 0)? {
+        Some(format_string) => String::from_utf16_lossy(format_string),
+        None => return Err(ExecutionError::exception("System.ArgumentNullException", "Value cannot be null.\nParameter name: format"))
+      };
+      let value = format(heap, &format_string, &args[1 ..])?;
+      Ok(Some(StackValue::Object(Some(heap.alloc_string(&value)))))
+    });
+  }
+}
@@ -0,0 +1,483 @@
+use std::ops::{BitAnd, BitOr, BitXor};
+
+use metadata::signature::TypeSig;
+use metadata::tables::{Index, FieldEntry, MethodDefEntry};
+use runtime::generics::GenericContext;
+
+/// A reference to an object on the managed heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectRef(pub usize);
+
+/// What a managed pointer (`&`) points to. Frames are identified by their depth on the call stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pointer {
+  Local { frame: usize, index: u16 },
+  Argument { frame: usize, index: u16 },
+  /// An instance field, by its position in the class layout
+  Field { object: ObjectRef, slot: usize },
+  /// A static field, with the class of the instantiation it belongs to for fields of generic types,
+  /// as each instantiation has statics of its own
+  StaticField(Index<FieldEntry>, Option<usize>),
+  /// A field of the value type instance stored at another location
+  ValueField(Box<Pointer>, usize),
+  /// The value inside a boxed value type, as pushed by unbox
+  Boxed(ObjectRef),
+  /// An element of an array, by its position in row-major order
+  Element { array: ObjectRef, index: usize }
+}
+
+/// An instance of a value type other than the primitive types and enums, whose fields are stored
+/// inline. Assigning it copies all of the fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueTypeInstance {
+  /// The type of the value, which is a ValueType or a generic instantiation of one like `Nullable<int>`
+  pub type_: TypeSig,
+  /// A value for each instance field, in layout order
+  pub fields: Vec<StackValue>
+}
+
+/// A value on the evaluation stack, typed with the CLI stack types (ECMA 335, I.12.3.2.1).
+/// Values smaller than 4 bytes are widened to Int32, and float32 is widened to Float.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackValue {
+  Int32(i32),
+  Int64(i64),
+  NativeInt(isize),
+  Float(f64),
+  /// An object reference, None being null.
+  Object(Option<ObjectRef>),
+  ManagedPointer(Pointer),
+  ValueType(ValueTypeInstance),
+  /// A native int holding the address of a method, as pushed by ldftn and ldvirtftn, with the type
+  /// arguments of the instantiation it points to
+  FunctionPointer(Index<MethodDefEntry>, GenericContext),
+  /// A System.RuntimeFieldHandle, as pushed by ldtoken for a field
+  FieldHandle(Index<FieldEntry>),
+  /// A System.RuntimeTypeHandle, as pushed by ldtoken for a type
+  TypeHandle(TypeSig)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticError {
+  /// The operand types can't be combined, which only happens with invalid IL.
+  InvalidOperands,
+  Overflow,
+  DivideByZero
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  DivUn,
+  Rem,
+  RemUn,
+  And,
+  Or,
+  Xor,
+  AddOvf,
+  AddOvfUn,
+  SubOvf,
+  SubOvfUn,
+  MulOvf,
+  MulOvfUn
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftOp {
+  Shl,
+  Shr,
+  ShrUn
+}
+
+/// The comparisons done by ceq, cgt, clt and the conditional branches. The unsigned variants
+/// compare integers as unsigned and are true for unordered floats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+  Eq,
+  Gt,
+  GtUn,
+  Ge,
+  GeUn,
+  Lt,
+  LtUn,
+  Le,
+  LeUn
+}
+
+/// The target types of the conv family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+  I1,
+  I2,
+  I4,
+  I8,
+  U1,
+  U2,
+  U4,
+  U8,
+  I,
+  U,
+  R4,
+  R8,
+  /// conv.r.un, which reads the integer as unsigned
+  RUn
+}
+
+// Both operands of a binary operation, widened to a common type (ECMA 335, III.1.5)
+enum Operands {
+  Int32(i32, i32),
+  Int64(i64, i64),
+  NativeInt(isize, isize),
+  Float(f64, f64)
+}
+
+fn operands(a: StackValue, b: StackValue) -> Result<Operands, ArithmeticError> {
+  match (a, b) {
+    (StackValue::Int32(a), StackValue::Int32(b)) => Ok(Operands::Int32(a, b)),
+    (StackValue::Int32(a), StackValue::NativeInt(b)) => Ok(Operands::NativeInt(a as isize, b)),
+    (StackValue::NativeInt(a), StackValue::Int32(b)) => Ok(Operands::NativeInt(a, b as isize)),
+    (StackValue::NativeInt(a), StackValue::NativeInt(b)) => Ok(Operands::NativeInt(a, b)),
+    (StackValue::Int64(a), StackValue::Int64(b)) => Ok(Operands::Int64(a, b)),
+    (StackValue::Float(a), StackValue::Float(b)) => Ok(Operands::Float(a, b)),
+    _ => Err(ArithmeticError::InvalidOperands)
+  }
+}
+
+// Applies a method returning the result directly, like wrapping_add, to integer operands
+macro_rules! wrapping {
+  ($operands:expr, $method:ident) => {
+    match $operands {
+      Operands::Int32(a, b) => StackValue::Int32(a.$method(b)),
+      Operands::Int64(a, b) => StackValue::Int64(a.$method(b)),
+      Operands::NativeInt(a, b) => StackValue::NativeInt(a.$method(b)),
+      Operands::Float(_, _) => return Err(ArithmeticError::InvalidOperands)
+    }
+  }
+}
+
+// Applies a method returning an Option, like checked_add, to integer operands. None is an overflow.
+macro_rules! checked {
+  ($operands:expr, $method:ident) => {
+    match $operands {
+      Operands::Int32(a, b) => a.$method(b).map(StackValue::Int32),
+      Operands::Int64(a, b) => a.$method(b).map(StackValue::Int64),
+      Operands::NativeInt(a, b) => a.$method(b).map(StackValue::NativeInt),
+      Operands::Float(_, _) => return Err(ArithmeticError::InvalidOperands)
+    }.ok_or(ArithmeticError::Overflow)
+  }
+}
+
+// The same as checked!, treating the operands as unsigned
+macro_rules! checked_unsigned {
+  ($operands:expr, $method:ident) => {
+    match $operands {
+      Operands::Int32(a, b) => (a as u32).$method(b as u32).map(|x| StackValue::Int32(x as i32)),
+      Operands::Int64(a, b) => (a as u64).$method(b as u64).map(|x| StackValue::Int64(x as i64)),
+      Operands::NativeInt(a, b) => (a as usize).$method(b as usize).map(|x| StackValue::NativeInt(x as isize)),
+      Operands::Float(_, _) => return Err(ArithmeticError::InvalidOperands)
+    }.ok_or(ArithmeticError::Overflow)
+  }
+}
+
+// div and rem, which fail on zero divisors and on MinValue / -1
+macro_rules! division {
+  ($operands:expr, $method:ident, $float_op:tt) => {
+    match $operands {
+      Operands::Float(a, b) => Ok(StackValue::Float(a $float_op b)),
+      Operands::Int32(_, 0) | Operands::Int64(_, 0) | Operands::NativeInt(_, 0) => Err(ArithmeticError::DivideByZero),
+      operands => checked!(operands, $method)
+    }
+  }
+}
+
+// div.un and rem.un
+macro_rules! division_unsigned {
+  ($operands:expr, $method:ident) => {
+    match $operands {
+      Operands::Int32(_, 0) | Operands::Int64(_, 0) | Operands::NativeInt(_, 0) => Err(ArithmeticError::DivideByZero),
+      operands => checked_unsigned!(operands, $method)
+    }
+  }
+}
+
+// The integer value being converted, or a float
+enum ConversionSource {
+  Signed(i64),
+  Unsigned(u64),
+  Float(f64)
+}
+
+impl ConversionSource {
+  fn in_range(&self, min: i64, max: u64) -> bool {
+    match *self {
+      ConversionSource::Signed(value) => value >= min && (value < 0 || value as u64 <= max),
+      ConversionSource::Unsigned(value) => value <= max,
+      ConversionSource::Float(value) => {
+        let value = value.trunc();
+        // Above 2^53, max + 1 is a power of two and max itself rounds up to it
+        let below_max = if max >= 1 << 53 { value < max as f64 } else { value <= max as f64 };
+        value >= min as f64 && below_max
+      }
+    }
+  }
+
+  // The two's complement bits of the value, truncating floats towards zero
+  fn bits(&self, unsigned_target: bool) -> i64 {
+    match *self {
+      ConversionSource::Signed(value) => value,
+      ConversionSource::Unsigned(value) => value as i64,
+      ConversionSource::Float(value) if unsigned_target => value as u64 as i64,
+      ConversionSource::Float(value) => value as i64
+    }
+  }
+
+  fn as_float(&self) -> f64 {
+    match *self {
+      ConversionSource::Signed(value) => value as f64,
+      ConversionSource::Unsigned(value) => value as f64,
+      ConversionSource::Float(value) => value
+    }
+  }
+}
+
+impl StackValue {
+  /// The zero value of a local, argument or field of the given type, or None for value types,
+  /// whose layout the interpreter knows.
+  pub fn default_for(type_sig: &TypeSig) -> Option<StackValue> {
+    Some(match *type_sig {
+      TypeSig::Boolean | TypeSig::Char | TypeSig::I1 | TypeSig::U1 | TypeSig::I2 | TypeSig::U2 |
+      TypeSig::I4 | TypeSig::U4 => StackValue::Int32(0),
+      TypeSig::I8 | TypeSig::U8 => StackValue::Int64(0),
+      TypeSig::I | TypeSig::U | TypeSig::Ptr(_) | TypeSig::FnPtr(_) => StackValue::NativeInt(0),
+      TypeSig::R4 | TypeSig::R8 => StackValue::Float(0.0),
+      TypeSig::String | TypeSig::Object | TypeSig::Class(_) | TypeSig::SzArray(_) | TypeSig::Array(_, _) |
+      TypeSig::Var(_) | TypeSig::MVar(_) => StackValue::Object(None),
+      TypeSig::GenericInst(ref generic_type, _) => match **generic_type {
+        TypeSig::Class(_) => StackValue::Object(None),
+        _ => return None
+      },
+      // Managed pointers can't be null, but uninitialized locals of this type still need a value
+      TypeSig::ByRef(_) => StackValue::NativeInt(0),
+      TypeSig::CModReqd(_, ref inner) | TypeSig::CModOpt(_, ref inner) | TypeSig::Pinned(ref inner) =>
+        return StackValue::default_for(inner),
+      _ => return None
+    })
+  }
+
+  /// Narrows the value for storing in a location of the given type, as stloc, starg and stind do.
+  /// Storing also normalizes small integers, so loading them back needs no extension.
+  pub fn store_as(&self, type_sig: &TypeSig) -> StackValue {
+    match (self, type_sig) {
+      (&StackValue::Int32(value), &TypeSig::I1) => StackValue::Int32(value as i8 as i32),
+      (&StackValue::Int32(value), &TypeSig::U1) => StackValue::Int32(value as u8 as i32),
+      (&StackValue::Int32(value), &TypeSig::Boolean) => StackValue::Int32(value as u8 as i32),
+      (&StackValue::Int32(value), &TypeSig::I2) => StackValue::Int32(value as i16 as i32),
+      (&StackValue::Int32(value), &TypeSig::U2) => StackValue::Int32(value as u16 as i32),
+      (&StackValue::Int32(value), &TypeSig::Char) => StackValue::Int32(value as u16 as i32),
+      (&StackValue::Int32(value), &TypeSig::I) => StackValue::NativeInt(value as isize),
+      (&StackValue::Int32(value), &TypeSig::U) => StackValue::NativeInt(value as u32 as usize as isize),
+      (&StackValue::NativeInt(value), &TypeSig::I4) | (&StackValue::NativeInt(value), &TypeSig::U4) =>
+        StackValue::Int32(value as i32),
+      (&StackValue::Float(value), &TypeSig::R4) => StackValue::Float(value as f32 as f64),
+      (value, &TypeSig::CModReqd(_, ref inner)) | (value, &TypeSig::CModOpt(_, ref inner)) | (value, &TypeSig::Pinned(ref inner)) =>
+        value.store_as(inner),
+      (value, _) => value.clone()
+    }
+  }
+
+  /// The value of brtrue: non-zero integers and non-null references are true.
+  pub fn is_true(&self) -> Result<bool, ArithmeticError> {
+    match *self {
+      StackValue::Int32(value) => Ok(value != 0),
+      StackValue::Int64(value) => Ok(value != 0),
+      StackValue::NativeInt(value) => Ok(value != 0),
+      StackValue::Object(value) => Ok(value.is_some()),
+      StackValue::ManagedPointer(_) | StackValue::FunctionPointer(..) => Ok(true),
+      StackValue::Float(_) | StackValue::ValueType(_) | StackValue::FieldHandle(_) | StackValue::TypeHandle(_) =>
+        Err(ArithmeticError::InvalidOperands)
+    }
+  }
+
+  pub fn binary(op: BinaryOp, a: StackValue, b: StackValue) -> Result<StackValue, ArithmeticError> {
+    let operands = operands(a, b)?;
+
+    match op {
+      BinaryOp::Add => Ok(match operands {
+        Operands::Float(a, b) => StackValue::Float(a + b),
+        operands => wrapping!(operands, wrapping_add)
+      }),
+      BinaryOp::Sub => Ok(match operands {
+        Operands::Float(a, b) => StackValue::Float(a - b),
+        operands => wrapping!(operands, wrapping_sub)
+      }),
+      BinaryOp::Mul => Ok(match operands {
+        Operands::Float(a, b) => StackValue::Float(a * b),
+        operands => wrapping!(operands, wrapping_mul)
+      }),
+      BinaryOp::Div => division!(operands, checked_div, /),
+      BinaryOp::Rem => division!(operands, checked_rem, %),
+      BinaryOp::DivUn => division_unsigned!(operands, checked_div),
+      BinaryOp::RemUn => division_unsigned!(operands, checked_rem),
+      BinaryOp::And => Ok(wrapping!(operands, bitand)),
+      BinaryOp::Or => Ok(wrapping!(operands, bitor)),
+      BinaryOp::Xor => Ok(wrapping!(operands, bitxor)),
+      BinaryOp::AddOvf => checked!(operands, checked_add),
+      BinaryOp::SubOvf => checked!(operands, checked_sub),
+      BinaryOp::MulOvf => checked!(operands, checked_mul),
+      BinaryOp::AddOvfUn => checked_unsigned!(operands, checked_add),
+      BinaryOp::SubOvfUn => checked_unsigned!(operands, checked_sub),
+      BinaryOp::MulOvfUn => checked_unsigned!(operands, checked_mul)
+    }
+  }
+
+  pub fn shift(op: ShiftOp, value: StackValue, amount: StackValue) -> Result<StackValue, ArithmeticError> {
+    let amount = match amount {
+      StackValue::Int32(amount) => amount as u32,
+      StackValue::NativeInt(amount) => amount as u32,
+      _ => return Err(ArithmeticError::InvalidOperands)
+    };
+
+    // Shifting by the width of the value or more is unspecified, so only the low bits of the amount are used
+    Ok(match (op, value) {
+      (ShiftOp::Shl, StackValue::Int32(value)) => StackValue::Int32(value.wrapping_shl(amount)),
+      (ShiftOp::Shr, StackValue::Int32(value)) => StackValue::Int32(value.wrapping_shr(amount)),
+      (ShiftOp::ShrUn, StackValue::Int32(value)) => StackValue::Int32((value as u32).wrapping_shr(amount) as i32),
+      (ShiftOp::Shl, StackValue::Int64(value)) => StackValue::Int64(value.wrapping_shl(amount)),
+      (ShiftOp::Shr, StackValue::Int64(value)) => StackValue::Int64(value.wrapping_shr(amount)),
+      (ShiftOp::ShrUn, StackValue::Int64(value)) => StackValue::Int64((value as u64).wrapping_shr(amount) as i64),
+      (ShiftOp::Shl, StackValue::NativeInt(value)) => StackValue::NativeInt(value.wrapping_shl(amount)),
+      (ShiftOp::Shr, StackValue::NativeInt(value)) => StackValue::NativeInt(value.wrapping_shr(amount)),
+      (ShiftOp::ShrUn, StackValue::NativeInt(value)) => StackValue::NativeInt((value as usize).wrapping_shr(amount) as isize),
+      _ => return Err(ArithmeticError::InvalidOperands)
+    })
+  }
+
+  pub fn neg(&self) -> Result<StackValue, ArithmeticError> {
+    match *self {
+      StackValue::Int32(value) => Ok(StackValue::Int32(value.wrapping_neg())),
+      StackValue::Int64(value) => Ok(StackValue::Int64(value.wrapping_neg())),
+      StackValue::NativeInt(value) => Ok(StackValue::NativeInt(value.wrapping_neg())),
+      StackValue::Float(value) => Ok(StackValue::Float(-value)),
+      _ => Err(ArithmeticError::InvalidOperands)
+    }
+  }
+
+  pub fn not(&self) -> Result<StackValue, ArithmeticError> {
+    match *self {
+      StackValue::Int32(value) => Ok(StackValue::Int32(!value)),
+      StackValue::Int64(value) => Ok(StackValue::Int64(!value)),
+      StackValue::NativeInt(value) => Ok(StackValue::NativeInt(!value)),
+      _ => Err(ArithmeticError::InvalidOperands)
+    }
+  }
+
+  pub fn compare(op: CompareOp, a: StackValue, b: StackValue) -> Result<bool, ArithmeticError> {
+    // References can be compared for equality, and cgt.un is used to compare them against null
+    match (&a, &b) {
+      (&StackValue::Object(a), &StackValue::Object(b)) => return match op {
+        CompareOp::Eq => Ok(a == b),
+        CompareOp::GtUn => Ok(a != b),
+        _ => Err(ArithmeticError::InvalidOperands)
+      },
+      (&StackValue::ManagedPointer(ref a), &StackValue::ManagedPointer(ref b)) => return match op {
+        CompareOp::Eq => Ok(a == b),
+        _ => Err(ArithmeticError::InvalidOperands)
+      },
+      (&StackValue::FunctionPointer(a, ref a_context), &StackValue::FunctionPointer(b, ref b_context)) => return match op {
+        CompareOp::Eq => Ok(a == b && a_context == b_context),
+        _ => Err(ArithmeticError::InvalidOperands)
+      },
+      _ => ()
+    }
+
+    macro_rules! compare_integers {
+      ($a:expr, $b:expr, $unsigned:ty) => {
+        match op {
+          CompareOp::Eq => $a == $b,
+          CompareOp::Gt => $a > $b,
+          CompareOp::Ge => $a >= $b,
+          CompareOp::Lt => $a < $b,
+          CompareOp::Le => $a <= $b,
+          CompareOp::GtUn => $a as $unsigned > $b as $unsigned,
+          CompareOp::GeUn => $a as $unsigned >= $b as $unsigned,
+          CompareOp::LtUn => ($a as $unsigned) < $b as $unsigned,
+          CompareOp::LeUn => $a as $unsigned <= $b as $unsigned
+        }
+      }
+    }
+
+    Ok(match operands(a, b)? {
+      Operands::Int32(a, b) => compare_integers!(a, b, u32),
+      Operands::Int64(a, b) => compare_integers!(a, b, u64),
+      Operands::NativeInt(a, b) => compare_integers!(a, b, usize),
+      Operands::Float(a, b) => {
+        let unordered = a.is_nan() || b.is_nan();
+        match op {
+          CompareOp::Eq => a == b,
+          CompareOp::Gt => a > b,
+          CompareOp::Ge => a >= b,
+          CompareOp::Lt => a < b,
+          CompareOp::Le => a <= b,
+          CompareOp::GtUn => unordered || a > b,
+          CompareOp::GeUn => unordered || a >= b,
+          CompareOp::LtUn => unordered || a < b,
+          CompareOp::LeUn => unordered || a <= b
+        }
+      }
+    })
+  }
+
+  /// Implements the conv family. `checked` is for conv.ovf, and `unsigned_source` for the .un variants.
+  pub fn convert(&self, target: Conversion, checked: bool, unsigned_source: bool) -> Result<StackValue, ArithmeticError> {
+    let source = match *self {
+      StackValue::Int32(value) if unsigned_source => ConversionSource::Unsigned(value as u32 as u64),
+      StackValue::Int32(value) => ConversionSource::Signed(value as i64),
+      StackValue::Int64(value) if unsigned_source => ConversionSource::Unsigned(value as u64),
+      StackValue::Int64(value) => ConversionSource::Signed(value),
+      StackValue::NativeInt(value) if unsigned_source => ConversionSource::Unsigned(value as usize as u64),
+      StackValue::NativeInt(value) => ConversionSource::Signed(value as i64),
+      StackValue::Float(value) => ConversionSource::Float(value),
+      _ => return Err(ArithmeticError::InvalidOperands)
+    };
+
+    let (min, max): (i64, u64) = match target {
+      Conversion::I1 => (i8::min_value() as i64, i8::max_value() as u64),
+      Conversion::I2 => (i16::min_value() as i64, i16::max_value() as u64),
+      Conversion::I4 => (i32::min_value() as i64, i32::max_value() as u64),
+      Conversion::I8 => (i64::min_value(), i64::max_value() as u64),
+      Conversion::I => (isize::min_value() as i64, isize::max_value() as u64),
+      Conversion::U1 => (0, u8::max_value() as u64),
+      Conversion::U2 => (0, u16::max_value() as u64),
+      Conversion::U4 => (0, u32::max_value() as u64),
+      Conversion::U8 => (0, u64::max_value()),
+      Conversion::U => (0, usize::max_value() as u64),
+      Conversion::R4 | Conversion::R8 | Conversion::RUn => {
+        let value = source.as_float();
+        return Ok(StackValue::Float(if target == Conversion::R4 { value as f32 as f64 } else { value }));
+      }
+    };
+
+    if checked && !source.in_range(min, max) {
+      return Err(ArithmeticError::Overflow);
+    }
+
+    // Widening an int32 to an unsigned type zero-extends it
+    let bits = match (self, target) {
+      (&StackValue::Int32(value), Conversion::U8) | (&StackValue::Int32(value), Conversion::U) => value as u32 as i64,
+      _ => source.bits(min == 0)
+    };
+
+    Ok(match target {
+      Conversion::I1 => StackValue::Int32(bits as i8 as i32),
+      Conversion::I2 => StackValue::Int32(bits as i16 as i32),
+      Conversion::I4 | Conversion::U4 => StackValue::Int32(bits as i32),
+      Conversion::U1 => StackValue::Int32(bits as u8 as i32),
+      Conversion::U2 => StackValue::Int32(bits as u16 as i32),
+      Conversion::I8 | Conversion::U8 => StackValue::Int64(bits),
+      _ => StackValue::NativeInt(bits as isize)
+    })
+  }
+}
@@ -0,0 +1,78 @@
+use std::fmt::Debug;
+use std::collections::HashMap;
+use typemap::{Key, TypeMap};
+
+use metadata::Metadata;
+use metadata::heap::{Heaps, StringHeap, UserStringHeap, BlobHeap};
+use metadata::tables::{self, Index};
+
+/// Builds in-memory metadata for tests, so that they don't need compiled assemblies.
+pub struct MetadataBuilder {
+  metadata: Metadata,
+  string_heap_size: u32,
+  user_string_heap_size: u32,
+  blob_heap_size: u32
+}
+
+impl MetadataBuilder {
+  pub fn new() -> MetadataBuilder {
+    let heaps = Heaps {
+      strings: StringHeap { strings: HashMap::new() },
+      user_strings: UserStringHeap { strings: HashMap::new() },
+      blobs: BlobHeap { blobs: HashMap::new() }
+    };
+
+    let mut builder = MetadataBuilder {
+      metadata: Metadata { tables: TypeMap::custom(), heaps, method_bodies: HashMap::new(), field_data: HashMap::new() },
+      string_heap_size: 0,
+      user_string_heap_size: 0,
+      blob_heap_size: 0
+    };
+
+    // Like real heaps, index 0 is always the empty string or blob
+    builder.string("");
+    builder.user_string("");
+    builder.blob(vec![]);
+    builder
+  }
+
+  pub fn string(&mut self, value: &str) -> Index<tables::StringHeap> {
+    let index = self.string_heap_size;
+    self.metadata.heaps.strings.strings.insert(index, value.to_string());
+    self.string_heap_size += value.len() as u32 + 1;
+    Index::new(index)
+  }
+
+  pub fn user_string(&mut self, value: &str) -> u32 {
+    self.user_string_utf16(value.encode_utf16().collect())
+  }
+
+  /// Adds a string literal given as UTF-16 code units, which needn't be valid UTF-16.
+  pub fn user_string_utf16(&mut self, value: Vec<u16>) -> u32 {
+    let index = self.user_string_heap_size;
+    self.user_string_heap_size += value.len() as u32 * 2 + 2;
+    self.metadata.heaps.user_strings.strings.insert(index, value);
+    index
+  }
+
+  pub fn blob(&mut self, value: Vec<u8>) -> Index<tables::BlobHeap> {
+    let index = self.blob_heap_size;
+    self.blob_heap_size += value.len() as u32 + 1;
+    self.metadata.heaps.blobs.blobs.insert(index, value);
+    Index::new(index)
+  }
+
+  pub fn row<T: Key<Value = Vec<T>> + Debug>(&mut self, entry: T) -> Index<T> {
+    if self.metadata.tables.get::<T>().is_none() {
+      self.metadata.tables.insert::<T>(vec![]);
+    }
+
+    let table = self.metadata.tables.get_mut::<T>().unwrap();
+    table.push(entry);
+    Index::new(table.len() as u32)
+  }
+
+  pub fn build(self) -> Metadata {
+    self.metadata
+  }
+}
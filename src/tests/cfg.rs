@@ -0,0 +1,82 @@
+use std::io::Cursor;
+
+use analysis::cfg::*;
+use loader::code::*;
+
+fn tiny_body(code: &[u8]) -> MethodBody {
+  let mut bytes = vec![0x02 | ((code.len() as u8) << 2)];
+  bytes.extend_from_slice(code);
+  MethodBody::read(&mut Cursor::new(bytes)).unwrap()
+}
+
+#[test]
+fn switch_edges_and_dominators() {
+  let body = tiny_body(&[
+    0x02,                                           // 0: ldarg.0
+    0x45, 0x02, 0x00, 0x00, 0x00,                   // 1: switch (14, 16)
+    0x00, 0x00, 0x00, 0x00,
+    0x02, 0x00, 0x00, 0x00,
+    0x17,                                           // 14: ldc.i4.1
+    0x2A,                                           // 15: ret
+    0x18,                                           // 16: ldc.i4.2
+    0x2A                                            // 17: ret
+  ]);
+
+  let graph = ControlFlowGraph::build(&body).unwrap();
+
+  assert_eq!(vec![(0, 14), (14, 16), (16, 18)], graph.blocks.iter().map(|block| (block.start, block.end)).collect::<Vec<_>>());
+  assert_eq!(vec![
+    Edge { from: 0, to: 1, kind: EdgeKind::Switch(0) },
+    Edge { from: 0, to: 2, kind: EdgeKind::Switch(1) },
+    Edge { from: 0, to: 1, kind: EdgeKind::Fallthrough }
+  ], graph.edges);
+  assert_eq!(vec![1, 2], graph.successors(0));
+  assert_eq!(Some(1), graph.block_containing(15));
+
+  let dominators = graph.dominators();
+  assert_eq!(vec![None, Some(0), Some(0)], dominators.immediate_dominators);
+  assert!(dominators.dominates(0, 2) && !dominators.dominates(1, 2));
+
+  let dot = graph.to_dot("Sample::Run");
+  assert!(dot.starts_with("digraph \"Sample::Run\" {"));
+  assert!(dot.contains("  b0 -> b2 [label=\"case 1\"];"));
+  assert!(dot.contains("IL_0001: switch\\l"));
+}
+
+#[test]
+fn exceptional_edges_and_unreachable_blocks() {
+  let mut body = tiny_body(&[
+    0x00,                                           // 0: nop
+    0xDE, 0x03,                                     // 1: leave.s 6
+    0x00,                                           // 3: nop
+    0xDC,                                           // 4: endfinally
+    0x00,                                           // 5: nop
+    0x2A                                            // 6: ret
+  ]);
+  body.exception_clauses.push(ExceptionClause {
+    kind: ExceptionClauseKind::Finally, try_offset: 0, try_length: 3, handler_offset: 3, handler_length: 2
+  });
+
+  let graph = ControlFlowGraph::build(&body).unwrap();
+
+  assert_eq!(vec![0, 3, 5, 6], graph.blocks.iter().map(|block| block.start).collect::<Vec<_>>());
+  assert_eq!(vec![
+    Edge { from: 0, to: 3, kind: EdgeKind::Leave },
+    Edge { from: 0, to: 1, kind: EdgeKind::Exceptional },
+    Edge { from: 2, to: 3, kind: EdgeKind::Fallthrough }
+  ], graph.edges);
+  assert_eq!(vec![true, true, false, true], graph.reachable());
+
+  let dominators = graph.dominators();
+  assert_eq!(vec![None, Some(0), None, Some(0)], dominators.immediate_dominators);
+  assert!(!dominators.dominates(2, 3));
+  assert_eq!(vec![1, 3], dominators.children(0));
+  assert!(graph.to_dot("M").contains("  b0 -> b1 [style=dashed];"));
+}
+
+#[test]
+fn branch_into_instruction() {
+  // br.s 1 jumps into the middle of itself
+  let body = tiny_body(&[0x2B, 0xFF, 0x2A]);
+  assert!(ControlFlowGraph::build(&body).is_err());
+}
@@ -0,0 +1,56 @@
+use std::{f32, f64};
+
+use metadata::constants::ConstantValue;
+use metadata::signature::ElementType;
+use metadata::tables::*;
+use tests::builder::MetadataBuilder;
+
+#[test]
+fn literal_field_and_parameter_defaults() {
+  let mut builder = MetadataBuilder::new();
+
+  let value = builder.blob(vec![0xD6, 0xFF, 0xFF, 0xFF]);
+  builder.row(ConstantEntry { type_: ElementType::I4, parent: HasConstant::Field(Index::new(1)), value });
+
+  // "Hi" in UTF-16
+  let value = builder.blob(vec![0x48, 0x00, 0x69, 0x00]);
+  builder.row(ConstantEntry { type_: ElementType::String, parent: HasConstant::Param(Index::new(1)), value });
+
+  let value = builder.blob(vec![0x00, 0x00, 0x00, 0x00]);
+  builder.row(ConstantEntry { type_: ElementType::Class, parent: HasConstant::Param(Index::new(2)), value });
+
+  let metadata = builder.build();
+
+  let field_value = metadata.get_constant(HasConstant::Field(Index::new(1))).unwrap().unwrap();
+  assert_eq!(ConstantValue::I4(-42), field_value);
+  assert_eq!("-42", field_value.as_csharp());
+
+  let param_value = metadata.get_constant(HasConstant::Param(Index::new(1))).unwrap().unwrap();
+  assert_eq!(ConstantValue::String(vec![0x48, 0x69]), param_value);
+  assert_eq!("\"Hi\"", param_value.as_csharp());
+
+  assert_eq!(Some(ConstantValue::Null), metadata.get_constant(HasConstant::Param(Index::new(2))).unwrap());
+  assert_eq!(None, metadata.get_constant(HasConstant::Property(Index::new(1))).unwrap());
+}
+
+#[test]
+fn floating_point_and_char_constants() {
+  assert_eq!(ConstantValue::R8(1.5), ConstantValue::decode(ElementType::R8, &[0, 0, 0, 0, 0, 0, 0xF8, 0x3F]).unwrap());
+  assert_eq!("1.5f", ConstantValue::R4(1.5).as_csharp());
+  assert_eq!("float.NaN", ConstantValue::R4(f32::NAN).as_csharp());
+  assert_eq!("float.PositiveInfinity", ConstantValue::R4(f32::INFINITY).as_csharp());
+  assert_eq!("double.NegativeInfinity", ConstantValue::R8(f64::NEG_INFINITY).as_csharp());
+  assert_eq!("double.NaN", ConstantValue::R8(f64::NAN).as_csharp());
+  assert_eq!("'a'", ConstantValue::decode(ElementType::Char, &[0x61, 0x00]).unwrap().as_csharp());
+  assert!(ConstantValue::decode(ElementType::SzArray, &[]).is_err());
+  assert!(ConstantValue::decode(ElementType::String, &[0x48, 0x00, 0x69]).is_err());
+}
+
+#[test]
+fn csharp_escapes() {
+  assert_eq!("'\\''", ConstantValue::Char(0x27).as_csharp());
+  assert_eq!("'\\0'", ConstantValue::Char(0).as_csharp());
+  assert_eq!("'\\ud800'", ConstantValue::Char(0xD800).as_csharp());
+  assert_eq!("\"a\\\"b\\\\c'\\u001f\\né\"", ConstantValue::String("a\"b\\c'\u{1f}\né".encode_utf16().collect()).as_csharp());
+  assert_eq!("\"a\\ud800\"", ConstantValue::decode(ElementType::String, &[0x61, 0x00, 0x00, 0xD8]).unwrap().as_csharp());
+}
@@ -0,0 +1,151 @@
+use metadata::custom_attributes::*;
+use metadata::tables::*;
+use tests::builder::MetadataBuilder;
+
+fn ser_string(value: &str) -> Vec<u8> {
+  let mut bytes = vec![value.len() as u8];
+  bytes.extend_from_slice(value.as_bytes());
+  bytes
+}
+
+#[test]
+fn obsolete_attribute_from_member_ref() {
+  let mut builder = MetadataBuilder::new();
+
+  let name = builder.string("mscorlib");
+  let mscorlib = builder.row(AssemblyRefEntry {
+    major_version: 4, minor_version: 0, build_number: 0, revision_number: 0, flags: 0,
+    public_key_or_token: Index::new(0), name, culture: Index::new(0), hash_value: Index::new(0)
+  });
+
+  let (name, namespace) = (builder.string("ObsoleteAttribute"), builder.string("System"));
+  let obsolete = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::AssemblyRef(mscorlib), name, namespace });
+
+  // instance void .ctor(string, bool)
+  let (name, signature) = (builder.string(".ctor"), builder.blob(vec![0x20, 0x02, 0x01, 0x0E, 0x02]));
+  let constructor = builder.row(MemberRefEntry { class: MemberRefParent::TypeRef(obsolete), name, signature });
+
+  let mut value = vec![0x01, 0x00];
+  value.extend(ser_string("Use Bar instead"));
+  value.extend(&[0x01, 0x00, 0x00]);
+  let value = builder.blob(value);
+
+  let parent = HasCustomAttribute::MethodDef(Index::new(1));
+  builder.row(CustomAttributeEntry { parent, constructor: CustomAttributeType::MemberRef(constructor), value });
+
+  let metadata = builder.build();
+  let attributes = metadata.custom_attributes_of(parent).unwrap();
+
+  assert_eq!(1, attributes.len());
+  assert_eq!("System.ObsoleteAttribute", attributes[0].attribute_type);
+  assert_eq!(vec![
+    CustomAttributeValue::String(Some("Use Bar instead".to_string())),
+    CustomAttributeValue::Bool(true)
+  ], attributes[0].fixed_args);
+  assert!(attributes[0].named_args.is_empty());
+
+  assert!(metadata.custom_attributes_of(HasCustomAttribute::MethodDef(Index::new(2))).unwrap().is_empty());
+}
+
+#[test]
+fn enums_types_boxed_values_arrays_and_named_args() {
+  let mut builder = MetadataBuilder::new();
+
+  let (name, namespace) = (builder.string("Type"), builder.string("System"));
+  let system_type = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::Module(Index::new(1)), name, namespace });
+
+  let (name, namespace) = (builder.string("<Module>"), builder.string(""));
+  builder.row(TypeDefEntry {
+    flags: tdClass, name, namespace, extends: TypeDefOrRef::TypeDef(Index::new(0)),
+    fields: Index::new(1), methods: Index::new(1)
+  });
+
+  // enum Ns.Color : byte { Red }
+  let (name, namespace) = (builder.string("Color"), builder.string("Ns"));
+  let color = builder.row(TypeDefEntry {
+    flags: tdSealed, name, namespace, extends: TypeDefOrRef::TypeRef(Index::new(0)),
+    fields: Index::new(1), methods: Index::new(1)
+  });
+  let (name, signature) = (builder.string("value__"), builder.blob(vec![0x06, 0x05]));
+  builder.row(FieldEntry { flags: 0x0606, name, signature });
+  let (name, signature) = (builder.string("Red"), builder.blob(vec![0x06, 0x11, (color.0 << 2) as u8]));
+  builder.row(FieldEntry { flags: 0x8056, name, signature });
+
+  let (name, namespace) = (builder.string("MyAttribute"), builder.string("Ns"));
+  let attribute = builder.row(TypeDefEntry {
+    flags: tdPublic, name, namespace, extends: TypeDefOrRef::TypeRef(Index::new(0)),
+    fields: Index::new(3), methods: Index::new(1)
+  });
+
+  // instance void .ctor(Ns.Color, System.Type, object, int[])
+  let (name, signature) = (builder.string(".ctor"), builder.blob(vec![
+    0x20, 0x04, 0x01, 0x11, (color.0 << 2) as u8, 0x12, ((system_type.0 << 2) | 1) as u8, 0x1C, 0x1D, 0x08
+  ]));
+  let constructor = builder.row(MethodDefEntry {
+    rva: 0, impl_flags: 0, flags: 0x1886, name, signature, param_list: Index::new(1)
+  });
+
+  let mut value = vec![0x01, 0x00, 0x02];
+  value.extend(ser_string("System.Int32"));
+  value.extend(&[0x08, 0x07, 0x00, 0x00, 0x00]);
+  value.extend(&[0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]);
+  // 3 named arguments
+  value.extend(&[0x03, 0x00]);
+  // string[] Names = null
+  value.extend(&[0x54, 0x1D, 0x0E]);
+  value.extend(ser_string("Names"));
+  value.extend(&[0xFF, 0xFF, 0xFF, 0xFF]);
+  // Ns.Color Shade = 1
+  value.extend(&[0x53, 0x55]);
+  value.extend(ser_string("Ns.Color, Test"));
+  value.extend(ser_string("Shade"));
+  value.push(0x01);
+  // string Note = null
+  value.extend(&[0x54, 0x0E]);
+  value.extend(ser_string("Note"));
+  value.push(0xFF);
+  let value = builder.blob(value);
+
+  let parent = HasCustomAttribute::TypeDef(attribute);
+  builder.row(CustomAttributeEntry { parent, constructor: CustomAttributeType::MethodDef(constructor), value });
+
+  let metadata = builder.build();
+  let attributes = metadata.custom_attributes_of(parent).unwrap();
+
+  assert_eq!("Ns.MyAttribute", attributes[0].attribute_type);
+  assert_eq!(vec![
+    CustomAttributeValue::Enum("Ns.Color".to_string(), box CustomAttributeValue::U1(2)),
+    CustomAttributeValue::Type(Some("System.Int32".to_string())),
+    CustomAttributeValue::I4(7),
+    CustomAttributeValue::Array(Some(vec![CustomAttributeValue::I4(1), CustomAttributeValue::I4(2)]))
+  ], attributes[0].fixed_args);
+
+  assert_eq!(Some(&CustomAttributeValue::Array(None)), attributes[0].get_named_arg("Names"));
+  assert_eq!(NamedArgumentKind::Field, attributes[0].named_args[1].kind);
+  assert_eq!(Some(&CustomAttributeValue::Enum("Ns.Color, Test".to_string(), box CustomAttributeValue::U1(1))),
+             attributes[0].get_named_arg("Shade"));
+  assert_eq!(Some(&CustomAttributeValue::String(None)), attributes[0].get_named_arg("Note"));
+}
+
+#[test]
+fn enum_from_unresolved_assembly_is_an_error() {
+  let mut builder = MetadataBuilder::new();
+
+  let (name, namespace) = (builder.string("LevelAttribute"), builder.string("Lib"));
+  let level = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::Module(Index::new(1)), name, namespace });
+  // instance void .ctor()
+  let (name, signature) = (builder.string(".ctor"), builder.blob(vec![0x20, 0x00, 0x01]));
+  let constructor = builder.row(MemberRefEntry { class: MemberRefParent::TypeRef(level), name, signature });
+
+  // Lib.Level Value = 1, whose size isn't known without Lib
+  let mut value = vec![0x01, 0x00, 0x01, 0x00, 0x53, 0x55];
+  value.extend(ser_string("Lib.Level, Lib"));
+  value.extend(ser_string("Value"));
+  value.push(0x01);
+  let value = builder.blob(value);
+
+  let parent = HasCustomAttribute::MethodDef(Index::new(1));
+  builder.row(CustomAttributeEntry { parent, constructor: CustomAttributeType::MemberRef(constructor), value });
+
+  assert!(builder.build().custom_attributes_of(parent).is_err());
+}
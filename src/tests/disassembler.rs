@@ -0,0 +1,95 @@
+use loader::code::*;
+use metadata::tables::*;
+use tests::builder::MetadataBuilder;
+
+fn type_ref(builder: &mut MetadataBuilder, mscorlib: Index<AssemblyRefEntry>, name: &str) -> Index<TypeRefEntry> {
+  let (name, namespace) = (builder.string(name), builder.string("System"));
+  builder.row(TypeRefEntry { resolution_scope: ResolutionScope::AssemblyRef(mscorlib), name, namespace })
+}
+
+#[test]
+fn hello_world_with_try_catch() {
+  let mut builder = MetadataBuilder::new();
+
+  let (name, culture) = (builder.string("mscorlib"), builder.string(""));
+  let token = builder.blob(vec![0xB7, 0x7A, 0x5C, 0x56, 0x19, 0x34, 0xE0, 0x89]);
+  let mscorlib = builder.row(AssemblyRefEntry {
+    major_version: 4, minor_version: 0, build_number: 0, revision_number: 0, flags: 0,
+    public_key_or_token: token, name, culture, hash_value: Index::new(0)
+  });
+
+  let object = type_ref(&mut builder, mscorlib, "Object");
+  let console = type_ref(&mut builder, mscorlib, "Console");
+  let exception = type_ref(&mut builder, mscorlib, "Exception");
+  let disposable = type_ref(&mut builder, mscorlib, "IDisposable");
+
+  let (name, signature) = (builder.string("WriteLine"), builder.blob(vec![0x00, 0x01, 0x01, 0x0E]));
+  builder.row(MemberRefEntry { class: MemberRefParent::TypeRef(console), name, signature });
+
+  for &(namespace, name, flags, extends) in &[
+    ("", "<Module>", tdClass, TypeDefOrRef::TypeDef(Index::new(0))),
+    ("Sample", "Program", tdPublic | tdBeforeFieldInit, TypeDefOrRef::TypeRef(object))
+  ] {
+    let (name, namespace) = (builder.string(name), builder.string(namespace));
+    builder.row(TypeDefEntry { flags, name, namespace, extends, fields: Index::new(1), methods: Index::new(1) });
+  }
+  builder.row(InterfaceImplEntry { class: Index::new(2), interface: TypeDefOrRef::TypeRef(disposable) });
+
+  // public static void Main(string[] args)
+  let (name, signature) = (builder.string("Main"), builder.blob(vec![0x00, 0x01, 0x01, 0x1D, 0x0E]));
+  let main = builder.row(MethodDefEntry { rva: 0x2050, impl_flags: 0, flags: 0x0096, name, signature, param_list: Index::new(1) });
+  let name = builder.string("args");
+  builder.row(ParamEntry { flags: 0, sequence: 1, name });
+
+  let hello = builder.user_string("Hello");
+  let mut metadata = builder.build();
+
+  let code = vec![
+    0x72, hello as u8, 0x00, 0x00, 0x70, // 0: ldstr "Hello"
+    0x28, 0x01, 0x00, 0x00, 0x0A,        // 5: call Console::WriteLine
+    0xDE, 0x03,                          // 10: leave.s 15
+    0x26,                                // 12: pop
+    0xDE, 0x00,                          // 13: leave.s 15
+    0x2A                                 // 15: ret
+  ];
+  let header = MethodHeader { flags: FatFormat, max_stack: 1, code_size: code.len() as u32, local_var_signature_token: 0 };
+  let exception_clauses = vec![ExceptionClause {
+    kind: ExceptionClauseKind::Catch(0x0100_0000 | exception.0), try_offset: 0, try_length: 12, handler_offset: 12, handler_length: 3
+  }];
+  metadata.method_bodies.insert(main.0 - 1, MethodBody { header, code, exception_clauses });
+
+  let listing = metadata.disassemble(Some(main));
+  let lines = listing.lines().collect::<Vec<_>>();
+
+  let expected = [
+    ".assembly extern mscorlib",
+    "  .publickeytoken = ( B7 7A 5C 56 19 34 E0 89 )",
+    "  .ver 4:0:0:0",
+    ".class public auto ansi beforefieldinit Sample.Program",
+    "       extends [mscorlib]System.Object",
+    "       implements [mscorlib]System.IDisposable",
+    "  .method public hidebysig static void  Main(string[] args) cil managed",
+    "    .entrypoint",
+    "    .maxstack  1",
+    "    .try",
+    "      IL_0000:  ldstr      \"Hello\"",
+    "      IL_0005:  call       void [mscorlib]System.Console::WriteLine(string)",
+    "      IL_000a:  leave.s    IL_000f",
+    "    }  // end .try",
+    "    catch [mscorlib]System.Exception",
+    "      IL_000c:  pop",
+    "    }  // end handler",
+    "    IL_000f:  ret",
+    "  } // end of method Program::Main",
+    "} // end of class Program"
+  ];
+
+  // The expected lines must appear in this order
+  let mut position = 0;
+  for line in &expected {
+    match lines[position ..].iter().position(|x| x == line) {
+      Some(found) => position += found + 1,
+      None => panic!("Missing or out of order: {}\n{}", line, listing)
+    }
+  }
+}
@@ -0,0 +1,68 @@
+use metadata::generics::*;
+use metadata::signature::TypeSig;
+use metadata::tables::*;
+use tests::builder::MetadataBuilder;
+
+#[test]
+fn generic_params_with_constraints() {
+  let mut builder = MetadataBuilder::new();
+
+  let (name, namespace) = (builder.string("IComparable"), builder.string("System"));
+  let comparable = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::Module(Index::new(1)), name, namespace });
+  let (name, namespace) = (builder.string("ValueType"), builder.string("System"));
+  let value_type = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::Module(Index::new(1)), name, namespace });
+
+  let owner = TypeOrMethodDef::TypeDef(Index::new(1));
+
+  // Declared out of order, to check that the parameters are sorted by number
+  let name = builder.string("TValue");
+  let value = builder.row(GenericParamEntry { number: 1, flags: 0x0008 | 0x0010, owner, name });
+  let name = builder.string("TKey");
+  let key = builder.row(GenericParamEntry { number: 0, flags: 0x0004 | 0x0010, owner, name });
+  let name = builder.string("T");
+  builder.row(GenericParamEntry { number: 0, flags: 0x0001, owner: TypeOrMethodDef::TypeDef(Index::new(2)), name });
+
+  builder.row(GenericParamConstraintEntry { owner: key, constraint: TypeDefOrRef::TypeRef(comparable) });
+  builder.row(GenericParamConstraintEntry { owner: value, constraint: TypeDefOrRef::TypeRef(value_type) });
+
+  let metadata = builder.build();
+  let params = metadata.generic_params_of(owner);
+
+  assert_eq!(2, params.len());
+  assert_eq!("TKey", params[0].name);
+  assert_eq!(Variance::Invariant, params[0].variance);
+  assert!(params[0].reference_type && params[0].default_constructor && !params[0].value_type);
+  assert_eq!(vec![TypeDefOrRef::TypeRef(comparable)], params[0].constraints);
+  assert_eq!(Some("where TKey : class, System.IComparable, new()".to_string()), params[0].constraint_clause(&metadata));
+
+  assert_eq!("TValue", params[1].name);
+  assert_eq!(Some("where TValue : struct".to_string()), params[1].constraint_clause(&metadata));
+
+  let params = metadata.generic_params_of(TypeOrMethodDef::TypeDef(Index::new(2)));
+  assert_eq!("out T", params[0].as_csharp());
+  assert_eq!(None, params[0].constraint_clause(&metadata));
+
+  assert!(metadata.generic_params_of(TypeOrMethodDef::MethodDef(Index::new(1))).is_empty());
+}
+
+#[test]
+fn method_spec_instantiation() {
+  let mut builder = MetadataBuilder::new();
+
+  let (name, namespace) = (builder.string("Enumerable"), builder.string("System.Linq"));
+  let enumerable = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::Module(Index::new(1)), name, namespace });
+
+  // static !!1 Select<2>(!!0) - simplified
+  let (name, signature) = (builder.string("Select"), builder.blob(vec![0x10, 0x02, 0x01, 0x1E, 0x01, 0x1E, 0x00]));
+  let select = builder.row(MemberRefEntry { class: MemberRefParent::TypeRef(enumerable), name, signature });
+
+  let instantiation = builder.blob(vec![0x0A, 0x02, 0x08, 0x0E]);
+  let method_spec = builder.row(MethodSpecEntry { method: MethodDefOrRef::MethodRef(select), instantiation });
+
+  let metadata = builder.build();
+  let method_spec = metadata.get_method_spec(&method_spec).unwrap();
+
+  assert_eq!(MethodDefOrRef::MethodRef(select), method_spec.method);
+  assert_eq!(vec![TypeSig::I4, TypeSig::String], method_spec.type_args);
+  assert_eq!("System.Linq.Enumerable.Select<int, string>", method_spec.as_csharp(&metadata));
+}
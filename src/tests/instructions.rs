@@ -0,0 +1,68 @@
+use loader::instructions::*;
+use loader::stream::TableId;
+
+fn decode(code: &[u8]) -> Vec<(u32, Instruction)> {
+  Instructions::new(code).collect::<Result<Vec<_>, _>>().unwrap()
+}
+
+#[test]
+fn operands() {
+  let code = [
+    0x1F, 0xFE,                                           // ldc.i4.s -2
+    0x21, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, // ldc.i8 0x8000000000000001
+    0x22, 0x00, 0x00, 0xC0, 0x3F,                         // ldc.r4 1.5
+    0x72, 0x01, 0x00, 0x00, 0x70,                         // ldstr #US 1
+    0x28, 0x05, 0x00, 0x00, 0x0A,                         // call MemberRef 5
+    0xFE, 0x0C, 0x00, 0x01,                               // ldloc 256
+    0x2A                                                  // ret
+  ];
+
+  assert_eq!(vec![
+    (0, Instruction::LdcI4S(-2)),
+    (2, Instruction::LdcI8(0x8000_0000_0000_0001u64 as i64)),
+    (11, Instruction::LdcR4(1.5)),
+    (16, Instruction::Ldstr(Token::UserString(1))),
+    (21, Instruction::Call(Token::Table(TableId::MemberRef, 5))),
+    (26, Instruction::Ldloc(256)),
+    (30, Instruction::Ret)
+  ], decode(&code));
+
+  assert_eq!(0x0A00_0005, Token::Table(TableId::MemberRef, 5).to_raw());
+  assert_eq!("ldloc", Instruction::Ldloc(256).mnemonic());
+  assert_eq!(0xFE0C, Instruction::Ldloc(256).opcode());
+}
+
+#[test]
+fn branch_targets_and_prefixes() {
+  let code = [
+    0x2B, 0x02,                                     // 0: br.s 4
+    0x00,                                           // 2: nop
+    0x00,                                           // 3: nop
+    0x45, 0x02, 0x00, 0x00, 0x00,                   // 4: switch (17, 12)
+    0x04, 0x00, 0x00, 0x00,
+    0xFF, 0xFF, 0xFF, 0xFF,
+    0xDD, 0xF0, 0xFF, 0xFF, 0xFF,                   // 17: leave 6
+    0xFE, 0x16, 0x02, 0x00, 0x00, 0x1B,             // 22: constrained. TypeSpec 2
+    0xFE, 0x14,                                     // 28: tail.
+    0x6F, 0x01, 0x00, 0x00, 0x0A                    // 30: callvirt MemberRef 1
+  ];
+
+  let instructions = decode(&code);
+
+  assert_eq!((0, Instruction::BrS(4)), instructions[0]);
+  assert_eq!((4, Instruction::Switch(vec![21, 16])), instructions[3]);
+  assert_eq!((17, Instruction::Leave(6)), instructions[4]);
+  assert_eq!((22, Instruction::Constrained(Token::Table(TableId::TypeSpec, 2))), instructions[5]);
+  assert!(instructions[5].1.is_prefix() && instructions[6].1.is_prefix());
+  assert_eq!("tail.", instructions[6].1.mnemonic());
+  assert_eq!((30, Instruction::Callvirt(Token::Table(TableId::MemberRef, 1))), instructions[7]);
+}
+
+#[test]
+fn invalid_opcodes() {
+  let mut instructions = Instructions::new(&[0x00, 0x24, 0x00]);
+
+  assert_eq!((0, Instruction::Nop), instructions.next().unwrap().unwrap());
+  assert!(instructions.next().unwrap().is_err());
+  assert!(instructions.next().is_none());
+}
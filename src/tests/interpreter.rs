@@ -0,0 +1,1506 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use loader::code::*;
+use metadata::Metadata;
+use metadata::tables::*;
+use runtime::error::{ExecutionError, ManagedException, StackFrame};
+use runtime::internal_calls::{InternalCalls, MethodKey};
+use runtime::interpreter::Interpreter;
+use runtime::value::StackValue;
+use tests::builder::MetadataBuilder;
+
+// A method, given as (name, signature blob, local variable signature blob without the 0x07 prefix, code).
+// Methods with `this` in their signature are instance methods, and .ctor is a constructor.
+type TestMethod<'a> = (&'a str, Vec<u8>, Vec<u8>, Vec<u8>);
+
+// A type in the Sample namespace
+struct TestType<'a> {
+  name: &'a str,
+  extends: TypeDefOrRef,
+  // Fields as (name, flags, signature blob)
+  fields: Vec<(&'a str, u16, Vec<u8>)>,
+  methods: Vec<TestMethod<'a>>,
+  // Flags of methods that aren't public static, public instance or constructors, by name
+  method_flags: Vec<(&'a str, u16)>
+}
+
+fn build(methods: Vec<TestMethod>) -> Metadata {
+  build_with(MetadataBuilder::new(), methods)
+}
+
+// Builds a Sample.Program class with the given methods
+fn build_with(builder: MetadataBuilder, methods: Vec<TestMethod>) -> Metadata {
+  build_types(builder, vec![TestType { name: "Program", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields: vec![], methods, method_flags: vec![] }])
+}
+
+fn build_types(mut builder: MetadataBuilder, types: Vec<TestType>) -> Metadata {
+  let namespace = builder.string("Sample");
+  let (mut field_count, mut method_count) = (0, 0);
+  let mut bodies = vec![];
+
+  for type_ in types {
+    let name = builder.string(type_.name);
+    builder.row(TypeDefEntry {
+      flags: tdPublic, name, namespace, extends: type_.extends,
+      fields: Index::new(field_count + 1), methods: Index::new(method_count + 1)
+    });
+
+    for (name, flags, signature) in type_.fields {
+      let (name, signature) = (builder.string(name), builder.blob(signature));
+      builder.row(FieldEntry { flags, name, signature });
+      field_count += 1;
+    }
+
+    for (name, signature, locals, code) in type_.methods {
+      // Unless given, public static, public instance or public specialname rtspecialname instance
+      let custom_flags = type_.method_flags.iter().find(|&&(other, _)| other == name).map(|&(_, flags)| flags);
+      let flags = match (custom_flags, name, signature[0] & 0x20 != 0) {
+        (Some(flags), _, _) => flags,
+        (None, ".ctor", _) => 0x1806,
+        (None, _, true) => 0x0006,
+        (None, _, false) => 0x0016
+      };
+      let (name, signature) = (builder.string(name), builder.blob(signature));
+      let method = builder.row(MethodDefEntry { rva: 0x2050, impl_flags: 0, flags, name, signature, param_list: Index::new(1) });
+      method_count += 1;
+
+      let mut local_var_signature_token = 0;
+      if !locals.is_empty() {
+        let mut blob = vec![0x07];
+        blob.extend_from_slice(&locals);
+        let signature = builder.blob(blob);
+        local_var_signature_token = 0x1100_0000 | builder.row(StandAloneSigEntry { signature }).0;
+      }
+
+      let header = MethodHeader { flags: FatFormat, max_stack: 8, code_size: code.len() as u32, local_var_signature_token };
+      bodies.push((method, MethodBody { header, code, exception_clauses: vec![] }));
+    }
+  }
+
+  let mut metadata = builder.build();
+  for (method, body) in bodies {
+    metadata.method_bodies.insert(method.0 - 1, body);
+  }
+  metadata
+}
+
+fn run(method: TestMethod, args: Vec<StackValue>) -> Result<Option<StackValue>, ExecutionError> {
+  let metadata = build(vec![method]);
+  let mut interpreter = Interpreter::new(&metadata);
+  interpreter.run(&Index::new(1), args)
+}
+
+fn exception(type_name: &str, message: &str) -> Result<Option<StackValue>, ExecutionError> {
+  Err(ExecutionError::exception(type_name, message))
+}
+
+// Drops the stack trace of an unhandled exception, for comparing it with `exception`
+fn without_stack_trace(result: Result<Option<StackValue>, ExecutionError>) -> Result<Option<StackValue>, ExecutionError> {
+  match result {
+    Err(ExecutionError::UnhandledException(exception)) =>
+      Err(ExecutionError::UnhandledException(ManagedException { stack_trace: vec![], ..exception })),
+    other => other
+  }
+}
+
+#[test]
+fn addition_sample() {
+  // Main from sample/addition/add.cs: nop, ldc.i4.1, stloc.0, ldc.i4.2, stloc.1, ldc.i4.3, stloc.2, ret
+  let main = ("Main", vec![0x00, 0x00, 0x01], vec![0x03, 0x08, 0x08, 0x08], vec![0x00, 0x17, 0x0A, 0x18, 0x0B, 0x19, 0x0C, 0x2A]);
+  assert_eq!(Ok(None), run(main, vec![]));
+}
+
+#[test]
+fn loop_with_branches() {
+  // static int32 Sum(int32 n) { int s = 0; for (int i = 1; i <= n; i++) s += i; return s; }
+  let code = vec![
+    0x16, 0x0B,                                     // 0: ldc.i4.0, stloc.1
+    0x17, 0x0A,                                     // 2: ldc.i4.1, stloc.0
+    0x2B, 0x08,                                     // 4: br.s 14
+    0x07, 0x06, 0x58, 0x0B,                         // 6: ldloc.1, ldloc.0, add, stloc.1
+    0x06, 0x17, 0x58, 0x0A,                         // 10: ldloc.0, ldc.i4.1, add, stloc.0
+    0x06, 0x02, 0x31, 0xF4,                         // 14: ldloc.0, ldarg.0, ble.s 6
+    0x07, 0x2A                                      // 18: ldloc.1, ret
+  ];
+  let sum = ("Sum", vec![0x00, 0x01, 0x08, 0x08], vec![0x02, 0x08, 0x08], code);
+
+  assert_eq!(Ok(Some(StackValue::Int32(55))), run(sum.clone(), vec![StackValue::Int32(10)]));
+  assert_eq!(Ok(Some(StackValue::Int32(0))), run(sum, vec![StackValue::Int32(-1)]));
+}
+
+#[test]
+fn arithmetic_exceptions() {
+  // static int32 F(int32, int32) { ldarg.0, ldarg.1, <op>, ret }
+  let binary = |op| ("F", vec![0x00, 0x02, 0x08, 0x08, 0x08], vec![], vec![0x02, 0x03, op, 0x2A]);
+  let max = StackValue::Int32(i32::max_value());
+
+  assert_eq!(Ok(Some(StackValue::Int32(i32::min_value()))), run(binary(0x58), vec![max.clone(), StackValue::Int32(1)]));
+  assert_eq!(exception("System.OverflowException", "Arithmetic operation resulted in an overflow."),
+    without_stack_trace(run(binary(0xD6), vec![max, StackValue::Int32(1)])));
+  assert_eq!(exception("System.DivideByZeroException", "Attempted to divide by zero."),
+    without_stack_trace(run(binary(0x5B), vec![StackValue::Int32(1), StackValue::Int32(0)])));
+  assert_eq!(Ok(Some(StackValue::Int32(-1))), run(binary(0x5D), vec![StackValue::Int32(-7), StackValue::Int32(2)]));
+
+  // static int64 G(float64) { ldarg.0, conv.ovf.i4, conv.i8, ret }
+  let convert = ("G", vec![0x00, 0x01, 0x0A, 0x0D], vec![], vec![0x02, 0xB7, 0x6A, 0x2A]);
+  assert_eq!(Ok(Some(StackValue::Int64(-3))), run(convert.clone(), vec![StackValue::Float(-3.7)]));
+  assert_eq!(exception("System.OverflowException", "Arithmetic operation resulted in an overflow."),
+    without_stack_trace(run(convert, vec![StackValue::Float(1e10)])));
+}
+
+#[test]
+fn float_comparisons_and_invalid_programs() {
+  // static bool Less(float64, float64) { ldarg.0, ldarg.1, clt(.un), ret }
+  let less = |unsigned: bool| ("Less", vec![0x00, 0x02, 0x02, 0x0D, 0x0D], vec![], vec![0x02, 0x03, 0xFE, if unsigned { 0x05 } else { 0x04 }, 0x2A]);
+  let args = vec![StackValue::Float(::std::f64::NAN), StackValue::Float(1.0)];
+
+  assert_eq!(Ok(Some(StackValue::Int32(0))), run(less(false), args.clone()));
+  assert_eq!(Ok(Some(StackValue::Int32(1))), run(less(true), args));
+
+  // ldc.i4.1, ldc.r8 1.0, add
+  let code = vec![0x17, 0x23, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F, 0x58, 0x2A];
+  let invalid = run(("Bad", vec![0x00, 0x00, 0x08], vec![], code), vec![]);
+  assert_eq!(Err(ExecutionError::InvalidProgram { method: "Sample.Program.Bad".to_string(), offset: 10, reason: "Invalid operand types".to_string() }), invalid);
+}
+
+#[test]
+fn recursive_calls() {
+  // static int32 Fib(int32 n) { return n < 2 ? n : Fib(n - 1) + Fib(n - 2); }
+  let code = vec![
+    0x02, 0x18, 0x2F, 0x02,                         // 0: ldarg.0, ldc.i4.2, bge.s 6
+    0x02, 0x2A,                                     // 4: ldarg.0, ret
+    0x02, 0x17, 0x59, 0x28, 0x01, 0x00, 0x00, 0x06, // 6: ldarg.0, ldc.i4.1, sub, call Fib
+    0x02, 0x18, 0x59, 0x28, 0x01, 0x00, 0x00, 0x06, // 14: ldarg.0, ldc.i4.2, sub, call Fib
+    0x58, 0x2A                                      // 22: add, ret
+  ];
+  let fib = ("Fib", vec![0x00, 0x01, 0x08, 0x08], vec![], code);
+
+  assert_eq!(Ok(Some(StackValue::Int32(6765))), run(fib, vec![StackValue::Int32(20)]));
+}
+
+#[test]
+fn mutual_recursion_and_stack_overflow() {
+  // static bool IsEven(int32 n) { return n == 0 || IsOdd(n - 1); }
+  // static bool IsOdd(int32 n) { return n != 0 && IsEven(n - 1); }
+  let parity = |name, result, other| {
+    let code = vec![
+      0x02, 0x2D, 0x02,                             // 0: ldarg.0, brtrue.s 5
+      result, 0x2A,                                 // 3: ldc.i4 result, ret
+      0x02, 0x17, 0x59, 0x28, other, 0x00, 0x00, 0x06, // 5: ldarg.0, ldc.i4.1, sub, call other
+      0x2A                                          // 13: ret
+    ];
+    (name, vec![0x00, 0x01, 0x02, 0x08], vec![], code)
+  };
+  let metadata = build(vec![parity("IsEven", 0x17, 0x02), parity("IsOdd", 0x16, 0x01)]);
+  let (is_even, is_odd) = (Index::new(1), Index::new(2));
+
+  let mut interpreter = Interpreter::new(&metadata);
+  assert_eq!(Ok(Some(StackValue::Int32(1))), interpreter.run(&is_even, vec![StackValue::Int32(10)]));
+  assert_eq!(Ok(Some(StackValue::Int32(0))), interpreter.run(&is_odd, vec![StackValue::Int32(10)]));
+  assert_eq!(Ok(Some(StackValue::Int32(1))), interpreter.run(&is_odd, vec![StackValue::Int32(7)]));
+
+  interpreter.set_max_depth(50);
+  assert_eq!(exception("System.StackOverflowException", "Operation caused a stack overflow."),
+    without_stack_trace(interpreter.run(&is_even, vec![StackValue::Int32(100)])));
+  // The interpreter is still usable afterwards
+  assert_eq!(Ok(Some(StackValue::Int32(1))), interpreter.run(&is_even, vec![StackValue::Int32(48)]));
+}
+
+// Console output captured by a test
+#[derive(Clone)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+// Adds a reference to `[assembly]namespace.type_name`
+fn type_ref(builder: &mut MetadataBuilder, assembly: &str, namespace: &str, type_name: &str) -> Index<TypeRefEntry> {
+  let name_index = builder.string(assembly);
+  let assembly_ref = builder.row(AssemblyRefEntry {
+    major_version: 4, minor_version: 0, build_number: 0, revision_number: 0, flags: 0,
+    public_key_or_token: Index::new(0), name: name_index, culture: Index::new(0), hash_value: Index::new(0)
+  });
+  let (type_name, namespace) = (builder.string(type_name), builder.string(namespace));
+  builder.row(TypeRefEntry { resolution_scope: ResolutionScope::AssemblyRef(assembly_ref), name: type_name, namespace })
+}
+
+// Adds `[assembly]namespace.type_name` and a static method of it with the given signature, returning the MemberRef token
+fn member_ref(builder: &mut MetadataBuilder, assembly: &str, namespace: &str, type_name: &str, name: &str, signature: Vec<u8>) -> u32 {
+  let type_ref = type_ref(builder, assembly, namespace, type_name);
+  let (name, signature) = (builder.string(name), builder.blob(signature));
+  0x0A00_0000 | builder.row(MemberRefEntry { class: MemberRefParent::TypeRef(type_ref), name, signature }).0
+}
+
+fn token_bytes(token: u32) -> Vec<u8> {
+  vec![token as u8, (token >> 8) as u8, (token >> 16) as u8, (token >> 24) as u8]
+}
+
+#[test]
+fn hello_world() {
+  let mut builder = MetadataBuilder::new();
+  let write = member_ref(&mut builder, "System.Console", "System", "Console", "Write", vec![0x00, 0x01, 0x01, 0x0E]);
+  let write_line = member_ref(&mut builder, "System.Console", "System", "Console", "WriteLine", vec![0x00, 0x01, 0x01, 0x0E]);
+  let (hello, world) = (builder.user_string("Hello, "), builder.user_string("world!"));
+
+  // Main from sample/helloworld/HelloWorld.cs: ldstr, call Console.Write, ldstr, call Console.WriteLine, ret
+  let mut code = vec![];
+  for &(string, method) in &[(hello, write), (world, write_line)] {
+    code.push(0x72);
+    code.extend(token_bytes(0x7000_0000 | string));
+    code.push(0x28);
+    code.extend(token_bytes(method));
+  }
+  code.push(0x2A);
+  let metadata = build_with(builder, vec![("Main", vec![0x00, 0x00, 0x01], vec![], code)]);
+
+  let output = SharedBuffer(Rc::new(RefCell::new(vec![])));
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(output.clone()));
+  assert_eq!(Ok(None), interpreter.run(&Index::new(1), vec![]));
+  assert_eq!("Hello, world!\n", String::from_utf8(output.0.borrow().clone()).unwrap());
+}
+
+#[test]
+fn custom_internal_calls() {
+  let mut builder = MetadataBuilder::new();
+  let square = member_ref(&mut builder, "Host", "Host", "Math", "Square", vec![0x00, 0x01, 0x08, 0x08]);
+  let cube = member_ref(&mut builder, "Host", "Host", "Math", "Cube", vec![0x00, 0x01, 0x08, 0x08]);
+
+  // static int32 F(int32 x) { return Square(x) + Cube(x); }
+  let mut code = vec![0x02, 0x28];
+  code.extend(token_bytes(square));
+  code.extend(vec![0x02, 0x28]);
+  code.extend(token_bytes(cube));
+  code.extend(vec![0x58, 0x2A]);
+  let metadata = build_with(builder, vec![("F", vec![0x00, 0x01, 0x08, 0x08], vec![], code)]);
+
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::new());
+  interpreter.internal_calls().register(MethodKey::new("Host", "Host", "Math", "Square", "int32(int32)"), |_, args| {
+    match args[0] {
+      StackValue::Int32(x) => Ok(Some(StackValue::Int32(x * x))),
+      _ => unreachable!()
+    }
+  });
+
+  assert_eq!(exception("System.MissingMethodException", "Method not found: '[Host]Host.Math::Cube int32(int32)'."),
+    without_stack_trace(interpreter.run(&Index::new(1), vec![StackValue::Int32(3)])));
+
+  interpreter.internal_calls().register(MethodKey::new("Host", "Host", "Math", "Cube", "int32(int32)"), |_, args| {
+    match args[0] {
+      StackValue::Int32(x) => Ok(Some(StackValue::Int32(x * x * x))),
+      _ => unreachable!()
+    }
+  });
+  assert_eq!(Ok(Some(StackValue::Int32(36))), interpreter.run(&Index::new(1), vec![StackValue::Int32(3)]));
+}
+
+// Parts of a test method using System.String
+enum Il {
+  Code(Vec<u8>),
+  Ldstr(&'static str),
+  // A call to a method of [System.Runtime]System.String, with its name and signature
+  Call(&'static str, Vec<u8>),
+  // An instruction taking the token of a type of [System.Runtime]System, e.g. box or newarr
+  Typed(Vec<u8>, &'static str)
+}
+
+fn build_strings(return_type: u8, parts: Vec<Il>) -> Metadata {
+  let mut builder = MetadataBuilder::new();
+  let mut code = vec![];
+  for part in parts {
+    match part {
+      Il::Code(bytes) => code.extend(bytes),
+      Il::Ldstr(value) => {
+        code.push(0x72);
+        code.extend(token_bytes(0x7000_0000 | builder.user_string(value)));
+      },
+      Il::Call(name, signature) => {
+        code.push(0x28);
+        code.extend(token_bytes(member_ref(&mut builder, "System.Runtime", "System", "String", name, signature)));
+      },
+      Il::Typed(opcode, type_name) => {
+        code.extend(opcode);
+        code.extend(token_bytes(0x0100_0000 | type_ref(&mut builder, "System.Runtime", "System", type_name).0));
+      }
+    }
+  }
+  code.push(0x2A);
+
+  build_with(builder, vec![("F", vec![0x00, 0x00, return_type], vec![], code)])
+}
+
+#[test]
+fn strings() {
+  let concat = || vec![Il::Ldstr("ab"), Il::Ldstr("cd"), Il::Call("Concat", vec![0x00, 0x02, 0x0E, 0x0E, 0x0E])];
+  let int32 = |parts: Vec<Il>| {
+    let metadata = build_strings(0x08, parts);
+    let result = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink())).run(&Index::new(1), vec![]);
+    result
+  };
+
+  let mut length = concat();
+  length.push(Il::Call("get_Length", vec![0x20, 0x00, 0x08]));
+  assert_eq!(Ok(Some(StackValue::Int32(4))), int32(length));
+
+  let mut chars = concat();
+  chars.push(Il::Code(vec![0x18]));
+  chars.push(Il::Call("get_Chars", vec![0x20, 0x01, 0x03, 0x08]));
+  assert_eq!(Ok(Some(StackValue::Int32('c' as i32))), int32(chars));
+
+  let mut out_of_range = concat();
+  out_of_range.push(Il::Code(vec![0x1A]));
+  out_of_range.push(Il::Call("get_Chars", vec![0x20, 0x01, 0x03, 0x08]));
+  assert_eq!(exception("System.IndexOutOfRangeException", "Index was outside the bounds of the array."), without_stack_trace(int32(out_of_range)));
+
+  // Literals are interned, but concatenation creates a new string which is only equal by value
+  assert_eq!(Ok(Some(StackValue::Int32(1))), int32(vec![Il::Ldstr("ab"), Il::Ldstr("ab"), Il::Code(vec![0xFE, 0x01])]));
+  let mut identity = concat();
+  identity.extend(vec![Il::Ldstr("abcd"), Il::Code(vec![0xFE, 0x01])]);
+  assert_eq!(Ok(Some(StackValue::Int32(0))), int32(identity));
+  let mut equality = concat();
+  equality.extend(vec![Il::Ldstr("abcd"), Il::Call("op_Equality", vec![0x00, 0x02, 0x02, 0x0E, 0x0E])]);
+  assert_eq!(Ok(Some(StackValue::Int32(1))), int32(equality));
+
+  // string.Format("{0}-{1,3}|{{}}", "ab" + "cd", "x")
+  let mut format = vec![Il::Ldstr("{0}-{1,3}|{{}}")];
+  format.extend(concat());
+  format.extend(vec![Il::Ldstr("x"), Il::Call("Format", vec![0x00, 0x03, 0x0E, 0x0E, 0x1C, 0x1C])]);
+  let metadata = build_strings(0x0E, format);
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink()));
+  match interpreter.run(&Index::new(1), vec![]) {
+    Ok(Some(StackValue::Object(Some(object)))) => assert_eq!(Some("abcd-  x|{}".to_string()), interpreter.heap().get_string(object)),
+    other => panic!("Unexpected result {:?}", other)
+  }
+  let string = |parts: Vec<Il>| {
+    let metadata = build_strings(0x0E, parts);
+    let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink()));
+    match interpreter.run(&Index::new(1), vec![]) {
+      Ok(Some(StackValue::Object(Some(object)))) => Ok(interpreter.heap().get_string(object).unwrap()),
+      other => Err(without_stack_trace(other).unwrap_err())
+    }
+  };
+
+  // Alignments of a million or more are rejected, including the ones whose absolute value overflows
+  for item in &["{0,1000000}", "{0,-2147483648}"] {
+    let format = vec![Il::Ldstr(item), Il::Ldstr("x"), Il::Call("Format", vec![0x00, 0x02, 0x0E, 0x0E, 0x1C])];
+    assert_eq!(Err(ExecutionError::exception("System.FormatException", "Input string was not in a correct format.")), string(format));
+  }
+  let format = vec![Il::Ldstr("{0,-3}|"), Il::Ldstr("x"), Il::Call("Format", vec![0x00, 0x02, 0x0E, 0x0E, 0x1C])];
+  assert_eq!(Ok("x  |".to_string()), string(format));
+
+  // string.Concat("x", 0.1f, null), where floats print with their own precision
+  let objects = vec![
+    Il::Ldstr("x"), Il::Code(vec![0x22, 0xCD, 0xCC, 0xCC, 0x3D]), Il::Typed(vec![0x8C], "Single"), Il::Code(vec![0x14]),
+    Il::Call("Concat", vec![0x00, 0x03, 0x0E, 0x1C, 0x1C, 0x1C])
+  ];
+  assert_eq!(Ok("x0.1".to_string()), string(objects));
+
+  // string.Concat(new[] { "a", null, "c" })
+  let array = vec![
+    Il::Code(vec![0x19]), Il::Typed(vec![0x8D], "String"),
+    Il::Code(vec![0x25, 0x16]), Il::Ldstr("a"), Il::Code(vec![0xA2, 0x25, 0x18]), Il::Ldstr("c"), Il::Code(vec![0xA2]),
+    Il::Call("Concat", vec![0x00, 0x01, 0x0E, 0x1D, 0x0E])
+  ];
+  assert_eq!(Ok("ac".to_string()), string(array));
+  let null_array = vec![Il::Code(vec![0x14]), Il::Call("Concat", vec![0x00, 0x01, 0x0E, 0x1D, 0x0E])];
+  assert_eq!(Err(ExecutionError::exception("System.ArgumentNullException", "Value cannot be null.\nParameter name: values")), string(null_array));
+
+  // Literals are UTF-16, and may contain lone surrogates
+  let mut builder = MetadataBuilder::new();
+  let literal = builder.user_string_utf16(vec!['a' as u16, 0xD800]);
+  let get_chars = member_ref(&mut builder, "System.Runtime", "System", "String", "get_Chars", vec![0x20, 0x01, 0x03, 0x08]);
+  let code = [vec![0x72], token_bytes(0x7000_0000 | literal), vec![0x17, 0x28], token_bytes(get_chars), vec![0x2A]].concat();
+  let metadata = build_with(builder, vec![("F", vec![0x00, 0x00, 0x08], vec![], code)]);
+  let result = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink())).run(&Index::new(1), vec![]);
+  assert_eq!(Ok(Some(StackValue::Int32(0xD800))), result);
+}
+
+#[test]
+fn objects_and_fields() {
+  let mut builder = MetadataBuilder::new();
+  let object_constructor = member_ref(&mut builder, "mscorlib", "System", "Object", ".ctor", vec![0x20, 0x00, 0x01]);
+
+  let mut base_constructor = vec![0x02, 0x28];                      // ldarg.0, call object::.ctor
+  base_constructor.extend(token_bytes(object_constructor));
+  base_constructor.push(0x2A);
+  let base = TestType {
+    name: "Base", extends: TypeDefOrRef::TypeRef(Index::new(1)),
+    fields: vec![("x", 0x0006, vec![0x06, 0x08])],
+    methods: vec![(".ctor", vec![0x20, 0x00, 0x01], vec![], base_constructor)],
+    method_flags: vec![]
+  };
+
+  // Derived(int x, int y) : base() { this.x = x; this.y = y; count++; }
+  let derived = TestType {
+    name: "Derived", extends: TypeDefOrRef::TypeDef(Index::new(1)),
+    fields: vec![("y", 0x0006, vec![0x06, 0x08]), ("count", 0x0016, vec![0x06, 0x08])],
+    methods: vec![(".ctor", vec![0x20, 0x02, 0x01, 0x08, 0x08], vec![], vec![
+      0x02, 0x28, 0x01, 0x00, 0x00, 0x06,                         // ldarg.0, call Base::.ctor
+      0x02, 0x03, 0x7D, 0x01, 0x00, 0x00, 0x04,                   // ldarg.0, ldarg.1, stfld x
+      0x02, 0x04, 0x7D, 0x02, 0x00, 0x00, 0x04,                   // ldarg.0, ldarg.2, stfld y
+      0x7E, 0x03, 0x00, 0x00, 0x04, 0x17, 0x58,                   // ldsfld count, ldc.i4.1, add
+      0x80, 0x03, 0x00, 0x00, 0x04, 0x2A                          // stsfld count, ret
+    ])],
+    method_flags: vec![]
+  };
+
+  // static int Run() { var d = new Derived(3, 4); new Derived(5, 6); var r = d.x * d.y + Derived.count; d.y += 10; return r + d.y; }
+  let run = ("Run", vec![0x00, 0x00, 0x08], vec![0x01, 0x12, 0x08], vec![
+    0x19, 0x1A, 0x73, 0x02, 0x00, 0x00, 0x06, 0x0A,               // ldc.i4.3, ldc.i4.4, newobj Derived, stloc.0
+    0x1B, 0x1C, 0x73, 0x02, 0x00, 0x00, 0x06, 0x26,               // ldc.i4.5, ldc.i4.6, newobj Derived, pop
+    0x06, 0x7B, 0x01, 0x00, 0x00, 0x04,                           // ldloc.0, ldfld x
+    0x06, 0x7B, 0x02, 0x00, 0x00, 0x04, 0x5A,                     // ldloc.0, ldfld y, mul
+    0x7E, 0x03, 0x00, 0x00, 0x04, 0x58,                           // ldsfld count, add
+    0x06, 0x7C, 0x02, 0x00, 0x00, 0x04,                           // ldloc.0, ldflda y
+    0x25, 0x4A, 0x1F, 0x0A, 0x58, 0x54,                           // dup, ldind.i4, ldc.i4.s 10, add, stind.i4
+    0x06, 0x7B, 0x02, 0x00, 0x00, 0x04, 0x58, 0x2A                // ldloc.0, ldfld y, add, ret
+  ]);
+
+  // Sums the results of four casts, weighted 1, 2, 4 and 8
+  let casts = ("Casts", vec![0x00, 0x00, 0x08], vec![], vec![
+    0x16, 0x16, 0x73, 0x02, 0x00, 0x00, 0x06,                     // new Derived(0, 0)
+    0x75, 0x01, 0x00, 0x00, 0x02, 0x14, 0xFE, 0x03,               // is Base
+    0x14, 0x75, 0x01, 0x00, 0x00, 0x02, 0x14, 0xFE, 0x01,         // (null as Base) == null
+    0x18, 0x5A, 0x58,
+    0x73, 0x01, 0x00, 0x00, 0x06,                                 // new Base()
+    0x75, 0x02, 0x00, 0x00, 0x02, 0x14, 0xFE, 0x03,               // is Derived
+    0x1A, 0x5A, 0x58,
+    0x73, 0x01, 0x00, 0x00, 0x06,                                 // new Base()
+    0x75, 0x01, 0x00, 0x00, 0x01, 0x14, 0xFE, 0x03,               // is object
+    0x1E, 0x5A, 0x58, 0x2A
+  ]);
+
+  // (Derived)new Base()
+  let bad_cast = ("BadCast", vec![0x00, 0x00, 0x01], vec![], vec![0x73, 0x01, 0x00, 0x00, 0x06, 0x74, 0x02, 0x00, 0x00, 0x02, 0x26, 0x2A]);
+  // ((Base)null).x
+  let null_field = ("NullField", vec![0x00, 0x00, 0x08], vec![], vec![0x14, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x2A]);
+
+  let program = TestType { name: "Program", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields: vec![], methods: vec![run, casts, bad_cast, null_field], method_flags: vec![] };
+  let metadata = build_types(builder, vec![base, derived, program]);
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink()));
+
+  assert_eq!(Ok(Some(StackValue::Int32(28))), interpreter.run(&Index::new(3), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(11))), interpreter.run(&Index::new(4), vec![]));
+  assert_eq!(exception("System.InvalidCastException", "Unable to cast object of type 'Sample.Base' to type 'Sample.Derived'."),
+    without_stack_trace(interpreter.run(&Index::new(5), vec![])));
+  assert_eq!(exception("System.NullReferenceException", "Object reference not set to an instance of an object."),
+    without_stack_trace(interpreter.run(&Index::new(6), vec![])));
+
+  // class A : B { static void New() { new A(); } }, class B : A
+  let new_a = ("New", vec![0x00, 0x00, 0x01], vec![], vec![0x73, 0x02, 0x00, 0x00, 0x06, 0x26, 0x2A]);
+  let constructor = (".ctor", vec![0x20, 0x00, 0x01], vec![], vec![0x2A]);
+  let metadata = build_types(MetadataBuilder::new(), vec![
+    TestType { name: "A", extends: TypeDefOrRef::TypeDef(Index::new(2)), fields: vec![], methods: vec![new_a, constructor], method_flags: vec![] },
+    TestType { name: "B", extends: TypeDefOrRef::TypeDef(Index::new(1)), fields: vec![], methods: vec![], method_flags: vec![] }
+  ]);
+  match Interpreter::new(&metadata).run(&Index::new(1), vec![]) {
+    Err(ExecutionError::InvalidProgram { ref reason, .. }) => assert_eq!("Sample.A derives from itself", reason),
+    other => panic!("Unexpected result {:?}", other)
+  }
+}
+
+#[test]
+fn value_types() {
+  let mut builder = MetadataBuilder::new();
+  type_ref(&mut builder, "mscorlib", "System", "ValueType");
+  type_ref(&mut builder, "mscorlib", "System", "Enum");
+  type_ref(&mut builder, "mscorlib", "System", "Int32");
+  type_ref(&mut builder, "mscorlib", "System", "Byte");
+  type_ref(&mut builder, "mscorlib", "System", "Boolean");
+  let write_line = member_ref(&mut builder, "mscorlib", "System", "Console", "WriteLine", vec![0x00, 0x01, 0x01, 0x1C]);
+
+  // struct Point { int x, y; Point(int x, int y); void Scale(int k) { x *= k; } int Sum() { return x + y; } }
+  let point = TestType {
+    name: "Point", extends: TypeDefOrRef::TypeRef(Index::new(1)),
+    fields: vec![("x", 0x0006, vec![0x06, 0x08]), ("y", 0x0006, vec![0x06, 0x08])],
+    methods: vec![
+      (".ctor", vec![0x20, 0x02, 0x01, 0x08, 0x08], vec![], vec![
+        0x02, 0x03, 0x7D, 0x01, 0x00, 0x00, 0x04,                 // ldarg.0, ldarg.1, stfld x
+        0x02, 0x04, 0x7D, 0x02, 0x00, 0x00, 0x04, 0x2A            // ldarg.0, ldarg.2, stfld y, ret
+      ]),
+      ("Scale", vec![0x20, 0x01, 0x01, 0x08], vec![], vec![
+        0x02, 0x25, 0x7B, 0x01, 0x00, 0x00, 0x04,                 // ldarg.0, dup, ldfld x
+        0x03, 0x5A, 0x7D, 0x01, 0x00, 0x00, 0x04, 0x2A            // ldarg.1, mul, stfld x, ret
+      ]),
+      ("Sum", vec![0x20, 0x00, 0x08], vec![], vec![0x02, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x02, 0x7B, 0x02, 0x00, 0x00, 0x04, 0x58, 0x2A])
+    ],
+    method_flags: vec![]
+  };
+  // enum Color : byte
+  let color = TestType {
+    name: "Color", extends: TypeDefOrRef::TypeRef(Index::new(2)),
+    fields: vec![("value__", 0x0606, vec![0x06, 0x05])],
+    methods: vec![],
+    method_flags: vec![]
+  };
+
+  let point_locals = |count: u8| {
+    let mut locals = vec![count];
+    for _ in 0 .. count {
+      locals.extend(vec![0x11, 0x04]);
+    }
+    locals
+  };
+
+  // var a = new Point(1, 2); var b = a; b.x = 10; return a.x * 100 + b.x;
+  let copy = ("Copy", vec![0x00, 0x00, 0x08], point_locals(2), vec![
+    0x17, 0x18, 0x73, 0x01, 0x00, 0x00, 0x06, 0x0A,               // ldc.i4.1, ldc.i4.2, newobj Point, stloc.0
+    0x06, 0x0B,                                                   // ldloc.0, stloc.1
+    0x12, 0x01, 0x1F, 0x0A, 0x7D, 0x01, 0x00, 0x00, 0x04,         // ldloca.s 1, ldc.i4.s 10, stfld x
+    0x06, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x1F, 0x64, 0x5A,         // ldloc.0, ldfld x, ldc.i4.s 100, mul
+    0x07, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x58, 0x2A                // ldloc.1, ldfld x, add, ret
+  ]);
+
+  // var p = new Point(2, 3); p.Scale(10); return p.Sum();
+  let methods = ("Methods", vec![0x00, 0x00, 0x08], point_locals(1), vec![
+    0x18, 0x19, 0x73, 0x01, 0x00, 0x00, 0x06, 0x0A,               // ldc.i4.2, ldc.i4.3, newobj Point, stloc.0
+    0x12, 0x00, 0x1F, 0x0A, 0x28, 0x02, 0x00, 0x00, 0x06,         // ldloca.s 0, ldc.i4.s 10, call Scale
+    0x12, 0x00, 0x28, 0x03, 0x00, 0x00, 0x06, 0x2A                // ldloca.s 0, call Sum, ret
+  ]);
+
+  // var a = new Point(3, 4); object o = a; a.x = 7; var c = (Point)o;
+  // var r = c.x + a.x * 10; c = default; return r + c.y + ((Point)o).y;
+  let boxing = ("Boxing", vec![0x00, 0x00, 0x08], vec![0x03, 0x11, 0x04, 0x1C, 0x11, 0x04], vec![
+    0x19, 0x1A, 0x73, 0x01, 0x00, 0x00, 0x06, 0x0A,               // ldc.i4.3, ldc.i4.4, newobj Point, stloc.0
+    0x06, 0x8C, 0x01, 0x00, 0x00, 0x02, 0x0B,                     // ldloc.0, box Point, stloc.1
+    0x12, 0x00, 0x1D, 0x7D, 0x01, 0x00, 0x00, 0x04,               // ldloca.s 0, ldc.i4.7, stfld x
+    0x07, 0xA5, 0x01, 0x00, 0x00, 0x02, 0x0C,                     // ldloc.1, unbox.any Point, stloc.2
+    0x08, 0x7B, 0x01, 0x00, 0x00, 0x04,                           // ldloc.2, ldfld x
+    0x06, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x1F, 0x0A, 0x5A, 0x58,   // ldloc.0, ldfld x, ldc.i4.s 10, mul, add
+    0x12, 0x02, 0xFE, 0x15, 0x01, 0x00, 0x00, 0x02,               // ldloca.s 2, initobj Point
+    0x08, 0x7B, 0x02, 0x00, 0x00, 0x04, 0x58,                     // ldloc.2, ldfld y, add
+    0x07, 0x79, 0x01, 0x00, 0x00, 0x02, 0x7B, 0x02, 0x00, 0x00, 0x04, 0x58, // ldloc.1, unbox Point, ldfld y, add
+    0x2A
+  ]);
+
+  // Color c = (Color)300; return (byte)(object)c + sizeof(Color) + sizeof(Point);
+  let enums = ("Enums", vec![0x00, 0x00, 0x08], vec![0x01, 0x11, 0x08], vec![
+    0x20, 0x2C, 0x01, 0x00, 0x00, 0x0A,                           // ldc.i4 300, stloc.0
+    0x06, 0x8C, 0x02, 0x00, 0x00, 0x02, 0xA5, 0x04, 0x00, 0x00, 0x01, // ldloc.0, box Color, unbox.any Byte
+    0xFE, 0x1C, 0x02, 0x00, 0x00, 0x02, 0x58,                     // sizeof Color, add
+    0xFE, 0x1C, 0x01, 0x00, 0x00, 0x02, 0x58, 0x2A                // sizeof Point, add, ret
+  ]);
+
+  // (int)(object)new Point(1, 2)
+  let bad_unbox = ("BadUnbox", vec![0x00, 0x00, 0x08], vec![], vec![
+    0x17, 0x18, 0x73, 0x01, 0x00, 0x00, 0x06, 0x8C, 0x01, 0x00, 0x00, 0x02, 0xA5, 0x03, 0x00, 0x00, 0x01, 0x2A
+  ]);
+
+  // Console.WriteLine((object)true); Console.WriteLine((object)42);
+  let mut print = vec![0x17, 0x8C, 0x05, 0x00, 0x00, 0x01, 0x28];
+  print.extend(token_bytes(write_line));
+  print.extend(vec![0x1F, 0x2A, 0x8C, 0x03, 0x00, 0x00, 0x01, 0x28]);
+  print.extend(token_bytes(write_line));
+  print.push(0x2A);
+  let print = ("Print", vec![0x00, 0x00, 0x01], vec![], print);
+
+  let program = TestType {
+    name: "Program", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields: vec![],
+    methods: vec![copy, methods, boxing, enums, bad_unbox, print],
+    method_flags: vec![]
+  };
+  let metadata = build_types(builder, vec![point, color, program]);
+  let output = SharedBuffer(Rc::new(RefCell::new(vec![])));
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(output.clone()));
+
+  assert_eq!(Ok(Some(StackValue::Int32(110))), interpreter.run(&Index::new(4), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(23))), interpreter.run(&Index::new(5), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(77))), interpreter.run(&Index::new(6), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(53))), interpreter.run(&Index::new(7), vec![]));
+  assert_eq!(exception("System.InvalidCastException", "Specified cast is not valid."), without_stack_trace(interpreter.run(&Index::new(8), vec![])));
+  assert_eq!(Ok(None), interpreter.run(&Index::new(9), vec![]));
+  assert_eq!("True\n42\n", String::from_utf8(output.0.borrow().clone()).unwrap());
+}
+
+#[test]
+fn nullable_values() {
+  let mut builder = MetadataBuilder::new();
+  type_ref(&mut builder, "mscorlib", "System", "Nullable`1");
+  type_ref(&mut builder, "mscorlib", "System", "Int32");
+  // Nullable<int>
+  let signature = builder.blob(vec![0x15, 0x11, 0x05, 0x01, 0x08]);
+  let nullable = builder.row(TypeSpecEntry { signature });
+  for &(name, ref signature) in &[
+    (".ctor", vec![0x20, 0x01, 0x01, 0x13, 0x00]),
+    ("get_HasValue", vec![0x20, 0x00, 0x02]),
+    ("GetValueOrDefault", vec![0x20, 0x00, 0x13, 0x00]),
+    ("get_Value", vec![0x20, 0x00, 0x13, 0x00])
+  ] {
+    let (name, signature) = (builder.string(name), builder.blob(signature.clone()));
+    builder.row(MemberRefEntry { class: MemberRefParent::TypeSpec(nullable), name, signature });
+  }
+
+  let method = |name, code| (name, vec![0x00, 0x00, 0x08], vec![0x01, 0x15, 0x11, 0x05, 0x01, 0x08], code);
+  let methods = vec![
+    // int? n = null; return (object)n == null;
+    method("BoxNull", vec![0x12, 0x00, 0xFE, 0x15, 0x01, 0x00, 0x00, 0x1B, 0x06, 0x8C, 0x01, 0x00, 0x00, 0x1B, 0x14, 0xFE, 0x01, 0x2A]),
+    // int? n = 5; return (int)(object)n;
+    method("BoxValue", vec![
+      0x12, 0x00, 0x1B, 0x28, 0x01, 0x00, 0x00, 0x0A,             // ldloca.s 0, ldc.i4.5, call .ctor
+      0x06, 0x8C, 0x01, 0x00, 0x00, 0x1B, 0xA5, 0x02, 0x00, 0x00, 0x01, 0x2A // ldloc.0, box, unbox.any Int32, ret
+    ]),
+    // return ((int?)(object)null).HasValue;
+    method("UnboxNull", vec![0x14, 0xA5, 0x01, 0x00, 0x00, 0x1B, 0x0A, 0x12, 0x00, 0x28, 0x02, 0x00, 0x00, 0x0A, 0x2A]),
+    // return ((int?)(object)7).GetValueOrDefault();
+    method("UnboxValue", vec![
+      0x1D, 0x8C, 0x02, 0x00, 0x00, 0x01, 0xA5, 0x01, 0x00, 0x00, 0x1B, 0x0A, // ldc.i4.7, box Int32, unbox.any, stloc.0
+      0x12, 0x00, 0x28, 0x03, 0x00, 0x00, 0x0A, 0x2A              // ldloca.s 0, call GetValueOrDefault, ret
+    ]),
+    // int? n = null; return n.Value;
+    method("Empty", vec![0x12, 0x00, 0xFE, 0x15, 0x01, 0x00, 0x00, 0x1B, 0x12, 0x00, 0x28, 0x04, 0x00, 0x00, 0x0A, 0x2A]),
+    // return new int?(9).Value;
+    method("NewObj", vec![0x1F, 0x09, 0x73, 0x01, 0x00, 0x00, 0x0A, 0x0A, 0x12, 0x00, 0x28, 0x04, 0x00, 0x00, 0x0A, 0x2A])
+  ];
+  let metadata = build_with(builder, methods);
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink()));
+
+  assert_eq!(Ok(Some(StackValue::Int32(1))), interpreter.run(&Index::new(1), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(5))), interpreter.run(&Index::new(2), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(0))), interpreter.run(&Index::new(3), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(7))), interpreter.run(&Index::new(4), vec![]));
+  assert_eq!(exception("System.InvalidOperationException", "Nullable object must have a value."), without_stack_trace(interpreter.run(&Index::new(5), vec![])));
+  assert_eq!(Ok(Some(StackValue::Int32(9))), interpreter.run(&Index::new(6), vec![]));
+}
+
+#[test]
+fn virtual_dispatch() {
+  let mut builder = MetadataBuilder::new();
+  type_ref(&mut builder, "mscorlib", "System", "Object");
+  type_ref(&mut builder, "mscorlib", "System", "ValueType");
+  // The calli signature, instance int32()
+  let signature = builder.blob(vec![0x20, 0x00, 0x08]);
+  builder.row(StandAloneSigEntry { signature });
+  builder.row(InterfaceImplEntry { class: Index::new(5), interface: TypeDefOrRef::TypeDef(Index::new(1)) });
+  builder.row(InterfaceImplEntry { class: Index::new(6), interface: TypeDefOrRef::TypeDef(Index::new(1)) });
+  builder.row(MethodImplEntry {
+    class: Index::new(5), method_body: MethodDefOrRef::MethodDef(Index::new(14)), method_declaration: MethodDefOrRef::MethodDef(Index::new(3))
+  });
+
+  let int_method = |name, code| (name, vec![0x20, 0x00, 0x08], vec![], code);
+  let constructor = || (".ctor", vec![0x20, 0x00, 0x01], vec![], vec![0x2A]);
+  // public virtual newslot, public virtual (override), public virtual final, public virtual abstract newslot
+  let (newslot, override_, final_, abstract_) = (0x01C6, 0x00C6, 0x00E6, 0x05C6);
+
+  // interface IShape { int Area(); int Describe() => Area() * 10; int Name(); }
+  let shape = TestType {
+    name: "IShape", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields: vec![],
+    methods: vec![
+      int_method("Area", vec![]),
+      int_method("Describe", vec![0x02, 0x6F, 0x01, 0x00, 0x00, 0x06, 0x1F, 0x0A, 0x5A, 0x2A]),
+      int_method("Name", vec![])
+    ],
+    method_flags: vec![("Area", abstract_), ("Describe", newslot), ("Name", abstract_)]
+  };
+  // Animal.Speak() => 1, Animal.Legs() => 4; Dog overrides Speak with 2 and hides Legs with a new 3; Puppy seals Speak with 3
+  let animal = TestType {
+    name: "Animal", extends: TypeDefOrRef::TypeRef(Index::new(1)), fields: vec![],
+    methods: vec![constructor(), int_method("Speak", vec![0x17, 0x2A]), int_method("Legs", vec![0x1A, 0x2A])],
+    method_flags: vec![("Speak", newslot), ("Legs", newslot)]
+  };
+  let dog = TestType {
+    name: "Dog", extends: TypeDefOrRef::TypeDef(Index::new(2)), fields: vec![],
+    methods: vec![constructor(), int_method("Speak", vec![0x18, 0x2A]), int_method("Legs", vec![0x19, 0x2A])],
+    method_flags: vec![("Speak", override_), ("Legs", newslot)]
+  };
+  let puppy = TestType {
+    name: "Puppy", extends: TypeDefOrRef::TypeDef(Index::new(3)), fields: vec![],
+    methods: vec![constructor(), int_method("Speak", vec![0x19, 0x2A])],
+    method_flags: vec![("Speak", final_)]
+  };
+  // class Square : IShape { public int Area() => 16; int IShape.Name() => 7; }
+  let square = TestType {
+    name: "Square", extends: TypeDefOrRef::TypeRef(Index::new(1)), fields: vec![],
+    methods: vec![constructor(), int_method("Area", vec![0x1F, 0x10, 0x2A]), int_method("Sample.IShape.Name", vec![0x1D, 0x2A])],
+    method_flags: vec![("Area", newslot | 0x0020), ("Sample.IShape.Name", 0x01E1)]
+  };
+  // struct Tally : IShape { int n; public int Area() => ++n; }
+  let tally = TestType {
+    name: "Tally", extends: TypeDefOrRef::TypeRef(Index::new(2)),
+    fields: vec![("n", 0x0006, vec![0x06, 0x08])],
+    methods: vec![int_method("Area", vec![
+      0x02, 0x02, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x17, 0x58,       // ldarg.0, ldarg.0, ldfld n, ldc.i4.1, add
+      0x7D, 0x01, 0x00, 0x00, 0x04,                               // stfld n
+      0x02, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x2A                    // ldarg.0, ldfld n, ret
+    ])],
+    method_flags: vec![("Area", newslot | 0x0020)]
+  };
+  // Kitten tries to override the sealed Puppy.Speak
+  let kitten = TestType {
+    name: "Kitten", extends: TypeDefOrRef::TypeDef(Index::new(4)), fields: vec![],
+    methods: vec![constructor(), int_method("Speak", vec![0x1B, 0x2A])],
+    method_flags: vec![("Speak", override_)]
+  };
+
+  // new Dog().Speak() * 100 + new Puppy().Speak() * 10 + ((Animal)new Dog()).Legs() + new Dog().Legs()
+  //   + calli(ldvirtftn Animal.Speak on a Puppy) + calli(ldftn Dog.Speak)
+  let dispatch = ("Dispatch", vec![0x00, 0x00, 0x08], vec![], vec![
+    0x73, 0x07, 0x00, 0x00, 0x06, 0x6F, 0x05, 0x00, 0x00, 0x06, 0x1F, 0x64, 0x5A,       // newobj Dog, callvirt Animal.Speak, * 100
+    0x73, 0x0A, 0x00, 0x00, 0x06, 0x6F, 0x05, 0x00, 0x00, 0x06, 0x1F, 0x0A, 0x5A, 0x58, // newobj Puppy, callvirt Animal.Speak, * 10, add
+    0x73, 0x07, 0x00, 0x00, 0x06, 0x6F, 0x06, 0x00, 0x00, 0x06, 0x58,                   // newobj Dog, callvirt Animal.Legs, add
+    0x73, 0x07, 0x00, 0x00, 0x06, 0x6F, 0x09, 0x00, 0x00, 0x06, 0x58,                   // newobj Dog, callvirt Dog.Legs, add
+    0x73, 0x0A, 0x00, 0x00, 0x06, 0x25, 0xFE, 0x07, 0x05, 0x00, 0x00, 0x06,             // newobj Puppy, dup, ldvirtftn Animal.Speak
+    0x29, 0x01, 0x00, 0x00, 0x11, 0x58,                                                 // calli, add
+    0x73, 0x07, 0x00, 0x00, 0x06, 0xFE, 0x06, 0x08, 0x00, 0x00, 0x06,                   // newobj Dog, ldftn Dog.Speak
+    0x29, 0x01, 0x00, 0x00, 0x11, 0x58, 0x2A                                            // calli, add, ret
+  ]);
+
+  // IShape s = new Square(); return s.Area() + s.Name() + s.Describe();
+  let interfaces = ("Interfaces", vec![0x00, 0x00, 0x08], vec![0x01, 0x12, 0x14], vec![
+    0x73, 0x0C, 0x00, 0x00, 0x06, 0x0A,                           // newobj Square, stloc.0
+    0x06, 0x6F, 0x01, 0x00, 0x00, 0x06,                           // ldloc.0, callvirt IShape.Area
+    0x06, 0x6F, 0x03, 0x00, 0x00, 0x06, 0x58,                     // ldloc.0, callvirt IShape.Name, add
+    0x06, 0x6F, 0x02, 0x00, 0x00, 0x06, 0x58, 0x2A                // ldloc.0, callvirt IShape.Describe, add, ret
+  ]);
+
+  // Tally t; calls Area twice in place through constrained., then once on a boxed copy, then Describe,
+  // which boxes t as Tally doesn't implement it: 1 + 2 + 3 + t.n * 10 + 30
+  let constrained = ("Constrained", vec![0x00, 0x00, 0x08], vec![0x01, 0x11, 0x18], vec![
+    0x12, 0x00, 0xFE, 0x16, 0x06, 0x00, 0x00, 0x02, 0x6F, 0x01, 0x00, 0x00, 0x06,       // ldloca.s 0, constrained. Tally, callvirt IShape.Area
+    0x12, 0x00, 0xFE, 0x16, 0x06, 0x00, 0x00, 0x02, 0x6F, 0x01, 0x00, 0x00, 0x06, 0x58, // ldloca.s 0, constrained. Tally, callvirt IShape.Area, add
+    0x06, 0x8C, 0x06, 0x00, 0x00, 0x02, 0x6F, 0x01, 0x00, 0x00, 0x06, 0x58,             // ldloc.0, box Tally, callvirt IShape.Area, add
+    0x12, 0x00, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x1F, 0x0A, 0x5A, 0x58,                   // ldloca.s 0, ldfld n, * 10, add
+    0x12, 0x00, 0xFE, 0x16, 0x06, 0x00, 0x00, 0x02, 0x6F, 0x02, 0x00, 0x00, 0x06, 0x58, // ldloca.s 0, constrained. Tally, callvirt IShape.Describe, add
+    0x2A
+  ]);
+
+  // new Kitten().Speak()
+  let new_kitten = ("NewKitten", vec![0x00, 0x00, 0x08], vec![], vec![0x73, 0x10, 0x00, 0x00, 0x06, 0x6F, 0x05, 0x00, 0x00, 0x06, 0x2A]);
+
+  // (new Square() is IShape) * 1000 + (new Dog() is IShape) * 100 + (new Disc() is IShape) * 10 + ((IShape)(object)t).Area()
+  let casts = ("Casts", vec![0x00, 0x00, 0x08], vec![0x01, 0x11, 0x18], vec![
+    0x73, 0x0C, 0x00, 0x00, 0x06, 0x75, 0x01, 0x00, 0x00, 0x02,       // newobj Square, isinst IShape
+    0x14, 0xFE, 0x03, 0x20, 0xE8, 0x03, 0x00, 0x00, 0x5A,             // ldnull, cgt.un, ldc.i4 1000, mul
+    0x73, 0x07, 0x00, 0x00, 0x06, 0x75, 0x01, 0x00, 0x00, 0x02,       // newobj Dog, isinst IShape
+    0x14, 0xFE, 0x03, 0x1F, 0x64, 0x5A, 0x58,                         // ldnull, cgt.un, ldc.i4.s 100, mul, add
+    0x73, 0x18, 0x00, 0x00, 0x06, 0x75, 0x01, 0x00, 0x00, 0x02,       // newobj Disc, isinst IShape
+    0x14, 0xFE, 0x03, 0x1F, 0x0A, 0x5A, 0x58,                         // ldnull, cgt.un, ldc.i4.s 10, mul, add
+    0x06, 0x8C, 0x06, 0x00, 0x00, 0x02, 0x74, 0x01, 0x00, 0x00, 0x02, // ldloc.0, box Tally, castclass IShape
+    0x6F, 0x01, 0x00, 0x00, 0x06, 0x58, 0x2A                          // callvirt IShape.Area, add, ret
+  ]);
+
+  // Tally t; constrained. Tally ldfld n
+  let stray_prefix = ("StrayPrefix", vec![0x00, 0x00, 0x08], vec![0x01, 0x11, 0x18], vec![
+    0x12, 0x00, 0xFE, 0x16, 0x06, 0x00, 0x00, 0x02, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x2A
+  ]);
+
+  let program = TestType {
+    name: "Program", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields: vec![],
+    methods: vec![dispatch, interfaces, constrained, new_kitten, casts, stray_prefix],
+    method_flags: vec![]
+  };
+  // class Disc : Square { }, which implements IShape through its base class
+  let disc = TestType {
+    name: "Disc", extends: TypeDefOrRef::TypeDef(Index::new(5)), fields: vec![],
+    methods: vec![constructor()],
+    method_flags: vec![]
+  };
+  let metadata = build_types(builder, vec![shape, animal, dog, puppy, square, tally, kitten, program, disc]);
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink()));
+
+  assert_eq!(Ok(Some(StackValue::Int32(242))), interpreter.run(&Index::new(18), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(183))), interpreter.run(&Index::new(19), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(56))), interpreter.run(&Index::new(20), vec![]));
+  assert_eq!(Err(ExecutionError::InvalidProgram {
+    method: "Sample.Program.NewKitten".to_string(),
+    offset: 0,
+    reason: "Sample.Kitten.Speak can't override the final method Sample.Puppy.Speak".to_string()
+  }), interpreter.run(&Index::new(21), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(1011))), interpreter.run(&Index::new(22), vec![]));
+  assert_eq!(Err(ExecutionError::InvalidProgram {
+    method: "Sample.Program.StrayPrefix".to_string(),
+    offset: 2,
+    reason: "constrained. must be followed by callvirt".to_string()
+  }), interpreter.run(&Index::new(23), vec![]));
+}
+
+#[test]
+fn exception_handling() {
+  let mut builder = MetadataBuilder::new();
+  type_ref(&mut builder, "mscorlib", "System", "Exception");
+  type_ref(&mut builder, "mscorlib", "System", "SystemException");
+  type_ref(&mut builder, "mscorlib", "System", "NullReferenceException");
+  let base_constructor = member_ref(&mut builder, "mscorlib", "System", "Exception", ".ctor", vec![0x20, 0x01, 0x01, 0x0E]);
+  let get_message = member_ref(&mut builder, "mscorlib", "System", "Exception", "get_Message", vec![0x20, 0x00, 0x0E]);
+  let invalid_operation = member_ref(&mut builder, "mscorlib", "System", "InvalidOperationException", ".ctor", vec![0x20, 0x01, 0x01, 0x0E]);
+  let not_now = 0x7000_0000 | builder.user_string("Not now");
+  let null_argument = member_ref(&mut builder, "mscorlib", "System", "ArgumentNullException", ".ctor", vec![0x20, 0x01, 0x01, 0x0E]);
+  let out_of_range = member_ref(&mut builder, "mscorlib", "System", "ArgumentOutOfRangeException", ".ctor", vec![0x20, 0x02, 0x01, 0x0E, 0x0E]);
+  let bad_argument = member_ref(&mut builder, "mscorlib", "System", "ArgumentException", ".ctor", vec![0x20, 0x02, 0x01, 0x0E, 0x0E]);
+  let value = 0x7000_0000 | builder.user_string("value");
+  let index = 0x7000_0000 | builder.user_string("index");
+
+  // class MyError : Exception { int code; MyError(string message) : base(message) { } }
+  let mut constructor = vec![0x02, 0x03, 0x28];
+  constructor.extend(token_bytes(base_constructor));
+  constructor.push(0x2A);
+  let my_error = TestType {
+    name: "MyError", extends: TypeDefOrRef::TypeRef(Index::new(1)),
+    fields: vec![("code", 0x0006, vec![0x06, 0x08])],
+    methods: vec![(".ctor", vec![0x20, 0x01, 0x01, 0x0E], vec![], constructor)],
+    method_flags: vec![]
+  };
+
+  // static void Log(int k) { trace = trace * 10 + k; }
+  let log = ("Log", vec![0x00, 0x01, 0x01, 0x08], vec![], vec![
+    0x7E, 0x02, 0x00, 0x00, 0x04, 0x1F, 0x0A, 0x5A, 0x02, 0x58, 0x80, 0x02, 0x00, 0x00, 0x04, 0x2A
+  ]);
+
+  // try { Log(1); throw new MyError(null); } finally { Log(2); }
+  let thrower = ("Thrower", vec![0x00, 0x00, 0x01], vec![], vec![
+    0x17, 0x28, 0x02, 0x00, 0x00, 0x06,                           // IL_0000: ldc.i4.1, call Log
+    0x14, 0x73, 0x01, 0x00, 0x00, 0x06, 0x7A,                     // IL_0006: ldnull, newobj MyError, throw
+    0x18, 0x28, 0x02, 0x00, 0x00, 0x06, 0xDC,                     // IL_000d: ldc.i4.2, call Log, endfinally
+    0x2A                                                          // IL_0014: ret
+  ]);
+
+  // try { try { Thrower(); } catch when (Log(3) is false) { } } catch (MyError) { Log(4); } return trace;
+  let unwinding = ("Unwinding", vec![0x00, 0x00, 0x08], vec![], vec![
+    0x28, 0x03, 0x00, 0x00, 0x06, 0xDE, 0x16,                     // IL_0000: call Thrower, leave.s IL_001d
+    0x26, 0x19, 0x28, 0x02, 0x00, 0x00, 0x06, 0x16, 0xFE, 0x11,   // IL_0007: pop, ldc.i4.3, call Log, ldc.i4.0, endfilter
+    0x26, 0xDE, 0x09,                                             // IL_0011: pop, leave.s IL_001d
+    0x26, 0x1A, 0x28, 0x02, 0x00, 0x00, 0x06, 0xDE, 0x00,         // IL_0014: pop, ldc.i4.4, call Log, leave.s IL_001d
+    0x7E, 0x02, 0x00, 0x00, 0x04, 0x2A                            // IL_001d: ldsfld trace, ret
+  ]);
+
+  // try { try { try { _ = ((MyError)null).code; } catch (NullReferenceException) { Log(1); throw; } } finally { Log(2); } }
+  // catch (SystemException) { Log(3); } return trace;
+  let runtime = ("Runtime", vec![0x00, 0x00, 0x08], vec![], vec![
+    0x14, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x26, 0xDE, 0x09,         // IL_0000: ldnull, ldfld code, pop, leave.s IL_0012
+    0x26, 0x17, 0x28, 0x02, 0x00, 0x00, 0x06, 0xFE, 0x1A,         // IL_0009: pop, ldc.i4.1, call Log, rethrow
+    0xDE, 0x10,                                                   // IL_0012: leave.s IL_0024
+    0x18, 0x28, 0x02, 0x00, 0x00, 0x06, 0xDC,                     // IL_0014: ldc.i4.2, call Log, endfinally
+    0x26, 0x19, 0x28, 0x02, 0x00, 0x00, 0x06, 0xDE, 0x00,         // IL_001b: pop, ldc.i4.3, call Log, leave.s IL_0024
+    0x7E, 0x02, 0x00, 0x00, 0x04, 0x2A                            // IL_0024: ldsfld trace, ret
+  ]);
+
+  // try { throw new InvalidOperationException("Not now"); } catch (Exception e) { message = e.Message; } return message;
+  let mut messages = vec![0x72];
+  messages.extend(token_bytes(not_now));
+  messages.push(0x73);
+  messages.extend(token_bytes(invalid_operation));
+  messages.extend(vec![0x7A, 0x6F]);
+  messages.extend(token_bytes(get_message));
+  messages.extend(vec![0x0A, 0xDE, 0x00, 0x06, 0x2A]);
+  let messages = ("Messages", vec![0x00, 0x00, 0x0E], vec![0x01, 0x0E], messages);
+
+  // Inner() { throw new MyError(null); }
+  let unhandled = ("Unhandled", vec![0x00, 0x00, 0x01], vec![], vec![0x28, 0x08, 0x00, 0x00, 0x06, 0x2A]);
+  let inner = ("Inner", vec![0x00, 0x00, 0x01], vec![], vec![0x14, 0x73, 0x01, 0x00, 0x00, 0x06, 0x7A]);
+
+  // int x; try { throw new MyError(null); } catch when ((x = 42) != 0) { } return x;
+  let filter_locals = ("FilterLocals", vec![0x00, 0x00, 0x08], vec![0x01, 0x08], vec![
+    0x14, 0x73, 0x01, 0x00, 0x00, 0x06, 0x7A,                     // IL_0000: ldnull, newobj MyError, throw
+    0x26, 0x1F, 0x2A, 0x0A, 0x17, 0xFE, 0x11,                     // IL_0007: pop, ldc.i4.s 42, stloc.0, ldc.i4.1, endfilter
+    0x26, 0xDE, 0x00,                                             // IL_000e: pop, leave.s IL_0011
+    0x06, 0x2A                                                    // IL_0011: ldloc.0, ret
+  ]);
+
+  // throw new ArgumentNullException("value"), throw new ArgumentOutOfRangeException("index", "Not now"),
+  // throw new ArgumentException("Not now", "value")
+  let throw_new = |strings: Vec<u32>, constructor| {
+    let mut code = vec![];
+    for string in strings {
+      code.push(0x72);
+      code.extend(token_bytes(string));
+    }
+    code.push(0x73);
+    code.extend(token_bytes(constructor));
+    code.push(0x7A);
+    code
+  };
+  let null_argument = ("NullArgument", vec![0x00, 0x00, 0x01], vec![], throw_new(vec![value], null_argument));
+  let out_of_range = ("OutOfRange", vec![0x00, 0x00, 0x01], vec![], throw_new(vec![index, not_now], out_of_range));
+  let bad_argument = ("BadArgument", vec![0x00, 0x00, 0x01], vec![], throw_new(vec![not_now, value], bad_argument));
+
+  let program = TestType {
+    name: "Program", extends: TypeDefOrRef::TypeDef(Index::new(0)),
+    fields: vec![("trace", 0x0016, vec![0x06, 0x08])],
+    methods: vec![log, thrower, unwinding, runtime, messages, unhandled, inner, filter_locals, null_argument, out_of_range, bad_argument],
+    method_flags: vec![]
+  };
+  let mut metadata = build_types(builder, vec![my_error, program]);
+
+  let clause = |kind, try_offset, try_length, handler_offset, handler_length|
+    ExceptionClause { kind, try_offset, try_length, handler_offset, handler_length };
+  let clauses = vec![
+    (3, vec![clause(ExceptionClauseKind::Finally, 0x00, 0x0D, 0x0D, 0x07)]),
+    (4, vec![
+      clause(ExceptionClauseKind::Filter(0x07), 0x00, 0x07, 0x11, 0x03),
+      clause(ExceptionClauseKind::Catch(0x0200_0001), 0x00, 0x14, 0x14, 0x09)
+    ]),
+    (5, vec![
+      clause(ExceptionClauseKind::Catch(0x0100_0003), 0x00, 0x09, 0x09, 0x09),
+      clause(ExceptionClauseKind::Finally, 0x00, 0x14, 0x14, 0x07),
+      clause(ExceptionClauseKind::Catch(0x0100_0002), 0x00, 0x1B, 0x1B, 0x09)
+    ]),
+    (6, vec![clause(ExceptionClauseKind::Catch(0x0100_0001), 0x00, 0x0B, 0x0B, 0x08)]),
+    (9, vec![clause(ExceptionClauseKind::Filter(0x07), 0x00, 0x07, 0x0E, 0x03)])
+  ];
+  for (method, method_clauses) in clauses {
+    metadata.method_bodies.get_mut(&(method - 1)).unwrap().exception_clauses = method_clauses;
+  }
+
+  let run = |method| Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink())).run(&Index::new(method), vec![]);
+  // The filter runs before the finally handler below it
+  assert_eq!(Ok(Some(StackValue::Int32(1324))), run(4));
+  assert_eq!(Ok(Some(StackValue::Int32(123))), run(5));
+  // What the filter stores in locals is seen by the handler
+  assert_eq!(Ok(Some(StackValue::Int32(42))), run(9));
+
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink()));
+  let message = match interpreter.run(&Index::new(6), vec![]) {
+    Ok(Some(StackValue::Object(Some(object)))) => interpreter.heap().get_string(object),
+    other => panic!("Expected a string, got {:?}", other)
+  };
+  assert_eq!(Some("Not now".to_string()), message);
+
+  let stack_trace = vec![
+    StackFrame { method: "Sample.Program.Inner".to_string(), offset: 6 },
+    StackFrame { method: "Sample.Program.Unhandled".to_string(), offset: 0 }
+  ];
+  assert_eq!(Err(ExecutionError::UnhandledException(ManagedException {
+    type_name: "Sample.MyError".to_string(),
+    message: "Exception of type 'Sample.MyError' was thrown.".to_string(),
+    stack_trace
+  })), run(7));
+
+  // The parameter name comes first or second depending on the type
+  assert_eq!(exception("System.ArgumentNullException", "Value cannot be null.\nParameter name: value"), without_stack_trace(run(10)));
+  assert_eq!(exception("System.ArgumentOutOfRangeException", "Not now\nParameter name: index"), without_stack_trace(run(11)));
+  assert_eq!(exception("System.ArgumentException", "Not now\nParameter name: value"), without_stack_trace(run(12)));
+}
+
+#[test]
+fn arrays() {
+  let mut builder = MetadataBuilder::new();
+  type_ref(&mut builder, "mscorlib", "System", "Object");
+  type_ref(&mut builder, "mscorlib", "System", "String");
+  type_ref(&mut builder, "mscorlib", "System", "Int32");
+  let array = type_ref(&mut builder, "mscorlib", "System", "Array");
+  let runtime_helpers = type_ref(&mut builder, "mscorlib", "System.Runtime.CompilerServices", "RuntimeHelpers");
+  type_ref(&mut builder, "mscorlib", "System", "RuntimeFieldHandle");
+  // int32[,]
+  let signature = builder.blob(vec![0x14, 0x08, 0x02, 0x00, 0x00]);
+  let matrix = builder.row(TypeSpecEntry { signature });
+  for &(class, name, ref signature) in &[
+    (MemberRefParent::TypeSpec(matrix), ".ctor", vec![0x20, 0x02, 0x01, 0x08, 0x08]),
+    (MemberRefParent::TypeSpec(matrix), "Set", vec![0x20, 0x03, 0x01, 0x08, 0x08, 0x08]),
+    (MemberRefParent::TypeSpec(matrix), "Get", vec![0x20, 0x02, 0x08, 0x08, 0x08]),
+    (MemberRefParent::TypeRef(array), "get_Length", vec![0x20, 0x00, 0x08]),
+    (MemberRefParent::TypeRef(array), "Copy", vec![0x00, 0x03, 0x01, 0x12, 0x11, 0x12, 0x11, 0x08]),
+    (MemberRefParent::TypeRef(runtime_helpers), "InitializeArray", vec![0x00, 0x02, 0x01, 0x12, 0x11, 0x11, 0x19]),
+    (MemberRefParent::TypeSpec(matrix), ".ctor", vec![0x20, 0x04, 0x01, 0x08, 0x08, 0x08, 0x08]),
+    (MemberRefParent::TypeSpec(matrix), "Address", vec![0x20, 0x02, 0x10, 0x08, 0x08, 0x08]),
+    (MemberRefParent::TypeRef(array), "Clear", vec![0x00, 0x03, 0x01, 0x12, 0x11, 0x08, 0x08]),
+    (MemberRefParent::TypeRef(array), "GetLowerBound", vec![0x20, 0x01, 0x08, 0x08]),
+    // static void Resize<T>(ref !!0[], int32)
+    (MemberRefParent::TypeRef(array), "Resize", vec![0x10, 0x01, 0x02, 0x01, 0x10, 0x1D, 0x1E, 0x00, 0x08])
+  ] {
+    let (name, signature) = (builder.string(name), builder.blob(signature.clone()));
+    builder.row(MemberRefEntry { class, name, signature });
+  }
+  // Resize<int32>
+  let instantiation = builder.blob(vec![0x0A, 0x01, 0x08]);
+  builder.row(MethodSpecEntry { method: MethodDefOrRef::MethodRef(Index::new(11)), instantiation });
+  type_ref(&mut builder, "mscorlib", "System", "ValueType");
+  builder.row(InterfaceImplEntry { class: Index::new(3), interface: TypeDefOrRef::TypeDef(Index::new(2)) });
+  builder.row(InterfaceImplEntry { class: Index::new(4), interface: TypeDefOrRef::TypeDef(Index::new(2)) });
+  let item = builder.user_string("item");
+
+  let methods = vec![
+    // int[] a = new int[3]; a[1] = 5; return a[1] + a.Length;
+    ("Elements", vec![0x00, 0x00, 0x08], vec![0x01, 0x1D, 0x08], vec![
+      0x19, 0x8D, 0x03, 0x00, 0x00, 0x01, 0x0A,             // ldc.i4.3, newarr Int32, stloc.0
+      0x06, 0x17, 0x1B, 0x9E,                               // ldloc.0, ldc.i4.1, ldc.i4.5, stelem.i4
+      0x06, 0x17, 0x94, 0x06, 0x8E, 0x69, 0x58, 0x2A        // ldloc.0, ldc.i4.1, ldelem.i4, ldloc.0, ldlen, conv.i4, add, ret
+    ]),
+    // return new int[2][2];
+    ("OutOfRange", vec![0x00, 0x00, 0x08], vec![], vec![0x18, 0x8D, 0x03, 0x00, 0x00, 0x01, 0x18, 0x94, 0x2A]),
+    // object[] a = new string[1]; a[0] = "item"; a[0] = 1; return 0;
+    ("Covariance", vec![0x00, 0x00, 0x08], vec![0x01, 0x1D, 0x1C], vec![
+      0x17, 0x8D, 0x02, 0x00, 0x00, 0x01, 0x0A,             // ldc.i4.1, newarr String, stloc.0
+      0x06, 0x16, 0x72, item as u8, 0x00, 0x00, 0x70, 0xA2, // ldloc.0, ldc.i4.0, ldstr, stelem.ref
+      0x06, 0x16, 0x17, 0x8C, 0x03, 0x00, 0x00, 0x01, 0xA2, // ldloc.0, ldc.i4.0, ldc.i4.1, box Int32, stelem.ref
+      0x16, 0x2A
+    ]),
+    // int[,] m = new int[2, 3]; m[1, 2] = 7; return m[1, 2] + m.Length;
+    ("Matrix", vec![0x00, 0x00, 0x08], vec![0x01, 0x14, 0x08, 0x02, 0x00, 0x00], vec![
+      0x18, 0x19, 0x73, 0x01, 0x00, 0x00, 0x0A, 0x0A,       // ldc.i4.2, ldc.i4.3, newobj .ctor, stloc.0
+      0x06, 0x17, 0x18, 0x1D, 0x28, 0x02, 0x00, 0x00, 0x0A, // ldloc.0, ldc.i4.1, ldc.i4.2, ldc.i4.7, call Set
+      0x06, 0x17, 0x18, 0x28, 0x03, 0x00, 0x00, 0x0A,       // ldloc.0, ldc.i4.1, ldc.i4.2, call Get
+      0x06, 0x6F, 0x04, 0x00, 0x00, 0x0A, 0x58, 0x2A        // ldloc.0, callvirt get_Length, add, ret
+    ]),
+    // int[] a = { 10, 20, 30 }; int[] b = new int[3]; Array.Copy(a, b, 2); return b[1] + b[2];
+    ("Copy", vec![0x00, 0x00, 0x08], vec![0x02, 0x1D, 0x08, 0x1D, 0x08], vec![
+      0x19, 0x8D, 0x03, 0x00, 0x00, 0x01, 0x25,             // ldc.i4.3, newarr Int32, dup
+      0xD0, 0x01, 0x00, 0x00, 0x04, 0x28, 0x06, 0x00, 0x00, 0x0A, 0x0A, // ldtoken Data, call InitializeArray, stloc.0
+      0x19, 0x8D, 0x03, 0x00, 0x00, 0x01, 0x0B,             // ldc.i4.3, newarr Int32, stloc.1
+      0x06, 0x07, 0x18, 0x28, 0x05, 0x00, 0x00, 0x0A,       // ldloc.0, ldloc.1, ldc.i4.2, call Copy
+      0x07, 0x17, 0x94, 0x07, 0x18, 0x94, 0x58, 0x2A        // ldloc.1, ldc.i4.1, ldelem.i4, ldloc.1, ldc.i4.2, ldelem.i4, add, ret
+    ]),
+    // IThing[] a = new IThing[2]; Point p; a[0] = new Thing(); a[1] = p; return (a[0] (readonly. ldelema object) != null) + a.Length;
+    ("InterfaceElements", vec![0x00, 0x00, 0x08], vec![0x02, 0x1D, 0x12, 0x08, 0x11, 0x10], vec![
+      0x18, 0x8D, 0x02, 0x00, 0x00, 0x02, 0x0A,             // ldc.i4.2, newarr IThing, stloc.0
+      0x06, 0x16, 0x73, 0x0E, 0x00, 0x00, 0x06, 0xA2,       // ldloc.0, ldc.i4.0, newobj Thing, stelem.ref
+      0x06, 0x17, 0x07, 0x8C, 0x04, 0x00, 0x00, 0x02, 0xA2, // ldloc.0, ldc.i4.1, ldloc.1, box Point, stelem.ref
+      0x06, 0x16, 0xFE, 0x1E, 0x8F, 0x01, 0x00, 0x00, 0x01, // ldloc.0, ldc.i4.0, readonly. ldelema Object
+      0x50, 0x14, 0xFE, 0x03,                               // ldind.ref, ldnull, cgt.un
+      0x06, 0x8E, 0x69, 0x58, 0x2A                          // ldloc.0, ldlen, conv.i4, add, ret
+    ]),
+    // IThing[] a = new IThing[1]; a[0] = "item";
+    ("InterfaceMismatch", vec![0x00, 0x00, 0x01], vec![], vec![
+      0x17, 0x8D, 0x02, 0x00, 0x00, 0x02,                   // ldc.i4.1, newarr IThing
+      0x16, 0x72, item as u8, 0x00, 0x00, 0x70, 0xA2, 0x2A  // ldc.i4.0, ldstr, stelem.ref, ret
+    ]),
+    // ldelema Object on an IThing[], without readonly.
+    ("WritableAddress", vec![0x00, 0x00, 0x01], vec![], vec![
+      0x17, 0x8D, 0x02, 0x00, 0x00, 0x02,                   // ldc.i4.1, newarr IThing
+      0x16, 0x8F, 0x01, 0x00, 0x00, 0x01, 0x26, 0x2A        // ldc.i4.0, ldelema Object, pop, ret
+    ]),
+    // int[,] m = new int[1..2, 5..7]; m.Address(2, 7) = 9; return m[2, 7] + m.GetLowerBound(1);
+    ("LowerBounds", vec![0x00, 0x00, 0x08], vec![0x01, 0x14, 0x08, 0x02, 0x00, 0x00], vec![
+      0x17, 0x18, 0x1B, 0x19, 0x73, 0x07, 0x00, 0x00, 0x0A, 0x0A, // ldc.i4.1, ldc.i4.2, ldc.i4.5, ldc.i4.3, newobj .ctor, stloc.0
+      0x06, 0x18, 0x1D, 0x28, 0x08, 0x00, 0x00, 0x0A,       // ldloc.0, ldc.i4.2, ldc.i4.7, call Address
+      0x1F, 0x09, 0x54,                                     // ldc.i4.s 9, stind.i4
+      0x06, 0x18, 0x1D, 0x28, 0x03, 0x00, 0x00, 0x0A,       // ldloc.0, ldc.i4.2, ldc.i4.7, call Get
+      0x06, 0x17, 0x6F, 0x0A, 0x00, 0x00, 0x0A, 0x58, 0x2A  // ldloc.0, ldc.i4.1, callvirt GetLowerBound, add, ret
+    ]),
+    // new int[1..2, 5..7][2, 4]
+    ("BelowBounds", vec![0x00, 0x00, 0x08], vec![], vec![
+      0x17, 0x18, 0x1B, 0x19, 0x73, 0x07, 0x00, 0x00, 0x0A, // ldc.i4.1, ldc.i4.2, ldc.i4.5, ldc.i4.3, newobj .ctor
+      0x18, 0x1A, 0x28, 0x03, 0x00, 0x00, 0x0A, 0x2A        // ldc.i4.2, ldc.i4.4, call Get, ret
+    ]),
+    // int[] a = { 10, 20, 30 }; Array.Resize(ref a, 5); Array.Clear(a, 0, 1); return a[0] + a[1] + a[4] + a.Length;
+    ("ResizeClear", vec![0x00, 0x00, 0x08], vec![0x01, 0x1D, 0x08], vec![
+      0x19, 0x8D, 0x03, 0x00, 0x00, 0x01, 0x25,             // ldc.i4.3, newarr Int32, dup
+      0xD0, 0x01, 0x00, 0x00, 0x04, 0x28, 0x06, 0x00, 0x00, 0x0A, 0x0A, // ldtoken Data, call InitializeArray, stloc.0
+      0x12, 0x00, 0x1B, 0x28, 0x01, 0x00, 0x00, 0x2B,       // ldloca.s 0, ldc.i4.5, call Resize<int32>
+      0x06, 0x16, 0x17, 0x28, 0x09, 0x00, 0x00, 0x0A,       // ldloc.0, ldc.i4.0, ldc.i4.1, call Clear
+      0x06, 0x16, 0x94, 0x06, 0x17, 0x94, 0x58,             // ldloc.0, ldc.i4.0, ldelem.i4, ldloc.0, ldc.i4.1, ldelem.i4, add
+      0x06, 0x1A, 0x94, 0x58, 0x06, 0x8E, 0x69, 0x58, 0x2A  // ldloc.0, ldc.i4.4, ldelem.i4, add, ldloc.0, ldlen, conv.i4, add, ret
+    ]),
+    // return new int[int.MaxValue].Length;
+    ("Huge", vec![0x00, 0x00, 0x08], vec![], vec![
+      0x20, 0xFF, 0xFF, 0xFF, 0x7F, 0x8D, 0x03, 0x00, 0x00, 0x01, // ldc.i4 0x7FFFFFFF, newarr Int32
+      0x8E, 0x69, 0x2A                                      // ldlen, conv.i4, ret
+    ]),
+    // new int[0x10000, 0x10000]
+    ("HugeMatrix", vec![0x00, 0x00, 0x01], vec![], vec![
+      0x20, 0x00, 0x00, 0x01, 0x00, 0x25, 0x73, 0x01, 0x00, 0x00, 0x0A, // ldc.i4 0x10000, dup, newobj .ctor
+      0x26, 0x2A                                            // pop, ret
+    ])
+  ];
+  // private static assembly HasFieldRVA int32 Data
+  let fields = vec![("Data", 0x0111, vec![0x06, 0x08])];
+  // interface IThing { }, class Thing : IThing { }, struct Point : IThing { }
+  let types = vec![
+    TestType { name: "Program", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields, methods, method_flags: vec![] },
+    TestType { name: "IThing", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields: vec![], methods: vec![], method_flags: vec![] },
+    TestType {
+      name: "Thing", extends: TypeDefOrRef::TypeRef(Index::new(1)), fields: vec![],
+      methods: vec![(".ctor", vec![0x20, 0x00, 0x01], vec![], vec![0x2A])], method_flags: vec![]
+    },
+    TestType { name: "Point", extends: TypeDefOrRef::TypeRef(Index::new(7)), fields: vec![], methods: vec![], method_flags: vec![] }
+  ];
+  let mut metadata = build_types(builder, types);
+  metadata.field_data.insert(0, vec![10, 0, 0, 0, 20, 0, 0, 0, 30, 0, 0, 0]);
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink()));
+
+  assert_eq!(Ok(Some(StackValue::Int32(8))), interpreter.run(&Index::new(1), vec![]));
+  assert_eq!(exception("System.IndexOutOfRangeException", "Index was outside the bounds of the array."),
+    without_stack_trace(interpreter.run(&Index::new(2), vec![])));
+  assert_eq!(exception("System.ArrayTypeMismatchException", "Attempted to access an element as a type incompatible with the array."),
+    without_stack_trace(interpreter.run(&Index::new(3), vec![])));
+  assert_eq!(Ok(Some(StackValue::Int32(13))), interpreter.run(&Index::new(4), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(20))), interpreter.run(&Index::new(5), vec![]));
+  // Arrays of interfaces take instances of classes and boxed value types implementing them
+  assert_eq!(Ok(Some(StackValue::Int32(3))), interpreter.run(&Index::new(6), vec![]));
+  assert_eq!(exception("System.ArrayTypeMismatchException", "Attempted to access an element as a type incompatible with the array."),
+    without_stack_trace(interpreter.run(&Index::new(7), vec![])));
+  assert_eq!(exception("System.ArrayTypeMismatchException", "Attempted to access an element as a type incompatible with the array."),
+    without_stack_trace(interpreter.run(&Index::new(8), vec![])));
+  assert_eq!(Ok(Some(StackValue::Int32(14))), interpreter.run(&Index::new(9), vec![]));
+  assert_eq!(exception("System.IndexOutOfRangeException", "Index was outside the bounds of the array."),
+    without_stack_trace(interpreter.run(&Index::new(10), vec![])));
+  assert_eq!(Ok(Some(StackValue::Int32(25))), interpreter.run(&Index::new(11), vec![]));
+  // Arrays longer than .NET allows throw instead of taking down the host
+  for method in 12 .. 14 {
+    assert_eq!(exception("System.OutOfMemoryException", "Array dimensions exceeded supported range."),
+      without_stack_trace(interpreter.run(&Index::new(method), vec![])));
+  }
+}
+
+#[test]
+fn type_initializers() {
+  let int32 = || vec![0x06, 0x08];
+  let static_method = |name, code| (name, vec![0x00, 0x00, 0x08], vec![], code);
+  let initializer = |code| (".cctor", vec![0x00, 0x00, 0x01], vec![], code);
+  let type_ = |name, fields, methods| TestType { name, extends: TypeDefOrRef::TypeDef(Index::new(0)), fields, methods, method_flags: vec![] };
+  // Count++, as the start of the initializers
+  let increment = || vec![0x7E, 0x01, 0x00, 0x00, 0x04, 0x17, 0x58, 0x80, 0x01, 0x00, 0x00, 0x04];
+
+  let types = vec![
+    type_("Program", vec![("Count", 0x0016, int32())], vec![
+      // return Lazy.Value + Lazy.Value + Count;
+      static_method("LazyField", vec![0x7E, 0x02, 0x00, 0x00, 0x04, 0x7E, 0x02, 0x00, 0x00, 0x04, 0x58, 0x7E, 0x01, 0x00, 0x00, 0x04, 0x58, 0x2A]),
+      // Lazy.Get(); return Count;
+      static_method("LazyMethod", vec![0x28, 0x08, 0x00, 0x00, 0x06, 0x26, 0x7E, 0x01, 0x00, 0x00, 0x04, 0x2A]),
+      // Eager.Get(); return Count;
+      static_method("EagerMethod", vec![0x28, 0x0A, 0x00, 0x00, 0x06, 0x26, 0x7E, 0x01, 0x00, 0x00, 0x04, 0x2A]),
+      // return A.X + B.Y * 100;
+      static_method("Cycle", vec![0x7E, 0x03, 0x00, 0x00, 0x04, 0x7E, 0x04, 0x00, 0x00, 0x04, 0x1F, 0x64, 0x5A, 0x58, 0x2A]),
+      // return Broken.Value;
+      static_method("Broken", vec![0x7E, 0x05, 0x00, 0x00, 0x04, 0x2A]),
+      static_method("Count", vec![0x7E, 0x01, 0x00, 0x00, 0x04, 0x2A])
+    ]),
+    // beforefieldinit, static Lazy() { Count++; Value = 42; }
+    type_("Lazy", vec![("Value", 0x0016, int32())], vec![
+      initializer([increment(), vec![0x1F, 0x2A, 0x80, 0x02, 0x00, 0x00, 0x04, 0x2A]].concat()),
+      static_method("Get", vec![0x1D, 0x2A])
+    ]),
+    // static Eager() { Count++; }
+    type_("Eager", vec![], vec![initializer([increment(), vec![0x2A]].concat()), static_method("Get", vec![0x1D, 0x2A])]),
+    // static A() { X = B.Y + 1; }
+    type_("A", vec![("X", 0x0016, int32())], vec![initializer(vec![0x7E, 0x04, 0x00, 0x00, 0x04, 0x17, 0x58, 0x80, 0x03, 0x00, 0x00, 0x04, 0x2A])]),
+    // static B() { Y = A.X + 10; }
+    type_("B", vec![("Y", 0x0016, int32())], vec![initializer(vec![0x7E, 0x03, 0x00, 0x00, 0x04, 0x1F, 0x0A, 0x58, 0x80, 0x04, 0x00, 0x00, 0x04, 0x2A])]),
+    // static Broken() { Count++; Value = 0 / 0; }
+    type_("Broken", vec![("Value", 0x0016, int32())], vec![
+      initializer([increment(), vec![0x16, 0x16, 0x5B, 0x80, 0x05, 0x00, 0x00, 0x04, 0x2A]].concat()),
+      static_method("Get", vec![0x1D, 0x2A])
+    ])
+  ];
+  let mut metadata = build_types(MetadataBuilder::new(), types);
+  metadata.tables.get_mut::<TypeDefEntry>().unwrap()[1].flags = tdPublic | tdBeforeFieldInit;
+  let run = |method| Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink())).run(&Index::new(method), vec![]);
+
+  // Initializers run once, and beforefieldinit types only run theirs for static fields
+  assert_eq!(Ok(Some(StackValue::Int32(85))), run(1));
+  assert_eq!(Ok(Some(StackValue::Int32(0))), run(2));
+  assert_eq!(Ok(Some(StackValue::Int32(1))), run(3));
+  // B's initializer sees A.X before A's initializer sets it
+  assert_eq!(Ok(Some(StackValue::Int32(1011))), run(4));
+
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink()));
+  for _ in 0 .. 2 {
+    assert_eq!(exception("System.TypeInitializationException", "The type initializer for 'Sample.Broken' threw an exception."),
+      without_stack_trace(interpreter.run(&Index::new(5), vec![])));
+  }
+  assert_eq!(Ok(Some(StackValue::Int32(1))), interpreter.run(&Index::new(6), vec![]));
+
+  // Running a method of the type itself fails before the method is entered
+  let mut interpreter = Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink()));
+  assert_eq!(Err(ExecutionError::UnhandledException(ManagedException {
+    type_name: "System.TypeInitializationException".to_string(),
+    message: "The type initializer for 'Sample.Broken' threw an exception.".to_string(),
+    stack_trace: vec![]
+  })), interpreter.run(&Index::new(14), vec![]));
+  assert_eq!(Ok(Some(StackValue::Int32(1))), interpreter.run(&Index::new(6), vec![]));
+}
+
+#[test]
+fn generics() {
+  let mut builder = MetadataBuilder::new();
+  let object = type_ref(&mut builder, "mscorlib", "System", "Object");
+  let type_ = type_ref(&mut builder, "mscorlib", "System", "Type");
+  type_ref(&mut builder, "mscorlib", "System", "RuntimeTypeHandle");
+  let console = type_ref(&mut builder, "System.Console", "System", "Console");
+  // List`1<!0>, List`1<int32>, List`1<string>, List`1<!!0> and !0 (T), with List`1 being TypeDef 2
+  let mut specs = vec![];
+  for signature in vec![vec![0x13, 0x00], vec![0x08], vec![0x0E], vec![0x1E, 0x00]] {
+    let signature = builder.blob([vec![0x15, 0x12, 0x08, 0x01], signature].concat());
+    specs.push(builder.row(TypeSpecEntry { signature }));
+  }
+  // !0 and !!0
+  for signature in vec![vec![0x13, 0x00], vec![0x1E, 0x00]] {
+    let signature = builder.blob(signature);
+    builder.row(TypeSpecEntry { signature });
+  }
+  let (list_t, list_int, list_string, list_m) = (specs[0], specs[1], specs[2], specs[3]);
+
+  for &(class, name, ref signature) in &[
+    (MemberRefParent::TypeSpec(list_t), "items", vec![0x06, 0x1D, 0x13, 0x00]),
+    (MemberRefParent::TypeSpec(list_t), "count", vec![0x06, 0x08]),
+    (MemberRefParent::TypeSpec(list_t), "Instances", vec![0x06, 0x08]),
+    (MemberRefParent::TypeSpec(list_int), ".ctor", vec![0x20, 0x00, 0x01]),
+    (MemberRefParent::TypeSpec(list_int), "Add", vec![0x20, 0x01, 0x01, 0x13, 0x00]),
+    (MemberRefParent::TypeSpec(list_int), "IndexOf", vec![0x20, 0x01, 0x08, 0x13, 0x00]),
+    (MemberRefParent::TypeSpec(list_int), "Instances", vec![0x06, 0x08]),
+    (MemberRefParent::TypeSpec(list_string), ".ctor", vec![0x20, 0x00, 0x01]),
+    (MemberRefParent::TypeSpec(list_string), "Add", vec![0x20, 0x01, 0x01, 0x13, 0x00]),
+    (MemberRefParent::TypeSpec(list_string), "IndexOf", vec![0x20, 0x01, 0x08, 0x13, 0x00]),
+    (MemberRefParent::TypeSpec(list_string), "Instances", vec![0x06, 0x08]),
+    (MemberRefParent::TypeSpec(list_m), "get_Item", vec![0x20, 0x01, 0x13, 0x00, 0x08]),
+    (MemberRefParent::TypeRef(object), "Equals", vec![0x20, 0x01, 0x02, 0x1C]),
+    (MemberRefParent::TypeRef(type_), "GetTypeFromHandle", vec![0x00, 0x01, 0x12, 0x09, 0x11, 0x0D]),
+    (MemberRefParent::TypeRef(type_), "get_FullName", vec![0x20, 0x00, 0x0E]),
+    (MemberRefParent::TypeRef(console), "WriteLine", vec![0x00, 0x01, 0x01, 0x0E])
+  ] {
+    let (name, signature) = (builder.string(name), builder.blob(signature.clone()));
+    builder.row(MemberRefEntry { class, name, signature });
+  }
+  // First<int32> and First<string>
+  for &argument in &[0x08, 0x0E] {
+    let instantiation = builder.blob(vec![0x0A, 0x01, argument]);
+    builder.row(MethodSpecEntry { method: MethodDefOrRef::MethodDef(Index::new(5)), instantiation });
+  }
+  // Size<int64>
+  let instantiation = builder.blob(vec![0x0A, 0x01, 0x0A]);
+  builder.row(MethodSpecEntry { method: MethodDefOrRef::MethodDef(Index::new(7)), instantiation });
+  let (a, b) = (builder.user_string("a"), builder.user_string("b"));
+  // The calli signature, int32()
+  let signature = builder.blob(vec![0x00, 0x00, 0x08]);
+  builder.row(StandAloneSigEntry { signature });
+
+  let program = vec![
+    // var list = new List<int>(); list.Add(10); list.Add(20); return list.IndexOf(20) * 100 + First(list) + list.IndexOf(30);
+    ("Ints", vec![0x00, 0x00, 0x08], vec![0x01, 0x1C], vec![
+      0x73, 0x04, 0x00, 0x00, 0x0A, 0x0A,                   // newobj List<int>::.ctor, stloc.0
+      0x06, 0x1F, 0x0A, 0x6F, 0x05, 0x00, 0x00, 0x0A,       // ldloc.0, ldc.i4.s 10, callvirt Add
+      0x06, 0x1F, 0x14, 0x6F, 0x05, 0x00, 0x00, 0x0A,       // ldloc.0, ldc.i4.s 20, callvirt Add
+      0x06, 0x1F, 0x14, 0x6F, 0x06, 0x00, 0x00, 0x0A,       // ldloc.0, ldc.i4.s 20, callvirt IndexOf
+      0x1F, 0x64, 0x5A,                                     // ldc.i4.s 100, mul
+      0x06, 0x28, 0x01, 0x00, 0x00, 0x2B, 0x58,             // ldloc.0, call First<int>, add
+      0x06, 0x1F, 0x1E, 0x6F, 0x06, 0x00, 0x00, 0x0A, 0x58, // ldloc.0, ldc.i4.s 30, callvirt IndexOf, add
+      0x2A
+    ]),
+    // var list = new List<string>(); list.Add("a"); list.Add("b"); Console.WriteLine(First(list)); return list.IndexOf("b");
+    ("Strings", vec![0x00, 0x00, 0x08], vec![0x01, 0x1C], vec![
+      0x73, 0x08, 0x00, 0x00, 0x0A, 0x0A,                   // newobj List<string>::.ctor, stloc.0
+      0x06, 0x72, a as u8, 0x00, 0x00, 0x70, 0x6F, 0x09, 0x00, 0x00, 0x0A, // ldloc.0, ldstr "a", callvirt Add
+      0x06, 0x72, b as u8, 0x00, 0x00, 0x70, 0x6F, 0x09, 0x00, 0x00, 0x0A, // ldloc.0, ldstr "b", callvirt Add
+      0x06, 0x28, 0x02, 0x00, 0x00, 0x2B,                   // ldloc.0, call First<string>
+      0x28, 0x10, 0x00, 0x00, 0x0A,                         // call Console.WriteLine
+      0x06, 0x72, b as u8, 0x00, 0x00, 0x70, 0x6F, 0x0A, 0x00, 0x00, 0x0A, // ldloc.0, ldstr "b", callvirt IndexOf
+      0x2A
+    ]),
+    // new List<int>(); new List<int>(); new List<string>(); return List<int>.Instances * 10 + List<string>.Instances;
+    ("Statics", vec![0x00, 0x00, 0x08], vec![], vec![
+      0x73, 0x04, 0x00, 0x00, 0x0A, 0x26, 0x73, 0x04, 0x00, 0x00, 0x0A, 0x26, 0x73, 0x08, 0x00, 0x00, 0x0A, 0x26,
+      0x7E, 0x07, 0x00, 0x00, 0x0A, 0x1F, 0x0A, 0x5A,       // ldsfld List<int>::Instances, ldc.i4.s 10, mul
+      0x7E, 0x0B, 0x00, 0x00, 0x0A, 0x58, 0x2A              // ldsfld List<string>::Instances, add, ret
+    ]),
+    // Console.WriteLine(typeof(List<int>).FullName);
+    ("Types", vec![0x00, 0x00, 0x01], vec![], vec![
+      0xD0, 0x02, 0x00, 0x00, 0x1B, 0x28, 0x0E, 0x00, 0x00, 0x0A, // ldtoken List<int>, call GetTypeFromHandle
+      0x6F, 0x0F, 0x00, 0x00, 0x0A, 0x28, 0x10, 0x00, 0x00, 0x0A, // callvirt get_FullName, call Console.WriteLine
+      0x2A
+    ]),
+    // static T First<T>(List<T> list) { return list[0]; }
+    ("First", vec![0x10, 0x01, 0x01, 0x1E, 0x00, 0x15, 0x12, 0x08, 0x01, 0x1E, 0x00], vec![], vec![
+      0x02, 0x16, 0x6F, 0x0C, 0x00, 0x00, 0x0A, 0x2A        // ldarg.0, ldc.i4.0, callvirt List<T>::get_Item, ret
+    ]),
+    // return ((delegate*<int>)&Size<long>)();
+    ("Pointer", vec![0x00, 0x00, 0x08], vec![], vec![
+      0xFE, 0x06, 0x03, 0x00, 0x00, 0x2B, 0x29, 0x01, 0x00, 0x00, 0x11, 0x2A // ldftn Size<int64>, calli, ret
+    ]),
+    // static int Size<T>() { return sizeof(T); }
+    ("Size", vec![0x10, 0x01, 0x00, 0x08], vec![], vec![0xFE, 0x1C, 0x06, 0x00, 0x00, 0x1B, 0x2A])
+  ];
+  let list = vec![
+    // public List() { items = new T[4]; Instances++; }
+    (".ctor", vec![0x20, 0x00, 0x01], vec![], vec![
+      0x02, 0x1A, 0x8D, 0x05, 0x00, 0x00, 0x1B, 0x7D, 0x01, 0x00, 0x00, 0x0A, // ldarg.0, ldc.i4.4, newarr T, stfld items
+      0x7E, 0x03, 0x00, 0x00, 0x0A, 0x17, 0x58, 0x80, 0x03, 0x00, 0x00, 0x0A, // ldsfld Instances, ldc.i4.1, add, stsfld Instances
+      0x2A
+    ]),
+    // public void Add(T item) { items[count] = item; count++; }
+    ("Add", vec![0x20, 0x01, 0x01, 0x13, 0x00], vec![], vec![
+      0x02, 0x7B, 0x01, 0x00, 0x00, 0x0A, 0x02, 0x7B, 0x02, 0x00, 0x00, 0x0A, // ldarg.0, ldfld items, ldarg.0, ldfld count
+      0x03, 0xA4, 0x05, 0x00, 0x00, 0x1B,                   // ldarg.1, stelem T
+      0x02, 0x02, 0x7B, 0x02, 0x00, 0x00, 0x0A,             // ldarg.0, ldarg.0, ldfld count
+      0x17, 0x58, 0x7D, 0x02, 0x00, 0x00, 0x0A, 0x2A        // ldc.i4.1, add, stfld count, ret
+    ]),
+    // public T get_Item(int index) { return items[index]; }
+    ("get_Item", vec![0x20, 0x01, 0x13, 0x00, 0x08], vec![], vec![
+      0x02, 0x7B, 0x01, 0x00, 0x00, 0x0A, 0x03, 0xA3, 0x05, 0x00, 0x00, 0x1B, 0x2A // ldarg.0, ldfld items, ldarg.1, ldelem T, ret
+    ]),
+    // public int IndexOf(T item) { for (int i = 0; i < count; i++) if (items[i].Equals(item)) return i; return -1; }
+    ("IndexOf", vec![0x20, 0x01, 0x08, 0x13, 0x00], vec![0x01, 0x08], vec![
+      0x16, 0x0A, 0x2B, 0x25,                               // 0: ldc.i4.0, stloc.0, br.s 41
+      0x02, 0x7B, 0x01, 0x00, 0x00, 0x0A,                   // 4: ldarg.0, ldfld items
+      0x06, 0x8F, 0x05, 0x00, 0x00, 0x1B,                   // 10: ldloc.0, ldelema T
+      0x03, 0x8C, 0x05, 0x00, 0x00, 0x1B,                   // 16: ldarg.1, box T
+      0xFE, 0x16, 0x05, 0x00, 0x00, 0x1B,                   // 22: constrained. T
+      0x6F, 0x0D, 0x00, 0x00, 0x0A, 0x2C, 0x02,             // 28: callvirt Object::Equals, brfalse.s 37
+      0x06, 0x2A,                                           // 35: ldloc.0, ret
+      0x06, 0x17, 0x58, 0x0A,                               // 37: ldloc.0, ldc.i4.1, add, stloc.0
+      0x06, 0x02, 0x7B, 0x02, 0x00, 0x00, 0x0A, 0x32, 0xD2, // 41: ldloc.0, ldarg.0, ldfld count, blt.s 4
+      0x15, 0x2A                                            // 50: ldc.i4.m1, ret
+    ])
+  ];
+  let fields = vec![("items", 0x0001, vec![0x06, 0x1D, 0x13, 0x00]), ("count", 0x0001, vec![0x06, 0x08]), ("Instances", 0x0016, vec![0x06, 0x08])];
+  let metadata = build_types(builder, vec![
+    TestType { name: "Program", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields: vec![], methods: program, method_flags: vec![] },
+    TestType { name: "List`1", extends: TypeDefOrRef::TypeRef(object), fields, methods: list, method_flags: vec![] }
+  ]);
+
+  let output = SharedBuffer(Rc::new(RefCell::new(vec![])));
+  let run = |method| Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(output.clone())).run(&Index::new(method), vec![]);
+  assert_eq!(Ok(Some(StackValue::Int32(109))), run(1));
+  assert_eq!(Ok(Some(StackValue::Int32(1))), run(2));
+  // Each instantiation has its own statics
+  assert_eq!(Ok(Some(StackValue::Int32(21))), run(3));
+  assert_eq!(Ok(None), run(4));
+  assert_eq!("a\nSample.List`1[System.Int32]\n", String::from_utf8(output.0.borrow().clone()).unwrap());
+  // Function pointers keep the type arguments of the instantiation they point to
+  assert_eq!(Ok(Some(StackValue::Int32(8))), run(6));
+}
+
+#[test]
+fn generic_instantiations() {
+  let mut builder = MetadataBuilder::new();
+  type_ref(&mut builder, "mscorlib", "System", "Object");
+  type_ref(&mut builder, "mscorlib", "System", "ValueType");
+  // Pair`2, Cell`1 and IGetter`1 are TypeDefs 2, 3 and 4
+  for signature in vec![
+    vec![0x15, 0x12, 0x08, 0x02, 0x08, 0x15, 0x11, 0x0C, 0x01, 0x0A], // 1: Pair<int32, Cell<int64>>
+    vec![0x15, 0x11, 0x0C, 0x01, 0x0A],                               // 2: Cell<int64>
+    vec![0x15, 0x11, 0x0C, 0x01, 0x13, 0x00],                         // 3: Cell<!0>
+    vec![0x15, 0x12, 0x08, 0x02, 0x13, 0x00, 0x13, 0x01],             // 4: Pair<!0, !1>
+    vec![0x15, 0x12, 0x10, 0x01, 0x08],                               // 5: IGetter<int32>
+    vec![0x15, 0x12, 0x10, 0x01, 0x0E],                               // 6: IGetter<string>
+    vec![0x15, 0x11, 0x0C, 0x01, 0x08],                               // 7: Cell<int32>
+    vec![0x15, 0x12, 0x08, 0x02, 0x0A, 0x08],                         // 8: Pair<int64, int32>
+    vec![0x13, 0x00], vec![0x13, 0x01], vec![0x1E, 0x00],             // 9, 10, 11: !0, !1, !!0
+    vec![0x15, 0x12, 0x10, 0x01, 0x13, 0x00]                          // 12: IGetter<!0>
+  ] {
+    let signature = builder.blob(signature);
+    builder.row(TypeSpecEntry { signature });
+  }
+  // struct Cell<T> : IGetter<T>, class Holder : IGetter<int>
+  builder.row(InterfaceImplEntry { class: Index::new(3), interface: TypeDefOrRef::TypeSpec(Index::new(12)) });
+  builder.row(InterfaceImplEntry { class: Index::new(5), interface: TypeDefOrRef::TypeSpec(Index::new(5)) });
+
+  for &(type_spec, name, ref signature) in &[
+    (4, "first", vec![0x06, 0x13, 0x00]),
+    (4, "second", vec![0x06, 0x13, 0x01]),
+    (1, ".ctor", vec![0x20, 0x02, 0x01, 0x13, 0x00, 0x13, 0x01]),
+    (1, "Second", vec![0x20, 0x00, 0x13, 0x01]),
+    (1, "first", vec![0x06, 0x13, 0x00]),
+    (2, "value", vec![0x06, 0x13, 0x00]),
+    (3, "value", vec![0x06, 0x13, 0x00]),
+    (8, "Sizes", vec![0x10, 0x01, 0x00, 0x08]),
+    (5, "Get", vec![0x20, 0x00, 0x13, 0x00]),
+    (7, "value", vec![0x06, 0x13, 0x00])
+  ] {
+    let (name, signature) = (builder.string(name), builder.blob(signature.clone()));
+    builder.row(MemberRefEntry { class: MemberRefParent::TypeSpec(Index::new(type_spec)), name, signature });
+  }
+  // Pair<int64, int32>::Sizes<int16> and Base::Echo<int64>
+  for &(method, argument) in &[(MethodDefOrRef::MethodRef(Index::new(8)), 0x06), (MethodDefOrRef::MethodDef(Index::new(14)), 0x0A)] {
+    let instantiation = builder.blob(vec![0x0A, 0x01, argument]);
+    builder.row(MethodSpecEntry { method, instantiation });
+  }
+
+  let program = vec![
+    // Cell<long> c; c.value = 7; var p = new Pair<int, Cell<long>>(3, c); c = p.Second(); return (int)c.value + p.first * 10;
+    ("Pairs", vec![0x00, 0x00, 0x08], vec![0x02, 0x15, 0x12, 0x08, 0x02, 0x08, 0x15, 0x11, 0x0C, 0x01, 0x0A, 0x15, 0x11, 0x0C, 0x01, 0x0A], vec![
+      0x12, 0x01, 0x1D, 0x6A, 0x7D, 0x06, 0x00, 0x00, 0x0A, // ldloca.s 1, ldc.i4.7, conv.i8, stfld Cell<int64>::value
+      0x19, 0x07, 0x73, 0x03, 0x00, 0x00, 0x0A, 0x0A,       // ldc.i4.3, ldloc.1, newobj Pair<int32, Cell<int64>>::.ctor, stloc.0
+      0x06, 0x6F, 0x04, 0x00, 0x00, 0x0A, 0x0B,             // ldloc.0, callvirt Second, stloc.1
+      0x12, 0x01, 0x7B, 0x06, 0x00, 0x00, 0x0A, 0x69,       // ldloca.s 1, ldfld Cell<int64>::value, conv.i4
+      0x06, 0x7B, 0x05, 0x00, 0x00, 0x0A, 0x1F, 0x0A, 0x5A, // ldloc.0, ldfld Pair<int32, Cell<int64>>::first, ldc.i4.s 10, mul
+      0x58, 0x2A                                            // add, ret
+    ]),
+    // return Pair<long, int>.Sizes<short>();
+    ("Sizes", vec![0x00, 0x00, 0x08], vec![], vec![0x28, 0x01, 0x00, 0x00, 0x2B, 0x2A]),
+    // Cell<int> c; c.value = 40; return ((IGetter<int>)new Holder()).Get() + ((IGetter<int>)c).Get() + c.Get() (constrained.);
+    ("Interfaces", vec![0x00, 0x00, 0x08], vec![0x01, 0x15, 0x11, 0x0C, 0x01, 0x08], vec![
+      0x73, 0x0B, 0x00, 0x00, 0x06, 0x6F, 0x09, 0x00, 0x00, 0x0A, // newobj Holder, callvirt IGetter<int32>::Get
+      0x12, 0x00, 0x1F, 0x28, 0x7D, 0x0A, 0x00, 0x00, 0x0A, // ldloca.s 0, ldc.i4.s 40, stfld Cell<int32>::value
+      0x06, 0x8C, 0x07, 0x00, 0x00, 0x1B,                   // ldloc.0, box Cell<int32>
+      0x6F, 0x09, 0x00, 0x00, 0x0A, 0x58,                   // callvirt IGetter<int32>::Get, add
+      0x12, 0x00, 0xFE, 0x16, 0x07, 0x00, 0x00, 0x1B,       // ldloca.s 0, constrained. Cell<int32>
+      0x6F, 0x09, 0x00, 0x00, 0x0A, 0x58, 0x2A              // callvirt IGetter<int32>::Get, add, ret
+    ]),
+    // (new Holder() is IGetter<int>) * 1000 + (new Holder() is IGetter<string>) * 100 + (cell is IGetter<int>) * 10
+    //   + (longCell is IGetter<int>)
+    ("Casts", vec![0x00, 0x00, 0x08], vec![0x02, 0x15, 0x11, 0x0C, 0x01, 0x08, 0x15, 0x11, 0x0C, 0x01, 0x0A], vec![
+      0x73, 0x0B, 0x00, 0x00, 0x06, 0x75, 0x05, 0x00, 0x00, 0x1B, // newobj Holder, isinst IGetter<int32>
+      0x14, 0xFE, 0x03, 0x20, 0xE8, 0x03, 0x00, 0x00, 0x5A, // ldnull, cgt.un, ldc.i4 1000, mul
+      0x73, 0x0B, 0x00, 0x00, 0x06, 0x75, 0x06, 0x00, 0x00, 0x1B, // newobj Holder, isinst IGetter<string>
+      0x14, 0xFE, 0x03, 0x1F, 0x64, 0x5A, 0x58,             // ldnull, cgt.un, ldc.i4.s 100, mul, add
+      0x06, 0x8C, 0x07, 0x00, 0x00, 0x1B, 0x75, 0x05, 0x00, 0x00, 0x1B, // ldloc.0, box Cell<int32>, isinst IGetter<int32>
+      0x14, 0xFE, 0x03, 0x1F, 0x0A, 0x5A, 0x58,             // ldnull, cgt.un, ldc.i4.s 10, mul, add
+      0x07, 0x8C, 0x02, 0x00, 0x00, 0x1B, 0x75, 0x05, 0x00, 0x00, 0x1B, // ldloc.1, box Cell<int64>, isinst IGetter<int32>
+      0x14, 0xFE, 0x03, 0x58, 0x2A                          // ldnull, cgt.un, add, ret
+    ]),
+    // return ((Base)new Derived()).Echo<long>(21);
+    ("Echo", vec![0x00, 0x00, 0x08], vec![], vec![
+      0x73, 0x0F, 0x00, 0x00, 0x06, 0x1F, 0x15, 0x6A,       // newobj Derived, ldc.i4.s 21, conv.i8
+      0x6F, 0x02, 0x00, 0x00, 0x2B, 0x2A                    // callvirt Base::Echo<int64>, ret
+    ])
+  ];
+  let pair = vec![
+    // public Pair(A first, B second) { this.first = first; this.second = second; }
+    (".ctor", vec![0x20, 0x02, 0x01, 0x13, 0x00, 0x13, 0x01], vec![], vec![
+      0x02, 0x03, 0x7D, 0x01, 0x00, 0x00, 0x0A, 0x02, 0x04, 0x7D, 0x02, 0x00, 0x00, 0x0A, 0x2A
+    ]),
+    // public B Second() { return second; }
+    ("Second", vec![0x20, 0x00, 0x13, 0x01], vec![], vec![0x02, 0x7B, 0x02, 0x00, 0x00, 0x0A, 0x2A]),
+    // public static int Sizes<M>() { return sizeof(A) * 100 + sizeof(B) * 10 + sizeof(M); }
+    ("Sizes", vec![0x10, 0x01, 0x00, 0x08], vec![], vec![
+      0xFE, 0x1C, 0x09, 0x00, 0x00, 0x1B, 0x1F, 0x64, 0x5A, // sizeof !0, ldc.i4.s 100, mul
+      0xFE, 0x1C, 0x0A, 0x00, 0x00, 0x1B, 0x1F, 0x0A, 0x5A, 0x58, // sizeof !1, ldc.i4.s 10, mul, add
+      0xFE, 0x1C, 0x0B, 0x00, 0x00, 0x1B, 0x58, 0x2A        // sizeof !!0, add, ret
+    ])
+  ];
+  let int_method = |name, code| (name, vec![0x20, 0x00, 0x08], vec![], code);
+  let constructor = || (".ctor", vec![0x20, 0x00, 0x01], vec![], vec![0x2A]);
+  // Echo<M>(M value): Base returns 1, Derived sizeof(M) * 10 + 2
+  let echo = |code| ("Echo", vec![0x30, 0x01, 0x01, 0x08, 0x1E, 0x00], vec![], code);
+  let (final_, abstract_, newslot, override_) = (0x01E6, 0x05C6, 0x01C6, 0x00C6);
+
+  let metadata = build_types(builder, vec![
+    TestType { name: "Program", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields: vec![], methods: program, method_flags: vec![] },
+    TestType {
+      name: "Pair`2", extends: TypeDefOrRef::TypeRef(Index::new(1)),
+      fields: vec![("first", 0x0001, vec![0x06, 0x13, 0x00]), ("second", 0x0001, vec![0x06, 0x13, 0x01])],
+      methods: pair, method_flags: vec![]
+    },
+    // struct Cell<T> : IGetter<T> { public T value; public T Get() { return value; } }
+    TestType {
+      name: "Cell`1", extends: TypeDefOrRef::TypeRef(Index::new(2)), fields: vec![("value", 0x0006, vec![0x06, 0x13, 0x00])],
+      methods: vec![("Get", vec![0x20, 0x00, 0x13, 0x00], vec![], vec![0x02, 0x7B, 0x07, 0x00, 0x00, 0x0A, 0x2A])],
+      method_flags: vec![("Get", final_)]
+    },
+    TestType {
+      name: "IGetter`1", extends: TypeDefOrRef::TypeDef(Index::new(0)), fields: vec![],
+      methods: vec![("Get", vec![0x20, 0x00, 0x13, 0x00], vec![], vec![])], method_flags: vec![("Get", abstract_)]
+    },
+    TestType {
+      name: "Holder", extends: TypeDefOrRef::TypeRef(Index::new(1)), fields: vec![],
+      methods: vec![constructor(), int_method("Get", vec![0x1B, 0x2A])], method_flags: vec![("Get", final_)]
+    },
+    TestType {
+      name: "Base", extends: TypeDefOrRef::TypeRef(Index::new(1)), fields: vec![],
+      methods: vec![constructor(), echo(vec![0x17, 0x2A])], method_flags: vec![("Echo", newslot)]
+    },
+    TestType {
+      name: "Derived", extends: TypeDefOrRef::TypeDef(Index::new(6)), fields: vec![],
+      methods: vec![constructor(), echo(vec![0xFE, 0x1C, 0x0B, 0x00, 0x00, 0x1B, 0x1F, 0x0A, 0x5A, 0x18, 0x58, 0x2A])],
+      method_flags: vec![("Echo", override_)]
+    }
+  ]);
+  let run = |method| Interpreter::with_internal_calls(&metadata, InternalCalls::corlib(io::sink())).run(&Index::new(method), vec![]);
+
+  // A generic struct as the type argument of a type with two type parameters
+  assert_eq!(Ok(Some(StackValue::Int32(37))), run(1));
+  // !0, !1 and !!0 in a generic method of a generic type
+  assert_eq!(Ok(Some(StackValue::Int32(842))), run(2));
+  // Methods of a generic interface, on a class and on a generic struct, boxed or through constrained.
+  assert_eq!(Ok(Some(StackValue::Int32(85))), run(3));
+  // Casts to an instantiation of a generic interface only succeed for the implemented one
+  assert_eq!(Ok(Some(StackValue::Int32(1010))), run(4));
+  // Overrides of generic virtual methods get the method's type arguments
+  assert_eq!(Ok(Some(StackValue::Int32(82))), run(5));
+}
@@ -0,0 +1,47 @@
+use metadata::marshal::*;
+use metadata::tables::*;
+use tests::builder::MetadataBuilder;
+
+#[test]
+fn arrays_with_sizes() {
+  assert_eq!(MarshalSpec::ByValArray { size: 16, element_type: Some(NativeType::I4) },
+             MarshalSpec::from_blob(&[0x1E, 0x10, 0x07]).unwrap());
+
+  assert_eq!(MarshalSpec::LPArray { element_type: Some(NativeType::LPWStr), size_param_index: Some(1), size_const: None },
+             MarshalSpec::from_blob(&[0x2A, 0x15, 0x01]).unwrap());
+
+  // SizeConst = 5, with SizeParamIndex explicitly marked as unspecified
+  assert_eq!(MarshalSpec::LPArray { element_type: None, size_param_index: None, size_const: Some(5) },
+             MarshalSpec::from_blob(&[0x2A, 0x50, 0x00, 0x05, 0x00]).unwrap());
+}
+
+#[test]
+fn custom_marshaler() {
+  let mut blob = vec![0x2C, 0x00, 0x00, 0x0C];
+  blob.extend_from_slice(b"My.Marshaler");
+  blob.extend_from_slice(&[0x01, b'x']);
+
+  assert_eq!(MarshalSpec::CustomMarshaler {
+    guid: String::new(),
+    native_type_name: String::new(),
+    marshaler_type: "My.Marshaler".to_string(),
+    cookie: "x".to_string()
+  }, MarshalSpec::from_blob(&blob).unwrap());
+}
+
+#[test]
+fn marshal_specs_of_fields_and_params() {
+  let mut builder = MetadataBuilder::new();
+
+  let native_type = builder.blob(vec![0x30]);
+  builder.row(FieldMarshalEntry { parent: HasFieldMarshall::Field(Index::new(1)), native_type });
+  let native_type = builder.blob(vec![0x1C, 0x02]);
+  builder.row(FieldMarshalEntry { parent: HasFieldMarshall::Param(Index::new(1)), native_type });
+
+  let metadata = builder.build();
+
+  assert_eq!(Some(MarshalSpec::Native(NativeType::LPUTF8Str)), metadata.get_field_marshal_spec(&Index::new(1)).unwrap());
+  assert_eq!(Some(MarshalSpec::Interface { kind: NativeType::Interface, iid_param_index: Some(2) }),
+             metadata.get_param_marshal_spec(&Index::new(1)).unwrap());
+  assert_eq!(None, metadata.get_param_marshal_spec(&Index::new(2)).unwrap());
+}
@@ -0,0 +1,112 @@
+use loader::clr::field_data_size;
+use metadata::tables::*;
+use tests::builder::MetadataBuilder;
+
+fn method(builder: &mut MetadataBuilder, name: &str, param_list: u32) -> Index<MethodDefEntry> {
+  let (name, signature) = (builder.string(name), builder.blob(vec![0x20, 0x00, 0x01]));
+  builder.row(MethodDefEntry { rva: 0, impl_flags: 0, flags: 0, name, signature, param_list: Index::new(param_list) })
+}
+
+#[test]
+fn fields_methods_properties_and_events() {
+  let mut builder = MetadataBuilder::new();
+
+  // <Module> owns nothing, Counter owns two fields and all of the methods
+  for &(name, fields) in &[("<Module>", 1), ("Counter", 1), ("Empty", 3)] {
+    let (name, namespace) = (builder.string(name), builder.string(""));
+    builder.row(TypeDefEntry {
+      flags: tdClass, name, namespace, extends: TypeDefOrRef::TypeDef(Index::new(0)),
+      fields: Index::new(fields), methods: Index::new(if fields == 3 { 6 } else { 1 })
+    });
+  }
+  let counter = Index::new(2);
+
+  for name in &["value", "Changed"] {
+    let (name, signature) = (builder.string(name), builder.blob(vec![0x06, 0x08]));
+    builder.row(FieldEntry { flags: 0, name, signature });
+  }
+
+  let get_value = method(&mut builder, "get_Value", 1);
+  let set_value = method(&mut builder, "set_Value", 1);
+  let add_changed = method(&mut builder, "add_Changed", 2);
+  let remove_changed = method(&mut builder, "remove_Changed", 3);
+  method(&mut builder, "Increment", 4);
+
+  for name in &["value", "handler", "handler"] {
+    let name = builder.string(name);
+    builder.row(ParamEntry { flags: 0, sequence: 1, name });
+  }
+
+  let (name, signature) = (builder.string("Value"), builder.blob(vec![0x28, 0x00, 0x08]));
+  let value = builder.row(PropertyEntry { flags: 0, name, signature });
+  builder.row(PropertyMapEntry { parent: counter, property_list: value });
+
+  let (name, namespace) = (builder.string("EventHandler"), builder.string("System"));
+  let handler = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::Module(Index::new(1)), name, namespace });
+  let name = builder.string("Changed");
+  let changed = builder.row(EventEntry { flags: 0, name, event_type: TypeDefOrRef::TypeRef(handler) });
+  builder.row(EventMapEntry { parent: counter, event_list: changed });
+
+  builder.row(MethodSemanticsEntry { semantics: 0x0002, method: get_value, association: HasSemantics::Property(value) });
+  builder.row(MethodSemanticsEntry { semantics: 0x0001, method: set_value, association: HasSemantics::Property(value) });
+  builder.row(MethodSemanticsEntry { semantics: 0x0008, method: add_changed, association: HasSemantics::Event(changed) });
+  builder.row(MethodSemanticsEntry { semantics: 0x0010, method: remove_changed, association: HasSemantics::Event(changed) });
+
+  let metadata = builder.build();
+
+  let members = metadata.type_members(&counter);
+  assert_eq!(vec![Index::new(1), Index::new(2)], members.fields);
+  assert_eq!(vec![get_value, set_value, add_changed, remove_changed, Index::new(5)], members.methods);
+
+  assert_eq!(1, members.properties.len());
+  assert_eq!("Value", members.properties[0].name);
+  assert_eq!((Some(get_value), Some(set_value)), (members.properties[0].getter, members.properties[0].setter));
+
+  assert_eq!(1, members.events.len());
+  assert_eq!("Changed", members.events[0].name);
+  assert_eq!(TypeDefOrRef::TypeRef(handler), members.events[0].event_type);
+  assert_eq!((Some(add_changed), Some(remove_changed), None), (members.events[0].add, members.events[0].remove, members.events[0].raise));
+
+  let module = metadata.type_members(&Index::new(1));
+  assert!(module.fields.is_empty() && module.methods.is_empty() && module.properties.is_empty());
+
+  let empty = metadata.type_members(&Index::new(3));
+  assert!(empty.fields.is_empty() && empty.methods.is_empty() && empty.events.is_empty());
+
+  // get_Value has no parameters, set_Value owns the first one
+  assert_eq!(1 .. 1, metadata.get_param_range(&get_value));
+  assert_eq!(1 .. 2, metadata.get_param_range(&set_value));
+  assert_eq!(4 .. 4, metadata.get_param_range(&Index::new(5)));
+  assert_eq!(Some(remove_changed), metadata.get_param_owner(&Index::new(3)));
+  assert_eq!(Some(set_value), metadata.get_param_owner(&Index::new(1)));
+
+  // Types with empty member lists don't own the members starting at the same row
+  assert_eq!(Some(counter), metadata.get_method_owner(&Index::new(1)));
+  assert_eq!(Some(counter), metadata.get_field_owner(&Index::new(2)));
+  assert_eq!(None, metadata.get_method_owner(&Index::new(6)));
+}
+
+#[test]
+fn field_data_sizes() {
+  let mut builder = MetadataBuilder::new();
+
+  // Program and the struct holding the data of a 12-byte array initializer, whose size comes from ClassLayout
+  for &(name, fields) in &[("Program", 1), ("__StaticArrayInitTypeSize=12", 5), ("Unsized", 5)] {
+    let (name, namespace) = (builder.string(name), builder.string(""));
+    builder.row(TypeDefEntry {
+      flags: tdClass, name, namespace, extends: TypeDefOrRef::TypeDef(Index::new(0)),
+      fields: Index::new(fields), methods: Index::new(1)
+    });
+  }
+  builder.row(ClassLayoutEntry { packing_size: 1, class_size: 12, parent: Index::new(2) });
+
+  // int64, __StaticArrayInitTypeSize=12, Unsized, object
+  for signature in vec![vec![0x06, 0x0A], vec![0x06, 0x11, 0x08], vec![0x06, 0x11, 0x0C], vec![0x06, 0x1C]] {
+    let (name, signature) = (builder.string("Data"), builder.blob(signature));
+    builder.row(FieldEntry { flags: 0x0113, name, signature });
+  }
+  let metadata = builder.build();
+
+  let sizes = (1 .. 5).map(|field| field_data_size(&metadata, &Index::new(field))).collect::<Vec<_>>();
+  assert_eq!(vec![Some(8), Some(12), None, None], sizes);
+}
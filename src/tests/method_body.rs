@@ -0,0 +1,49 @@
+use std::io::Cursor;
+
+use loader::code::*;
+
+#[test]
+fn tiny_header() {
+  // ldc.i4.1, ret
+  let body = MethodBody::read(&mut Cursor::new(vec![0x02 | (2 << 2), 0x17, 0x2A])).unwrap();
+
+  assert_eq!(2, body.header.code_size);
+  assert_eq!(8, body.header.max_stack);
+  assert_eq!(vec![0x17, 0x2A], body.code);
+  assert!(body.exception_clauses.is_empty());
+}
+
+#[test]
+fn fat_header_with_exception_clauses() {
+  // Fat format, MoreSects and InitLocals, with a header size of 3 dwords
+  let mut bytes = vec![0x1B, 0x30, 0x02, 0x00, 0x05, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x11];
+  // nop, leave.s +1, nop, ret
+  bytes.extend_from_slice(&[0x00, 0xDE, 0x01, 0x00, 0x2A]);
+  // Padding up to the next 4-byte boundary
+  bytes.extend_from_slice(&[0x00, 0x00, 0x00]);
+
+  // A small EH table with a finally clause, followed by another section
+  bytes.extend_from_slice(&[0x81, 16, 0x00, 0x00]);
+  bytes.extend_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x03, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]);
+
+  // A fat EH table with a typed catch and a filter
+  bytes.extend_from_slice(&[0x41, 52, 0x00, 0x00]);
+  for &word in &[0x0000, 0, 3, 3, 1, 0x0100_0005, 0x0001, 0, 3, 4, 1, 3] {
+    bytes.extend_from_slice(&[word as u8, (word >> 8) as u8, (word >> 16) as u8, (word >> 24) as u8]);
+  }
+
+  let body = MethodBody::read(&mut Cursor::new(bytes)).unwrap();
+
+  assert!(body.header.flags.contains(InitLocals));
+  assert_eq!(0x1100_0001, body.header.local_var_signature_token);
+  assert_eq!(5, body.code.len());
+
+  assert_eq!(vec![
+    ExceptionClause { kind: ExceptionClauseKind::Finally, try_offset: 0, try_length: 3, handler_offset: 3, handler_length: 1 },
+    ExceptionClause { kind: ExceptionClauseKind::Catch(0x0100_0005), try_offset: 0, try_length: 3, handler_offset: 3, handler_length: 1 },
+    ExceptionClause { kind: ExceptionClauseKind::Filter(3), try_offset: 0, try_length: 3, handler_offset: 4, handler_length: 1 }
+  ], body.exception_clauses);
+
+  assert!(body.exception_clauses[0].try_contains(2) && !body.exception_clauses[0].try_contains(3));
+  assert!(body.exception_clauses[0].handler_contains(3));
+}
@@ -1,2 +1,17 @@
+mod builder;
 
-mod tagged_index;
\ No newline at end of file
+mod tagged_index;
+mod signature;
+mod custom_attributes;
+mod constants;
+mod marshal;
+mod security;
+mod generics;
+mod types;
+mod members;
+mod method_body;
+mod instructions;
+mod disassembler;
+mod cfg;
+mod verifier;
+mod interpreter;
@@ -0,0 +1,147 @@
+use metadata::custom_attributes::*;
+use metadata::security::*;
+use metadata::tables::*;
+use tests::builder::MetadataBuilder;
+
+#[test]
+fn binary_permission_set() {
+  let mut builder = MetadataBuilder::new();
+
+  let type_name = "System.Security.Permissions.SecurityPermissionAttribute, mscorlib";
+
+  // UnmanagedCode = true
+  let mut arguments = vec![0x01, 0x54, 0x02, 0x0D];
+  arguments.extend_from_slice(b"UnmanagedCode");
+  arguments.push(0x01);
+
+  let mut blob = vec![b'.', 0x01, type_name.len() as u8];
+  blob.extend_from_slice(type_name.as_bytes());
+  blob.push(arguments.len() as u8);
+  blob.extend(arguments);
+
+  let permission_set = builder.blob(blob);
+  let parent = HasDeclSecurity::MethodDef(Index::new(1));
+  builder.row(DeclSecurityEntry { action: 6, parent, permission_set });
+
+  let metadata = builder.build();
+  let permission_sets = metadata.declarative_security_of(parent).unwrap();
+
+  assert_eq!(vec![PermissionSet {
+    action: SecurityAction::LinkDemand,
+    format: PermissionSetFormat::Binary,
+    unrestricted: false,
+    permissions: vec![Permission {
+      type_name: type_name.to_string(),
+      named_args: vec![NamedArgument {
+        kind: NamedArgumentKind::Property,
+        name: "UnmanagedCode".to_string(),
+        value: CustomAttributeValue::Bool(true)
+      }]
+    }]
+  }], permission_sets);
+
+  assert!(metadata.declarative_security_of(HasDeclSecurity::MethodDef(Index::new(2))).unwrap().is_empty());
+}
+
+#[test]
+fn xml_permission_set() {
+  let mut builder = MetadataBuilder::new();
+
+  let xml = "<PermissionSet class=\"System.Security.PermissionSet\" version=\"1\">\r\n\
+             <IPermission class=\"System.Security.Permissions.FileIOPermission, mscorlib\" version=\"1\" \
+             Read=\"C:\\Temp &amp; Logs\"/>\r\n</PermissionSet>";
+  let blob = xml.encode_utf16().flat_map(|unit| vec![unit as u8, (unit >> 8) as u8]).collect();
+
+  let permission_set = builder.blob(blob);
+  let parent = HasDeclSecurity::Assembly(Index::new(1));
+  builder.row(DeclSecurityEntry { action: 8, parent, permission_set });
+
+  let metadata = builder.build();
+  let permission_sets = metadata.declarative_security_of(parent).unwrap();
+
+  assert_eq!(1, permission_sets.len());
+  assert_eq!(SecurityAction::RequestMinimum, permission_sets[0].action);
+  assert_eq!(PermissionSetFormat::Xml, permission_sets[0].format);
+  assert!(!permission_sets[0].unrestricted);
+  assert_eq!(vec![Permission {
+    type_name: "System.Security.Permissions.FileIOPermission, mscorlib".to_string(),
+    named_args: vec![NamedArgument {
+      kind: NamedArgumentKind::Property,
+      name: "Read".to_string(),
+      value: CustomAttributeValue::String(Some("C:\\Temp & Logs".to_string()))
+    }]
+  }], permission_sets[0].permissions);
+}
+
+#[test]
+fn xml_permission_set_encodings() {
+  let mut builder = MetadataBuilder::new();
+
+  let xml = "<PermissionSet class=\"System.Security.PermissionSet\" version=\"1\" Unrestricted=\"true\"/>";
+  // UTF-16 with a byte order mark, then UTF-8
+  let utf16 = xml.encode_utf16().flat_map(|unit| vec![unit as u8, (unit >> 8) as u8]);
+  let blobs = vec![vec![0xFF, 0xFE].into_iter().chain(utf16).collect::<Vec<_>>(), xml.as_bytes().to_vec()];
+
+  let parent = HasDeclSecurity::Assembly(Index::new(1));
+  for blob in blobs {
+    let permission_set = builder.blob(blob);
+    builder.row(DeclSecurityEntry { action: 8, parent, permission_set });
+  }
+
+  let metadata = builder.build();
+  let permission_sets = metadata.declarative_security_of(parent).unwrap();
+  assert_eq!(2, permission_sets.len());
+  assert!(permission_sets.iter().all(|permission_set| permission_set.format == PermissionSetFormat::Xml && permission_set.unrestricted));
+}
+
+#[test]
+fn binary_permission_set_with_enum_from_reference() {
+  // mscorlib's enum System.Security.Permissions.SecurityPermissionFlag : int
+  let mut builder = MetadataBuilder::new();
+  let (name, namespace) = (builder.string("Enum"), builder.string("System"));
+  let system_enum = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::Module(Index::new(1)), name, namespace });
+  let (name, namespace) = (builder.string("<Module>"), builder.string(""));
+  builder.row(TypeDefEntry {
+    flags: tdClass, name, namespace, extends: TypeDefOrRef::TypeDef(Index::new(0)),
+    fields: Index::new(1), methods: Index::new(1)
+  });
+  let (name, namespace) = (builder.string("SecurityPermissionFlag"), builder.string("System.Security.Permissions"));
+  builder.row(TypeDefEntry {
+    flags: tdPublic | tdSealed, name, namespace, extends: TypeDefOrRef::TypeRef(system_enum),
+    fields: Index::new(1), methods: Index::new(1)
+  });
+  let (name, signature) = (builder.string("value__"), builder.blob(vec![0x06, 0x08]));
+  builder.row(FieldEntry { flags: 0x0606, name, signature });
+  let mscorlib = builder.build();
+
+  let mut builder = MetadataBuilder::new();
+
+  let type_name = "System.Security.Permissions.SecurityPermissionAttribute, mscorlib";
+  let enum_name = "System.Security.Permissions.SecurityPermissionFlag, mscorlib";
+
+  // Flags = SecurityPermissionFlag.UnmanagedCode
+  let mut arguments = vec![0x01, 0x54, 0x55, enum_name.len() as u8];
+  arguments.extend_from_slice(enum_name.as_bytes());
+  arguments.push(0x05);
+  arguments.extend_from_slice(b"Flags");
+  arguments.extend_from_slice(&[0x02, 0x00, 0x00, 0x00]);
+
+  let mut blob = vec![b'.', 0x01, type_name.len() as u8];
+  blob.extend_from_slice(type_name.as_bytes());
+  blob.push(arguments.len() as u8);
+  blob.extend(arguments);
+
+  let permission_set = builder.blob(blob);
+  let parent = HasDeclSecurity::TypeDef(Index::new(1));
+  builder.row(DeclSecurityEntry { action: 2, parent, permission_set });
+
+  let metadata = builder.build();
+  assert!(metadata.declarative_security_of(parent).is_err());
+
+  let permission_sets = metadata.declarative_security_of_with_references(parent, &[&mscorlib]).unwrap();
+  assert_eq!(vec![NamedArgument {
+    kind: NamedArgumentKind::Property,
+    name: "Flags".to_string(),
+    value: CustomAttributeValue::Enum(enum_name.to_string(), box CustomAttributeValue::I4(2))
+  }], permission_sets[0].permissions[0].named_args);
+}
@@ -0,0 +1,110 @@
+use metadata::Metadata;
+use metadata::signature::*;
+use metadata::tables::{Index, TypeDefOrRef, StandAloneSigEntry};
+use loader::code::{MethodHeader, FatFormat};
+use tests::builder::MetadataBuilder;
+
+fn empty_metadata() -> Metadata {
+  MetadataBuilder::new().build()
+}
+
+#[test]
+fn array_with_sizes_and_lower_bounds() {
+  // int[3, -2...], encoded as ARRAY I4 rank=2 sizes=[3] lower_bounds=[0, -2]
+  let blob = [0x14, 0x08, 0x02, 0x01, 0x03, 0x02, 0x00, 0x7D];
+  let type_sig = TypeSig::from_blob(&blob).unwrap();
+
+  let expected_shape = ArrayShape { rank: 2, sizes: vec![3], lower_bounds: vec![0, -2] };
+  assert_eq!(TypeSig::Array(box TypeSig::I4, expected_shape), type_sig);
+  assert_eq!("int[,]", type_sig.as_csharp(&empty_metadata()));
+}
+
+#[test]
+fn generic_instance_of_type_ref() {
+  // GENERICINST CLASS TypeRef(1) 1 I4
+  let blob = [0x15, 0x12, 0x05, 0x01, 0x08];
+  let type_sig = TypeSig::from_blob(&blob).unwrap();
+
+  let generic_type = TypeSig::Class(TypeDefOrRef::TypeRef(Index::new(1)));
+  assert_eq!(TypeSig::GenericInst(box generic_type, vec![TypeSig::I4]), type_sig);
+}
+
+#[test]
+fn modifiers_and_pointers() {
+  // CMOD_REQD TypeRef(2) BYREF PTR SZARRAY U1
+  let blob = [0x1F, 0x09, 0x10, 0x0F, 0x1D, 0x05];
+  let type_sig = TypeSig::from_blob(&blob).unwrap();
+
+  let inner = TypeSig::ByRef(box TypeSig::Ptr(box TypeSig::SzArray(box TypeSig::U1)));
+  assert_eq!(TypeSig::CModReqd(TypeDefOrRef::TypeRef(Index::new(2)), box inner), type_sig);
+  assert_eq!("modreq(TypeRef#2) ref byte[]*", type_sig.as_csharp(&empty_metadata()));
+}
+
+#[test]
+fn vararg_method_signature() {
+  // VARARG, 2 params, returns void, (int, ..., string)
+  let blob = [0x05, 0x02, 0x01, 0x08, 0x41, 0x0E];
+  let signature = MethodSignature::from_blob(&blob).unwrap();
+
+  assert_eq!(CallingConvention::VarArg, signature.calling_convention);
+  assert_eq!(vec![TypeSig::I4, TypeSig::String], signature.params);
+  assert_eq!(Some(1), signature.sentinel);
+  assert_eq!("static void M(int, ..., string)", signature.as_csharp(&empty_metadata(), "M"));
+}
+
+#[test]
+fn function_pointer() {
+  // FNPTR DEFAULT, 1 param, returns int, (string)
+  let blob = [0x1B, 0x00, 0x01, 0x08, 0x0E];
+  let type_sig = TypeSig::from_blob(&blob).unwrap();
+
+  assert_eq!("delegate*<string, int>", type_sig.as_csharp(&empty_metadata()));
+}
+
+#[test]
+fn unknown_element_type_is_an_error() {
+  assert!(TypeSig::from_blob(&[0x17]).is_err());
+}
+
+#[test]
+fn field_signature_with_modifier() {
+  // FIELD CMOD_OPT TypeRef(2) I4
+  let blob = [0x06, 0x20, 0x09, 0x08];
+  let field_sig = FieldSig::from_blob(&blob).unwrap();
+
+  assert_eq!(TypeSig::CModOpt(TypeDefOrRef::TypeRef(Index::new(2)), box TypeSig::I4), field_sig.type_);
+  assert!(FieldSig::from_blob(&[0x07, 0x08]).is_err());
+}
+
+#[test]
+fn indexer_property_signature() {
+  // PROPERTY | HASTHIS, 1 param, int this[string]
+  let blob = [0x28, 0x01, 0x08, 0x0E];
+  let property_sig = PropertySig::from_blob(&blob).unwrap();
+
+  assert!(property_sig.has_this);
+  assert_eq!(TypeSig::I4, property_sig.type_);
+  assert_eq!(vec![TypeSig::String], property_sig.params);
+}
+
+#[test]
+fn pinned_and_byref_locals() {
+  let mut builder = MetadataBuilder::new();
+  // LOCAL_SIG, 4 locals: int, pinned ref int, ref string, modreq(TypeRef 1) ref int
+  let signature = builder.blob(vec![0x07, 0x04, 0x08, 0x45, 0x10, 0x08, 0x10, 0x0E, 0x1F, 0x05, 0x10, 0x08]);
+  builder.row(StandAloneSigEntry { signature });
+  let metadata = builder.build();
+
+  let header = MethodHeader { flags: FatFormat, max_stack: 8, code_size: 0, local_var_signature_token: 0x11000001 };
+  let locals = metadata.get_local_var_signature(&header).unwrap().unwrap().locals;
+
+  assert_eq!(vec![
+    LocalVariable { type_: TypeSig::I4, pinned: false, by_ref: false },
+    LocalVariable { type_: TypeSig::I4, pinned: true, by_ref: true },
+    LocalVariable { type_: TypeSig::String, pinned: false, by_ref: true },
+    LocalVariable { type_: TypeSig::CModReqd(TypeDefOrRef::TypeRef(Index::new(1)), box TypeSig::I4), pinned: false, by_ref: true }
+  ], locals);
+
+  let no_locals_header = MethodHeader { flags: FatFormat, max_stack: 8, code_size: 0, local_var_signature_token: 0 };
+  assert_eq!(None, metadata.get_local_var_signature(&no_locals_header).unwrap());
+}
@@ -0,0 +1,59 @@
+use metadata::tables::*;
+use metadata::types::FullTypeName;
+use tests::builder::MetadataBuilder;
+
+fn type_def(builder: &mut MetadataBuilder, namespace: &str, name: &str) -> Index<TypeDefEntry> {
+  let (name, namespace) = (builder.string(name), builder.string(namespace));
+  builder.row(TypeDefEntry {
+    flags: tdClass, name, namespace, extends: TypeDefOrRef::TypeDef(Index::new(0)),
+    fields: Index::new(1), methods: Index::new(1)
+  })
+}
+
+#[test]
+fn nested_type_defs() {
+  let mut builder = MetadataBuilder::new();
+
+  let outer = type_def(&mut builder, "Sample", "Outer");
+  let inner = type_def(&mut builder, "", "Inner");
+  let closure = type_def(&mut builder, "", "<>c__DisplayClass0_0");
+  // A top-level type with the same name as the nested one
+  let top_level_inner = type_def(&mut builder, "", "Inner");
+
+  builder.row(NestedClassEntry { nested_class: inner, enclosing_class: outer });
+  builder.row(NestedClassEntry { nested_class: closure, enclosing_class: inner });
+
+  let metadata = builder.build();
+
+  let name = metadata.full_type_name(&TypeDefOrRef::TypeDef(closure));
+  assert_eq!(FullTypeName {
+    namespace: "Sample".to_string(),
+    names: vec!["Outer".to_string(), "Inner".to_string(), "<>c__DisplayClass0_0".to_string()]
+  }, name);
+  assert_eq!("Sample.Outer+Inner+<>c__DisplayClass0_0", name.as_reflection());
+  assert_eq!("Sample.Outer.Inner.<>c__DisplayClass0_0", name.as_csharp());
+  assert_eq!("Sample.Outer.Inner", metadata.get_type_name(&TypeDefOrRef::TypeDef(inner)));
+
+  assert_eq!(Some(outer), metadata.get_enclosing_type(&inner));
+  assert_eq!(vec![inner], metadata.get_nested_types(&outer));
+
+  assert_eq!(Some(top_level_inner), metadata.find_type_def("", "Inner"));
+  assert_eq!(Some(inner), metadata.find_type_def_by_full_name("Sample.Outer+Inner"));
+  assert_eq!(None, metadata.find_type_def_by_full_name("Sample.Outer+Missing"));
+}
+
+#[test]
+fn nested_type_refs() {
+  let mut builder = MetadataBuilder::new();
+
+  let (name, namespace) = (builder.string("Dictionary`2"), builder.string("System.Collections.Generic"));
+  let dictionary = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::AssemblyRef(Index::new(1)), name, namespace });
+  let (name, namespace) = (builder.string("Enumerator"), builder.string(""));
+  let enumerator = builder.row(TypeRefEntry { resolution_scope: ResolutionScope::TypeRef(dictionary), name, namespace });
+
+  let metadata = builder.build();
+  let name = metadata.full_type_name(&TypeDefOrRef::TypeRef(enumerator));
+
+  assert_eq!("System.Collections.Generic.Dictionary`2+Enumerator", name.as_reflection());
+  assert_eq!("System.Collections.Generic.Dictionary`2.Enumerator", name.as_csharp());
+}
@@ -0,0 +1,82 @@
+use analysis::verifier::VerificationError;
+use loader::code::*;
+use metadata::tables::*;
+use tests::builder::MetadataBuilder;
+
+// Verifies `static int32 Sample.Calc.Add(int32, int32)` with the given body
+fn verify(code: Vec<u8>, max_stack: u16, exception_clauses: Vec<ExceptionClause>) -> Result<(), VerificationError> {
+  let mut builder = MetadataBuilder::new();
+
+  let (name, namespace) = (builder.string("Calc"), builder.string("Sample"));
+  builder.row(TypeDefEntry {
+    flags: tdPublic, name, namespace, extends: TypeDefOrRef::TypeDef(Index::new(0)),
+    fields: Index::new(1), methods: Index::new(1)
+  });
+
+  let (name, signature) = (builder.string("Add"), builder.blob(vec![0x00, 0x02, 0x08, 0x08, 0x08]));
+  let method = builder.row(MethodDefEntry { rva: 0x2050, impl_flags: 0, flags: 0x0016, name, signature, param_list: Index::new(1) });
+
+  let mut metadata = builder.build();
+  let header = MethodHeader { flags: FatFormat, max_stack, code_size: code.len() as u32, local_var_signature_token: 0 };
+  metadata.method_bodies.insert(method.0 - 1, MethodBody { header, code, exception_clauses });
+
+  metadata.verify_method(&method)
+}
+
+fn error(offset: u32, reason: &str) -> Result<(), VerificationError> {
+  Err(VerificationError { method: "Sample.Calc.Add".to_string(), offset, reason: reason.to_string() })
+}
+
+#[test]
+fn valid_method() {
+  // ldarg.0, ldarg.1, add.ovf, ret
+  assert_eq!(Ok(()), verify(vec![0x02, 0x03, 0xD6, 0x2A], 2, vec![]));
+}
+
+#[test]
+fn stack_depth() {
+  assert_eq!(error(1, "Stack depth 2 exceeds .maxstack 1"), verify(vec![0x02, 0x03, 0x58, 0x2A], 1, vec![]));
+  assert_eq!(error(0, "Stack underflow"), verify(vec![0x58, 0x2A], 8, vec![]));
+  assert_eq!(error(2, "The stack must be empty after ret, but has 1 more values"), verify(vec![0x02, 0x03, 0x2A], 8, vec![]));
+
+  // ldarg.0, brtrue.s 4, ldc.i4.1, ldc.i4.2 (reached with one and zero values), ret
+  assert_eq!(error(4, "Stack depth differs between paths: 0 and 1"), verify(vec![0x02, 0x2D, 0x01, 0x17, 0x18, 0x2A], 8, vec![]));
+}
+
+#[test]
+fn operand_types_and_indices() {
+  // ldarg.0, ldc.r8 1.0, add
+  let mut code = vec![0x02, 0x23];
+  code.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F]);
+  code.extend_from_slice(&[0x58, 0x2A]);
+  assert_eq!(error(10, "Invalid operands for add: int32 and F"), verify(code, 8, vec![]));
+
+  assert_eq!(error(0, "Local variable index 0 is out of range, the method has 0 locals"), verify(vec![0x06, 0x2A], 8, vec![]));
+  assert_eq!(error(0, "Argument index 2 is out of range, the method has 2 arguments"), verify(vec![0x04, 0x2A], 8, vec![]));
+  assert_eq!(error(0, "Branch target IL_0001 is not the start of an instruction"), verify(vec![0x2B, 0xFF, 0x2A], 8, vec![]));
+  assert_eq!(error(1, "Control falls through the end of the method"), verify(vec![0x02, 0x26], 8, vec![]));
+}
+
+#[test]
+fn exception_handling_regions() {
+  let catch = |handler_offset, handler_length| ExceptionClause {
+    kind: ExceptionClauseKind::Catch(0x0100_0001), try_offset: 0, try_length: 3, handler_offset, handler_length
+  };
+
+  // try { nop, leave.s 6 } catch { pop, leave.s 6 } ldarg.0, ret
+  let code = vec![0x00, 0xDE, 0x03, 0x26, 0xDE, 0x00, 0x02, 0x2A];
+  assert_eq!(Ok(()), verify(code.clone(), 1, vec![catch(3, 3)]));
+
+  // The same, with the leave in the try block jumping into the handler
+  let mut into_handler = code.clone();
+  into_handler[2] = 0x01;
+  assert_eq!(error(1, "leave target IL_0004 is inside a region which doesn't contain the leave"), verify(into_handler, 1, vec![catch(3, 3)]));
+
+  // try { ldarg.0, leave.s 6 } with the argument still on the stack
+  let mut unbalanced = code.clone();
+  unbalanced[0] = 0x02;
+  assert_eq!(error(1, "The stack must be empty at leave, but has 1 values"), verify(unbalanced, 1, vec![catch(3, 3)]));
+
+  // A handler whose end doesn't fit in 32 bits
+  assert_eq!(error(3, "Exception handling region is out of range"), verify(code, 1, vec![catch(3, u32::max_value())]));
+}